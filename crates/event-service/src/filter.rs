@@ -0,0 +1,82 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Server-side filtering of the events a client subscribes to
+
+use std::collections::BTreeSet;
+
+use hotshot_types::{
+    event::{Event, EventKind},
+    traits::node_implementation::NodeType,
+};
+
+/// The events a client wants: of some kinds, and in a range of views
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventFilter<TYPES: NodeType> {
+    /// The kinds of event to send, or every kind if `None`
+    pub kinds: Option<BTreeSet<EventKind>>,
+    /// The first view to send events of, which we replay buffered events from
+    pub from: Option<TYPES::View>,
+    /// The last view to send events of
+    pub to: Option<TYPES::View>,
+}
+
+impl<TYPES: NodeType> Default for EventFilter<TYPES> {
+    fn default() -> Self {
+        Self {
+            kinds: None,
+            from: None,
+            to: None,
+        }
+    }
+}
+
+impl<TYPES: NodeType> EventFilter<TYPES> {
+    /// Parse the filter from the parameters of a request.
+    ///
+    /// `kinds` is `all`, or a comma-separated list of [`EventKind::name`]s.
+    ///
+    /// # Errors
+    /// Returns an error if one of the kinds is unknown, or the range of views is empty.
+    pub fn parse(
+        kinds: Option<&str>,
+        from: Option<TYPES::View>,
+        to: Option<TYPES::View>,
+    ) -> Result<Self, String> {
+        let kinds = match kinds {
+            None | Some("all") => None,
+            Some(kinds) => Some(
+                kinds
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<BTreeSet<_>, _>>()?,
+            ),
+        };
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Err(format!("View range from {from:?} to {to:?} is empty"));
+            }
+        }
+
+        Ok(Self { kinds, from, to })
+    }
+
+    /// Whether the client wants `event`
+    #[must_use]
+    pub fn matches(&self, event: &Event<TYPES>) -> bool {
+        self.kinds
+            .as_ref()
+            .map_or(true, |kinds| kinds.contains(&event.event.kind()))
+            && self.from.map_or(true, |from| event.view_number >= from)
+            && !self.is_past(event.view_number)
+    }
+
+    /// Whether `view` is after the range of views the client wants, so no later event will match
+    #[must_use]
+    pub fn is_past(&self, view: TYPES::View) -> bool {
+        self.to.is_some_and(|to| view > to)
+    }
+}
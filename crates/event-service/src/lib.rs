@@ -0,0 +1,165 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Service streaming the events of a `HotShot` node to other processes, such as indexers and
+//! dashboards, over WebSockets or server-sent events.
+//!
+//! Clients pick the kinds of event and the range of views they want, and resume from a view after
+//! a disconnect with the events we still buffer.
+
+/// Server-side filtering of events
+pub mod filter;
+/// Streaming of events as server-sent events
+pub mod sse;
+/// Fan-out and buffering of events
+pub mod streamer;
+
+use std::io::{self, ErrorKind};
+
+use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt};
+use hotshot_types::{
+    event::Event,
+    traits::node_implementation::{ConsensusTime, NodeType},
+};
+use tide_disco::{api::ApiError, error::ServerError, Api, App, RequestParams, StatusCode};
+use url::Url;
+use vbs::version::{StaticVersion, StaticVersionType};
+
+use crate::{
+    filter::EventFilter,
+    sse::define_sse_app,
+    streamer::{EventStreamError, EventStreamer},
+};
+
+/// Event service Version (major)
+pub const EVENT_SERVICE_MAJOR_VERSION: u16 = 0;
+/// Event service Version (minor)
+pub const EVENT_SERVICE_MINOR_VERSION: u16 = 1;
+/// Event service Version as a type
+pub type EventServiceVersion =
+    StaticVersion<EVENT_SERVICE_MAJOR_VERSION, EVENT_SERVICE_MINOR_VERSION>;
+/// Event service Version as a type-binding instance
+pub const EVENT_SERVICE_VERSION: EventServiceVersion = StaticVersion {};
+
+/// Number of events a client may fall behind the node before we disconnect it
+pub const CLIENT_LAG_LIMIT: usize = 1024;
+
+impl From<EventStreamError> for ServerError {
+    fn from(e: EventStreamError) -> Self {
+        let status = match e {
+            EventStreamError::Evicted { .. } | EventStreamError::EvictedAfter { .. } => {
+                StatusCode::GONE
+            }
+            EventStreamError::UnknownEvent { .. } => StatusCode::BAD_REQUEST,
+            EventStreamError::Lagged { .. } => StatusCode::TOO_MANY_REQUESTS,
+        };
+
+        ServerError {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Parse the filter of a request to the `events` or `replay` route
+fn parse_filter<TYPES: NodeType>(req: &RequestParams) -> Result<EventFilter<TYPES>, ServerError> {
+    let kinds = req.opt_string_param("kinds")?;
+    let from = req
+        .opt_integer_param::<_, u64>("from")?
+        .map(TYPES::View::new);
+    let to = req.opt_integer_param::<_, u64>("to")?.map(TYPES::View::new);
+
+    EventFilter::parse(kinds, from, to).map_err(|message| ServerError {
+        status: StatusCode::BAD_REQUEST,
+        message,
+    })
+}
+
+/// Define the API of the event service
+///
+/// # Errors
+/// Returns an error if the routes do not match `api.toml`.
+///
+/// # Panics
+/// Panics if `api.toml` is not valid TOML.
+pub fn define_api<TYPES, VER>() -> Result<Api<EventStreamer<TYPES>, ServerError, VER>, ApiError>
+where
+    TYPES: NodeType,
+    VER: StaticVersionType + 'static,
+{
+    let api_toml = toml::from_str::<toml::Value>(include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/api.toml"
+    )))
+    .expect("API file is not valid toml");
+    let mut api = Api::<EventStreamer<TYPES>, ServerError, VER>::new(api_toml)?;
+    api.stream("events", |req, state| {
+        async move {
+            let filter = parse_filter::<TYPES>(&req)?;
+            let events = state.subscribe(filter).await?;
+            Ok(events.map_err(ServerError::from))
+        }
+        .try_flatten_stream()
+        .boxed()
+    })?
+    .get("replay", |req, state| {
+        async move {
+            let filter = parse_filter::<TYPES>(&req)?;
+            Ok(state.replay(&filter).await?)
+        }
+        .boxed()
+    })?;
+    Ok(api)
+}
+
+/// Serve `events`, such as the event stream of a node, to clients at `url`, and as server-sent
+/// events at `sse_url` if given, buffering the last `replay_capacity` events for clients to resume
+/// from
+///
+/// # Errors
+/// Returns an error if we fail to define the API, or tide disco runs into an issue serving it.
+pub async fn run_event_service<TYPES: NodeType>(
+    events: impl futures::Stream<Item = Event<TYPES>> + Send + 'static,
+    url: Url,
+    sse_url: Option<Url>,
+    replay_capacity: usize,
+) -> io::Result<()> {
+    let streamer = EventStreamer::new(replay_capacity, CLIENT_LAG_LIMIT);
+
+    let api = define_api::<TYPES, EventServiceVersion>().map_err(|e| {
+        io::Error::new(
+            ErrorKind::Other,
+            format!("Failed to define the event service api: {e:?}"),
+        )
+    })?;
+    let mut app = App::<EventStreamer<TYPES>, ServerError>::with_state(streamer.clone());
+    app.register_module::<ServerError, EventServiceVersion>("hotshot-events", api)
+        .map_err(|e| {
+            io::Error::new(
+                ErrorKind::Other,
+                format!("Failed to register the event service api: {e:?}"),
+            )
+        })?;
+
+    let sse = sse_url.map(|sse_url| {
+        let sse_app = define_sse_app(streamer.clone());
+        tracing::info!("Serving server-sent events on {sse_url}");
+        tokio::spawn(async move {
+            if let Err(e) = sse_app.listen(sse_url).await {
+                tracing::error!("Failed to serve server-sent events; error = {e:#}");
+            }
+        })
+    });
+    let consumer = tokio::spawn(streamer.consume(events));
+    tracing::info!("Serving events on {url}");
+    let result = app.serve(url, EVENT_SERVICE_VERSION).await;
+    consumer.abort();
+    if let Some(sse) = sse {
+        sse.abort();
+    }
+
+    result
+}
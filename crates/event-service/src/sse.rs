@@ -0,0 +1,144 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Streaming of events as server-sent events, for clients that cannot open a WebSocket, such as
+//! the `EventSource` of a browser.
+//!
+//! Tide disco has no SSE routes, so these are served by a plain tide app.
+
+use std::sync::Mutex;
+
+use futures::{stream::BoxStream, StreamExt};
+use hotshot_types::traits::node_implementation::{ConsensusTime, NodeType};
+use tide::{sse::Sender, Request, Server, StatusCode};
+
+use crate::{
+    filter::EventFilter,
+    streamer::{EventStreamError, EventStreamer, SequencedEvent},
+};
+
+/// Paths of the SSE stream, mirroring those of the `events` WebSocket route
+const SSE_PATHS: [&str; 4] = [
+    "hotshot-events/sse",
+    "hotshot-events/sse/:kinds",
+    "hotshot-events/sse/:kinds/from/:from",
+    "hotshot-events/sse/:kinds/from/:from/to/:to",
+];
+
+/// Name of the event sent before the stream ends because the client fell too far behind
+pub const STREAM_ERROR_EVENT: &str = "stream_error";
+
+/// Define a tide app streaming the events of `streamer` as server-sent events
+///
+/// Each event is sent as an SSE event named after its kind, with its sequence number among the
+/// events of the node as ID, and the event encoded as JSON as data.
+#[must_use]
+pub fn define_sse_app<TYPES: NodeType>(
+    streamer: EventStreamer<TYPES>,
+) -> Server<EventStreamer<TYPES>> {
+    let mut app = tide::with_state(streamer);
+    for path in SSE_PATHS {
+        app.at(path).get(stream_events::<TYPES>);
+    }
+    app
+}
+
+/// The view in the route parameter `name`, if present
+fn view_param<TYPES: NodeType>(
+    req: &Request<EventStreamer<TYPES>>,
+    name: &str,
+) -> tide::Result<Option<TYPES::View>> {
+    req.param(name)
+        .ok()
+        .map(|view| {
+            view.parse().map(TYPES::View::new).map_err(|_| {
+                tide::Error::from_str(StatusCode::BadRequest, format!("Invalid view {view}"))
+            })
+        })
+        .transpose()
+}
+
+/// Parse the filter of a request to the SSE stream
+fn parse_filter<TYPES: NodeType>(
+    req: &Request<EventStreamer<TYPES>>,
+) -> tide::Result<EventFilter<TYPES>> {
+    let from = view_param(req, "from")?;
+    let to = view_param(req, "to")?;
+
+    EventFilter::parse(req.param("kinds").ok(), from, to)
+        .map_err(|message| tide::Error::from_str(StatusCode::BadRequest, message))
+}
+
+/// The ID of the last event a reconnecting client received, if it sent one
+fn last_event_id<TYPES: NodeType>(
+    req: &Request<EventStreamer<TYPES>>,
+) -> tide::Result<Option<u64>> {
+    req.header("Last-Event-ID")
+        .map(|id| {
+            id.as_str().parse().map_err(|_| {
+                tide::Error::from_str(StatusCode::BadRequest, format!("Invalid event ID {id}"))
+            })
+        })
+        .transpose()
+}
+
+/// Subscribe to the events a request asks for, and stream them as server-sent events
+///
+/// A client reconnecting with a `Last-Event-ID` resumes right after the event with that ID,
+/// rather than from the view in the path.
+async fn stream_events<TYPES: NodeType>(req: Request<EventStreamer<TYPES>>) -> tide::Result {
+    let filter = parse_filter(&req)?;
+    let after = last_event_id(&req)?;
+    let events = req
+        .state()
+        .subscribe_sequenced(filter, after)
+        .await
+        .map_err(|e| {
+            let status = match e {
+                EventStreamError::Evicted { .. } | EventStreamError::EvictedAfter { .. } => {
+                    StatusCode::Gone
+                }
+                EventStreamError::UnknownEvent { .. } => StatusCode::BadRequest,
+                EventStreamError::Lagged { .. } => StatusCode::TooManyRequests,
+            };
+            tide::Error::from_str(status, e.to_string())
+        })?;
+
+    // Tide requires the SSE handler to be `Sync`, which the subscription is not, and calls it once
+    let events = Mutex::new(Some(events));
+    Ok(tide::sse::upgrade(req, move |_req, sender| {
+        let events = events.lock().ok().and_then(|mut events| events.take());
+        async move {
+            match events {
+                Some(events) => send_events(events, &sender).await,
+                None => Ok(()),
+            }
+        }
+    }))
+}
+
+/// Send `events` over `sender` until they end, the client falls too far behind or disconnects
+async fn send_events<TYPES: NodeType>(
+    mut events: BoxStream<'static, Result<SequencedEvent<TYPES>, EventStreamError>>,
+    sender: &Sender,
+) -> tide::Result<()> {
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(SequencedEvent { sequence, event }) => {
+                let data = serde_json::to_string(&event)?;
+                sender
+                    .send(event.event.kind().name(), data, Some(&sequence.to_string()))
+                    .await?;
+            }
+            Err(e) => {
+                sender.send(STREAM_ERROR_EVENT, e.to_string(), None).await?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,343 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Fan-out of the events of a node to the clients subscribed to them, with a bounded buffer of
+//! recent events for clients to resume from
+
+use std::{collections::VecDeque, pin::pin, sync::Arc};
+
+use async_broadcast::{broadcast, InactiveReceiver, RecvError, Sender};
+use async_lock::RwLock;
+use async_trait::async_trait;
+use futures::{
+    future::BoxFuture,
+    stream::{self, BoxStream, Stream, StreamExt},
+};
+use hotshot_types::{
+    event::Event,
+    traits::node_implementation::{ConsensusTime, NodeType},
+};
+use thiserror::Error;
+use tide_disco::method::ReadState;
+
+use crate::filter::EventFilter;
+
+/// Why we cannot send a client the events it asked for
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum EventStreamError {
+    /// The client asked to resume from a view we no longer buffer the events of
+    #[error(
+        "The events of view {from} are no longer buffered, the oldest buffered view is {oldest:?}"
+    )]
+    Evicted {
+        /// The view the client asked to resume from
+        from: u64,
+        /// The oldest view we still buffer events of, if any
+        oldest: Option<u64>,
+    },
+    /// The client asked to resume after an event we no longer buffer the events following
+    #[error(
+        "The events after event {after} are no longer buffered, the oldest buffered event is \
+         {oldest:?}"
+    )]
+    EvictedAfter {
+        /// The sequence number of the last event the client received
+        after: u64,
+        /// The sequence number of the oldest event we still buffer, if any
+        oldest: Option<u64>,
+    },
+    /// The client asked to resume after an event we never sent
+    #[error("There is no event {after} to resume after")]
+    UnknownEvent {
+        /// The sequence number the client asked to resume after
+        after: u64,
+    },
+    /// The client fell so far behind that we dropped events it had not received yet
+    #[error("Fell {skipped} events behind, resume from view {last_view:?}")]
+    Lagged {
+        /// Number of events the client missed
+        skipped: u64,
+        /// The view of the last event the client received, if any
+        last_view: Option<u64>,
+    },
+}
+
+/// An event of a node, numbered in the order the node emitted it
+#[derive(Clone, Debug)]
+pub struct SequencedEvent<TYPES: NodeType> {
+    /// Number of events the node emitted before this one
+    pub sequence: u64,
+    /// The event
+    pub event: Event<TYPES>,
+}
+
+/// The most recent events of a node, in the order the node emitted them
+#[derive(Clone, Debug)]
+pub struct ReplayBuffer<TYPES: NodeType> {
+    /// Most events we keep
+    capacity: usize,
+    /// The events we keep, oldest first
+    events: VecDeque<Arc<SequencedEvent<TYPES>>>,
+    /// The newest view we dropped an event of, if we dropped any
+    evicted_through: Option<TYPES::View>,
+    /// The sequence number of the next event
+    next_sequence: u64,
+}
+
+impl<TYPES: NodeType> ReplayBuffer<TYPES> {
+    /// Create an empty buffer, which keeps the last `capacity` events
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+            evicted_through: None,
+            next_sequence: 0,
+        }
+    }
+
+    /// Number `event` and add it, dropping the oldest event if the buffer is full
+    pub fn push(&mut self, event: Event<TYPES>) -> Arc<SequencedEvent<TYPES>> {
+        let event = Arc::new(SequencedEvent {
+            sequence: self.next_sequence,
+            event,
+        });
+        self.next_sequence += 1;
+
+        if self.events.len() == self.capacity {
+            if let Some(evicted) = self.events.pop_front() {
+                self.evicted_through = self.evicted_through.max(Some(evicted.event.view_number));
+            }
+        }
+        if self.capacity > 0 {
+            self.events.push_back(Arc::clone(&event));
+        }
+        event
+    }
+
+    /// The buffered events `filter` matches, oldest first
+    ///
+    /// # Errors
+    /// Returns an error if we dropped some of the events of the view `filter` starts from.
+    pub fn replay(
+        &self,
+        filter: &EventFilter<TYPES>,
+    ) -> Result<Vec<Arc<SequencedEvent<TYPES>>>, EventStreamError> {
+        if let (Some(from), Some(evicted_through)) = (filter.from, self.evicted_through) {
+            if from <= evicted_through {
+                return Err(EventStreamError::Evicted {
+                    from: from.u64(),
+                    oldest: self
+                        .events
+                        .front()
+                        .map(|event| event.event.view_number.u64()),
+                });
+            }
+        }
+
+        Ok(self
+            .events
+            .iter()
+            .filter(|event| filter.matches(&event.event))
+            .cloned()
+            .collect())
+    }
+
+    /// The buffered events after the one numbered `after` that `filter` matches, oldest first
+    ///
+    /// # Errors
+    /// Returns an error if we dropped some of the events after `after`, or never numbered an
+    /// event `after`.
+    pub fn replay_after(
+        &self,
+        after: u64,
+        filter: &EventFilter<TYPES>,
+    ) -> Result<Vec<Arc<SequencedEvent<TYPES>>>, EventStreamError> {
+        if after >= self.next_sequence {
+            return Err(EventStreamError::UnknownEvent { after });
+        }
+        let oldest = self.events.front().map(|event| event.sequence);
+        if after + 1 < oldest.unwrap_or(self.next_sequence) {
+            return Err(EventStreamError::EvictedAfter { after, oldest });
+        }
+
+        Ok(self
+            .events
+            .iter()
+            .filter(|event| event.sequence > after && filter.matches(&event.event))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Sends the events of a node to the clients subscribed to them.
+///
+/// Clones share the same buffer and subscribers.
+pub struct EventStreamer<TYPES: NodeType> {
+    /// The most recent events, for clients to resume from
+    buffer: Arc<RwLock<ReplayBuffer<TYPES>>>,
+    /// Sends every event to the subscribed clients, dropping the oldest events for clients that
+    /// fall behind
+    sender: Sender<Arc<SequencedEvent<TYPES>>>,
+    /// Kept to subscribe new clients, and so the channel stays open without any
+    receiver: InactiveReceiver<Arc<SequencedEvent<TYPES>>>,
+}
+
+impl<TYPES: NodeType> Clone for EventStreamer<TYPES> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: Arc::clone(&self.buffer),
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+impl<TYPES: NodeType> EventStreamer<TYPES> {
+    /// Create a streamer buffering the last `replay_capacity` events, which disconnects clients
+    /// that fall more than `lag_limit` events behind
+    #[must_use]
+    pub fn new(replay_capacity: usize, lag_limit: usize) -> Self {
+        let (mut sender, receiver) = broadcast(lag_limit);
+        sender.set_overflow(true);
+
+        Self {
+            buffer: Arc::new(RwLock::new(ReplayBuffer::new(replay_capacity))),
+            sender,
+            receiver: receiver.deactivate(),
+        }
+    }
+
+    /// Number and buffer `event`, and send it to the subscribed clients
+    pub async fn handle_event(&self, event: Event<TYPES>) {
+        // Hold the lock while we send, so a client subscribing now gets the event either from the
+        // buffer or from the channel, but not both
+        let mut buffer = self.buffer.write().await;
+        let event = buffer.push(event);
+        // With overflow on, this only fails if no client is subscribed
+        let _ = self.sender.try_broadcast(event);
+    }
+
+    /// Buffer and send the events of `events`, such as the event stream of a node, until it ends
+    pub async fn consume(self, events: impl Stream<Item = Event<TYPES>>) {
+        let mut events = pin!(events);
+        while let Some(event) = events.next().await {
+            self.handle_event(event).await;
+        }
+    }
+
+    /// The buffered events `filter` matches, oldest first
+    ///
+    /// # Errors
+    /// Returns an error if we no longer buffer the events of the view `filter` starts from.
+    pub async fn replay(
+        &self,
+        filter: &EventFilter<TYPES>,
+    ) -> Result<Vec<Event<TYPES>>, EventStreamError> {
+        Ok(self
+            .buffer
+            .read()
+            .await
+            .replay(filter)?
+            .into_iter()
+            .map(|event| event.event.clone())
+            .collect())
+    }
+
+    /// Subscribe to the events `filter` matches: first the buffered ones from the view the filter
+    /// starts from, if any, then new ones as they happen.
+    ///
+    /// The stream ends once an event for a later view than the filter ends at happens, and with
+    /// an error if the client falls too far behind.
+    ///
+    /// # Errors
+    /// Returns an error if we no longer buffer the events of the view `filter` starts from.
+    pub async fn subscribe(
+        &self,
+        filter: EventFilter<TYPES>,
+    ) -> Result<BoxStream<'static, Result<Event<TYPES>, EventStreamError>>, EventStreamError> {
+        Ok(self
+            .subscribe_sequenced(filter, None)
+            .await?
+            .map(|event| event.map(|event| event.event))
+            .boxed())
+    }
+
+    /// Subscribe to the events `filter` matches, with their sequence numbers, like
+    /// [`subscribe`](Self::subscribe).
+    ///
+    /// If `after` is given, the buffered events replayed are the ones after the event numbered
+    /// `after`, rather than the ones from the view the filter starts from, so a client that
+    /// received that event resumes without receiving any event twice.
+    ///
+    /// # Errors
+    /// Returns an error if we no longer buffer the events to replay, or never numbered an event
+    /// `after`.
+    pub async fn subscribe_sequenced(
+        &self,
+        filter: EventFilter<TYPES>,
+        after: Option<u64>,
+    ) -> Result<BoxStream<'static, Result<SequencedEvent<TYPES>, EventStreamError>>, EventStreamError>
+    {
+        let buffer = self.buffer.read().await;
+        let replayed = match after {
+            Some(after) => buffer.replay_after(after, &filter)?,
+            None if filter.from.is_some() => buffer.replay(&filter)?,
+            None => Vec::new(),
+        };
+        let receiver = self.receiver.activate_cloned();
+        drop(buffer);
+
+        let last_view = replayed.last().map(|event| event.event.view_number);
+        let live = stream::unfold(Some((receiver, filter, last_view)), |state| async move {
+            let (mut receiver, filter, last_view) = state?;
+            loop {
+                match receiver.recv_direct().await {
+                    Ok(event) => {
+                        if filter.matches(&event.event) {
+                            let view = event.event.view_number;
+                            return Some((
+                                Ok((*event).clone()),
+                                Some((receiver, filter, Some(view))),
+                            ));
+                        }
+                        if filter.is_past(event.event.view_number) {
+                            return None;
+                        }
+                    }
+                    Err(RecvError::Overflowed(skipped)) => {
+                        return Some((
+                            Err(EventStreamError::Lagged {
+                                skipped,
+                                last_view: last_view.map(|view| view.u64()),
+                            }),
+                            None,
+                        ));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok(
+            stream::iter(replayed.into_iter().map(|event| Ok((*event).clone())))
+                .chain(live)
+                .boxed(),
+        )
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType> ReadState for EventStreamer<TYPES> {
+    type State = Self;
+
+    async fn read<T>(
+        &self,
+        op: impl Send + for<'a> FnOnce(&'a Self::State) -> BoxFuture<'a, T> + 'async_trait,
+    ) -> T {
+        op(self).await
+    }
+}
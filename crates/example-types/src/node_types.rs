@@ -6,16 +6,23 @@
 
 use hotshot::traits::{
     election::{
-        randomized_committee::RandomizedCommittee, static_committee::StaticCommittee,
+        dynamic::DynamicCommittee, epoch_committee::EpochCommittee,
+        randomized_committee::RandomizedCommittee,
+        stake_weighted_committee::StakeWeightedCommittee, static_committee::StaticCommittee,
         static_committee_leader_two_views::StaticCommitteeLeaderForTwoViews,
     },
-    implementations::{CombinedNetworks, Libp2pNetwork, MemoryNetwork, PushCdnNetwork},
+    implementations::{
+        CombinedNetworks, Libp2pNetwork, MemoryNetwork, PushCdnNetwork, SimulatedNetwork,
+    },
     NodeImplementation,
 };
 use hotshot_types::{
     data::{EpochNumber, ViewNumber},
     signature_key::{BLSPubKey, BuilderKey},
-    traits::node_implementation::{NodeType, Versions},
+    traits::{
+        mempool::FifoPriority,
+        node_implementation::{NodeType, Versions},
+    },
 };
 use serde::{Deserialize, Serialize};
 use vbs::version::StaticVersion;
@@ -27,6 +34,10 @@ use crate::{
     storage_types::TestStorage,
 };
 
+/// DRB difficulty of the test types, low enough for tests with short epochs to finish every DRB
+/// computation in time
+pub const TEST_DRB_DIFFICULTY: u64 = 1000;
+
 #[derive(
     Copy,
     Clone,
@@ -55,6 +66,7 @@ impl NodeType for TestTypes {
     type InstanceState = TestInstanceState;
     type Membership = StaticCommittee<TestTypes>;
     type BuilderSignatureKey = BuilderKey;
+    const DRB_DIFFICULTY: u64 = TEST_DRB_DIFFICULTY;
 }
 
 #[derive(
@@ -85,6 +97,100 @@ impl NodeType for TestTypesRandomizedLeader {
     type InstanceState = TestInstanceState;
     type Membership = RandomizedCommittee<TestTypesRandomizedLeader>;
     type BuilderSignatureKey = BuilderKey;
+    const DRB_DIFFICULTY: u64 = TEST_DRB_DIFFICULTY;
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+/// filler struct to implement node type and allow us
+/// to select our traits
+pub struct TestTypesStakeWeightedLeader;
+impl NodeType for TestTypesStakeWeightedLeader {
+    type AuctionResult = TestAuctionResult;
+    type View = ViewNumber;
+    type Epoch = EpochNumber;
+    type BlockHeader = TestBlockHeader;
+    type BlockPayload = TestBlockPayload;
+    type SignatureKey = BLSPubKey;
+    type Transaction = TestTransaction;
+    type ValidatedState = TestValidatedState;
+    type InstanceState = TestInstanceState;
+    type Membership = StakeWeightedCommittee<TestTypesStakeWeightedLeader>;
+    type BuilderSignatureKey = BuilderKey;
+    const DRB_DIFFICULTY: u64 = TEST_DRB_DIFFICULTY;
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+/// filler struct to implement node type and allow us
+/// to select our traits
+pub struct TestTypesEpochCommittee;
+impl NodeType for TestTypesEpochCommittee {
+    type AuctionResult = TestAuctionResult;
+    type View = ViewNumber;
+    type Epoch = EpochNumber;
+    type BlockHeader = TestBlockHeader;
+    type BlockPayload = TestBlockPayload;
+    type SignatureKey = BLSPubKey;
+    type Transaction = TestTransaction;
+    type ValidatedState = TestValidatedState;
+    type InstanceState = TestInstanceState;
+    type Membership = EpochCommittee<TestTypesEpochCommittee>;
+    type BuilderSignatureKey = BuilderKey;
+    const DRB_DIFFICULTY: u64 = TEST_DRB_DIFFICULTY;
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+/// filler struct to implement node type and allow us
+/// to select our traits
+pub struct TestTypesDynamicCommittee;
+impl NodeType for TestTypesDynamicCommittee {
+    type AuctionResult = TestAuctionResult;
+    type View = ViewNumber;
+    type Epoch = EpochNumber;
+    type BlockHeader = TestBlockHeader;
+    type BlockPayload = TestBlockPayload;
+    type SignatureKey = BLSPubKey;
+    type Transaction = TestTransaction;
+    type ValidatedState = TestValidatedState;
+    type InstanceState = TestInstanceState;
+    type Membership = DynamicCommittee<TestTypesDynamicCommittee>;
+    type BuilderSignatureKey = BuilderKey;
+    const DRB_DIFFICULTY: u64 = TEST_DRB_DIFFICULTY;
 }
 
 #[derive(
@@ -115,6 +221,7 @@ impl NodeType for TestConsecutiveLeaderTypes {
     type InstanceState = TestInstanceState;
     type Membership = StaticCommitteeLeaderForTwoViews<TestConsecutiveLeaderTypes>;
     type BuilderSignatureKey = BuilderKey;
+    const DRB_DIFFICULTY: u64 = TEST_DRB_DIFFICULTY;
 }
 
 /// The Push CDN implementation
@@ -129,6 +236,10 @@ pub struct MemoryImpl;
 #[derive(Clone, Debug, Deserialize, Serialize, Hash, Eq, PartialEq)]
 pub struct Libp2pImpl;
 
+/// Simulated network implementation
+#[derive(Clone, Debug, Deserialize, Serialize, Hash, Eq, PartialEq)]
+pub struct SimulatedImpl;
+
 /// Web server network implementation
 #[derive(Clone, Debug, Deserialize, Serialize, Hash, Eq, PartialEq)]
 pub struct WebImpl;
@@ -137,6 +248,10 @@ pub struct WebImpl;
 #[derive(Clone, Debug, Deserialize, Serialize, Hash, Eq, PartialEq)]
 pub struct CombinedImpl;
 
+/// Combined network implementation over two memory networks
+#[derive(Clone, Debug, Deserialize, Serialize, Hash, Eq, PartialEq)]
+pub struct CombinedMemoryImpl;
+
 /// static committee type alias
 pub type StaticMembership = StaticCommittee<TestTypes>;
 
@@ -144,24 +259,46 @@ impl<TYPES: NodeType> NodeImplementation<TYPES> for PushCdnImpl {
     type Network = PushCdnNetwork<TYPES::SignatureKey>;
     type Storage = TestStorage<TYPES>;
     type AuctionResultsProvider = TestAuctionResultsProvider<TYPES>;
+    type TransactionPriority = FifoPriority;
 }
 
 impl<TYPES: NodeType> NodeImplementation<TYPES> for MemoryImpl {
     type Network = MemoryNetwork<TYPES::SignatureKey>;
     type Storage = TestStorage<TYPES>;
     type AuctionResultsProvider = TestAuctionResultsProvider<TYPES>;
+    type TransactionPriority = FifoPriority;
+}
+
+impl<TYPES: NodeType> NodeImplementation<TYPES> for SimulatedImpl {
+    type Network = SimulatedNetwork<TYPES::SignatureKey>;
+    type Storage = TestStorage<TYPES>;
+    type AuctionResultsProvider = TestAuctionResultsProvider<TYPES>;
+    type TransactionPriority = FifoPriority;
 }
 
 impl<TYPES: NodeType> NodeImplementation<TYPES> for CombinedImpl {
     type Network = CombinedNetworks<TYPES>;
     type Storage = TestStorage<TYPES>;
     type AuctionResultsProvider = TestAuctionResultsProvider<TYPES>;
+    type TransactionPriority = FifoPriority;
+}
+
+impl<TYPES: NodeType> NodeImplementation<TYPES> for CombinedMemoryImpl {
+    type Network = CombinedNetworks<
+        TYPES,
+        MemoryNetwork<TYPES::SignatureKey>,
+        MemoryNetwork<TYPES::SignatureKey>,
+    >;
+    type Storage = TestStorage<TYPES>;
+    type AuctionResultsProvider = TestAuctionResultsProvider<TYPES>;
+    type TransactionPriority = FifoPriority;
 }
 
 impl<TYPES: NodeType> NodeImplementation<TYPES> for Libp2pImpl {
     type Network = Libp2pNetwork<TYPES>;
     type Storage = TestStorage<TYPES>;
     type AuctionResultsProvider = TestAuctionResultsProvider<TYPES>;
+    type TransactionPriority = FifoPriority;
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -178,6 +315,10 @@ impl Versions for TestVersions {
     type Marketplace = StaticVersion<0, 3>;
 
     type Epochs = StaticVersion<0, 4>;
+
+    type Compression = StaticVersion<0, 5>;
+
+    type SignedRequestViews = StaticVersion<0, 1>;
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -194,6 +335,10 @@ impl Versions for MarketplaceUpgradeTestVersions {
     type Marketplace = StaticVersion<0, 3>;
 
     type Epochs = StaticVersion<0, 4>;
+
+    type Compression = StaticVersion<0, 5>;
+
+    type SignedRequestViews = StaticVersion<0, 1>;
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -210,6 +355,10 @@ impl Versions for MarketplaceTestVersions {
     type Marketplace = StaticVersion<0, 3>;
 
     type Epochs = StaticVersion<0, 4>;
+
+    type Compression = StaticVersion<0, 5>;
+
+    type SignedRequestViews = StaticVersion<0, 1>;
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -226,6 +375,30 @@ impl Versions for EpochsTestVersions {
     type Marketplace = StaticVersion<0, 3>;
 
     type Epochs = StaticVersion<0, 4>;
+
+    type Compression = StaticVersion<0, 5>;
+
+    type SignedRequestViews = StaticVersion<0, 1>;
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct CompressionUpgradeTestVersions {}
+
+impl Versions for CompressionUpgradeTestVersions {
+    type Base = StaticVersion<0, 1>;
+    type Upgrade = StaticVersion<0, 5>;
+    const UPGRADE_HASH: [u8; 32] = [
+        1, 0, 1, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0,
+        0, 0,
+    ];
+
+    type Marketplace = StaticVersion<0, 3>;
+
+    type Epochs = StaticVersion<0, 4>;
+
+    type Compression = StaticVersion<0, 5>;
+
+    type SignedRequestViews = StaticVersion<0, 1>;
 }
 
 #[cfg(test)]
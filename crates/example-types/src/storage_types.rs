@@ -15,12 +15,14 @@ use async_trait::async_trait;
 use hotshot_types::{
     consensus::CommitmentMap,
     data::{DaProposal, Leaf, Leaf2, QuorumProposal, QuorumProposal2, VidDisperseShare},
+    drb::DrbResult,
     event::HotShotAction,
+    evidence::EquivocationEvidence,
     message::Proposal,
     simple_certificate::{QuorumCertificate2, UpgradeCertificate},
     traits::{
         node_implementation::{ConsensusTime, NodeType},
-        storage::Storage,
+        storage::{PersistedConsensusState, RetentionPolicy, Storage},
     },
     utils::View,
     vid::VidSchemeType,
@@ -42,8 +44,15 @@ pub struct TestStorageState<TYPES: NodeType> {
     proposals2: BTreeMap<TYPES::View, Proposal<TYPES, QuorumProposal2<TYPES>>>,
     high_qc: Option<hotshot_types::simple_certificate::QuorumCertificate<TYPES>>,
     high_qc2: Option<hotshot_types::simple_certificate::QuorumCertificate2<TYPES>>,
+    anchor_leaf: Option<Leaf2<TYPES>>,
+    decided_leaves: BTreeMap<TYPES::View, Leaf2<TYPES>>,
+    undecided_leaves: CommitmentMap<Leaf2<TYPES>>,
+    undecided_state: BTreeMap<TYPES::View, View<TYPES>>,
     action: TYPES::View,
     epoch: TYPES::Epoch,
+    retained_from: TYPES::View,
+    evidence: Vec<EquivocationEvidence<TYPES>>,
+    drb_results: BTreeMap<TYPES::Epoch, DrbResult>,
 }
 
 impl<TYPES: NodeType> Default for TestStorageState<TYPES> {
@@ -55,8 +64,15 @@ impl<TYPES: NodeType> Default for TestStorageState<TYPES> {
             proposals2: BTreeMap::new(),
             high_qc: None,
             high_qc2: None,
+            anchor_leaf: None,
+            decided_leaves: BTreeMap::new(),
+            undecided_leaves: CommitmentMap::new(),
+            undecided_state: BTreeMap::new(),
             action: TYPES::View::genesis(),
             epoch: TYPES::Epoch::genesis(),
+            retained_from: TYPES::View::genesis(),
+            evidence: Vec::new(),
+            drb_results: BTreeMap::new(),
         }
     }
 }
@@ -68,6 +84,8 @@ pub struct TestStorage<TYPES: NodeType> {
     pub should_return_err: bool,
     pub delay_config: DelayConfig,
     pub decided_upgrade_certificate: Arc<RwLock<Option<UpgradeCertificate<TYPES>>>>,
+    /// How much decided data `collect_garbage` keeps.
+    pub retention: RetentionPolicy,
 }
 
 impl<TYPES: NodeType> Default for TestStorage<TYPES> {
@@ -77,6 +95,7 @@ impl<TYPES: NodeType> Default for TestStorage<TYPES> {
             should_return_err: false,
             delay_config: DelayConfig::default(),
             decided_upgrade_certificate: Arc::new(RwLock::new(None)),
+            retention: RetentionPolicy::default(),
         }
     }
 }
@@ -91,6 +110,34 @@ impl<TYPES: NodeType> TestableDelay for TestStorage<TYPES> {
     }
 }
 
+impl<TYPES: NodeType> TestStorageState<TYPES> {
+    /// Serialized size of the prunable data stored for each view.
+    fn view_sizes(&self) -> BTreeMap<TYPES::View, u64> {
+        let mut sizes = BTreeMap::new();
+        let mut add = |view: TYPES::View, size: bincode::Result<u64>| {
+            *sizes.entry(view).or_default() += size.unwrap_or_default();
+        };
+        for (view, shares) in &self.vids {
+            for share in shares.values() {
+                add(*view, bincode::serialized_size(share));
+            }
+        }
+        for (view, proposal) in &self.das {
+            add(*view, bincode::serialized_size(proposal));
+        }
+        for (view, proposal) in &self.proposals {
+            add(*view, bincode::serialized_size(proposal));
+        }
+        for (view, proposal) in &self.proposals2 {
+            add(*view, bincode::serialized_size(proposal));
+        }
+        for (view, leaf) in &self.decided_leaves {
+            add(*view, bincode::serialized_size(leaf));
+        }
+        sizes
+    }
+}
+
 impl<TYPES: NodeType> TestStorage<TYPES> {
     pub async fn proposals_cloned(
         &self,
@@ -174,6 +221,7 @@ impl<TYPES: NodeType> Storage<TYPES> for TestStorage<TYPES> {
     async fn record_action(
         &self,
         view: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
         action: hotshot_types::event::HotShotAction,
     ) -> Result<()> {
         if self.should_return_err {
@@ -182,6 +230,7 @@ impl<TYPES: NodeType> Storage<TYPES> for TestStorage<TYPES> {
         let mut inner = self.inner.write().await;
         if view > inner.action && matches!(action, HotShotAction::Vote | HotShotAction::Propose) {
             inner.action = view;
+            inner.epoch = epoch;
         }
         Self::run_delay_settings_from_config(&self.delay_config).await;
         Ok(())
@@ -237,13 +286,16 @@ impl<TYPES: NodeType> Storage<TYPES> for TestStorage<TYPES> {
     }
     async fn update_undecided_state2(
         &self,
-        _leaves: CommitmentMap<Leaf2<TYPES>>,
-        _state: BTreeMap<TYPES::View, View<TYPES>>,
+        leaves: CommitmentMap<Leaf2<TYPES>>,
+        state: BTreeMap<TYPES::View, View<TYPES>>,
     ) -> Result<()> {
         if self.should_return_err {
             bail!("Failed to update high qc to storage");
         }
         Self::run_delay_settings_from_config(&self.delay_config).await;
+        let mut inner = self.inner.write().await;
+        inner.undecided_leaves = leaves;
+        inner.undecided_state = state;
         Ok(())
     }
     async fn update_decided_upgrade_certificate(
@@ -255,6 +307,195 @@ impl<TYPES: NodeType> Storage<TYPES> for TestStorage<TYPES> {
         Ok(())
     }
 
+    async fn update_anchor_leaf(&self, leaf: Leaf2<TYPES>) -> Result<()> {
+        if self.should_return_err {
+            bail!("Failed to update anchor leaf to storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        let mut inner = self.inner.write().await;
+        if inner
+            .anchor_leaf
+            .as_ref()
+            .map_or(true, |current| leaf.view_number() > current.view_number())
+        {
+            inner.anchor_leaf = Some(leaf);
+        }
+        Ok(())
+    }
+
+    async fn add_drb_result(&self, epoch: TYPES::Epoch, drb_result: DrbResult) -> Result<()> {
+        if self.should_return_err {
+            bail!("Failed to add DRB result to storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        self.inner
+            .write()
+            .await
+            .drb_results
+            .insert(epoch, drb_result);
+        Ok(())
+    }
+
+    async fn load_vid_share(
+        &self,
+        view: TYPES::View,
+        key: &TYPES::SignatureKey,
+    ) -> Result<Option<Proposal<TYPES, VidDisperseShare<TYPES>>>> {
+        if self.should_return_err {
+            bail!("Failed to load VID share from storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        let inner = self.inner.read().await;
+        Ok(inner
+            .vids
+            .get(&view)
+            .and_then(|shares| shares.get(key))
+            .cloned())
+    }
+
+    async fn load_quorum_proposal(
+        &self,
+        view: TYPES::View,
+    ) -> Result<Option<Proposal<TYPES, QuorumProposal2<TYPES>>>> {
+        if self.should_return_err {
+            bail!("Failed to load quorum proposal from storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        Ok(self.inner.read().await.proposals2.get(&view).cloned())
+    }
+
+    async fn append_decided_leaves(&self, leaves: &[Leaf2<TYPES>]) -> Result<()> {
+        if self.should_return_err {
+            bail!("Failed to append decided leaves to storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        let mut inner = self.inner.write().await;
+        let retained_from = inner.retained_from;
+        inner.decided_leaves.extend(
+            leaves
+                .iter()
+                .filter(|leaf| leaf.view_number() >= retained_from)
+                .map(|leaf| (leaf.view_number(), leaf.clone())),
+        );
+        Ok(())
+    }
+
+    async fn load_decided_leaves(
+        &self,
+        from: TYPES::View,
+        to: TYPES::View,
+        limit: usize,
+    ) -> Result<Vec<Leaf2<TYPES>>> {
+        if self.should_return_err {
+            bail!("Failed to load decided leaves from storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        if from > to {
+            return Ok(Vec::new());
+        }
+        let inner = self.inner.read().await;
+        let mut leaves: Vec<_> = inner
+            .decided_leaves
+            .range(from..=to)
+            .rev()
+            .take(limit)
+            .map(|(_, leaf)| leaf.clone())
+            .collect();
+        leaves.reverse();
+        Ok(leaves)
+    }
+
+    async fn load_decided_leaves_by_height(
+        &self,
+        from: u64,
+        to: u64,
+        limit: usize,
+    ) -> Result<Vec<Leaf2<TYPES>>> {
+        if self.should_return_err {
+            bail!("Failed to load decided leaves from storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        let inner = self.inner.read().await;
+        let mut leaves: Vec<_> = inner
+            .decided_leaves
+            .values()
+            .rev()
+            .filter(|leaf| (from..=to).contains(&leaf.height()))
+            .take(limit)
+            .cloned()
+            .collect();
+        leaves.reverse();
+        Ok(leaves)
+    }
+
+    async fn append_equivocation_evidence(
+        &self,
+        evidence: &EquivocationEvidence<TYPES>,
+    ) -> Result<()> {
+        if self.should_return_err {
+            bail!("Failed to append equivocation evidence to storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        self.inner.write().await.evidence.push(evidence.clone());
+        Ok(())
+    }
+
+    async fn load_equivocation_evidence(&self) -> Result<Vec<EquivocationEvidence<TYPES>>> {
+        if self.should_return_err {
+            bail!("Failed to load equivocation evidence from storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        Ok(self.inner.read().await.evidence.clone())
+    }
+
+    async fn collect_garbage(&self, decided_view: TYPES::View) -> Result<()> {
+        if self.should_return_err {
+            bail!("Failed to collect garbage in storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        let mut inner = self.inner.write().await;
+        let Some(horizon) = self
+            .retention
+            .horizon(decided_view, inner.view_sizes().into_iter())
+        else {
+            return Ok(());
+        };
+        if horizon <= inner.retained_from {
+            return Ok(());
+        }
+
+        inner.retained_from = horizon;
+        inner.vids.retain(|view, _| *view >= horizon);
+        inner.das.retain(|view, _| *view >= horizon);
+        inner.proposals = inner.proposals.split_off(&horizon);
+        inner.proposals2 = inner.proposals2.split_off(&horizon);
+        inner.decided_leaves = inner.decided_leaves.split_off(&horizon);
+        Ok(())
+    }
+
+    async fn retained_from(&self) -> TYPES::View {
+        self.inner.read().await.retained_from
+    }
+
+    async fn load_consensus_state(&self) -> Result<PersistedConsensusState<TYPES>> {
+        if self.should_return_err {
+            bail!("Failed to load consensus state from storage");
+        }
+        Self::run_delay_settings_from_config(&self.delay_config).await;
+        let inner = self.inner.read().await;
+        Ok(PersistedConsensusState {
+            anchor_leaf: inner.anchor_leaf.clone(),
+            high_qc: inner.high_qc2.clone(),
+            last_actioned_view: Some(inner.action),
+            last_actioned_epoch: Some(inner.epoch),
+            saved_proposals: inner.proposals2.clone(),
+            undecided_leaves: inner.undecided_leaves.clone(),
+            undecided_state: inner.undecided_state.clone(),
+            decided_upgrade_certificate: self.decided_upgrade_certificate.read().await.clone(),
+            drb_results: inner.drb_results.clone(),
+        })
+    }
+
     async fn migrate_consensus(
         &self,
         _convert_leaf: fn(Leaf<TYPES>) -> Leaf2<TYPES>,
@@ -154,6 +154,7 @@ async fn main() {
                     advertise_address: Some(advertise_address.to_string()),
                     builder_address: Some(builder_address),
                     network_config_file: None,
+                    metrics_address: None,
                 },
             )
             .await;
@@ -24,7 +24,7 @@ use hotshot::{
     traits::{
         implementations::{
             derive_libp2p_multiaddr, derive_libp2p_peer_id, CdnMetricsValue, CdnTopic,
-            CombinedNetworks, Libp2pMetricsValue, Libp2pNetwork, PushCdnNetwork,
+            CombinedNetworks, Libp2pMetricsValue, Libp2pNetwork, PrometheusMetrics, PushCdnNetwork,
             WrappedSignatureKey,
         },
         BlockPayload, NodeImplementation,
@@ -55,6 +55,7 @@ use hotshot_types::{
     traits::{
         block_contents::{BlockHeader, TestableBlock},
         election::Membership,
+        metrics::{Metrics, NoMetrics},
         network::ConnectedNetwork,
         node_implementation::{ConsensusTime, NodeType, Versions},
         states::TestableState,
@@ -345,18 +346,22 @@ pub trait RunDa<
     Leaf<TYPES>: TestableLeaf,
     Self: Sync,
 {
-    /// Initializes networking, returns self
+    /// Initializes networking, reporting network metrics to `metrics`, returns self
     async fn initialize_networking(
         config: NetworkConfig<TYPES::SignatureKey>,
         validator_config: ValidatorConfig<TYPES::SignatureKey>,
         libp2p_advertise_address: Option<String>,
+        metrics: &dyn Metrics,
     ) -> Self;
 
     /// Initializes the genesis state and HotShot instance; does not start HotShot consensus
     /// # Panics if it cannot generate a genesis block, fails to initialize HotShot, or cannot
     /// get the anchored view
     /// Note: sequencing leaf does not have state, so does not return state
-    async fn initialize_state_and_hotshot(&self) -> SystemContextHandle<TYPES, NODE, V> {
+    async fn initialize_state_and_hotshot(
+        &self,
+        metrics: &dyn Metrics,
+    ) -> SystemContextHandle<TYPES, NODE, V> {
         let initializer =
             hotshot::HotShotInitializer::<TYPES>::from_genesis::<V>(TestInstanceState::default())
                 .await
@@ -399,7 +404,7 @@ pub trait RunDa<
             memberships,
             Arc::from(network),
             initializer,
-            ConsensusMetricsValue::default(),
+            ConsensusMetricsValue::new(&*metrics.subgroup("consensus".into())),
             TestStorage::<TYPES>::default(),
             marketplace_config,
         )
@@ -492,7 +497,7 @@ pub trait RunDa<
                                     let mut timestamp_vec = timestamp.to_be_bytes().to_vec();
                                     tx.append(&mut timestamp_vec);
 
-                                    () = context
+                                    context
                                         .submit_transaction(TestTransaction::new(tx))
                                         .await
                                         .unwrap();
@@ -622,6 +627,7 @@ where
         config: NetworkConfig<TYPES::SignatureKey>,
         validator_config: ValidatorConfig<TYPES::SignatureKey>,
         _libp2p_advertise_address: Option<String>,
+        metrics: &dyn Metrics,
     ) -> PushCdnDaRun<TYPES> {
         // Convert to the Push-CDN-compatible type
         let keypair = KeyPair {
@@ -643,7 +649,7 @@ where
                 .expect("`cdn_marshal_address` needs to be supplied for a push CDN run"),
             topics,
             keypair,
-            CdnMetricsValue::default(),
+            CdnMetricsValue::new(metrics),
         )
         .expect("failed to create network");
 
@@ -708,6 +714,7 @@ where
         config: NetworkConfig<TYPES::SignatureKey>,
         validator_config: ValidatorConfig<TYPES::SignatureKey>,
         libp2p_advertise_address: Option<String>,
+        metrics: &dyn Metrics,
     ) -> Libp2pDaRun<TYPES> {
         // Extrapolate keys for ease of use
         let public_key = &validator_config.public_key;
@@ -754,7 +761,7 @@ where
             bind_address,
             public_key,
             private_key,
-            Libp2pMetricsValue::default(),
+            Libp2pMetricsValue::new(metrics),
         )
         .await
         .expect("failed to create libp2p network");
@@ -820,6 +827,7 @@ where
         config: NetworkConfig<TYPES::SignatureKey>,
         validator_config: ValidatorConfig<TYPES::SignatureKey>,
         libp2p_advertise_address: Option<String>,
+        metrics: &dyn Metrics,
     ) -> CombinedDaRun<TYPES> {
         // Initialize our Libp2p network
         let libp2p_network: Libp2pDaRun<TYPES> = <Libp2pDaRun<TYPES> as RunDa<
@@ -831,6 +839,7 @@ where
             config.clone(),
             validator_config.clone(),
             libp2p_advertise_address.clone(),
+            metrics,
         )
         .await;
 
@@ -844,6 +853,7 @@ where
             config.clone(),
             validator_config.clone(),
             libp2p_advertise_address,
+            metrics,
         )
         .await;
 
@@ -908,6 +918,23 @@ pub async fn main_entry_point<
 
     info!("Starting validator");
 
+    // Serve metrics for scraping if requested, otherwise don't bother collecting them
+    let (metrics, _metrics_server): (Box<dyn Metrics>, _) =
+        if let Some(address) = args.metrics_address {
+            let metrics = PrometheusMetrics::new();
+            let server = metrics
+                .serve(address)
+                .await
+                .expect("Failed to start the metrics server");
+            info!(
+                "Serving metrics at http://{}/status/metrics",
+                server.local_addr()
+            );
+            (Box::new(metrics), Some(server))
+        } else {
+            (NoMetrics::boxed(), None)
+        };
+
     let orchestrator_client: OrchestratorClient = OrchestratorClient::new(args.url.clone());
 
     // We assume one node will not call this twice to generate two validator_config-s with same identity.
@@ -975,10 +1002,14 @@ pub async fn main_entry_point<
     );
 
     info!("Initializing networking");
-    let run =
-        RUNDA::initialize_networking(run_config.clone(), validator_config, args.advertise_address)
-            .await;
-    let hotshot = run.initialize_state_and_hotshot().await;
+    let run = RUNDA::initialize_networking(
+        run_config.clone(),
+        validator_config,
+        args.advertise_address,
+        &*metrics,
+    )
+    .await;
+    let hotshot = run.initialize_state_and_hotshot(&*metrics).await;
 
     if let Some(task) = builder_task {
         task.start(Box::new(hotshot.event_stream()));
@@ -11,7 +11,7 @@ use hotshot_example_types::{
     auction_results_provider_types::TestAuctionResultsProvider, state_types::TestTypes,
     storage_types::TestStorage,
 };
-use hotshot_types::traits::node_implementation::NodeImplementation;
+use hotshot_types::traits::{mempool::FifoPriority, node_implementation::NodeImplementation};
 use serde::{Deserialize, Serialize};
 
 use crate::infra::Libp2pDaRun;
@@ -27,6 +27,7 @@ impl NodeImplementation<TestTypes> for NodeImpl {
     type Network = Network;
     type Storage = TestStorage<TestTypes>;
     type AuctionResultsProvider = TestAuctionResultsProvider<TestTypes>;
+    type TransactionPriority = FifoPriority;
 }
 /// convenience type alias
 pub type ThisRun = Libp2pDaRun<TestTypes>;
@@ -155,6 +155,7 @@ async fn main() {
                     advertise_address: None,
                     builder_address: Some(builder_address),
                     network_config_file: None,
+                    metrics_address: None,
                 },
             )
             .await;
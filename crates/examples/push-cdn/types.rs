@@ -9,7 +9,7 @@ use hotshot_example_types::{
     auction_results_provider_types::TestAuctionResultsProvider, state_types::TestTypes,
     storage_types::TestStorage,
 };
-use hotshot_types::traits::node_implementation::NodeType;
+use hotshot_types::traits::{mempool::FifoPriority, node_implementation::NodeType};
 use serde::{Deserialize, Serialize};
 
 use crate::infra::PushCdnDaRun;
@@ -25,6 +25,7 @@ impl NodeImplementation<TestTypes> for NodeImpl {
     type Network = Network;
     type Storage = TestStorage<TestTypes>;
     type AuctionResultsProvider = TestAuctionResultsProvider<TestTypes>;
+    type TransactionPriority = FifoPriority;
 }
 
 /// Convenience type alias
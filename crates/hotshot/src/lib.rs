@@ -50,6 +50,7 @@ use hotshot_types::{
     consensus::{Consensus, ConsensusMetricsValue, OuterConsensus, View, ViewInner},
     constants::{EVENT_CHANNEL_SIZE, EXTERNAL_EVENT_CHANNEL_SIZE},
     data::{Leaf, Leaf2, QuorumProposal, QuorumProposal2},
+    drb::DrbResult,
     event::{EventType, LeafInfo},
     message::{convert_proposal, DataMessage, Message, MessageKind, Proposal},
     simple_certificate::{QuorumCertificate, QuorumCertificate2, UpgradeCertificate},
@@ -60,9 +61,11 @@ use hotshot_types::{
         node_implementation::{ConsensusTime, NodeType},
         signature_key::SignatureKey,
         states::ValidatedState,
-        storage::Storage,
+        storage::{PersistedConsensusState, Storage},
         EncodeBytes,
     },
+    utils::epoch_from_block_number,
+    vote::HasViewNumber,
     HotShotConfig,
 };
 // -- Rexports
@@ -75,7 +78,7 @@ use tracing::{debug, instrument, trace};
 use crate::{
     tasks::{add_consensus_tasks, add_network_tasks},
     traits::NodeImplementation,
-    types::{Event, SystemContextHandle},
+    types::{Event, SystemContextHandle, TransactionTracker},
 };
 
 /// Length, in bytes, of a 512 bit hash
@@ -327,7 +330,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
         } else {
             TYPES::Epoch::new(anchored_leaf.height() / config.epoch_height + 1)
         };
-        let consensus = Consensus::new(
+        let mut consensus = Consensus::new(
             validated_state_map,
             anchored_leaf.view_number(),
             anchored_epoch,
@@ -341,6 +344,18 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
             Arc::clone(&consensus_metrics),
             config.epoch_height,
         );
+        for (epoch, drb_result) in initializer.drb_results {
+            consensus.add_drb_result(epoch, drb_result);
+            memberships.add_drb_result(epoch, drb_result);
+        }
+        // Rebuild the stake tables fixed at the epoch roots we decided before a restart. The first
+        // block of epoch `e`, at height `(e - 1) * epoch_height + 1`, is the root of epoch `e + 1`.
+        if config.epoch_height != 0 && anchored_leaf.height() != 0 {
+            let last_root_epoch = (anchored_leaf.height() - 1) / config.epoch_height + 2;
+            for epoch in 2..=last_root_epoch {
+                memberships.add_epoch_root(TYPES::Epoch::new(epoch));
+            }
+        }
 
         let consensus = Arc::new(RwLock::new(consensus));
 
@@ -464,12 +479,6 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
         }
     }
 
-    /// Emit an external event
-    async fn send_external_event(&self, event: Event<TYPES>) {
-        debug!(?event, "send_external_event");
-        broadcast_event(event, &self.external_event_stream.0).await;
-    }
-
     /// Publishes a transaction asynchronously to the network.
     ///
     /// # Errors
@@ -512,13 +521,11 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
                         api.memberships.da_committee_members(view_number, TYPES::Epoch::new(1)).iter().cloned().collect(),
                         BroadcastDelay::None,
                     ),
-                api
-                    .send_external_event(Event {
-                        view_number,
-                        event: EventType::Transactions {
-                            transactions: vec![transaction],
-                        },
-                    }),
+                // The transaction task adds it to our mempool and tells the application about it
+                broadcast_event(
+                    Arc::new(HotShotEvent::TransactionsRecv(vec![transaction])),
+                    &api.internal_event_stream.0,
+                ),
             }
         });
         Ok(())
@@ -530,6 +537,12 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
         Arc::clone(&self.consensus.inner_consensus)
     }
 
+    /// Returns a copy of the consensus metrics
+    #[must_use]
+    pub fn metrics(&self) -> Arc<ConsensusMetricsValue> {
+        Arc::clone(&self.metrics)
+    }
+
     /// Returns a copy of the instance state
     pub fn instance_state(&self) -> Arc<TYPES::InstanceState> {
         Arc::clone(&self.instance_state)
@@ -654,6 +667,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
             network: Arc::clone(&self.network),
             memberships: Arc::clone(&self.memberships),
             epoch_height: self.config.epoch_height,
+            transaction_tracker: self.new_transaction_tracker(),
         };
 
         add_network_tasks::<TYPES, I, V>(&mut handle).await;
@@ -661,6 +675,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> SystemContext<T
 
         handle
     }
+
+    /// Create a tracker for the transactions submitted through a new handle
+    fn new_transaction_tracker(&self) -> Arc<RwLock<TransactionTracker<TYPES>>> {
+        let max_age = self.config.mempool.unwrap_or_default().max_age;
+
+        Arc::new(RwLock::new(TransactionTracker::new(max_age)))
+    }
 }
 
 /// An async broadcast channel
@@ -836,6 +857,7 @@ where
             network: Arc::clone(&left_system_context.network),
             memberships: Arc::clone(&left_system_context.memberships),
             epoch_height,
+            transaction_tracker: left_system_context.new_transaction_tracker(),
         };
 
         let mut right_handle = SystemContextHandle {
@@ -848,6 +870,7 @@ where
             network: Arc::clone(&right_system_context.network),
             memberships: Arc::clone(&right_system_context.memberships),
             epoch_height,
+            transaction_tracker: right_system_context.new_transaction_tracker(),
         };
 
         // add consensus tasks to each handle, using their individual internal event streams
@@ -1000,6 +1023,9 @@ pub struct HotShotInitializer<TYPES: NodeType> {
     undecided_state: BTreeMap<TYPES::View, View<TYPES>>,
     /// Proposals we have sent out to provide to others for catchup
     saved_proposals: BTreeMap<TYPES::View, Proposal<TYPES, QuorumProposal2<TYPES>>>,
+    /// DRB results we computed or took from decided leaves, by the epoch whose leaders they
+    /// determine
+    drb_results: BTreeMap<TYPES::Epoch, DrbResult>,
 }
 
 impl<TYPES: NodeType> HotShotInitializer<TYPES> {
@@ -1028,6 +1054,7 @@ impl<TYPES: NodeType> HotShotInitializer<TYPES> {
             decided_upgrade_certificate: None,
             undecided_leaves: Vec::new(),
             undecided_state: BTreeMap::new(),
+            drb_results: BTreeMap::new(),
             instance_state,
         })
     }
@@ -1052,6 +1079,7 @@ impl<TYPES: NodeType> HotShotInitializer<TYPES> {
         decided_upgrade_certificate: Option<UpgradeCertificate<TYPES>>,
         undecided_leaves: Vec<Leaf2<TYPES>>,
         undecided_state: BTreeMap<TYPES::View, View<TYPES>>,
+        drb_results: BTreeMap<TYPES::Epoch, DrbResult>,
     ) -> Self {
         Self {
             inner: anchor_leaf,
@@ -1066,6 +1094,89 @@ impl<TYPES: NodeType> HotShotInitializer<TYPES> {
             decided_upgrade_certificate,
             undecided_leaves,
             undecided_state,
+            drb_results,
+        }
+    }
+
+    /// Reload previous state from everything persisted through `storage`.
+    ///
+    /// Consensus restarts from the last decided leaf, or from genesis if nothing was decided yet.
+    /// The start view is the one after the highest of the anchor leaf, the high QC and the last
+    /// vote or proposal recorded with [`Storage::record_action`], so a restarted node never votes
+    /// or proposes twice in the same view.
+    ///
+    /// # Errors
+    /// If the state cannot be loaded from storage, or the genesis block cannot be applied to the
+    /// default state.
+    pub async fn from_storage<V: Versions>(
+        storage: &impl Storage<TYPES>,
+        instance_state: TYPES::InstanceState,
+        epoch_height: u64,
+    ) -> Result<Self, HotShotError<TYPES>> {
+        let PersistedConsensusState {
+            anchor_leaf,
+            high_qc,
+            last_actioned_view,
+            last_actioned_epoch,
+            saved_proposals,
+            undecided_leaves,
+            undecided_state,
+            decided_upgrade_certificate,
+            drb_results,
+        } = storage.load_consensus_state().await.map_err(|e| {
+            HotShotError::InvalidState(format!("Failed to load consensus state from storage: {e}"))
+        })?;
+
+        let mut initializer = Self::from_genesis::<V>(instance_state).await?;
+
+        if let Some(anchor_leaf) = anchor_leaf {
+            // The undecided state still holds the full validated state of the anchor, as long as
+            // it was persisted after the decide.
+            initializer.validated_state = undecided_state
+                .get(&anchor_leaf.view_number())
+                .and_then(|view| view.view_inner.leaf_and_state())
+                .filter(|(commitment, _)| *commitment == anchor_leaf.commit())
+                .map(|(_, state)| Arc::clone(state));
+            initializer.state_delta = None;
+            initializer.inner = anchor_leaf;
+        }
+        if let Some(high_qc) = high_qc {
+            initializer.high_qc = high_qc;
         }
+
+        let anchor_view = initializer.inner.view_number();
+        let actioned_view = last_actioned_view.unwrap_or(TYPES::View::genesis());
+        let last_view = anchor_view
+            .max(initializer.high_qc.view_number())
+            .max(actioned_view);
+
+        initializer.start_view = if last_view == TYPES::View::genesis() {
+            last_view
+        } else {
+            last_view + 1
+        };
+        // Like the view, the epoch we start in is the latest of those of the anchor, the high QC
+        // and our last action
+        let leaf_epoch = |leaf: &Leaf2<TYPES>| epoch_from_block_number(leaf.height(), epoch_height);
+        let high_qc_epoch = undecided_leaves
+            .get(&initializer.high_qc.data.leaf_commit)
+            .map_or(0, leaf_epoch);
+        initializer.start_epoch =
+            TYPES::Epoch::new(leaf_epoch(&initializer.inner).max(high_qc_epoch))
+                .max(last_actioned_epoch.unwrap_or(TYPES::Epoch::genesis()));
+        initializer.actioned_view = actioned_view;
+        initializer.saved_proposals = saved_proposals;
+        initializer.decided_upgrade_certificate = decided_upgrade_certificate;
+        initializer.drb_results = drb_results;
+        initializer.undecided_leaves = undecided_leaves
+            .into_values()
+            .filter(|leaf| leaf.view_number() > anchor_view)
+            .collect();
+        initializer.undecided_state = undecided_state
+            .into_iter()
+            .filter(|(view, _)| *view > anchor_view)
+            .collect();
+
+        Ok(initializer)
     }
 }
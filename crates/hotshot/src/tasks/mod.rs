@@ -8,7 +8,13 @@
 
 /// Provides trait to create task states from a `SystemContextHandle`
 pub mod task_state;
-use std::{collections::BTreeMap, fmt::Debug, num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_broadcast::{broadcast, RecvError};
 use async_lock::RwLock;
@@ -22,8 +28,10 @@ use hotshot_task::task::Task;
 use hotshot_task_impls::rewind::RewindTaskState;
 use hotshot_task_impls::{
     da::DaTaskState,
+    equivocation::EquivocationTaskState,
     events::HotShotEvent,
     network::{NetworkEventTaskState, NetworkMessageTaskState},
+    rate_limit::RateLimiter,
     request::NetworkRequestState,
     response::{run_response_task, NetworkResponseState},
     transactions::TransactionTaskState,
@@ -66,7 +74,7 @@ pub async fn add_request_network_task<
 >(
     handle: &mut SystemContextHandle<TYPES, I, V>,
 ) {
-    let state = NetworkRequestState::<TYPES, I>::create_from(handle).await;
+    let state = NetworkRequestState::<TYPES, I, V>::create_from(handle).await;
 
     let task = Task::new(
         state,
@@ -80,18 +88,23 @@ pub async fn add_request_network_task<
 pub fn add_response_task<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
     handle: &mut SystemContextHandle<TYPES, I, V>,
 ) {
-    let state = NetworkResponseState::<TYPES>::new(
+    let state = NetworkResponseState::<TYPES, I, V>::new(
         handle.hotshot.consensus(),
+        Arc::clone(&handle.storage),
         (*handle.hotshot.memberships).clone().into(),
         handle.public_key().clone(),
         handle.private_key().clone(),
         handle.hotshot.id,
+        handle.hotshot.config.rate_limits.request_quota,
+        handle.hotshot.upgrade_lock.clone(),
     );
-    handle.network_registry.register(run_response_task::<TYPES>(
-        state,
-        handle.internal_event_stream.1.activate_cloned(),
-        handle.internal_event_stream.0.clone(),
-    ));
+    handle
+        .network_registry
+        .register(run_response_task::<TYPES, I, V>(
+            state,
+            handle.internal_event_stream.1.activate_cloned(),
+            handle.internal_event_stream.0.clone(),
+        ));
 }
 
 /// Add a task which updates our queue length metric at a set interval
@@ -117,6 +130,38 @@ pub fn add_queue_len_task<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Vers
     handle.network_registry.register(task_handle);
 }
 
+/// Add a task which updates the status of the transactions submitted through the handle from the
+/// events it emits
+pub fn add_transaction_status_task<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    handle: &mut SystemContextHandle<TYPES, I, V>,
+) {
+    let tracker = Arc::clone(&handle.transaction_tracker);
+    let mut event_stream = handle.output_event_stream.1.activate_cloned();
+    let shutdown_signal = create_shutdown_event_monitor(handle).fuse();
+    let task_handle = spawn(async move {
+        futures::pin_mut!(shutdown_signal);
+        loop {
+            futures::select! {
+                () = shutdown_signal => {
+                    return;
+                },
+                event = event_stream.recv_direct().fuse() => {
+                    match event {
+                        Ok(event) => {
+                            tracker.write().await.handle_event(&event.event, Instant::now());
+                        }
+                        Err(RecvError::Closed) => return,
+                        Err(e) => {
+                            tracing::warn!("Transaction status task missed events: {e}");
+                        }
+                    }
+                }
+            }
+        }
+    });
+    handle.network_registry.register(task_handle);
+}
+
 /// Add the network task to handle messages and publish events.
 #[allow(clippy::missing_panics_doc)]
 pub fn add_network_message_task<
@@ -128,11 +173,14 @@ pub fn add_network_message_task<
     handle: &mut SystemContextHandle<TYPES, I, V>,
     channel: &Arc<NET>,
 ) {
-    let network_state: NetworkMessageTaskState<_> = NetworkMessageTaskState {
+    let network_state: NetworkMessageTaskState<_, _> = NetworkMessageTaskState {
         internal_event_stream: handle.internal_event_stream.0.clone(),
         external_event_stream: handle.output_event_stream.0.clone(),
         public_key: handle.public_key().clone(),
         transactions_cache: lru::LruCache::new(NonZeroUsize::new(100_000).unwrap()),
+        rate_limiter: RateLimiter::new(handle.hotshot.config.rate_limits.clone()),
+        metrics: Arc::clone(&handle.hotshot.metrics),
+        upgrade_lock: handle.hotshot.upgrade_lock.clone(),
     };
 
     let upgrade_lock = handle.hotshot.upgrade_lock.clone();
@@ -153,10 +201,10 @@ pub fn add_network_message_task<
                 }
 
                 // Wait for a message from the network
-                message = network.recv_message().fuse() => {
+                message = network.recv_message_from().fuse() => {
                     // Make sure the message did not fail
-                    let message = match message {
-                        Ok(message) => message,
+                    let (message, peer) = match message {
+                        Ok(received) => received,
                         Err(e) => {
                             tracing::error!("Failed to receive message: {:?}", e);
                             continue;
@@ -173,7 +221,7 @@ pub fn add_network_message_task<
                     };
 
                     // Handle the message
-                    state.handle_message(deserialized_message).await;
+                    state.handle_message(deserialized_message, peer).await;
                 }
             }
         }
@@ -199,6 +247,7 @@ pub fn add_network_event_task<
         membership,
         storage: Arc::clone(&handle.storage()),
         consensus: OuterConsensus::new(handle.consensus()),
+        metrics: handle.hotshot.metrics(),
         upgrade_lock: handle.hotshot.upgrade_lock.clone(),
         transmit_tasks: BTreeMap::new(),
     };
@@ -218,6 +267,7 @@ pub async fn add_consensus_tasks<TYPES: NodeType, I: NodeImplementation<TYPES>,
     handle.add_task(VidTaskState::<TYPES, I>::create_from(handle).await);
     handle.add_task(DaTaskState::<TYPES, I, V>::create_from(handle).await);
     handle.add_task(TransactionTaskState::<TYPES, I, V>::create_from(handle).await);
+    handle.add_task(EquivocationTaskState::<TYPES, I, V>::create_from(handle).await);
 
     {
         let mut upgrade_certificate_lock = handle
@@ -253,6 +303,7 @@ pub async fn add_consensus_tasks<TYPES: NodeType, I: NodeImplementation<TYPES>,
         handle.add_task(ConsensusTaskState::<TYPES, I, V>::create_from(handle).await);
     }
     add_queue_len_task(handle);
+    add_transaction_status_task(handle);
     #[cfg(feature = "rewind")]
     handle.add_task(RewindTaskState::<TYPES>::create_from(&handle).await);
 }
@@ -358,6 +409,7 @@ where
             network: Arc::clone(&hotshot.network),
             memberships: Arc::clone(&hotshot.memberships),
             epoch_height,
+            transaction_tracker: hotshot.new_transaction_tracker(),
         };
 
         add_consensus_tasks::<TYPES, I, V>(&mut handle).await;
@@ -5,14 +5,15 @@
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, VecDeque},
     sync::{atomic::AtomicBool, Arc},
 };
 
 use async_trait::async_trait;
 use chrono::Utc;
 use hotshot_task_impls::{
-    builder::BuilderClient, consensus::ConsensusTaskState, da::DaTaskState,
+    builder::BuilderClient, builder_reputation::BuilderReputation, consensus::ConsensusTaskState,
+    da::DaTaskState, equivocation::EquivocationTaskState, mempool::Mempool,
     quorum_proposal::QuorumProposalTaskState, quorum_proposal_recv::QuorumProposalRecvTaskState,
     quorum_vote::QuorumVoteTaskState, request::NetworkRequestState, rewind::RewindTaskState,
     transactions::TransactionTaskState, upgrade::UpgradeTaskState, vid::VidTaskState,
@@ -43,7 +44,7 @@ where
 
 #[async_trait]
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState<TYPES, I, V>
-    for NetworkRequestState<TYPES, I>
+    for NetworkRequestState<TYPES, I, V>
 {
     async fn create_from(handle: &SystemContextHandle<TYPES, I, V>) -> Self {
         Self {
@@ -57,6 +58,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState
             id: handle.hotshot.id,
             shutdown_flag: Arc::new(AtomicBool::new(false)),
             spawned_tasks: BTreeMap::new(),
+            latencies: Arc::default(),
+            upgrade_lock: handle.hotshot.upgrade_lock.clone(),
         }
     }
 }
@@ -147,6 +150,26 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState
             id: handle.hotshot.id,
             storage: Arc::clone(&handle.storage),
             upgrade_lock: handle.hotshot.upgrade_lock.clone(),
+            proposal_send_times: BTreeMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState<TYPES, I, V>
+    for EquivocationTaskState<TYPES, I, V>
+{
+    async fn create_from(handle: &SystemContextHandle<TYPES, I, V>) -> Self {
+        Self {
+            output_event_stream: handle.hotshot.external_event_stream.0.clone(),
+            cur_view: handle.cur_view().await,
+            cur_epoch: handle.cur_epoch().await,
+            membership: (*handle.hotshot.memberships).clone().into(),
+            storage: Arc::clone(&handle.storage),
+            upgrade_lock: handle.hotshot.upgrade_lock.clone(),
+            epoch_height: handle.hotshot.config.epoch_height,
+            id: handle.hotshot.id,
+            seen: BTreeMap::new(),
         }
     }
 }
@@ -202,6 +225,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState
                 .cloned()
                 .map(BuilderClient::new)
                 .collect(),
+            builder_reputation: BuilderReputation::new(
+                handle.hotshot.config.builder_urls.iter().cloned().collect(),
+            ),
             upgrade_lock: handle.hotshot.upgrade_lock.clone(),
             auction_results_provider: Arc::clone(
                 &handle.hotshot.marketplace_config.auction_results_provider,
@@ -211,6 +237,11 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState
                 .marketplace_config
                 .fallback_builder_url
                 .clone(),
+            mempool: handle
+                .hotshot
+                .config
+                .mempool
+                .map(|config| Mempool::new(config, Arc::new(I::TransactionPriority::default()))),
         }
     }
 }
@@ -236,6 +267,12 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState
             storage: Arc::clone(&handle.storage),
             upgrade_lock: handle.hotshot.upgrade_lock.clone(),
             epoch_height: handle.hotshot.config.epoch_height,
+            drb_computations: BTreeMap::new(),
+            leaf_catchup: None,
+            held_decides: VecDeque::new(),
+            recover_payloads: handle.hotshot.config.recover_payloads,
+            payload_recovery: None,
+            proposal_recv_times: BTreeMap::new(),
         }
     }
 }
@@ -316,6 +353,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> CreateTaskState
             id: handle.hotshot.id,
             upgrade_lock: handle.hotshot.upgrade_lock.clone(),
             epoch_height: handle.hotshot.config.epoch_height,
+            first_vote_times: BTreeMap::new(),
         }
     }
 }
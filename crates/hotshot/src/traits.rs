@@ -6,8 +6,10 @@
 
 /// Sortition trait
 pub mod election;
+mod metrics;
 mod networking;
 mod node_implementation;
+mod storage;
 
 pub use hotshot_types::traits::{BlockPayload, ValidatedState};
 pub use libp2p_networking::network::NetworkNodeConfigBuilder;
@@ -16,6 +18,7 @@ pub use node_implementation::{NodeImplementation, TestableNodeImplementation};
 
 /// Module for publicly usable implementations of the traits
 pub mod implementations {
+    pub use super::metrics::prometheus_metrics::{MetricsServer, PrometheusMetrics};
     pub use super::networking::{
         combined_network::{CombinedNetworks, UnderlyingCombinedNetworks},
         libp2p_network::{
@@ -27,5 +30,12 @@ pub mod implementations {
             CdnMetricsValue, KeyPair, ProductionDef, PushCdnNetwork, TestingDef, Topic as CdnTopic,
             WrappedSignatureKey,
         },
+        simulated_network::{
+            SimulatedLink, SimulatedNetwork, SimulatedNetworkConfig, SimulatedTopology, Simulator,
+            SIMULATION_SEED_ENV,
+        },
+    };
+    pub use super::storage::file_storage::{
+        FileStorage, FileStorageConfig, SyncMode, SyncPolicy, DEFAULT_MAX_SEGMENT_SIZE,
     };
 }
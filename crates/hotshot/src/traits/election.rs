@@ -6,10 +6,14 @@
 
 //! elections used for consensus
 
-/// Dynamic leader election with epochs.
+/// stake-weighted committee whose leaders are rotated by the distributed randomness beacon
 pub mod dynamic;
+/// stake-weighted committee whose stake table changes at epoch boundaries
+pub mod epoch_committee;
 /// leader completely randomized every view
 pub mod randomized_committee;
+/// leader sampled every view with probability proportional to stake
+pub mod stake_weighted_committee;
 /// static (round robin) committee election
 pub mod static_committee;
 /// static (round robin leader for 2 consecutive views) committee election
@@ -4,73 +4,206 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::hash::{DefaultHasher, Hash, Hasher};
-
-use hotshot_types::traits::{node_implementation::NodeType, signature_key::SignatureKey};
-use sha2::{Digest, Sha256};
-
-// TODO: Add the following consts once we bench the hash time.
-// <https://github.com/EspressoSystems/HotShot/issues/3880>
-// /// Highest number of hashes that a hardware can complete in a second.
-// const `HASHES_PER_SECOND`
-// /// Time a DRB calculation will take, in terms of number of views.
-// const `DRB_CALCULATION_NUM_VIEW`: u64 = 300;
-
-// TODO: Replace this with an accurate number calculated by `fn difficulty_level()` once we bench
-// the hash time.
-// <https://github.com/EspressoSystems/HotShot/issues/3880>
-/// Arbitrary number of times the hash function will be repeatedly called.
-const DIFFICULTY_LEVEL: u64 = 10;
-
-// TODO: Use `HASHES_PER_SECOND` * `VIEW_TIMEOUT` * `DRB_CALCULATION_NUM_VIEW` to calculate this
-// once we bench the hash time.
-// <https://github.com/EspressoSystems/HotShot/issues/3880>
-/// Difficulty level of the DRB calculation.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroU64,
+    sync::Arc,
+};
+
+use hotshot_types::{
+    drb::{DrbResult, DRB_EPOCHS_AHEAD, INITIAL_DRB_RESULT},
+    traits::{election::Membership, node_implementation::NodeType, signature_key::SignatureKey},
+    PeerConfig,
+};
+use parking_lot::RwLock;
+use utils::anytrace::*;
+
+use super::epoch_committee::{EpochCommittee, StakeTableUpdate};
+
+#[derive(Clone, Debug)]
+/// A committee whose leaders are rotated by the distributed randomness beacon
 ///
-/// Represents the number of times the hash function will be repeatedly called.
-#[must_use]
-pub fn difficulty_level() -> u64 {
-    unimplemented!("Use an arbitrary `DIFFICULTY_LEVEL` for now before we bench the hash time.");
+/// The stake tables change at epoch boundaries as in [`EpochCommittee`], but leaders are sampled
+/// by stake from the DRB result of the epoch instead of from the view number alone, so nobody
+/// can tell who leads an epoch before its seed is decided. The first [`DRB_EPOCHS_AHEAD`] epochs
+/// have no seed and use [`INITIAL_DRB_RESULT`]; for later epochs, leaders are unknown until
+/// consensus hands over the result with [`Membership::add_drb_result`].
+pub struct DynamicCommittee<T: NodeType> {
+    /// The stake tables of every epoch
+    committee: EpochCommittee<T>,
+    /// The DRB results we received, by epoch, shared by every clone
+    drb_results: Arc<RwLock<BTreeMap<T::Epoch, DrbResult>>>,
 }
 
-/// Compute the DRB result for the leader rotation.
-///
-/// This is to be started two epochs in advance and spawned in a non-blocking thread.
-///
-/// # Arguments
-/// * `drb_seed_input` - Serialized QC signature.
-#[must_use]
-pub fn compute_drb_result<TYPES: NodeType>(drb_seed_input: [u8; 32]) -> [u8; 32] {
-    let mut hash = drb_seed_input.to_vec();
-    for _iter in 0..DIFFICULTY_LEVEL {
-        // TODO: This may be optimized to avoid memcopies after we bench the hash time.
-        // <https://github.com/EspressoSystems/HotShot/issues/3880>
-        hash = Sha256::digest(hash).to_vec();
-    }
-
-    // Convert the hash to the DRB result.
-    let mut drb_result = [0u8; 32];
-    drb_result.copy_from_slice(&hash);
-    drb_result
+impl<TYPES: NodeType> DynamicCommittee<TYPES> {
+    /// Schedule `update` to take effect at the start of `epoch`.
+    ///
+    /// # Errors
+    /// Returns an error if the stake table of `epoch`, or of a later epoch, is already fixed.
+    pub fn schedule_update(
+        &self,
+        epoch: TYPES::Epoch,
+        update: StakeTableUpdate<TYPES::SignatureKey>,
+    ) -> Result<()> {
+        self.committee.schedule_update(epoch, update)
+    }
+
+    /// The DRB result the leaders of `epoch` are sampled from, if it is known
+    #[must_use]
+    pub fn drb_result(&self, epoch: TYPES::Epoch) -> Option<DrbResult> {
+        if *epoch <= DRB_EPOCHS_AHEAD {
+            return Some(INITIAL_DRB_RESULT);
+        }
+
+        self.drb_results.read().get(&epoch).copied()
+    }
 }
 
-/// Use the DRB result to get the leader.
-///
-/// The DRB result is the output of a spawned `compute_drb_result` call.
-#[must_use]
-pub fn leader<TYPES: NodeType>(
-    view_number: usize,
-    stake_table: &[<TYPES::SignatureKey as SignatureKey>::StakeTableEntry],
-    drb_result: [u8; 32],
-) -> TYPES::SignatureKey {
-    let mut hasher = DefaultHasher::new();
-    drb_result.hash(&mut hasher);
-    view_number.hash(&mut hasher);
-    #[allow(clippy::cast_possible_truncation)]
-    // TODO: Use the total stake rather than `len()` and update the indexing after switching to
-    // a weighted stake table.
-    // <https://github.com/EspressoSystems/HotShot/issues/3898>
-    let index = (hasher.finish() as usize) % stake_table.len();
-    let entry = stake_table[index].clone();
-    TYPES::SignatureKey::public_key(&entry)
+impl<TYPES: NodeType> Membership<TYPES> for DynamicCommittee<TYPES> {
+    type Error = utils::anytrace::Error;
+
+    /// Create a new election, with the given committee from the genesis epoch on
+    fn new(
+        committee_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+        da_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+    ) -> Self {
+        Self {
+            committee: EpochCommittee::new(committee_members, da_members),
+            drb_results: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+
+    /// Get the stake table for the epoch
+    fn stake_table(
+        &self,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Vec<<<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee.stake_table(epoch)
+    }
+
+    /// Get the DA stake table for the epoch
+    fn da_stake_table(
+        &self,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Vec<<<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee.da_stake_table(epoch)
+    }
+
+    /// Get all members of the committee for the view in the epoch
+    fn committee_members(
+        &self,
+        view_number: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.committee.committee_members(view_number, epoch)
+    }
+
+    /// Get all members of the DA committee for the view in the epoch
+    fn da_committee_members(
+        &self,
+        view_number: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.committee.da_committee_members(view_number, epoch)
+    }
+
+    /// Get all eligible leaders of the committee for the view in the epoch
+    fn committee_leaders(
+        &self,
+        view_number: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.committee.committee_leaders(view_number, epoch)
+    }
+
+    /// Get the stake table entry for a public key in the epoch
+    fn stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee.stake(pub_key, epoch)
+    }
+
+    /// Get the DA stake table entry for a public key in the epoch
+    fn da_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee.da_stake(pub_key, epoch)
+    }
+
+    /// Check if a node has stake in the committee in the epoch
+    fn has_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> bool {
+        self.committee.has_stake(pub_key, epoch)
+    }
+
+    /// Check if a node has stake in the DA committee in the epoch
+    fn has_da_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> bool {
+        self.committee.has_da_stake(pub_key, epoch)
+    }
+
+    /// Sample the leader for the view from the stake table and DRB result of the epoch
+    fn lookup_leader(
+        &self,
+        view_number: TYPES::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Result<TYPES::SignatureKey> {
+        let drb_result = self.drb_result(epoch).context(warn!(
+            "The DRB result for epoch {} is not available yet",
+            *epoch
+        ))?;
+
+        self.committee
+            .lookup_leader_with_drb(view_number, epoch, &drb_result)
+    }
+
+    /// Get the total number of nodes in the committee in the epoch
+    fn total_nodes(&self, epoch: <TYPES as NodeType>::Epoch) -> usize {
+        self.committee.total_nodes(epoch)
+    }
+
+    /// Get the total number of nodes in the DA committee in the epoch
+    fn da_total_nodes(&self, epoch: <TYPES as NodeType>::Epoch) -> usize {
+        self.committee.da_total_nodes(epoch)
+    }
+
+    /// Get the voting success threshold for the committee in the epoch
+    fn success_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee.success_threshold(epoch)
+    }
+
+    /// Get the voting success threshold for the DA committee in the epoch
+    fn da_success_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee.da_success_threshold(epoch)
+    }
+
+    /// Get the voting failure threshold for the committee in the epoch
+    fn failure_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee.failure_threshold(epoch)
+    }
+
+    /// Get the voting upgrade threshold for the committee in the epoch
+    fn upgrade_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee.upgrade_threshold(epoch)
+    }
+
+    /// Store the DRB result, making the leaders of the epoch known
+    fn add_drb_result(&self, epoch: <TYPES as NodeType>::Epoch, drb_result: DrbResult) {
+        self.drb_results.write().insert(epoch, drb_result);
+    }
+
+    /// Fix the stake table of the epoch, now that its epoch root is decided
+    fn add_epoch_root(&self, epoch: <TYPES as NodeType>::Epoch) {
+        self.committee.add_epoch_root(epoch);
+    }
 }
@@ -0,0 +1,417 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroU64,
+    sync::Arc,
+};
+
+use hotshot_types::{
+    drb::DrbResult,
+    traits::{
+        election::Membership,
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+    },
+    PeerConfig,
+};
+use parking_lot::RwLock;
+use utils::anytrace::*;
+
+use super::stake_weighted_committee::StakeWeightedCommittee;
+
+/// A change to the stake table, taking effect at the start of an epoch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StakeTableUpdate<KEY: SignatureKey> {
+    /// Add a node to the stake table, or replace its stake if it is already registered
+    Register {
+        /// The node's public key, stake and state key
+        peer: PeerConfig<KEY>,
+        /// Whether the node is also a member of the DA committee
+        da: bool,
+    },
+    /// Remove a node from the stake table and the DA committee
+    Deregister(KEY),
+}
+
+/// The stake table of a single epoch
+#[derive(Debug)]
+struct EpochStakeTable<T: NodeType> {
+    /// The nodes on the committee
+    members: Vec<PeerConfig<T::SignatureKey>>,
+    /// The nodes on the DA committee
+    da_members: Vec<PeerConfig<T::SignatureKey>>,
+    /// The committee built from `members` and `da_members`
+    committee: StakeWeightedCommittee<T>,
+}
+
+impl<T: NodeType> EpochStakeTable<T> {
+    /// Build the stake table for the given members
+    ///
+    /// # Errors
+    /// Returns an error if the total stake of the members does not fit in a `u64`.
+    fn new(
+        members: Vec<PeerConfig<T::SignatureKey>>,
+        da_members: Vec<PeerConfig<T::SignatureKey>>,
+    ) -> Result<Self> {
+        let committee = StakeWeightedCommittee::try_new(members.clone(), da_members.clone())?;
+        Ok(Self {
+            members,
+            da_members,
+            committee,
+        })
+    }
+
+    /// The stake table resulting from applying `updates` to this one
+    ///
+    /// # Errors
+    /// Returns an error if the total stake of the resulting table does not fit in a `u64`.
+    fn apply<'a>(
+        &self,
+        updates: impl IntoIterator<Item = &'a StakeTableUpdate<T::SignatureKey>>,
+    ) -> Result<Self> {
+        /// The public key of a peer
+        fn key<KEY: SignatureKey>(peer: &PeerConfig<KEY>) -> KEY {
+            KEY::public_key(&peer.stake_table_entry)
+        }
+
+        let mut members = self.members.clone();
+        let mut da_members = self.da_members.clone();
+        for update in updates {
+            match update {
+                StakeTableUpdate::Register { peer, da } => {
+                    let registered = key(peer);
+                    members.retain(|member| key(member) != registered);
+                    da_members.retain(|member| key(member) != registered);
+                    members.push(peer.clone());
+                    if *da {
+                        da_members.push(peer.clone());
+                    }
+                }
+                StakeTableUpdate::Deregister(deregistered) => {
+                    members.retain(|member| key(member) != *deregistered);
+                    da_members.retain(|member| key(member) != *deregistered);
+                }
+            }
+        }
+
+        Self::new(members, da_members)
+    }
+}
+
+/// Number of epochs before the newest fixed one whose stake tables we keep, so consensus can
+/// still check certificates from around the epoch boundary
+pub const RETAINED_EPOCHS: u64 = 2;
+
+/// Mutable state shared by every clone of an [`EpochCommittee`]
+#[derive(Debug)]
+struct EpochCommitteeState<T: NodeType> {
+    /// Stake tables fixed at epoch roots, by the epoch they take effect in. These never change
+    /// again, and each is in effect until the next one.
+    stake_tables: BTreeMap<T::Epoch, Arc<EpochStakeTable<T>>>,
+    /// Updates not applied yet, by the epoch they take effect in
+    updates: BTreeMap<T::Epoch, Vec<StakeTableUpdate<T::SignatureKey>>>,
+}
+
+#[derive(Clone, Debug)]
+/// A committee whose stake table changes at epoch boundaries
+///
+/// Registrations and deregistrations are scheduled for a future epoch. Its stake table is fixed,
+/// with every update scheduled for it, when consensus decides its epoch root, see
+/// [`Membership::add_epoch_root`]. Every node decides the same chain, so every node fixes the
+/// same stake table for an epoch. Until then, lookups for the epoch see the newest fixed table.
+/// Within an epoch, leaders are sampled by stake as in [`StakeWeightedCommittee`].
+///
+/// Nothing here is persisted. After a restart, the application schedules every update again from
+/// its own record of them, before it starts consensus, and consensus then rebuilds the stake
+/// tables fixed before the restart from the epoch roots up to its anchor leaf.
+pub struct EpochCommittee<T: NodeType> {
+    /// The stake tables and pending updates, shared by every clone
+    state: Arc<RwLock<EpochCommitteeState<T>>>,
+}
+
+impl<TYPES: NodeType> EpochCommittee<TYPES> {
+    /// Schedule `update` to take effect at the start of `epoch`.
+    ///
+    /// Updates for the same epoch are applied in the order they were scheduled.
+    ///
+    /// # Errors
+    /// Returns an error if the stake table of `epoch`, or of a later epoch, is already fixed.
+    /// Updates must be scheduled before consensus decides the epoch root of `epoch`, the first
+    /// block of the epoch before it.
+    pub fn schedule_update(
+        &self,
+        epoch: TYPES::Epoch,
+        update: StakeTableUpdate<TYPES::SignatureKey>,
+    ) -> Result<()> {
+        let mut state = self.state.write();
+        if let Some(last_fixed) = state.stake_tables.keys().next_back() {
+            ensure!(
+                epoch > *last_fixed,
+                warn!(
+                    "Cannot schedule a stake table update for epoch {}: the stake table of epoch \
+                     {} is already fixed",
+                    *epoch, **last_fixed
+                )
+            );
+        }
+        state.updates.entry(epoch).or_default().push(update);
+
+        Ok(())
+    }
+
+    /// The last epoch whose stake table is fixed
+    #[must_use]
+    pub fn last_fixed_epoch(&self) -> TYPES::Epoch {
+        self.state
+            .read()
+            .stake_tables
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or_else(TYPES::Epoch::genesis)
+    }
+
+    /// Sample the leader for the view from the stake table of the epoch, using the output of the
+    /// DRB as randomness
+    ///
+    /// # Errors
+    /// Returns an error if no node has stake in the epoch.
+    pub fn lookup_leader_with_drb(
+        &self,
+        view_number: TYPES::View,
+        epoch: TYPES::Epoch,
+        drb_result: &DrbResult,
+    ) -> Result<TYPES::SignatureKey> {
+        self.committee(epoch)
+            .committee
+            .lookup_leader_with_drb(view_number, drb_result)
+    }
+
+    /// Fix the stake table of `epoch` by applying every update scheduled up to it, and drop the
+    /// stake tables of epochs more than [`RETAINED_EPOCHS`] before it.
+    ///
+    /// Does nothing if the stake table of `epoch`, or of a later epoch, is already fixed.
+    fn fix_stake_table(&self, epoch: TYPES::Epoch) {
+        let mut state = self.state.write();
+        let Some((last_fixed, base)) = state
+            .stake_tables
+            .iter()
+            .next_back()
+            .map(|(last_fixed, base)| (*last_fixed, Arc::clone(base)))
+        else {
+            return;
+        };
+        if epoch <= last_fixed {
+            return;
+        }
+
+        let pending = state.updates.split_off(&TYPES::Epoch::new(*epoch + 1));
+        let due = std::mem::replace(&mut state.updates, pending);
+        let table = if due.is_empty() {
+            base
+        } else {
+            match base.apply(due.values().flatten()) {
+                Ok(table) => Arc::new(table),
+                Err(e) => {
+                    // Every node rejects the same updates, so they still agree on the table
+                    tracing::error!(
+                        "Rejected the stake table updates for epoch {}; error = {e}",
+                        *epoch
+                    );
+                    base
+                }
+            }
+        };
+        state.stake_tables.insert(epoch, table);
+
+        // Keep the table in effect at the oldest epoch we retain, and every later one
+        let oldest = TYPES::Epoch::new((*epoch).saturating_sub(RETAINED_EPOCHS));
+        let mut retained = state.stake_tables.split_off(&oldest);
+        if let Some(in_effect) = state.stake_tables.values().next_back() {
+            retained
+                .entry(oldest)
+                .or_insert_with(|| Arc::clone(in_effect));
+        }
+        state.stake_tables = retained;
+    }
+
+    /// The committee in effect at `epoch`: the newest stake table fixed at or before it.
+    ///
+    /// Epochs older than the ones we retain see the oldest stake table we still have.
+    fn committee(&self, epoch: TYPES::Epoch) -> Arc<EpochStakeTable<TYPES>> {
+        let state = self.state.read();
+        state
+            .stake_tables
+            .range(..=epoch)
+            .next_back()
+            .or_else(|| state.stake_tables.iter().next())
+            .map(|(_, table)| Arc::clone(table))
+            .expect("At least one stake table is always present")
+    }
+}
+
+impl<TYPES: NodeType> Membership<TYPES> for EpochCommittee<TYPES> {
+    type Error = utils::anytrace::Error;
+
+    /// Create a new election, with the given committee from the genesis epoch on
+    ///
+    /// # Panics
+    /// Panics if the total stake of the committee or of the DA committee does not fit in a
+    /// `u64`.
+    fn new(
+        committee_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+        da_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+    ) -> Self {
+        let genesis = Arc::new(
+            EpochStakeTable::new(committee_members, da_members)
+                .expect("Stake table is too large for stake-weighted thresholds"),
+        );
+
+        // The first epoch has no epoch root, so its stake table is the genesis one
+        Self {
+            state: Arc::new(RwLock::new(EpochCommitteeState {
+                stake_tables: BTreeMap::from([
+                    (TYPES::Epoch::genesis(), Arc::clone(&genesis)),
+                    (TYPES::Epoch::new(1), genesis),
+                ]),
+                updates: BTreeMap::new(),
+            })),
+        }
+    }
+
+    /// Get the stake table for the epoch
+    fn stake_table(
+        &self,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Vec<<<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee(epoch).committee.stake_table(epoch)
+    }
+
+    /// Get the DA stake table for the epoch
+    fn da_stake_table(
+        &self,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Vec<<<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee(epoch).committee.da_stake_table(epoch)
+    }
+
+    /// Get all members of the committee for the view in the epoch
+    fn committee_members(
+        &self,
+        view_number: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.committee(epoch)
+            .committee
+            .committee_members(view_number, epoch)
+    }
+
+    /// Get all members of the DA committee for the view in the epoch
+    fn da_committee_members(
+        &self,
+        view_number: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.committee(epoch)
+            .committee
+            .da_committee_members(view_number, epoch)
+    }
+
+    /// Get all eligible leaders of the committee for the view in the epoch
+    fn committee_leaders(
+        &self,
+        view_number: <TYPES as NodeType>::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.committee(epoch)
+            .committee
+            .committee_leaders(view_number, epoch)
+    }
+
+    /// Get the stake table entry for a public key in the epoch
+    fn stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee(epoch).committee.stake(pub_key, epoch)
+    }
+
+    /// Get the DA stake table entry for a public key in the epoch
+    fn da_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.committee(epoch).committee.da_stake(pub_key, epoch)
+    }
+
+    /// Check if a node has stake in the committee in the epoch
+    fn has_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> bool {
+        self.committee(epoch).committee.has_stake(pub_key, epoch)
+    }
+
+    /// Check if a node has stake in the DA committee in the epoch
+    fn has_da_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> bool {
+        self.committee(epoch).committee.has_da_stake(pub_key, epoch)
+    }
+
+    /// Sample the leader for the view from the stake table of the epoch
+    fn lookup_leader(
+        &self,
+        view_number: TYPES::View,
+        epoch: <TYPES as NodeType>::Epoch,
+    ) -> Result<TYPES::SignatureKey> {
+        self.committee(epoch)
+            .committee
+            .lookup_leader(view_number, epoch)
+    }
+
+    /// Get the total number of nodes in the committee in the epoch
+    fn total_nodes(&self, epoch: <TYPES as NodeType>::Epoch) -> usize {
+        self.committee(epoch).committee.total_nodes(epoch)
+    }
+
+    /// Get the total number of nodes in the DA committee in the epoch
+    fn da_total_nodes(&self, epoch: <TYPES as NodeType>::Epoch) -> usize {
+        self.committee(epoch).committee.da_total_nodes(epoch)
+    }
+
+    /// Get the voting success threshold for the committee in the epoch
+    fn success_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee(epoch).committee.success_threshold(epoch)
+    }
+
+    /// Get the voting success threshold for the DA committee in the epoch
+    fn da_success_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee(epoch).committee.da_success_threshold(epoch)
+    }
+
+    /// Get the voting failure threshold for the committee in the epoch
+    fn failure_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee(epoch).committee.failure_threshold(epoch)
+    }
+
+    /// Get the voting upgrade threshold for the committee in the epoch
+    fn upgrade_threshold(&self, epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        self.committee(epoch).committee.upgrade_threshold(epoch)
+    }
+
+    /// Fix the stake table of the epoch, now that its epoch root is decided
+    fn add_epoch_root(&self, epoch: <TYPES as NodeType>::Epoch) {
+        self.fix_stake_table(epoch);
+    }
+}
@@ -0,0 +1,345 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{cmp::max, collections::BTreeMap, num::NonZeroU64};
+
+use hotshot_types::{
+    drb::{leader_randomness, DrbResult},
+    traits::{
+        election::Membership,
+        node_implementation::NodeType,
+        signature_key::{SignatureKey, StakeTableEntryType},
+    },
+    PeerConfig,
+};
+use primitive_types::{U256, U512};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use utils::anytrace::*;
+
+/// Convert an amount of stake into a vote threshold.
+///
+/// The stake must fit in a `u64`, which [`StakeWeightedCommittee::try_new`] ensures for every
+/// fraction of the total stake we take.
+fn stake_threshold(stake: U256) -> NonZeroU64 {
+    NonZeroU64::new(max(stake.as_u64(), 1)).unwrap()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+/// The stake-weighted committee election
+///
+/// Leaders are sampled with probability proportional to their stake, and the voting thresholds
+/// are fractions of the total stake rather than of the number of nodes.
+pub struct StakeWeightedCommittee<T: NodeType> {
+    /// The nodes eligible for leadership.
+    /// NOTE: This is currently a hack because the DA leader needs to be the quorum
+    /// leader but without voting rights.
+    eligible_leaders: Vec<<T::SignatureKey as SignatureKey>::StakeTableEntry>,
+
+    /// Running total of the stake of `eligible_leaders`, used to sample a leader by stake
+    cumulative_leader_stake: Vec<U256>,
+
+    /// The nodes on the committee and their stake
+    stake_table: Vec<<T::SignatureKey as SignatureKey>::StakeTableEntry>,
+
+    /// The nodes on the committee and their stake
+    da_stake_table: Vec<<T::SignatureKey as SignatureKey>::StakeTableEntry>,
+
+    /// The nodes on the committee and their stake, indexed by public key
+    indexed_stake_table:
+        BTreeMap<T::SignatureKey, <T::SignatureKey as SignatureKey>::StakeTableEntry>,
+
+    /// The nodes on the committee and their stake, indexed by public key
+    indexed_da_stake_table:
+        BTreeMap<T::SignatureKey, <T::SignatureKey as SignatureKey>::StakeTableEntry>,
+
+    /// Total stake of the committee
+    total_stake: U256,
+
+    /// Total stake of the DA committee
+    total_da_stake: U256,
+}
+
+impl<TYPES: NodeType> StakeWeightedCommittee<TYPES> {
+    /// Sample a leader from 512 bits of randomness, weighted by stake.
+    ///
+    /// This follows `StakeTableScheme::sample`: the randomness is reduced modulo the total stake,
+    /// and the leader is the key whose share of the cumulative stake contains the result.
+    fn sample_leader(
+        &self,
+        randomness: &[u8; 64],
+    ) -> Option<&<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        let total = *self.cumulative_leader_stake.last()?;
+        let position =
+            U256::try_from(U512::from_big_endian(randomness) % U512::from(total)).ok()?;
+
+        let index = self
+            .cumulative_leader_stake
+            .partition_point(|stake| *stake <= position);
+        self.eligible_leaders.get(index)
+    }
+
+    /// Create a new election, unless the total stake of the committee or of the DA committee
+    /// does not fit in a `u64`.
+    ///
+    /// # Errors
+    /// Returns an error if either total stake does not fit in a `u64`, as the vote thresholds
+    /// could not represent a fraction of it.
+    pub fn try_new(
+        committee_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+        da_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+    ) -> Result<Self> {
+        // For each eligible leader, get the stake table entry
+        let eligible_leaders: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> =
+            committee_members
+                .iter()
+                .map(|member| member.stake_table_entry.clone())
+                .filter(|entry| entry.stake() > U256::zero())
+                .collect();
+
+        // Sum up the stake of the eligible leaders, in order
+        let cumulative_leader_stake: Vec<U256> = eligible_leaders
+            .iter()
+            .scan(U256::zero(), |total, entry| {
+                *total = total.saturating_add(entry.stake());
+                Some(*total)
+            })
+            .collect();
+
+        // For each member, get the stake table entry
+        let members: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> =
+            committee_members
+                .iter()
+                .map(|member| member.stake_table_entry.clone())
+                .filter(|entry| entry.stake() > U256::zero())
+                .collect();
+
+        // For each member, get the stake table entry
+        let da_members: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> = da_members
+            .iter()
+            .map(|member| member.stake_table_entry.clone())
+            .filter(|entry| entry.stake() > U256::zero())
+            .collect();
+
+        // Index the stake table by public key
+        let indexed_stake_table: BTreeMap<
+            TYPES::SignatureKey,
+            <TYPES::SignatureKey as SignatureKey>::StakeTableEntry,
+        > = members
+            .iter()
+            .map(|entry| (TYPES::SignatureKey::public_key(entry), entry.clone()))
+            .collect();
+
+        // Index the stake table by public key
+        let indexed_da_stake_table: BTreeMap<
+            TYPES::SignatureKey,
+            <TYPES::SignatureKey as SignatureKey>::StakeTableEntry,
+        > = da_members
+            .iter()
+            .map(|entry| (TYPES::SignatureKey::public_key(entry), entry.clone()))
+            .collect();
+
+        let total_stake = members.iter().fold(U256::zero(), |total, entry| {
+            total.saturating_add(entry.stake())
+        });
+        let total_da_stake = da_members.iter().fold(U256::zero(), |total, entry| {
+            total.saturating_add(entry.stake())
+        });
+
+        // Vote thresholds are `u64`, so rather than round them we reject stake tables they cannot
+        // represent
+        ensure!(
+            total_stake <= U256::from(u64::MAX),
+            error!("Total stake {total_stake} does not fit in a u64")
+        );
+        ensure!(
+            total_da_stake <= U256::from(u64::MAX),
+            error!("Total DA stake {total_da_stake} does not fit in a u64")
+        );
+
+        Ok(Self {
+            eligible_leaders,
+            cumulative_leader_stake,
+            stake_table: members,
+            da_stake_table: da_members,
+            indexed_stake_table,
+            indexed_da_stake_table,
+            total_stake,
+            total_da_stake,
+        })
+    }
+
+    /// Sample the leader for `view_number` from the output of the DRB, weighted by stake.
+    ///
+    /// # Errors
+    /// Returns an error if no node has stake.
+    pub fn lookup_leader_with_drb(
+        &self,
+        view_number: TYPES::View,
+        drb_result: &DrbResult,
+    ) -> Result<TYPES::SignatureKey> {
+        let leader = self
+            .sample_leader(&leader_randomness(*view_number, drb_result))
+            .context(error!("No node with stake is eligible to lead"))?;
+
+        Ok(TYPES::SignatureKey::public_key(leader))
+    }
+}
+
+impl<TYPES: NodeType> Membership<TYPES> for StakeWeightedCommittee<TYPES> {
+    type Error = utils::anytrace::Error;
+
+    /// Create a new election
+    ///
+    /// # Panics
+    /// Panics if the total stake of the committee or of the DA committee does not fit in a
+    /// `u64`, see [`StakeWeightedCommittee::try_new`].
+    fn new(
+        committee_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+        da_members: Vec<PeerConfig<<TYPES as NodeType>::SignatureKey>>,
+    ) -> Self {
+        Self::try_new(committee_members, da_members)
+            .expect("Stake table is too large for stake-weighted thresholds")
+    }
+
+    /// Get the stake table for the current view
+    fn stake_table(
+        &self,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> Vec<<<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.stake_table.clone()
+    }
+
+    /// Get the stake table for the current view
+    fn da_stake_table(
+        &self,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> Vec<<<TYPES as NodeType>::SignatureKey as SignatureKey>::StakeTableEntry> {
+        self.da_stake_table.clone()
+    }
+
+    /// Get all members of the committee for the current view
+    fn committee_members(
+        &self,
+        _view_number: <TYPES as NodeType>::View,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> std::collections::BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.stake_table
+            .iter()
+            .map(TYPES::SignatureKey::public_key)
+            .collect()
+    }
+
+    /// Get all members of the committee for the current view
+    fn da_committee_members(
+        &self,
+        _view_number: <TYPES as NodeType>::View,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> std::collections::BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.da_stake_table
+            .iter()
+            .map(TYPES::SignatureKey::public_key)
+            .collect()
+    }
+
+    /// Get all eligible leaders of the committee for the current view
+    fn committee_leaders(
+        &self,
+        _view_number: <TYPES as NodeType>::View,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> std::collections::BTreeSet<<TYPES as NodeType>::SignatureKey> {
+        self.eligible_leaders
+            .iter()
+            .map(TYPES::SignatureKey::public_key)
+            .collect()
+    }
+
+    /// Get the stake table entry for a public key
+    fn stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        // Only return the stake if it is above zero
+        self.indexed_stake_table.get(pub_key).cloned()
+    }
+
+    /// Get the stake table entry for a public key
+    fn da_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
+        // Only return the stake if it is above zero
+        self.indexed_da_stake_table.get(pub_key).cloned()
+    }
+
+    /// Check if a node has stake in the committee
+    fn has_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> bool {
+        self.indexed_stake_table
+            .get(pub_key)
+            .is_some_and(|x| x.stake() > U256::zero())
+    }
+
+    /// Check if a node has stake in the committee
+    fn has_da_stake(
+        &self,
+        pub_key: &<TYPES as NodeType>::SignatureKey,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> bool {
+        self.indexed_da_stake_table
+            .get(pub_key)
+            .is_some_and(|x| x.stake() > U256::zero())
+    }
+
+    /// Sample the leader for the view, weighted by stake
+    fn lookup_leader(
+        &self,
+        view_number: TYPES::View,
+        _epoch: <TYPES as NodeType>::Epoch,
+    ) -> Result<TYPES::SignatureKey> {
+        let mut rng = StdRng::seed_from_u64(*view_number);
+        let mut randomness = [0u8; 64];
+        rng.fill_bytes(&mut randomness);
+
+        let leader = self
+            .sample_leader(&randomness)
+            .context(error!("No node with stake is eligible to lead"))?;
+
+        Ok(TYPES::SignatureKey::public_key(leader))
+    }
+
+    /// Get the total number of nodes in the committee
+    fn total_nodes(&self, _epoch: <TYPES as NodeType>::Epoch) -> usize {
+        self.stake_table.len()
+    }
+    /// Get the total number of nodes in the committee
+    fn da_total_nodes(&self, _epoch: <TYPES as NodeType>::Epoch) -> usize {
+        self.da_stake_table.len()
+    }
+    /// Get the voting success threshold for the committee
+    fn success_threshold(&self, _epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        stake_threshold(self.total_stake * 2 / 3 + 1)
+    }
+
+    /// Get the voting success threshold for the committee
+    fn da_success_threshold(&self, _epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        stake_threshold(self.total_da_stake * 2 / 3 + 1)
+    }
+
+    /// Get the voting failure threshold for the committee
+    fn failure_threshold(&self, _epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        stake_threshold(self.total_stake / 3 + 1)
+    }
+
+    /// Get the voting upgrade threshold for the committee
+    fn upgrade_threshold(&self, _epoch: <TYPES as NodeType>::Epoch) -> NonZeroU64 {
+        stake_threshold(max(self.total_stake * 9 / 10, self.total_stake * 2 / 3 + 1))
+    }
+}
@@ -0,0 +1,14 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Metrics implementations
+//!
+//! This module contains implementations of the [`Metrics`](hotshot_types::traits::metrics::Metrics)
+//! trait. Currently this includes
+//! - [`PrometheusMetrics`](prometheus_metrics::PrometheusMetrics), backed by a Prometheus
+//!   registry that can be scraped over HTTP.
+
+pub mod prometheus_metrics;
@@ -0,0 +1,417 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! A [`Metrics`] implementation backed by a Prometheus registry.
+//!
+//! Every metric is registered under its name, prefixed by the names of the subgroups it was
+//! created in, joined with underscores. Characters Prometheus does not allow in a name are
+//! replaced by underscores. Creating a metric that already exists returns a handle to the
+//! existing one, so several components can share a registry without coordinating.
+//!
+//! Text metrics follow the Prometheus convention for info metrics: a gauge with the value 1,
+//! carrying the text in its labels.
+//!
+//! The registry can be exported in the Prometheus text format with
+//! [`PrometheusMetrics::export`], or served to a Prometheus scraper at `GET /status/metrics`
+//! with [`PrometheusMetrics::serve`].
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_lock::RwLock;
+use futures::FutureExt;
+use hotshot_types::traits::metrics::{
+    Counter, CounterFamily, Gauge, GaugeFamily, Histogram, HistogramFamily, Metrics, MetricsFamily,
+    NoMetrics, TextFamily,
+};
+use parking_lot::Mutex;
+use prometheus::{
+    core::Collector, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use tide_disco::{error::ServerError, Api, App};
+use tokio::{spawn, task::JoinHandle};
+use tracing::warn;
+use vbs::version::{StaticVersion, StaticVersionType};
+
+/// Version of the metrics API
+type MetricsApiVersion = StaticVersion<0, 1>;
+
+/// Replace every character Prometheus does not allow in a metric or label name with `_`
+fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// The help text of a metric, which Prometheus requires to be non-empty
+fn help(name: &str, unit_label: Option<String>) -> String {
+    match unit_label {
+        Some(unit) => format!("{name} ({unit})"),
+        None => name.to_string(),
+    }
+}
+
+/// The registry and every metric in it
+///
+/// Plain metrics are families without labels, so each kind of metric needs only one map.
+#[derive(Debug, Default)]
+struct Registered {
+    /// The registry the metrics are collected from
+    registry: Registry,
+    /// Counter families, by full name
+    counters: HashMap<String, IntCounterVec>,
+    /// Gauge and text families, by full name
+    gauges: HashMap<String, IntGaugeVec>,
+    /// Histogram families, by full name
+    histograms: HashMap<String, HistogramVec>,
+}
+
+impl Registered {
+    /// Look up the metric `name` in the map `select` picks, or create and register it.
+    ///
+    /// Returns `None` if the metric cannot be created or registered, for instance because a
+    /// metric of another kind already has the same name.
+    fn get_or_register<M: Collector + Clone + 'static>(
+        &mut self,
+        name: String,
+        select: fn(&mut Self) -> &mut HashMap<String, M>,
+        create: impl FnOnce(&str) -> prometheus::Result<M>,
+    ) -> Option<M> {
+        if let Some(metric) = select(self).get(&name) {
+            return Some(metric.clone());
+        }
+
+        let metric = create(&name).and_then(|metric| {
+            self.registry.register(Box::new(metric.clone()))?;
+            Ok(metric)
+        });
+        match metric {
+            Ok(metric) => {
+                select(self).insert(name, metric.clone());
+                Some(metric)
+            }
+            Err(e) => {
+                warn!("Failed to register metric {name}; error = {e:#}");
+                None
+            }
+        }
+    }
+}
+
+/// A [`Metrics`] implementation backed by a Prometheus registry
+///
+/// Clones and subgroups share the registry.
+#[derive(Clone, Debug, Default)]
+pub struct PrometheusMetrics {
+    /// Prefix of the names of the metrics created through this handle, empty at the root
+    namespace: String,
+    /// The registry and its metrics
+    registered: Arc<Mutex<Registered>>,
+}
+
+impl PrometheusMetrics {
+    /// Create metrics backed by a new, empty registry
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The full name of the metric `name` in this subgroup
+    fn full_name(&self, name: &str) -> String {
+        let name = sanitize(name);
+        if self.namespace.is_empty() {
+            name
+        } else {
+            format!("{}_{name}", self.namespace)
+        }
+    }
+
+    /// The counter family `name` with the label names `labels`
+    fn counters(
+        &self,
+        name: &str,
+        unit_label: Option<String>,
+        labels: &[String],
+    ) -> Option<IntCounterVec> {
+        let labels: Vec<String> = labels.iter().map(|label| sanitize(label)).collect();
+        self.registered.lock().get_or_register(
+            self.full_name(name),
+            |registered| &mut registered.counters,
+            |full_name| {
+                IntCounterVec::new(
+                    Opts::new(full_name, help(name, unit_label)),
+                    &labels.iter().map(String::as_str).collect::<Vec<_>>(),
+                )
+            },
+        )
+    }
+
+    /// The gauge family `name` with the label names `labels`
+    fn gauges(
+        &self,
+        name: &str,
+        unit_label: Option<String>,
+        labels: &[String],
+    ) -> Option<IntGaugeVec> {
+        let labels: Vec<String> = labels.iter().map(|label| sanitize(label)).collect();
+        self.registered.lock().get_or_register(
+            self.full_name(name),
+            |registered| &mut registered.gauges,
+            |full_name| {
+                IntGaugeVec::new(
+                    Opts::new(full_name, help(name, unit_label)),
+                    &labels.iter().map(String::as_str).collect::<Vec<_>>(),
+                )
+            },
+        )
+    }
+
+    /// The histogram family `name` with the label names `labels`
+    fn histograms(
+        &self,
+        name: &str,
+        unit_label: Option<String>,
+        labels: &[String],
+    ) -> Option<HistogramVec> {
+        let labels: Vec<String> = labels.iter().map(|label| sanitize(label)).collect();
+        self.registered.lock().get_or_register(
+            self.full_name(name),
+            |registered| &mut registered.histograms,
+            |full_name| {
+                HistogramVec::new(
+                    HistogramOpts::new(full_name, help(name, unit_label)),
+                    &labels.iter().map(String::as_str).collect::<Vec<_>>(),
+                )
+            },
+        )
+    }
+
+    /// Export every metric in the registry in the Prometheus text format.
+    ///
+    /// # Errors
+    /// Returns an error if the metrics cannot be encoded.
+    pub fn export(&self) -> Result<String> {
+        let families = self.registered.lock().registry.gather();
+
+        TextEncoder::new()
+            .encode_to_string(&families)
+            .context("Failed to encode metrics")
+    }
+
+    /// Serve the registry to Prometheus scrapers over HTTP, at `GET /status/metrics` on
+    /// `address`.
+    ///
+    /// The server runs in the background until the returned [`MetricsServer`] is dropped.
+    ///
+    /// # Errors
+    /// Returns an error if we fail to define the API, or `address` cannot be bound.
+    ///
+    /// # Panics
+    /// Panics if `api/metrics.toml` is not valid TOML.
+    pub async fn serve(&self, address: SocketAddr) -> Result<MetricsServer> {
+        let api_toml = toml::from_str::<toml::Value>(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/api/metrics.toml"
+        )))
+        .expect("API file is not valid toml");
+        let mut api = Api::<RwLock<Self>, ServerError, MetricsApiVersion>::new(api_toml)
+            .map_err(|e| anyhow!("Failed to define the metrics api: {e:?}"))?;
+        api.metrics("metrics", |_req, metrics| {
+            async move { Ok(Cow::Owned(metrics.registered.lock().registry.clone())) }.boxed()
+        })
+        .map_err(|e| anyhow!("Failed to define the metrics api: {e:?}"))?;
+
+        let mut app = App::<RwLock<Self>, ServerError>::with_state(RwLock::new(self.clone()));
+        app.register_module::<ServerError, MetricsApiVersion>("status", api)
+            .map_err(|e| anyhow!("Failed to register the metrics api: {e:?}"))?;
+
+        // Bind the listener ourselves, so we know the port when binding port 0
+        let listener = TcpListener::bind(address)
+            .with_context(|| format!("Failed to bind the metrics server to {address}"))?;
+        let local_addr = listener.local_addr()?;
+
+        let task = spawn(async move {
+            if let Err(e) = app.serve(listener, MetricsApiVersion::instance()).await {
+                warn!("Metrics server stopped; error = {e:#}");
+            }
+        });
+
+        Ok(MetricsServer { local_addr, task })
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn create_counter(&self, name: String, unit_label: Option<String>) -> Box<dyn Counter> {
+        PrometheusFamily(self.counters(&name, unit_label, &[])).create(vec![])
+    }
+
+    fn create_gauge(&self, name: String, unit_label: Option<String>) -> Box<dyn Gauge> {
+        let family = PrometheusFamily(self.gauges(&name, unit_label, &[]));
+        MetricsFamily::<Box<dyn Gauge>>::create(&family, vec![])
+    }
+
+    fn create_histogram(&self, name: String, unit_label: Option<String>) -> Box<dyn Histogram> {
+        PrometheusFamily(self.histograms(&name, unit_label, &[])).create(vec![])
+    }
+
+    fn create_text(&self, name: String) {
+        self.text_family(name, vec![]).create(vec![]);
+    }
+
+    fn counter_family(&self, name: String, labels: Vec<String>) -> Box<dyn CounterFamily> {
+        Box::new(PrometheusFamily(self.counters(&name, None, &labels)))
+    }
+
+    fn gauge_family(&self, name: String, labels: Vec<String>) -> Box<dyn GaugeFamily> {
+        Box::new(PrometheusFamily(self.gauges(&name, None, &labels)))
+    }
+
+    fn histogram_family(&self, name: String, labels: Vec<String>) -> Box<dyn HistogramFamily> {
+        Box::new(PrometheusFamily(self.histograms(&name, None, &labels)))
+    }
+
+    fn text_family(&self, name: String, labels: Vec<String>) -> Box<dyn TextFamily> {
+        Box::new(PrometheusFamily(self.gauges(&name, None, &labels)))
+    }
+
+    fn subgroup(&self, subgroup_name: String) -> Box<dyn Metrics> {
+        Box::new(Self {
+            namespace: self.full_name(&subgroup_name),
+            registered: Arc::clone(&self.registered),
+        })
+    }
+}
+
+/// A running metrics server, stopped when dropped
+#[derive(Debug)]
+pub struct MetricsServer {
+    /// The address the server is bound to
+    local_addr: SocketAddr,
+    /// The task running the tide disco app
+    task: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// The address the server is bound to, which tells the port when binding port 0
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A family of Prometheus metrics, or `None` if it could not be registered
+///
+/// The metrics of an unregistered family are no-ops.
+#[derive(Clone, Debug)]
+struct PrometheusFamily<V>(Option<V>);
+
+impl<V> PrometheusFamily<V> {
+    /// The metric with the given label values, or `None` if the family is unregistered or the
+    /// number of values does not match its labels
+    fn metric<M>(
+        &self,
+        labels: &[String],
+        get: impl FnOnce(&V, &[&str]) -> prometheus::Result<M>,
+    ) -> Option<M> {
+        let family = self.0.as_ref()?;
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        get(family, &labels)
+            .inspect_err(|e| warn!("Failed to create metric {labels:?}; error = {e:#}"))
+            .ok()
+    }
+}
+
+impl MetricsFamily<Box<dyn Counter>> for PrometheusFamily<IntCounterVec> {
+    fn create(&self, labels: Vec<String>) -> Box<dyn Counter> {
+        match self.metric(&labels, IntCounterVec::get_metric_with_label_values) {
+            Some(counter) => Box::new(PrometheusCounter(counter)),
+            None => Box::new(NoMetrics),
+        }
+    }
+}
+
+impl MetricsFamily<Box<dyn Gauge>> for PrometheusFamily<IntGaugeVec> {
+    fn create(&self, labels: Vec<String>) -> Box<dyn Gauge> {
+        match self.metric(&labels, IntGaugeVec::get_metric_with_label_values) {
+            Some(gauge) => Box::new(PrometheusGauge(gauge)),
+            None => Box::new(NoMetrics),
+        }
+    }
+}
+
+impl MetricsFamily<Box<dyn Histogram>> for PrometheusFamily<HistogramVec> {
+    fn create(&self, labels: Vec<String>) -> Box<dyn Histogram> {
+        match self.metric(&labels, HistogramVec::get_metric_with_label_values) {
+            Some(histogram) => Box::new(PrometheusHistogram(histogram)),
+            None => Box::new(NoMetrics),
+        }
+    }
+}
+
+impl MetricsFamily<()> for PrometheusFamily<IntGaugeVec> {
+    fn create(&self, labels: Vec<String>) {
+        if let Some(gauge) = self.metric(&labels, IntGaugeVec::get_metric_with_label_values) {
+            gauge.set(1);
+        }
+    }
+}
+
+/// A [`Counter`] backed by a Prometheus counter
+#[derive(Clone, Debug)]
+struct PrometheusCounter(IntCounter);
+
+impl Counter for PrometheusCounter {
+    fn add(&self, amount: usize) {
+        self.0.inc_by(u64::try_from(amount).unwrap_or(u64::MAX));
+    }
+}
+
+/// A [`Gauge`] backed by a Prometheus gauge
+#[derive(Clone, Debug)]
+struct PrometheusGauge(IntGauge);
+
+impl Gauge for PrometheusGauge {
+    fn set(&self, amount: usize) {
+        self.0.set(i64::try_from(amount).unwrap_or(i64::MAX));
+    }
+
+    fn update(&self, delta: i64) {
+        self.0.add(delta);
+    }
+}
+
+/// A [`Histogram`] backed by a Prometheus histogram
+#[derive(Clone, Debug)]
+struct PrometheusHistogram(prometheus::Histogram);
+
+impl Histogram for PrometheusHistogram {
+    fn add_point(&self, point: f64) {
+        self.0.observe(point);
+    }
+}
@@ -7,7 +7,7 @@
 //! Networking Implementation that has a primary and a fallback network.  If the primary
 //! Errors we will use the backup to send or receive
 use std::{
-    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
     future::Future,
     hash::{Hash, Hasher},
     num::NonZeroUsize,
@@ -21,7 +21,7 @@ use std::{
 use async_broadcast::{broadcast, InactiveReceiver, Sender};
 use async_lock::RwLock;
 use async_trait::async_trait;
-use futures::{join, select, FutureExt};
+use futures::{future::join_all, join, select, FutureExt};
 #[cfg(feature = "hotshot-testing")]
 use hotshot_types::traits::network::{
     AsyncGenerator, NetworkReliability, TestableNetworkingImplementation,
@@ -34,11 +34,12 @@ use hotshot_types::{
     },
     data::ViewNumber,
     traits::{
-        network::{BroadcastDelay, ConnectedNetwork, Topic},
+        network::{BroadcastDelay, ConnectedNetwork, PeerMisbehaviour, Topic},
         node_implementation::NodeType,
     },
     BoxSyncFuture,
 };
+use libp2p_identity::PeerId;
 use lru::LruCache;
 use parking_lot::RwLock as PlRwLock;
 use tokio::{spawn, sync::mpsc::error::TrySendError, time::sleep};
@@ -58,11 +59,18 @@ pub fn calculate_hash_of<T: Hash>(t: &T) -> u64 {
 type DelayedTasksChannelsMap = Arc<RwLock<BTreeMap<u64, (Sender<()>, InactiveReceiver<()>)>>>;
 
 /// A communication channel with 2 networks, where we can fall back to the slower network if the
-/// primary fails
+/// primary fails.
+///
+/// By default the primary network is the CDN and the secondary is libp2p, but any two networks
+/// can be combined.
 #[derive(Clone)]
-pub struct CombinedNetworks<TYPES: NodeType> {
+pub struct CombinedNetworks<
+    TYPES: NodeType,
+    P = PushCdnNetwork<<TYPES as NodeType>::SignatureKey>,
+    S = Libp2pNetwork<TYPES>,
+> {
     /// The two networks we'll use for send/recv
-    networks: Arc<UnderlyingCombinedNetworks<TYPES>>,
+    networks: Arc<UnderlyingCombinedNetworks<P, S>>,
 
     /// Last n seen messages to prevent processing duplicates
     message_cache: Arc<PlRwLock<LruCache<u64, ()>>>,
@@ -81,20 +89,23 @@ pub struct CombinedNetworks<TYPES: NodeType> {
 
     /// How many times messages were sent on secondary without delay because primary is down
     no_delay_counter: Arc<AtomicU64>,
+
+    /// The peers our messages are delivered to, if not all of them
+    reachable: Arc<PlRwLock<Option<HashSet<TYPES::SignatureKey>>>>,
 }
 
-impl<TYPES: NodeType> CombinedNetworks<TYPES> {
+impl<TYPES: NodeType, P, S> CombinedNetworks<TYPES, P, S>
+where
+    P: ConnectedNetwork<TYPES::SignatureKey>,
+    S: ConnectedNetwork<TYPES::SignatureKey>,
+{
     /// Constructor
     ///
     /// # Panics
     ///
     /// Panics if `COMBINED_NETWORK_CACHE_SIZE` is 0
     #[must_use]
-    pub fn new(
-        primary_network: PushCdnNetwork<TYPES::SignatureKey>,
-        secondary_network: Libp2pNetwork<TYPES>,
-        delay_duration: Option<Duration>,
-    ) -> Self {
+    pub fn new(primary_network: P, secondary_network: S, delay_duration: Option<Duration>) -> Self {
         // Create networks from the ones passed in
         let networks = Arc::from(UnderlyingCombinedNetworks(
             primary_network,
@@ -113,18 +124,27 @@ impl<TYPES: NodeType> CombinedNetworks<TYPES> {
             )),
             delayed_tasks_channels: Arc::default(),
             no_delay_counter: Arc::new(AtomicU64::new(0)),
+            reachable: Arc::default(),
         }
     }
 
+    /// Whether the messages we send to `peer` are delivered
+    fn reaches(&self, peer: &TYPES::SignatureKey) -> bool {
+        self.reachable
+            .read()
+            .as_ref()
+            .map_or(true, |reachable| reachable.contains(peer))
+    }
+
     /// Get a ref to the primary network
     #[must_use]
-    pub fn primary(&self) -> &PushCdnNetwork<TYPES::SignatureKey> {
+    pub fn primary(&self) -> &P {
         &self.networks.0
     }
 
     /// Get a ref to the backup network
     #[must_use]
-    pub fn secondary(&self) -> &Libp2pNetwork<TYPES> {
+    pub fn secondary(&self) -> &S {
         &self.networks.1
     }
 
@@ -242,17 +262,19 @@ impl<TYPES: NodeType> CombinedNetworks<TYPES> {
     }
 }
 
-/// Wrapper for the tuple of `PushCdnNetwork` and `Libp2pNetwork`
+/// Wrapper for the tuple of the primary and secondary networks
 /// We need this so we can impl `TestableNetworkingImplementation`
 /// on the tuple
 #[derive(Clone)]
-pub struct UnderlyingCombinedNetworks<TYPES: NodeType>(
-    pub PushCdnNetwork<TYPES::SignatureKey>,
-    pub Libp2pNetwork<TYPES>,
-);
+pub struct UnderlyingCombinedNetworks<P, S>(pub P, pub S);
 
 #[cfg(feature = "hotshot-testing")]
-impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetworks<TYPES> {
+impl<TYPES: NodeType, P, S> TestableNetworkingImplementation<TYPES>
+    for CombinedNetworks<TYPES, P, S>
+where
+    P: ConnectedNetwork<TYPES::SignatureKey> + TestableNetworkingImplementation<TYPES>,
+    S: ConnectedNetwork<TYPES::SignatureKey> + TestableNetworkingImplementation<TYPES>,
+{
     fn generator(
         expected_node_count: usize,
         num_bootstrap: usize,
@@ -262,7 +284,7 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
         secondary_network_delay: Duration,
     ) -> AsyncGenerator<Arc<Self>> {
         let generators = (
-            <PushCdnNetwork<TYPES::SignatureKey> as TestableNetworkingImplementation<TYPES>>::generator(
+            <P as TestableNetworkingImplementation<TYPES>>::generator(
                 expected_node_count,
                 num_bootstrap,
                 network_id,
@@ -270,31 +292,30 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
                 None,
                 Duration::default(),
             ),
-            <Libp2pNetwork<TYPES> as TestableNetworkingImplementation<TYPES>>::generator(
+            <S as TestableNetworkingImplementation<TYPES>>::generator(
                 expected_node_count,
                 num_bootstrap,
                 network_id,
                 da_committee_size,
                 reliability_config,
                 Duration::default(),
-            )
+            ),
         );
         Box::pin(move |node_id| {
             let gen0 = generators.0(node_id);
             let gen1 = generators.1(node_id);
 
             Box::pin(async move {
-                // Generate the CDN network
-                let cdn = gen0.await;
-                let cdn = Arc::<PushCdnNetwork<TYPES::SignatureKey>>::into_inner(cdn).unwrap();
+                // Generate the primary network
+                let primary = gen0.await;
 
-                // Generate the p2p network
-                let p2p = gen1.await;
+                // Generate the secondary network
+                let secondary = gen1.await;
 
                 // Combine the two
                 let underlying_combined = UnderlyingCombinedNetworks(
-                    cdn.clone(),
-                    Arc::<Libp2pNetwork<TYPES>>::unwrap_or_clone(p2p),
+                    Arc::<P>::unwrap_or_clone(primary),
+                    Arc::<S>::unwrap_or_clone(secondary),
                 );
 
                 // We want to use the same message cache between the two networks
@@ -311,6 +332,7 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
                     delay_duration: Arc::new(RwLock::new(secondary_network_delay)),
                     delayed_tasks_channels: Arc::default(),
                     no_delay_counter: Arc::new(AtomicU64::new(0)),
+                    reachable: Arc::default(),
                 };
 
                 Arc::new(combined_network)
@@ -327,7 +349,11 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
 }
 
 #[async_trait]
-impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks<TYPES> {
+impl<TYPES: NodeType, P, S> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks<TYPES, P, S>
+where
+    P: ConnectedNetwork<TYPES::SignatureKey>,
+    S: ConnectedNetwork<TYPES::SignatureKey>,
+{
     fn pause(&self) {
         self.networks.0.pause();
     }
@@ -360,6 +386,24 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
         topic: Topic,
         broadcast_delay: BroadcastDelay,
     ) -> Result<(), NetworkError> {
+        // The underlying networks cannot leave peers out of a broadcast, so while we are
+        // partitioned we message each reachable peer directly instead
+        let reachable = self.reachable.read().clone();
+        if let Some(reachable) = reachable {
+            let results = join_all(
+                reachable
+                    .into_iter()
+                    .map(|peer| self.direct_message(message.clone(), peer)),
+            )
+            .await;
+            let errors: Vec<_> = results.into_iter().filter_map(Result::err).collect();
+            return if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(NetworkError::Multiple(errors))
+            };
+        }
+
         let primary = self.primary().clone();
         let secondary = self.secondary().clone();
         let primary_message = message.clone();
@@ -385,9 +429,11 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
     async fn da_broadcast_message(
         &self,
         message: Vec<u8>,
-        recipients: Vec<TYPES::SignatureKey>,
+        mut recipients: Vec<TYPES::SignatureKey>,
         broadcast_delay: BroadcastDelay,
     ) -> Result<(), NetworkError> {
+        recipients.retain(|recipient| self.reaches(recipient));
+
         let primary = self.primary().clone();
         let secondary = self.secondary().clone();
         let primary_message = message.clone();
@@ -415,6 +461,10 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
         message: Vec<u8>,
         recipient: TYPES::SignatureKey,
     ) -> Result<(), NetworkError> {
+        if !self.reaches(&recipient) {
+            return Ok(());
+        }
+
         let primary = self.primary().clone();
         let secondary = self.secondary().clone();
         let primary_message = message.clone();
@@ -435,8 +485,10 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
 
     async fn vid_broadcast_message(
         &self,
-        messages: HashMap<TYPES::SignatureKey, Vec<u8>>,
+        mut messages: HashMap<TYPES::SignatureKey, Vec<u8>>,
     ) -> Result<(), NetworkError> {
+        messages.retain(|recipient, _| self.reaches(recipient));
+
         self.networks.0.vid_broadcast_message(messages).await
     }
 
@@ -445,13 +497,22 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
     /// # Errors
     /// Does not error
     async fn recv_message(&self) -> Result<Vec<u8>, NetworkError> {
+        Ok(self.recv_message_from().await?.0)
+    }
+
+    /// Receive one or many messages from the underlying network, along with the peer the
+    /// network they came through authenticated them as coming from.
+    ///
+    /// # Errors
+    /// Does not error
+    async fn recv_message_from(&self) -> Result<(Vec<u8>, Option<PeerId>), NetworkError> {
         loop {
             // Receive from both networks
-            let mut primary_fut = self.primary().recv_message().fuse();
-            let mut secondary_fut = self.secondary().recv_message().fuse();
+            let mut primary_fut = self.primary().recv_message_from().fuse();
+            let mut secondary_fut = self.secondary().recv_message_from().fuse();
 
             // Wait for one to return a message
-            let message = select! {
+            let (message, peer) = select! {
                 p = primary_fut => p?,
                 s = secondary_fut => s?,
             };
@@ -461,7 +522,7 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
 
             // Check if the hash is in the cache and update the cache
             if self.message_cache.write().put(message_hash, ()).is_none() {
-                break Ok(message);
+                break Ok((message, peer));
             }
         }
     }
@@ -495,14 +556,23 @@ impl<TYPES: NodeType> ConnectedNetwork<TYPES::SignatureKey> for CombinedNetworks
                 }
             }
         });
-        // Run `update_view` logic for the libp2p network
-        self.networks
-            .1
-            .update_view::<T>(view, epoch, membership)
-            .await;
+        // Run `update_view` logic for the underlying networks, e.g. libp2p looks up future leaders
+        join!(
+            self.primary().update_view::<T>(view, epoch, membership),
+            self.secondary().update_view::<T>(view, epoch, membership)
+        );
     }
 
     fn is_primary_down(&self) -> bool {
         self.primary_down.load(Ordering::Relaxed)
     }
+
+    fn report_peer(&self, peer: &PeerId, misbehaviour: PeerMisbehaviour) {
+        self.primary().report_peer(peer, misbehaviour);
+        self.secondary().report_peer(peer, misbehaviour);
+    }
+
+    fn set_reachable_peers(&self, reachable: Option<HashSet<TYPES::SignatureKey>>) {
+        *self.reachable.write() = reachable;
+    }
 }
@@ -39,7 +39,7 @@ use hotshot_types::{
     traits::{
         election::Membership,
         metrics::{Counter, Gauge, Metrics, NoMetrics},
-        network::{ConnectedNetwork, NetworkError, Topic},
+        network::{ConnectedNetwork, NetworkError, PeerMisbehaviour, Topic},
         node_implementation::{ConsensusTime, NodeType},
         signature_key::{PrivateSignatureKey, SignatureKey},
     },
@@ -147,9 +147,9 @@ struct Libp2pNetworkInner<T: NodeType> {
     /// handle to control the network
     handle: Arc<NetworkNodeHandle<T>>,
     /// Message Receiver
-    receiver: Mutex<Receiver<Vec<u8>>>,
+    receiver: Mutex<Receiver<(Vec<u8>, Option<PeerId>)>>,
     /// Sender for broadcast messages
-    sender: Sender<Vec<u8>>,
+    sender: Sender<(Vec<u8>, Option<PeerId>)>,
     /// Sender for node lookup (relevant view number, key of node) (None for shutdown)
     node_lookup_send: Sender<Option<(ViewNumber, T::SignatureKey)>>,
     /// this is really cheating to enable local tests
@@ -664,16 +664,16 @@ impl<T: NodeType> Libp2pNetwork<T> {
     fn handle_recvd_events(
         &self,
         msg: NetworkEvent,
-        sender: &Sender<Vec<u8>>,
+        sender: &Sender<(Vec<u8>, Option<PeerId>)>,
     ) -> Result<(), NetworkError> {
         match msg {
-            GossipMsg(msg) => {
-                sender.try_send(msg).map_err(|err| {
+            GossipMsg(msg, source) => {
+                sender.try_send((msg, source)).map_err(|err| {
                     NetworkError::ChannelSendError(format!("failed to send gossip message: {err}"))
                 })?;
             }
-            DirectRequest(msg, _pid, chan) => {
-                sender.try_send(msg).map_err(|err| {
+            DirectRequest(msg, pid, chan) => {
+                sender.try_send((msg, Some(pid))).map_err(|err| {
                     NetworkError::ChannelSendError(format!(
                         "failed to send direct request message: {err}"
                     ))
@@ -705,7 +705,11 @@ impl<T: NodeType> Libp2pNetwork<T> {
 
     /// task to propagate messages to handlers
     /// terminates on shut down of network
-    fn handle_event_generator(&self, sender: Sender<Vec<u8>>, mut network_rx: NetworkNodeReceiver) {
+    fn handle_event_generator(
+        &self,
+        sender: Sender<(Vec<u8>, Option<PeerId>)>,
+        mut network_rx: NetworkNodeReceiver,
+    ) {
         let handle = self.clone();
         let is_bootstrapped = Arc::clone(&self.inner.is_bootstrapped);
         spawn(async move {
@@ -728,7 +732,7 @@ impl<T: NodeType> Libp2pNetwork<T> {
                             NetworkEvent::IsBootstrapped => {
                                 is_bootstrapped.store(true, Ordering::Relaxed);
                             }
-                            GossipMsg(_) | DirectRequest(_, _, _) | DirectResponse(_, _) => {
+                            GossipMsg(_, _) | DirectRequest(_, _, _) | DirectResponse(_, _) => {
                                 let _ = handle.handle_recvd_events(message, &sender);
                             }
                             NetworkEvent::ConnectedPeersUpdate(num_peers) => {
@@ -793,10 +797,13 @@ impl<T: NodeType> ConnectedNetwork<T::SignatureKey> for Libp2pNetwork<T> {
         let topic = topic.to_string();
         if self.inner.subscribed_topics.contains(&topic) {
             // Short-circuit-send the message to ourselves
-            self.inner.sender.try_send(message.clone()).map_err(|_| {
-                self.inner.metrics.num_failed_messages.add(1);
-                NetworkError::ShutDown
-            })?;
+            self.inner
+                .sender
+                .try_send((message.clone(), None))
+                .map_err(|_| {
+                    self.inner.metrics.num_failed_messages.add(1);
+                    NetworkError::ShutDown
+                })?;
         }
 
         // NOTE: metrics is threadsafe, so clone is fine (and lightweight)
@@ -881,7 +888,7 @@ impl<T: NodeType> ConnectedNetwork<T::SignatureKey> for Libp2pNetwork<T> {
         // short circuit if we're dming ourselves
         if recipient == self.inner.pk {
             // panic if we already shut down?
-            self.inner.sender.try_send(message).map_err(|_x| {
+            self.inner.sender.try_send((message, None)).map_err(|_x| {
                 self.inner.metrics.num_failed_messages.add(1);
                 NetworkError::ShutDown
             })?;
@@ -942,6 +949,16 @@ impl<T: NodeType> ConnectedNetwork<T::SignatureKey> for Libp2pNetwork<T> {
     /// If there is a network-related failure.
     #[instrument(name = "Libp2pNetwork::recv_message", skip_all)]
     async fn recv_message(&self) -> Result<Vec<u8>, NetworkError> {
+        Ok(self.recv_message_from().await?.0)
+    }
+
+    /// Receive a message, along with the peer that published or sent it to us. Gossipsub checks
+    /// the signature of the publisher, so the peer is authenticated.
+    ///
+    /// # Errors
+    /// If there is a network-related failure.
+    #[instrument(name = "Libp2pNetwork::recv_message_from", skip_all)]
+    async fn recv_message_from(&self) -> Result<(Vec<u8>, Option<PeerId>), NetworkError> {
         let result = self
             .inner
             .receiver
@@ -998,6 +1015,12 @@ impl<T: NodeType> ConnectedNetwork<T::SignatureKey> for Libp2pNetwork<T> {
             .queue_node_lookup(ViewNumber::new(*future_view), future_leader)
             .map_err(|err| tracing::warn!("failed to process node lookup request: {err}"));
     }
+
+    fn report_peer(&self, peer: &PeerId, misbehaviour: PeerMisbehaviour) {
+        if let Err(err) = self.inner.handle.report_peer(*peer, misbehaviour) {
+            warn!("Failed to report peer {:?}: {}", peer, err);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -11,10 +11,11 @@
 
 use core::time::Duration;
 use std::{
+    collections::HashSet,
     fmt::Debug,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, PoisonError,
     },
 };
 
@@ -81,6 +82,9 @@ struct MemoryNetworkInner<K: SignatureKey> {
 
     /// config to introduce unreliability to the network
     reliability_config: Option<Box<dyn NetworkReliability>>,
+
+    /// The nodes our messages are delivered to, if not all of them
+    reachable: std::sync::RwLock<Option<HashSet<K>>>,
 }
 
 /// In memory only network simulator.
@@ -145,6 +149,7 @@ impl<K: SignatureKey> MemoryNetwork<K> {
                 master_map: Arc::clone(master_map),
                 in_flight_message_count,
                 reliability_config,
+                reachable: std::sync::RwLock::new(None),
             }),
         };
         // Insert our public key into the master map
@@ -161,6 +166,16 @@ impl<K: SignatureKey> MemoryNetwork<K> {
         mn
     }
 
+    /// Whether the messages we send to `key` are delivered
+    fn reaches(&self, key: &K) -> bool {
+        self.inner
+            .reachable
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map_or(true, |reachable| reachable.contains(key))
+    }
+
     /// Send a [`Vec<u8>`] message to the inner `input`
     async fn input(&self, message: Vec<u8>) -> Result<(), SendError<Vec<u8>>> {
         self.inner
@@ -260,6 +275,10 @@ impl<K: SignatureKey + 'static> ConnectedNetwork<K> for MemoryNetwork<K> {
         {
             // TODO delay/drop etc here
             let (key, node) = node;
+            if !self.reaches(key) {
+                trace!(?key, "Not sending message to unreachable node");
+                continue;
+            }
             trace!(?key, "Sending message to node");
             if let Some(ref config) = &self.inner.reliability_config {
                 {
@@ -320,6 +339,10 @@ impl<K: SignatureKey + 'static> ConnectedNetwork<K> for MemoryNetwork<K> {
         // debug!(?message, ?recipient, "Sending direct message");
         // Bincode the message
         trace!("Message bincoded, finding recipient");
+        if !self.reaches(&recipient) {
+            trace!(?recipient, "Not sending message to unreachable node");
+            return Ok(());
+        }
         if let Some(node) = self.inner.master_map.map.get(&recipient) {
             let node = node.value().clone();
             if let Some(ref config) = &self.inner.reliability_config {
@@ -357,6 +380,14 @@ impl<K: SignatureKey + 'static> ConnectedNetwork<K> for MemoryNetwork<K> {
         }
     }
 
+    fn set_reachable_peers(&self, reachable: Option<HashSet<K>>) {
+        *self
+            .inner
+            .reachable
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = reachable;
+    }
+
     /// Receive one or many messages from the underlying network.
     ///
     /// # Errors
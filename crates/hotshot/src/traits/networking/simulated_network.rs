@@ -0,0 +1,603 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Seeded network simulator
+//!
+//! This module provides a simulated network in which every link between two nodes has its own
+//! latency, jitter, bandwidth and loss. All randomness is drawn from a seeded RNG per link, so the
+//! delay and fate of the `n`th message on a link only depend on the seed.
+//!
+//! Which message is the `n`th on a link depends on the order in which nodes send, though. Only
+//! traffic driven by a single task on a paused clock (e.g. `#[tokio::test(start_paused = true)]`)
+//! replays exactly from the seed.
+//!
+//! A `TestRunner` run over this network does not replay from the seed, and the seed is not meant
+//! to reproduce one. Besides the multi-threaded runtime its tests run on, the nodes fetch their
+//! blocks from a builder over HTTP on real sockets, shuffle the peers they ask for data with
+//! `thread_rng`, compute VID and DRB results with `spawn_blocking`, and poll `select!` branches in
+//! random order, so the order in which they send differs from run to run.
+
+use core::time::Duration;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use async_trait::async_trait;
+use hotshot_types::{
+    boxed_sync,
+    traits::{
+        network::{
+            AsyncGenerator, BroadcastDelay, ConnectedNetwork, TestableNetworkingImplementation,
+            Topic,
+        },
+        node_implementation::NodeType,
+        signature_key::SignatureKey,
+    },
+    BoxSyncFuture,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::{
+    select,
+    sync::Notify,
+    time::{sleep_until, Instant},
+};
+use tracing::{info, instrument, trace, warn};
+
+use super::{NetworkError, NetworkReliability};
+
+/// Environment variable holding the seed of the simulated network
+pub const SIMULATION_SEED_ENV: &str = "HOTSHOT_SIMULATION_SEED";
+
+/// The characteristics of a one-way link between two simulated nodes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimulatedLink {
+    /// Time it takes a message to travel the link
+    pub latency: Duration,
+    /// Maximum extra delay added to each message, drawn uniformly
+    pub jitter: Duration,
+    /// Bytes per second the link carries, if limited. Messages queue up behind each other while
+    /// the link is busy.
+    pub bandwidth: Option<u64>,
+    /// Probability that a message is lost
+    pub loss: f64,
+}
+
+impl SimulatedLink {
+    /// A perfect link, delivering every message instantly
+    pub const PERFECT: Self = Self {
+        latency: Duration::ZERO,
+        jitter: Duration::ZERO,
+        bandwidth: None,
+        loss: 0.0,
+    };
+}
+
+impl Default for SimulatedLink {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(10),
+            jitter: Duration::from_millis(5),
+            bandwidth: None,
+            loss: 0.0,
+        }
+    }
+}
+
+/// The links between simulated nodes, by node index
+#[derive(Clone, Debug, Default)]
+pub struct SimulatedTopology {
+    /// The link between any two nodes without a link of their own
+    pub default_link: SimulatedLink,
+    /// The links from one node (first) to another (second)
+    pub links: HashMap<(u64, u64), SimulatedLink>,
+}
+
+impl SimulatedTopology {
+    /// A topology in which every link is `link`
+    #[must_use]
+    pub fn uniform(link: SimulatedLink) -> Self {
+        Self {
+            default_link: link,
+            links: HashMap::new(),
+        }
+    }
+
+    /// Use `link` from node `from` to node `to`
+    #[must_use]
+    pub fn with_link(mut self, from: u64, to: u64, link: SimulatedLink) -> Self {
+        self.links.insert((from, to), link);
+        self
+    }
+
+    /// The link from node `from` to node `to`. Nodes always reach themselves instantly.
+    #[must_use]
+    pub fn link(&self, from: u64, to: u64) -> SimulatedLink {
+        if from == to {
+            return SimulatedLink::PERFECT;
+        }
+        self.links
+            .get(&(from, to))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+}
+
+/// Configuration of a simulated network
+#[derive(Clone, Debug)]
+pub struct SimulatedNetworkConfig {
+    /// Seed for all the randomness of the network
+    pub seed: u64,
+    /// The links between the nodes
+    pub topology: SimulatedTopology,
+}
+
+impl SimulatedNetworkConfig {
+    /// A network with the default topology, seeded from [`SIMULATION_SEED_ENV`] if it is set and
+    /// randomly otherwise. The seed is logged, to reuse the link randomness of a failed run.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let seed = std::env::var(SIMULATION_SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random);
+        info!("Simulating network with seed {seed}, set {SIMULATION_SEED_ENV}={seed} to reuse it");
+
+        Self {
+            seed,
+            topology: SimulatedTopology::default(),
+        }
+    }
+}
+
+/// A message on its way to a node
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Delivery {
+    /// When the message arrives
+    deliver_at: Instant,
+    /// The node that sent the message
+    from: u64,
+    /// Position of the message on its link, breaking ties between messages arriving together
+    sequence: u64,
+    /// The message
+    message: Vec<u8>,
+}
+
+/// The state of a one-way link
+#[derive(Debug)]
+struct LinkState {
+    /// Number of messages sent over the link so far
+    sequence: u64,
+    /// When the link finishes transmitting the messages queued on it
+    busy_until: Instant,
+    /// Randomness of this link only
+    rng: StdRng,
+}
+
+impl LinkState {
+    /// A fresh link from `from` to `to`, seeded from the network `seed`
+    fn new(seed: u64, from: u64, to: u64, now: Instant) -> Self {
+        // Mix the endpoints into the seed, so every link draws independently
+        let link_seed = seed
+            ^ from.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ to.wrapping_mul(0xC2B2_AE3D_27D4_EB4F).rotate_left(32);
+
+        Self {
+            sequence: 0,
+            busy_until: now,
+            rng: StdRng::seed_from_u64(link_seed),
+        }
+    }
+}
+
+/// Mutable state shared by the nodes of a simulated network
+#[derive(Debug)]
+struct SimulatorState<K: SignatureKey> {
+    /// The nodes, by index. Nodes hold the simulator, so it only holds them weakly.
+    nodes: BTreeMap<u64, Weak<SimulatedNetworkInner<K>>>,
+    /// The index of each node
+    indices: HashMap<K, u64>,
+    /// The nodes subscribed to each topic
+    subscriptions: HashMap<Topic, BTreeSet<u64>>,
+    /// The links messages were sent over
+    links: HashMap<(u64, u64), LinkState>,
+}
+
+impl<K: SignatureKey> SimulatorState<K> {
+    /// The node with index `index`, if it still exists
+    fn node(&self, index: u64) -> Option<SimulatedNetwork<K>> {
+        let inner = self.nodes.get(&index)?.upgrade()?;
+        Some(SimulatedNetwork { inner })
+    }
+}
+
+/// Shared state of a simulated network.
+///
+/// This type plays the role of the [`MasterMap`](super::memory_network::MasterMap) for
+/// [`SimulatedNetwork`]s: every node of one network is created from the same `Simulator`.
+#[derive(Debug)]
+pub struct Simulator<K: SignatureKey> {
+    /// The configuration of the network
+    config: SimulatedNetworkConfig,
+    /// The nodes and links
+    state: Mutex<SimulatorState<K>>,
+}
+
+impl<K: SignatureKey> Simulator<K> {
+    /// Create a new simulated network without nodes
+    #[must_use]
+    pub fn new(config: SimulatedNetworkConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            state: Mutex::new(SimulatorState {
+                nodes: BTreeMap::new(),
+                indices: HashMap::new(),
+                subscriptions: HashMap::new(),
+                links: HashMap::new(),
+            }),
+        })
+    }
+
+    /// The seed of the network
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.config.seed
+    }
+
+    /// Lock the shared state
+    fn state(&self) -> std::sync::MutexGuard<'_, SimulatorState<K>> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Send `message` from node `from` to node `to`, if the link does not lose it
+    fn send(&self, from: u64, to: u64, message: Vec<u8>) -> Result<(), NetworkError> {
+        let now = Instant::now();
+        let mut state = self.state();
+        let Some(recipient) = state.node(to) else {
+            return Err(NetworkError::MessageSendError(
+                "node does not exist".to_string(),
+            ));
+        };
+        let sender_cut_off = state
+            .node(from)
+            .is_some_and(|sender| sender.is_paused() || !sender.reaches(&recipient.inner.key));
+
+        let link = self.config.topology.link(from, to);
+        let link_state = state
+            .links
+            .entry((from, to))
+            .or_insert_with(|| LinkState::new(self.config.seed, from, to, now));
+
+        let sequence = link_state.sequence;
+        link_state.sequence += 1;
+
+        // Draw the same randomness for every message, so the fate of one message never shifts
+        // that of the next
+        let lost = link_state.rng.gen::<f64>() < link.loss;
+        let jitter = link.jitter.mul_f64(link_state.rng.gen::<f64>());
+
+        // Lost messages still take up the link
+        #[allow(clippy::cast_precision_loss)]
+        let transmission = link.bandwidth.map_or(Duration::ZERO, |bandwidth| {
+            Duration::from_secs_f64(message.len() as f64 / bandwidth.max(1) as f64)
+        });
+        link_state.busy_until = link_state.busy_until.max(now) + transmission;
+        let deliver_at = link_state.busy_until + link.latency + jitter;
+        drop(state);
+
+        if lost || sender_cut_off || recipient.is_paused() {
+            trace!(from, to, sequence, "Dropping message");
+            return Ok(());
+        }
+
+        recipient.deliver(Delivery {
+            deliver_at,
+            from,
+            sequence,
+            message,
+        });
+        Ok(())
+    }
+}
+
+/// Internal state for a `SimulatedNetwork` instance
+#[derive(Debug)]
+struct SimulatedNetworkInner<K: SignatureKey> {
+    /// The index of this node
+    index: u64,
+    /// The public key of this node
+    key: K,
+    /// The network this node is part of
+    simulator: Arc<Simulator<K>>,
+    /// The messages on their way to this node, earliest first
+    inbox: Mutex<BinaryHeap<Reverse<Delivery>>>,
+    /// Wakes up the receiver when a message is added to the inbox
+    notify: Notify,
+    /// Whether the node is cut off from the network
+    paused: AtomicBool,
+    /// The nodes our messages are delivered to, if not all of them
+    reachable: Mutex<Option<HashSet<K>>>,
+    /// Whether the node was shut down
+    shut_down: AtomicBool,
+}
+
+/// Seeded network simulator.
+///
+/// Every node of the network is a `SimulatedNetwork` created from the same [`Simulator`], which
+/// decides when each message arrives and whether it is lost according to the topology and seed.
+#[derive(Clone)]
+pub struct SimulatedNetwork<K: SignatureKey> {
+    /// The actual internal state
+    inner: Arc<SimulatedNetworkInner<K>>,
+}
+
+impl<K: SignatureKey> Debug for SimulatedNetwork<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimulatedNetwork")
+            .field("index", &self.inner.index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K: SignatureKey> SimulatedNetwork<K> {
+    /// Creates the node `index` of the network simulated by `simulator`, subscribed to
+    /// `subscribed_topics`
+    pub fn new(
+        pub_key: &K,
+        index: u64,
+        simulator: &Arc<Simulator<K>>,
+        subscribed_topics: &[Topic],
+    ) -> SimulatedNetwork<K> {
+        let network = SimulatedNetwork {
+            inner: Arc::new(SimulatedNetworkInner {
+                index,
+                key: pub_key.clone(),
+                simulator: Arc::clone(simulator),
+                inbox: Mutex::new(BinaryHeap::new()),
+                notify: Notify::new(),
+                paused: AtomicBool::new(false),
+                reachable: Mutex::new(None),
+                shut_down: AtomicBool::new(false),
+            }),
+        };
+
+        let mut state = simulator.state();
+        state.nodes.insert(index, Arc::downgrade(&network.inner));
+        state.indices.insert(pub_key.clone(), index);
+        for topic in subscribed_topics {
+            state
+                .subscriptions
+                .entry(topic.clone())
+                .or_default()
+                .insert(index);
+        }
+        drop(state);
+
+        network
+    }
+
+    /// Generate the nodes of a network simulated according to `config`
+    #[must_use]
+    pub fn generator_with_config<TYPES: NodeType<SignatureKey = K>>(
+        config: SimulatedNetworkConfig,
+        da_committee_size: usize,
+    ) -> AsyncGenerator<Arc<Self>> {
+        let simulator = Simulator::new(config);
+        Box::pin(move |node_id| {
+            let pubkey = TYPES::SignatureKey::generated_from_seed_indexed([0u8; 32], node_id).0;
+
+            // Subscribe to topics based on our index
+            let subscribed_topics = if node_id < da_committee_size as u64 {
+                // DA node
+                vec![Topic::Da, Topic::Global]
+            } else {
+                // Non-DA node
+                vec![Topic::Global]
+            };
+
+            let net = SimulatedNetwork::new(&pubkey, node_id, &simulator, &subscribed_topics);
+            Box::pin(async move { net.into() })
+        })
+    }
+
+    /// Whether the node is cut off from the network
+    fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::Relaxed)
+    }
+
+    /// Whether the messages we send to `key` are delivered
+    fn reaches(&self, key: &K) -> bool {
+        self.inner
+            .reachable
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .map_or(true, |reachable| reachable.contains(key))
+    }
+
+    /// Lock the inbox
+    fn inbox(&self) -> std::sync::MutexGuard<'_, BinaryHeap<Reverse<Delivery>>> {
+        self.inner
+            .inbox
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Put a message on its way to this node
+    fn deliver(&self, delivery: Delivery) {
+        self.inbox().push(Reverse(delivery));
+        self.inner.notify.notify_one();
+    }
+
+    /// Send `message` to every node in `recipients`, in index order
+    fn send_to_all(&self, message: &[u8], recipients: impl IntoIterator<Item = u64>) {
+        for to in recipients {
+            if let Err(e) = self
+                .inner
+                .simulator
+                .send(self.inner.index, to, message.to_vec())
+            {
+                warn!(?e, to, "Error sending broadcast message to node");
+            }
+        }
+    }
+}
+
+impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES>
+    for SimulatedNetwork<TYPES::SignatureKey>
+{
+    fn generator(
+        _expected_node_count: usize,
+        _num_bootstrap: usize,
+        _network_id: usize,
+        da_committee_size: usize,
+        reliability_config: Option<Box<dyn NetworkReliability>>,
+        _secondary_network_delay: Duration,
+    ) -> AsyncGenerator<Arc<Self>> {
+        if reliability_config.is_some() {
+            warn!("The simulated network ignores the reliability config, use its topology instead");
+        }
+
+        Self::generator_with_config::<TYPES>(SimulatedNetworkConfig::from_env(), da_committee_size)
+    }
+
+    fn in_flight_message_count(&self) -> Option<usize> {
+        Some(self.inbox().len())
+    }
+}
+
+#[async_trait]
+impl<K: SignatureKey + 'static> ConnectedNetwork<K> for SimulatedNetwork<K> {
+    #[instrument(name = "SimulatedNetwork::ready_blocking")]
+    async fn wait_for_ready(&self) {}
+
+    fn pause(&self) {
+        self.inner.paused.store(true, Ordering::Relaxed);
+    }
+
+    fn resume(&self) {
+        self.inner.paused.store(false, Ordering::Relaxed);
+    }
+
+    fn set_reachable_peers(&self, reachable: Option<HashSet<K>>) {
+        *self
+            .inner
+            .reachable
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = reachable;
+    }
+
+    #[instrument(name = "SimulatedNetwork::shut_down")]
+    fn shut_down<'a, 'b>(&'a self) -> BoxSyncFuture<'b, ()>
+    where
+        'a: 'b,
+        Self: 'b,
+    {
+        let closure = async move {
+            self.inner.shut_down.store(true, Ordering::Relaxed);
+            self.inner.notify.notify_one();
+        };
+        boxed_sync(closure)
+    }
+
+    #[instrument(name = "SimulatedNetwork::broadcast_message", skip_all)]
+    async fn broadcast_message(
+        &self,
+        message: Vec<u8>,
+        topic: Topic,
+        _broadcast_delay: BroadcastDelay,
+    ) -> Result<(), NetworkError> {
+        let recipients = self
+            .inner
+            .simulator
+            .state()
+            .subscriptions
+            .get(&topic)
+            .cloned()
+            .unwrap_or_default();
+        self.send_to_all(&message, recipients);
+        Ok(())
+    }
+
+    #[instrument(name = "SimulatedNetwork::da_broadcast_message", skip_all)]
+    async fn da_broadcast_message(
+        &self,
+        message: Vec<u8>,
+        recipients: Vec<K>,
+        _broadcast_delay: BroadcastDelay,
+    ) -> Result<(), NetworkError> {
+        let recipients = {
+            let state = self.inner.simulator.state();
+            recipients
+                .iter()
+                .filter_map(|key| state.indices.get(key).copied())
+                .collect::<BTreeSet<_>>()
+        };
+        self.send_to_all(&message, recipients);
+        Ok(())
+    }
+
+    #[instrument(name = "SimulatedNetwork::direct_message", skip_all)]
+    async fn direct_message(&self, message: Vec<u8>, recipient: K) -> Result<(), NetworkError> {
+        let to = self
+            .inner
+            .simulator
+            .state()
+            .indices
+            .get(&recipient)
+            .copied()
+            .ok_or(NetworkError::MessageSendError(
+                "node does not exist".to_string(),
+            ))?;
+
+        self.inner.simulator.send(self.inner.index, to, message)
+    }
+
+    /// Receive the next message to arrive at this node, waiting for it if necessary.
+    ///
+    /// # Errors
+    /// If the node was shut down
+    #[instrument(name = "SimulatedNetwork::recv_message", skip_all)]
+    async fn recv_message(&self) -> Result<Vec<u8>, NetworkError> {
+        loop {
+            if self.inner.shut_down.load(Ordering::Relaxed) {
+                return Err(NetworkError::ShutDown);
+            }
+
+            let next_arrival = {
+                let mut inbox = self.inbox();
+                match inbox.peek() {
+                    Some(Reverse(delivery)) if delivery.deliver_at <= Instant::now() => {
+                        let Some(Reverse(delivery)) = inbox.pop() else {
+                            continue;
+                        };
+                        return Ok(delivery.message);
+                    }
+                    Some(Reverse(delivery)) => Some(delivery.deliver_at),
+                    None => None,
+                }
+            };
+
+            // Wait for the next message to arrive, or for an earlier one to be sent
+            match next_arrival {
+                Some(deliver_at) => {
+                    select! {
+                        () = sleep_until(deliver_at) => {}
+                        () = self.inner.notify.notified() => {}
+                    }
+                }
+                None => self.inner.notify.notified().await,
+            }
+        }
+    }
+}
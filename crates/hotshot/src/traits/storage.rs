@@ -0,0 +1,14 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Storage implementations
+//!
+//! This module contains implementations of the [`Storage`](hotshot_types::traits::storage::Storage)
+//! trait. Currently this includes
+//! - [`FileStorage`](file_storage::FileStorage), a durable implementation backed by append-only
+//!   segment files on disk.
+
+pub mod file_storage;
@@ -0,0 +1,1423 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Durable, file-backed implementation of the [`Storage`] trait
+//!
+//! Every write is appended as a checksummed frame to the active segment file in the storage
+//! directory, and the active segment is rolled over once it reaches a configured size. An
+//! in-memory index of the live frames is rebuilt on startup by replaying the segments in order.
+//! A frame that was only partially written when the process died is detected by its length prefix
+//! and checksum and truncated away, so the storage always reopens to the state after the last
+//! complete write.
+//!
+//! Decided data is pruned according to the configured [`RetentionPolicy`]: pruning writes a
+//! record marking the earliest retained view, and the pruned frames are reclaimed by compaction.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use async_lock::Mutex;
+use async_trait::async_trait;
+use committable::Committable;
+use hotshot_types::{
+    consensus::{CommitmentMap, View},
+    data::{DaProposal, Leaf, Leaf2, QuorumProposal, QuorumProposal2, VidDisperseShare},
+    drb::DrbResult,
+    event::HotShotAction,
+    evidence::{EquivocationEvidence, EquivocationKind},
+    message::Proposal,
+    simple_certificate::{QuorumCertificate, QuorumCertificate2, UpgradeCertificate},
+    traits::{
+        node_implementation::{ConsensusTime, NodeType},
+        storage::{PersistedConsensusState, RetentionPolicy, Storage},
+    },
+    vid::VidCommitment,
+    vote::HasViewNumber,
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+use tracing::{debug, warn};
+
+/// Extension of the segment files in the storage directory
+const SEGMENT_EXTENSION: &str = "seg";
+
+/// Size of a frame header: the `u32` payload length followed by the blake3 hash of the payload
+const FRAME_HEADER_SIZE: usize = 4 + 32;
+
+/// Default size after which the active segment is rolled over
+pub const DEFAULT_MAX_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// How far a write is pushed towards the disk before the storage call returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncMode {
+    /// Hand the write to the operating system and return. The write survives a crash of the
+    /// process, but not a crash of the machine.
+    None,
+    /// Flush the segment contents to disk (`fdatasync`) before returning.
+    Data,
+    /// Flush the segment contents and metadata to disk (`fsync`) before returning.
+    All,
+}
+
+/// The [`SyncMode`] used by each call of the [`Storage`] trait
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncPolicy {
+    /// Used by `append_vid`
+    pub vid: SyncMode,
+    /// Used by `append_da`
+    pub da: SyncMode,
+    /// Used by `append_proposal` and `append_proposal2`
+    pub proposal: SyncMode,
+    /// Used by `record_action`
+    pub action: SyncMode,
+    /// Used by `update_high_qc` and `update_high_qc2`
+    pub high_qc: SyncMode,
+    /// Used by `update_undecided_state` and `update_undecided_state2`
+    pub undecided_state: SyncMode,
+    /// Used by `update_decided_upgrade_certificate`
+    pub upgrade_certificate: SyncMode,
+    /// Used by `update_anchor_leaf`
+    pub anchor_leaf: SyncMode,
+    /// Used by `append_equivocation_evidence`
+    pub evidence: SyncMode,
+    /// Used by `add_drb_result`
+    pub drb_result: SyncMode,
+    /// Used by `append_decided_leaves`
+    pub decided_leaves: SyncMode,
+}
+
+impl SyncPolicy {
+    /// Use the same [`SyncMode`] for every call
+    #[must_use]
+    pub fn uniform(mode: SyncMode) -> Self {
+        Self {
+            vid: mode,
+            da: mode,
+            proposal: mode,
+            action: mode,
+            high_qc: mode,
+            undecided_state: mode,
+            upgrade_certificate: mode,
+            anchor_leaf: mode,
+            evidence: mode,
+            drb_result: mode,
+            decided_leaves: mode,
+        }
+    }
+}
+
+impl Default for SyncPolicy {
+    /// Everything consensus relies on after a restart is synced. The undecided state is rewritten
+    /// on every proposal and can be rebuilt from the network, and decided leaves are only kept to
+    /// serve other nodes, so both are only handed to the OS.
+    fn default() -> Self {
+        Self {
+            undecided_state: SyncMode::None,
+            decided_leaves: SyncMode::None,
+            ..Self::uniform(SyncMode::Data)
+        }
+    }
+}
+
+/// Configuration for a [`FileStorage`]
+#[derive(Clone, Debug)]
+pub struct FileStorageConfig {
+    /// Directory holding the segment files. It is created if it does not exist.
+    pub path: PathBuf,
+    /// Size in bytes after which the active segment is rolled over
+    pub max_segment_size: u64,
+    /// How hard each call syncs its write
+    pub sync: SyncPolicy,
+    /// How much decided data is kept
+    pub retention: RetentionPolicy,
+}
+
+impl FileStorageConfig {
+    /// Create a config for the given directory with the default segment size and sync policy,
+    /// keeping all decided data
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_segment_size: DEFAULT_MAX_SEGMENT_SIZE,
+            sync: SyncPolicy::default(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+/// A single entry of the log
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+enum Record<TYPES: NodeType> {
+    /// Written by `append_vid`
+    Vid(Proposal<TYPES, VidDisperseShare<TYPES>>),
+    /// Written by `append_da`
+    Da {
+        /// The DA proposal
+        proposal: Proposal<TYPES, DaProposal<TYPES>>,
+        /// The VID commitment of the proposed payload
+        vid_commit: VidCommitment,
+    },
+    /// Written by `append_proposal`
+    Proposal(Proposal<TYPES, QuorumProposal<TYPES>>),
+    /// Written by `append_proposal2`
+    Proposal2(Proposal<TYPES, QuorumProposal2<TYPES>>),
+    /// Written by `record_action`
+    Action {
+        /// The view the action was taken in
+        view: TYPES::View,
+        /// The epoch we were in when we took the action
+        epoch: TYPES::Epoch,
+        /// The action taken
+        action: HotShotAction,
+    },
+    /// Written by `update_high_qc`
+    HighQc(QuorumCertificate<TYPES>),
+    /// Written by `update_high_qc2`
+    HighQc2(QuorumCertificate2<TYPES>),
+    /// Written by `update_undecided_state`
+    UndecidedState {
+        /// Undecided leaves
+        leaves: CommitmentMap<Leaf<TYPES>>,
+        /// Undecided state
+        state: BTreeMap<TYPES::View, View<TYPES>>,
+    },
+    /// Written by `update_undecided_state2`
+    UndecidedState2 {
+        /// Undecided leaves
+        leaves: CommitmentMap<Leaf2<TYPES>>,
+        /// Undecided state
+        state: BTreeMap<TYPES::View, View<TYPES>>,
+    },
+    /// Written by `update_decided_upgrade_certificate`
+    UpgradeCertificate(Option<UpgradeCertificate<TYPES>>),
+    /// Written by `update_anchor_leaf`
+    AnchorLeaf(Leaf2<TYPES>),
+    /// Written by `collect_garbage`: the data of every view before this one is pruned
+    Prune(TYPES::View),
+    /// Written by `append_equivocation_evidence`
+    Evidence(EquivocationEvidence<TYPES>),
+    /// Written by `add_drb_result`
+    DrbResult {
+        /// The epoch whose leaders the result determines
+        epoch: TYPES::Epoch,
+        /// The result of the DRB computation
+        drb_result: DrbResult,
+    },
+    /// Written by `append_decided_leaves`, one record per leaf
+    DecidedLeaf(Leaf2<TYPES>),
+}
+
+impl<TYPES: NodeType> Record<TYPES> {
+    /// The index slot this record occupies
+    fn slot(&self) -> Slot<TYPES> {
+        match self {
+            Self::Vid(proposal) => Slot::Vid(
+                proposal.data.view_number(),
+                proposal.data.recipient_key.clone(),
+            ),
+            Self::Da { proposal, .. } => Slot::Da(proposal.data.view_number()),
+            Self::Proposal(proposal) => Slot::Proposal(proposal.data.view_number()),
+            Self::Proposal2(proposal) => Slot::Proposal2(proposal.data.view_number()),
+            Self::Action { view, action, .. } => Slot::Action(*view, *action),
+            Self::HighQc(qc) => Slot::HighQc(qc.view_number()),
+            Self::HighQc2(qc) => Slot::HighQc2(qc.view_number()),
+            Self::UndecidedState { .. } => Slot::UndecidedState,
+            Self::UndecidedState2 { .. } => Slot::UndecidedState2,
+            Self::UpgradeCertificate(_) => Slot::UpgradeCertificate,
+            Self::AnchorLeaf(leaf) => Slot::AnchorLeaf(leaf.view_number()),
+            Self::Prune(view) => Slot::Prune(*view),
+            Self::Evidence(evidence) => Slot::Evidence(
+                evidence.view_number(),
+                evidence.offender.clone(),
+                evidence.kind(),
+            ),
+            Self::DrbResult { epoch, .. } => Slot::DrbResult(*epoch),
+            Self::DecidedLeaf(leaf) => Slot::DecidedLeaf(leaf.view_number(), leaf.height()),
+        }
+    }
+
+    /// Decode a record from a frame payload
+    fn decode(payload: &[u8]) -> Result<Self> {
+        bincode::deserialize(payload).context("Failed to decode storage record")
+    }
+}
+
+/// Where a record lives in the index, along with what is needed to order it against the record
+/// currently in that place
+#[derive(Clone, Debug)]
+enum Slot<TYPES: NodeType> {
+    /// VID share for a view and recipient
+    Vid(TYPES::View, TYPES::SignatureKey),
+    /// DA proposal for a view
+    Da(TYPES::View),
+    /// Legacy quorum proposal for a view
+    Proposal(TYPES::View),
+    /// Quorum proposal for a view
+    Proposal2(TYPES::View),
+    /// Last action taken
+    Action(TYPES::View, HotShotAction),
+    /// Legacy high QC
+    HighQc(TYPES::View),
+    /// High QC
+    HighQc2(TYPES::View),
+    /// Legacy undecided state
+    UndecidedState,
+    /// Undecided state
+    UndecidedState2,
+    /// Decided upgrade certificate
+    UpgradeCertificate,
+    /// Most recently decided leaf
+    AnchorLeaf(TYPES::View),
+    /// Earliest retained view
+    Prune(TYPES::View),
+    /// Evidence of equivocation for a view, offender and kind of message
+    Evidence(TYPES::View, TYPES::SignatureKey, EquivocationKind),
+    /// DRB result for an epoch
+    DrbResult(TYPES::Epoch),
+    /// Decided leaf for a view, at a block height
+    DecidedLeaf(TYPES::View, u64),
+}
+
+/// Position of a frame in the segment files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    /// Id of the segment holding the frame
+    segment: u64,
+    /// Offset of the frame header within the segment
+    offset: u64,
+    /// Length of the frame payload
+    len: u32,
+}
+
+impl Location {
+    /// Size of the whole frame, including its header
+    fn frame_size(self) -> u64 {
+        FRAME_HEADER_SIZE as u64 + u64::from(self.len)
+    }
+}
+
+/// In-memory index of the live frames
+#[derive(Debug)]
+struct Index<TYPES: NodeType> {
+    /// VID shares by view and recipient
+    vids: BTreeMap<TYPES::View, HashMap<TYPES::SignatureKey, Location>>,
+    /// DA proposals by view
+    das: BTreeMap<TYPES::View, Location>,
+    /// Legacy quorum proposals by view
+    proposals: BTreeMap<TYPES::View, Location>,
+    /// Quorum proposals by view
+    proposals2: BTreeMap<TYPES::View, Location>,
+    /// Last vote or proposal we made
+    action: Option<(TYPES::View, Location)>,
+    /// Legacy high QC
+    high_qc: Option<(TYPES::View, Location)>,
+    /// High QC
+    high_qc2: Option<(TYPES::View, Location)>,
+    /// Legacy undecided state
+    undecided_state: Option<Location>,
+    /// Undecided state
+    undecided_state2: Option<Location>,
+    /// Decided upgrade certificate
+    upgrade_certificate: Option<Location>,
+    /// Most recently decided leaf
+    anchor_leaf: Option<(TYPES::View, Location)>,
+    /// Earliest retained view, if anything was pruned
+    retained_from: Option<(TYPES::View, Location)>,
+    /// Evidence of equivocation by view and kind of message, and offender, never pruned
+    evidence: BTreeMap<(TYPES::View, EquivocationKind), HashMap<TYPES::SignatureKey, Location>>,
+    /// DRB results by epoch, never pruned
+    drb_results: BTreeMap<TYPES::Epoch, Location>,
+    /// Decided leaves by view
+    decided_leaves: BTreeMap<TYPES::View, Location>,
+    /// Views of the decided leaves by block height
+    decided_heights: BTreeMap<u64, TYPES::View>,
+}
+
+impl<TYPES: NodeType> Default for Index<TYPES> {
+    fn default() -> Self {
+        Self {
+            vids: BTreeMap::new(),
+            das: BTreeMap::new(),
+            proposals: BTreeMap::new(),
+            proposals2: BTreeMap::new(),
+            action: None,
+            high_qc: None,
+            high_qc2: None,
+            undecided_state: None,
+            undecided_state2: None,
+            upgrade_certificate: None,
+            anchor_leaf: None,
+            retained_from: None,
+            evidence: BTreeMap::new(),
+            drb_results: BTreeMap::new(),
+            decided_leaves: BTreeMap::new(),
+            decided_heights: BTreeMap::new(),
+        }
+    }
+}
+
+impl<TYPES: NodeType> Index<TYPES> {
+    /// Whether a record for `slot` would be superseded by what is already indexed, in which case
+    /// there is no point in writing it.
+    fn is_stale(&self, slot: &Slot<TYPES>) -> bool {
+        match slot {
+            Slot::Vid(view, _)
+            | Slot::Da(view)
+            | Slot::Proposal(view)
+            | Slot::Proposal2(view)
+            | Slot::DecidedLeaf(view, _) => self.is_pruned(*view),
+            Slot::Action(view, action) => {
+                !matches!(action, HotShotAction::Vote | HotShotAction::Propose)
+                    || self.action.is_some_and(|(current, _)| *view < current)
+            }
+            Slot::HighQc(view) => self.high_qc.is_some_and(|(current, _)| *view < current),
+            Slot::HighQc2(view) => self.high_qc2.is_some_and(|(current, _)| *view < current),
+            Slot::AnchorLeaf(view) => self.anchor_leaf.is_some_and(|(current, _)| *view < current),
+            Slot::Prune(view) => self
+                .retained_from
+                .is_some_and(|(current, _)| *view <= current),
+            // One piece of evidence proves the offence, and a copy left behind by a compaction
+            // cut short by a crash must not be loaded twice
+            Slot::Evidence(view, key, kind) => self
+                .evidence
+                .get(&(*view, *kind))
+                .is_some_and(|evidence| evidence.contains_key(key)),
+            _ => false,
+        }
+    }
+
+    /// Whether the data of `view` was pruned
+    fn is_pruned(&self, view: TYPES::View) -> bool {
+        self.retained_from
+            .is_some_and(|(retained_from, _)| view < retained_from)
+    }
+
+    /// Index the frame at `location` for `slot`.
+    ///
+    /// Returns the locations of the frames that are no longer live as a result: the frame
+    /// previously in the slot, the frames it pruned, or the new frame itself if it was stale.
+    fn insert(&mut self, slot: Slot<TYPES>, location: Location) -> Vec<Location> {
+        if self.is_stale(&slot) {
+            return vec![location];
+        }
+
+        let replaced = match slot {
+            Slot::Vid(view, key) => self.vids.entry(view).or_default().insert(key, location),
+            Slot::Da(view) => self.das.insert(view, location),
+            Slot::Proposal(view) => self.proposals.insert(view, location),
+            Slot::Proposal2(view) => self.proposals2.insert(view, location),
+            Slot::Action(view, _) => self.action.replace((view, location)).map(|(_, old)| old),
+            Slot::HighQc(view) => self.high_qc.replace((view, location)).map(|(_, old)| old),
+            Slot::HighQc2(view) => self.high_qc2.replace((view, location)).map(|(_, old)| old),
+            Slot::UndecidedState => self.undecided_state.replace(location),
+            Slot::UndecidedState2 => self.undecided_state2.replace(location),
+            Slot::UpgradeCertificate => self.upgrade_certificate.replace(location),
+            Slot::AnchorLeaf(view) => self
+                .anchor_leaf
+                .replace((view, location))
+                .map(|(_, old)| old),
+            Slot::Prune(view) => {
+                let replaced = self
+                    .retained_from
+                    .replace((view, location))
+                    .map(|(_, old)| old);
+                return replaced.into_iter().chain(self.prune(view)).collect();
+            }
+            Slot::Evidence(view, key, kind) => self
+                .evidence
+                .entry((view, kind))
+                .or_default()
+                .insert(key, location),
+            Slot::DrbResult(epoch) => self.drb_results.insert(epoch, location),
+            Slot::DecidedLeaf(view, height) => {
+                self.decided_heights.insert(height, view);
+                self.decided_leaves.insert(view, location)
+            }
+        };
+
+        replaced.into_iter().collect()
+    }
+
+    /// Drop the data of every view before `view` from the index, returning the dropped locations
+    fn prune(&mut self, view: TYPES::View) -> Vec<Location> {
+        let vids = self.vids.split_off(&view);
+        let das = self.das.split_off(&view);
+        let proposals = self.proposals.split_off(&view);
+        let proposals2 = self.proposals2.split_off(&view);
+        let decided_leaves = self.decided_leaves.split_off(&view);
+        self.decided_heights
+            .retain(|_, leaf_view| *leaf_view >= view);
+
+        std::mem::replace(&mut self.vids, vids)
+            .into_values()
+            .flat_map(HashMap::into_values)
+            .chain(std::mem::replace(&mut self.das, das).into_values())
+            .chain(std::mem::replace(&mut self.proposals, proposals).into_values())
+            .chain(std::mem::replace(&mut self.proposals2, proposals2).into_values())
+            .chain(std::mem::replace(&mut self.decided_leaves, decided_leaves).into_values())
+            .collect()
+    }
+
+    /// Number of bytes stored for each view that can be pruned, in ascending view order
+    fn view_sizes(&self) -> BTreeMap<TYPES::View, u64> {
+        let mut sizes = BTreeMap::new();
+        let shares = self
+            .vids
+            .iter()
+            .flat_map(|(view, shares)| shares.values().map(move |location| (view, location)));
+        for (view, location) in shares
+            .chain(&self.das)
+            .chain(&self.proposals)
+            .chain(&self.proposals2)
+            .chain(&self.decided_leaves)
+        {
+            *sizes.entry(*view).or_default() += location.frame_size();
+        }
+
+        sizes
+    }
+
+    /// Apply `f` to the location of every live frame
+    fn for_each_location(&mut self, mut f: impl FnMut(&mut Location)) {
+        self.vids
+            .values_mut()
+            .flat_map(HashMap::values_mut)
+            .chain(self.das.values_mut())
+            .chain(self.proposals.values_mut())
+            .chain(self.proposals2.values_mut())
+            .chain(self.action.as_mut().map(|(_, location)| location))
+            .chain(self.high_qc.as_mut().map(|(_, location)| location))
+            .chain(self.high_qc2.as_mut().map(|(_, location)| location))
+            .chain(self.undecided_state.as_mut())
+            .chain(self.undecided_state2.as_mut())
+            .chain(self.upgrade_certificate.as_mut())
+            .chain(self.anchor_leaf.as_mut().map(|(_, location)| location))
+            .chain(self.retained_from.as_mut().map(|(_, location)| location))
+            .chain(self.evidence.values_mut().flat_map(HashMap::values_mut))
+            .chain(self.drb_results.values_mut())
+            .chain(self.decided_leaves.values_mut())
+            .for_each(&mut f);
+    }
+}
+
+/// Bookkeeping for a single segment file
+#[derive(Clone, Copy, Debug, Default)]
+struct Segment {
+    /// Total size of the segment file
+    size: u64,
+    /// Bytes of the segment file taken up by live frames
+    live: u64,
+}
+
+/// Result of reading a frame from a segment buffer
+enum Frame<'a> {
+    /// A complete frame with a valid checksum
+    Complete(&'a [u8]),
+    /// The end of the segment was reached cleanly
+    End,
+    /// A partially written or corrupted frame
+    Torn,
+}
+
+/// Parse the frame at the start of `buf`
+fn parse_frame(buf: &[u8]) -> Frame<'_> {
+    if buf.is_empty() {
+        return Frame::End;
+    }
+    let Some((len, rest)) = buf.split_first_chunk::<4>() else {
+        return Frame::Torn;
+    };
+    let Some((hash, rest)) = rest.split_first_chunk::<32>() else {
+        return Frame::Torn;
+    };
+    let len = u32::from_le_bytes(*len) as usize;
+    match rest.get(..len) {
+        Some(payload) if blake3::hash(payload).as_bytes() == hash => Frame::Complete(payload),
+        _ => Frame::Torn,
+    }
+}
+
+/// Encode `payload` as a frame
+fn encode_frame(payload: &[u8]) -> Result<Vec<u8>> {
+    let len = u32::try_from(payload.len()).context("Storage record is too large")?;
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(blake3::hash(payload).as_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Flush `file` according to `mode`
+fn sync_file(file: &File, mode: SyncMode) -> Result<()> {
+    match mode {
+        SyncMode::None => {}
+        SyncMode::Data => file.sync_data()?,
+        SyncMode::All => file.sync_all()?,
+    }
+    Ok(())
+}
+
+/// Make changes to the entries of `dir` durable
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// The mutable state of a [`FileStorage`], only touched from blocking threads except for reads of
+/// the index
+#[derive(Debug)]
+struct Inner<TYPES: NodeType> {
+    /// Storage directory
+    path: PathBuf,
+    /// Size after which the active segment is rolled over
+    max_segment_size: u64,
+    /// How much decided data is kept
+    retention: RetentionPolicy,
+    /// Bookkeeping for every segment on disk, keyed by id. The last one is the active segment.
+    segments: BTreeMap<u64, Segment>,
+    /// Append handle to the active segment
+    active: File,
+    /// Index of the live frames
+    index: Index<TYPES>,
+}
+
+impl<TYPES: NodeType> Inner<TYPES> {
+    /// Path of the segment file with the given id
+    fn segment_path(path: &Path, id: u64) -> PathBuf {
+        path.join(format!("{id:020}.{SEGMENT_EXTENSION}"))
+    }
+
+    /// Ids of the segment files in `path`, in ascending order
+    fn segment_ids(path: &Path) -> Result<Vec<u64>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry_path = entry?.path();
+            if entry_path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            if let Some(id) = entry_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Open the storage directory, replaying every segment to rebuild the index
+    fn open(config: &FileStorageConfig) -> Result<Self> {
+        let path = config.path.clone();
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create storage directory {}", path.display()))?;
+
+        let ids = Self::segment_ids(&path)?;
+        let mut segments = BTreeMap::new();
+        let mut index = Index::default();
+
+        for (position, &id) in ids.iter().enumerate() {
+            let segment_path = Self::segment_path(&path, id);
+            let bytes = fs::read(&segment_path)
+                .with_context(|| format!("Failed to read segment {}", segment_path.display()))?;
+            let mut segment = Segment::default();
+            let mut offset = 0;
+
+            loop {
+                match parse_frame(&bytes[offset..]) {
+                    Frame::Complete(payload) => {
+                        let record = Record::<TYPES>::decode(payload)?;
+                        let location = Location {
+                            segment: id,
+                            offset: offset as u64,
+                            len: u32::try_from(payload.len())?,
+                        };
+                        segment.size += location.frame_size();
+                        segment.live += location.frame_size();
+                        for dead in index.insert(record.slot(), location) {
+                            let owner = if dead.segment == id {
+                                &mut segment
+                            } else {
+                                segments
+                                    .get_mut(&dead.segment)
+                                    .context("Index refers to an unknown segment")?
+                            };
+                            owner.live -= dead.frame_size();
+                        }
+                        offset += FRAME_HEADER_SIZE + payload.len();
+                    }
+                    Frame::End => break,
+                    Frame::Torn => {
+                        // Only the active segment can be cut short by a crash: earlier segments
+                        // were complete when the next one was created.
+                        ensure!(
+                            position + 1 == ids.len(),
+                            "Segment {} is corrupted at offset {offset}",
+                            segment_path.display()
+                        );
+                        warn!(
+                            "Truncating torn write at offset {offset} of segment {}",
+                            segment_path.display()
+                        );
+                        let file = OpenOptions::new().write(true).open(&segment_path)?;
+                        file.set_len(offset as u64)?;
+                        file.sync_all()?;
+                        break;
+                    }
+                }
+            }
+
+            segments.insert(id, segment);
+        }
+
+        if segments.is_empty() {
+            segments.insert(0, Segment::default());
+        }
+        let active_id = *segments.keys().next_back().unwrap_or(&0);
+        let active = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::segment_path(&path, active_id))?;
+        sync_dir(&path)?;
+
+        debug!(
+            "Opened file storage at {} with {} segments",
+            path.display(),
+            segments.len()
+        );
+
+        Ok(Self {
+            path,
+            max_segment_size: config.max_segment_size,
+            retention: config.retention,
+            segments,
+            active,
+            index,
+        })
+    }
+
+    /// Id of the active segment
+    fn active_id(&self) -> u64 {
+        *self.segments.keys().next_back().unwrap_or(&0)
+    }
+
+    /// Mark the frame at `location` as no longer live
+    fn release(&mut self, location: Location) {
+        if let Some(segment) = self.segments.get_mut(&location.segment) {
+            segment.live = segment.live.saturating_sub(location.frame_size());
+        }
+    }
+
+    /// Append a raw frame to the active segment, returning its location
+    fn write_frame(&mut self, frame: &[u8]) -> Result<Location> {
+        let segment = self.active_id();
+        let info = self.segments.entry(segment).or_default();
+        let location = Location {
+            segment,
+            offset: info.size,
+            len: u32::try_from(frame.len() - FRAME_HEADER_SIZE)?,
+        };
+        self.active.write_all(frame)?;
+        info.size += location.frame_size();
+        info.live += location.frame_size();
+
+        Ok(location)
+    }
+
+    /// Append a record, unless it is already superseded by an indexed one
+    fn append(&mut self, slot: Slot<TYPES>, payload: &[u8], sync: SyncMode) -> Result<()> {
+        if self.index.is_stale(&slot) {
+            return Ok(());
+        }
+
+        let frame = encode_frame(payload)?;
+        let location = self.write_frame(&frame)?;
+        sync_file(&self.active, sync)?;
+
+        for dead in self.index.insert(slot, location) {
+            self.release(dead);
+        }
+
+        self.maybe_roll()
+    }
+
+    /// Prune the data that falls outside of the retention policy once `decided_view` is decided
+    fn collect_garbage(&mut self, decided_view: TYPES::View) -> Result<()> {
+        let Some(horizon) = self
+            .retention
+            .horizon(decided_view, self.index.view_sizes().into_iter())
+        else {
+            return Ok(());
+        };
+
+        // A prune record lost in a crash only brings back data that the next decide prunes again,
+        // so there is no need to wait for the disk.
+        let payload = bincode::serialize(&Record::<TYPES>::Prune(horizon))?;
+        self.append(Slot::Prune(horizon), &payload, SyncMode::None)
+    }
+
+    /// Read the payload of the frame at `location`
+    fn read_frame(&self, location: Location) -> Result<Vec<u8>> {
+        let mut file = File::open(Self::segment_path(&self.path, location.segment))?;
+        file.seek(SeekFrom::Start(location.offset + FRAME_HEADER_SIZE as u64))?;
+        let mut payload = vec![0; location.len as usize];
+        file.read_exact(&mut payload)?;
+
+        Ok(payload)
+    }
+
+    /// Start a new segment once the active one is full, and compact the closed segments
+    fn maybe_roll(&mut self) -> Result<()> {
+        let active_id = self.active_id();
+        if self.segments[&active_id].size < self.max_segment_size {
+            return Ok(());
+        }
+
+        // Make sure everything in the closed segment is durable before we depend on it never
+        // changing again.
+        self.active.sync_all()?;
+
+        let next_id = active_id + 1;
+        self.active = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::segment_path(&self.path, next_id))?;
+        self.segments.insert(next_id, Segment::default());
+        sync_dir(&self.path)?;
+
+        self.compact()
+    }
+
+    /// Remove closed segments that no longer hold any live frame, and move the live frames out of
+    /// closed segments that are mostly dead so those can be removed as well.
+    fn compact(&mut self) -> Result<()> {
+        let active_id = self.active_id();
+        let sparse: Vec<u64> = self
+            .segments
+            .iter()
+            .filter(|(&id, segment)| id != active_id && segment.live * 4 <= segment.size)
+            .map(|(&id, _)| id)
+            .collect();
+
+        if sparse.is_empty() {
+            return Ok(());
+        }
+
+        for &id in &sparse {
+            let mut live = Vec::new();
+            self.index.for_each_location(|location| {
+                if location.segment == id {
+                    live.push(*location);
+                }
+            });
+
+            let mut moved = HashMap::new();
+            for location in live {
+                let payload = self.read_frame(location)?;
+                let new_location = self.write_frame(&encode_frame(&payload)?)?;
+                moved.insert(location.offset, new_location);
+            }
+            // The copies must be durable before the originals go away.
+            self.active.sync_all()?;
+
+            self.index.for_each_location(|location| {
+                if location.segment == id {
+                    *location = moved[&location.offset];
+                }
+            });
+
+            self.segments.remove(&id);
+            fs::remove_file(Self::segment_path(&self.path, id))?;
+            debug!("Compacted storage segment {id}");
+        }
+
+        sync_dir(&self.path)
+    }
+}
+
+/// Durable storage for consensus data, backed by append-only segment files on disk
+#[derive(Clone, Debug)]
+pub struct FileStorage<TYPES: NodeType> {
+    /// The log and its index
+    inner: Arc<Mutex<Inner<TYPES>>>,
+    /// How hard each call syncs its write
+    sync: SyncPolicy,
+}
+
+impl<TYPES: NodeType> FileStorage<TYPES> {
+    /// Open the storage in `config.path`, creating it if needed.
+    ///
+    /// Any write that was torn by a crash is discarded, so the storage reflects every call that
+    /// completed before the crash.
+    ///
+    /// # Errors
+    /// Returns an error if the directory cannot be read, or a segment other than the last one is
+    /// corrupted.
+    pub fn open(config: FileStorageConfig) -> Result<Self> {
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner::open(&config)?)),
+            sync: config.sync,
+        })
+    }
+
+    /// Encode and append a record
+    async fn append(&self, record: Record<TYPES>, sync: SyncMode) -> Result<()> {
+        let slot = record.slot();
+        let payload = bincode::serialize(&record)?;
+        let inner = Arc::clone(&self.inner);
+
+        spawn_blocking(move || inner.lock_blocking().append(slot, &payload, sync)).await?
+    }
+
+    /// Read the records found by `locate` in the index
+    async fn read<F>(&self, locate: F) -> Result<Vec<Record<TYPES>>>
+    where
+        F: FnOnce(&Index<TYPES>) -> Vec<Location> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        let payloads = spawn_blocking(move || {
+            let inner = inner.lock_blocking();
+            locate(&inner.index)
+                .into_iter()
+                .map(|location| inner.read_frame(location))
+                .collect::<Result<Vec<_>>>()
+        })
+        .await??;
+
+        payloads
+            .iter()
+            .map(|payload| Record::decode(payload))
+            .collect()
+    }
+
+    /// The view of the last vote or proposal recorded by `record_action`, if any
+    pub async fn last_actioned_view(&self) -> Option<TYPES::View> {
+        self.inner.lock().await.index.action.map(|(view, _)| view)
+    }
+
+    /// The epoch we were in when we took the last vote or proposal recorded by `record_action`,
+    /// if any
+    ///
+    /// # Errors
+    /// Returns an error if the action cannot be read back from disk.
+    pub async fn load_last_actioned_epoch(&self) -> Result<Option<TYPES::Epoch>> {
+        let records = self
+            .read(|index| {
+                index
+                    .action
+                    .map(|(_, location)| location)
+                    .into_iter()
+                    .collect()
+            })
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::Action { epoch, .. } => Some(epoch),
+            _ => None,
+        }))
+    }
+
+    /// Load the DA proposal for `view`, along with the VID commitment it was stored with
+    ///
+    /// # Errors
+    /// Returns an error if the proposal cannot be read back from disk.
+    pub async fn load_da_proposal(
+        &self,
+        view: TYPES::View,
+    ) -> Result<Option<(Proposal<TYPES, DaProposal<TYPES>>, VidCommitment)>> {
+        let records = self
+            .read(move |index| index.das.get(&view).copied().into_iter().collect())
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::Da {
+                proposal,
+                vid_commit,
+            } => Some((proposal, vid_commit)),
+            _ => None,
+        }))
+    }
+
+    /// Load every stored quorum proposal
+    ///
+    /// # Errors
+    /// Returns an error if a proposal cannot be read back from disk.
+    pub async fn load_quorum_proposals(
+        &self,
+    ) -> Result<BTreeMap<TYPES::View, Proposal<TYPES, QuorumProposal2<TYPES>>>> {
+        let records = self
+            .read(|index| index.proposals2.values().copied().collect())
+            .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Proposal2(proposal) => Some((proposal.data.view_number(), proposal)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Load the highest QC stored by `update_high_qc2`
+    ///
+    /// # Errors
+    /// Returns an error if the QC cannot be read back from disk.
+    pub async fn load_high_qc(&self) -> Result<Option<QuorumCertificate2<TYPES>>> {
+        let records = self
+            .read(|index| {
+                index
+                    .high_qc2
+                    .map(|(_, location)| location)
+                    .into_iter()
+                    .collect()
+            })
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::HighQc2(qc) => Some(qc),
+            _ => None,
+        }))
+    }
+
+    /// Load the undecided leaves and state stored by `update_undecided_state2`
+    ///
+    /// # Errors
+    /// Returns an error if the state cannot be read back from disk.
+    #[allow(clippy::type_complexity)]
+    pub async fn load_undecided_state(
+        &self,
+    ) -> Result<
+        Option<(
+            CommitmentMap<Leaf2<TYPES>>,
+            BTreeMap<TYPES::View, View<TYPES>>,
+        )>,
+    > {
+        let records = self
+            .read(|index| index.undecided_state2.into_iter().collect())
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::UndecidedState2 { leaves, state } => Some((leaves, state)),
+            _ => None,
+        }))
+    }
+
+    /// Load the decided upgrade certificate
+    ///
+    /// # Errors
+    /// Returns an error if the certificate cannot be read back from disk.
+    pub async fn load_decided_upgrade_certificate(
+        &self,
+    ) -> Result<Option<UpgradeCertificate<TYPES>>> {
+        let records = self
+            .read(|index| index.upgrade_certificate.into_iter().collect())
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::UpgradeCertificate(certificate) => certificate,
+            _ => None,
+        }))
+    }
+
+    /// Load every stored DRB result, by epoch
+    ///
+    /// # Errors
+    /// Returns an error if a result cannot be read back from disk.
+    pub async fn load_drb_results(&self) -> Result<BTreeMap<TYPES::Epoch, DrbResult>> {
+        let records = self
+            .read(|index| index.drb_results.values().copied().collect())
+            .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::DrbResult { epoch, drb_result } => Some((epoch, drb_result)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Load the most recently decided leaf
+    ///
+    /// # Errors
+    /// Returns an error if the leaf cannot be read back from disk.
+    pub async fn load_anchor_leaf(&self) -> Result<Option<Leaf2<TYPES>>> {
+        let records = self
+            .read(|index| {
+                index
+                    .anchor_leaf
+                    .map(|(_, location)| location)
+                    .into_iter()
+                    .collect()
+            })
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::AnchorLeaf(leaf) => Some(leaf),
+            _ => None,
+        }))
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType> Storage<TYPES> for FileStorage<TYPES> {
+    async fn append_vid(&self, proposal: &Proposal<TYPES, VidDisperseShare<TYPES>>) -> Result<()> {
+        self.append(Record::Vid(proposal.clone()), self.sync.vid)
+            .await
+    }
+
+    async fn append_da(
+        &self,
+        proposal: &Proposal<TYPES, DaProposal<TYPES>>,
+        vid_commit: VidCommitment,
+    ) -> Result<()> {
+        self.append(
+            Record::Da {
+                proposal: proposal.clone(),
+                vid_commit,
+            },
+            self.sync.da,
+        )
+        .await
+    }
+
+    async fn append_proposal(
+        &self,
+        proposal: &Proposal<TYPES, QuorumProposal<TYPES>>,
+    ) -> Result<()> {
+        self.append(Record::Proposal(proposal.clone()), self.sync.proposal)
+            .await
+    }
+
+    async fn append_proposal2(
+        &self,
+        proposal: &Proposal<TYPES, QuorumProposal2<TYPES>>,
+    ) -> Result<()> {
+        self.append(Record::Proposal2(proposal.clone()), self.sync.proposal)
+            .await
+    }
+
+    async fn record_action(
+        &self,
+        view: TYPES::View,
+        epoch: TYPES::Epoch,
+        action: HotShotAction,
+    ) -> Result<()> {
+        self.append(
+            Record::Action {
+                view,
+                epoch,
+                action,
+            },
+            self.sync.action,
+        )
+        .await
+    }
+
+    async fn update_high_qc(&self, high_qc: QuorumCertificate<TYPES>) -> Result<()> {
+        self.append(Record::HighQc(high_qc), self.sync.high_qc)
+            .await
+    }
+
+    async fn update_high_qc2(&self, high_qc: QuorumCertificate2<TYPES>) -> Result<()> {
+        self.append(Record::HighQc2(high_qc), self.sync.high_qc)
+            .await
+    }
+
+    async fn update_undecided_state(
+        &self,
+        leaves: CommitmentMap<Leaf<TYPES>>,
+        state: BTreeMap<TYPES::View, View<TYPES>>,
+    ) -> Result<()> {
+        self.append(
+            Record::UndecidedState { leaves, state },
+            self.sync.undecided_state,
+        )
+        .await
+    }
+
+    async fn update_undecided_state2(
+        &self,
+        leaves: CommitmentMap<Leaf2<TYPES>>,
+        state: BTreeMap<TYPES::View, View<TYPES>>,
+    ) -> Result<()> {
+        self.append(
+            Record::UndecidedState2 { leaves, state },
+            self.sync.undecided_state,
+        )
+        .await
+    }
+
+    async fn update_decided_upgrade_certificate(
+        &self,
+        decided_upgrade_certificate: Option<UpgradeCertificate<TYPES>>,
+    ) -> Result<()> {
+        self.append(
+            Record::UpgradeCertificate(decided_upgrade_certificate),
+            self.sync.upgrade_certificate,
+        )
+        .await
+    }
+
+    async fn update_anchor_leaf(&self, leaf: Leaf2<TYPES>) -> Result<()> {
+        self.append(Record::AnchorLeaf(leaf), self.sync.anchor_leaf)
+            .await
+    }
+
+    async fn add_drb_result(&self, epoch: TYPES::Epoch, drb_result: DrbResult) -> Result<()> {
+        self.append(
+            Record::DrbResult { epoch, drb_result },
+            self.sync.drb_result,
+        )
+        .await
+    }
+
+    async fn load_vid_share(
+        &self,
+        view: TYPES::View,
+        key: &TYPES::SignatureKey,
+    ) -> Result<Option<Proposal<TYPES, VidDisperseShare<TYPES>>>> {
+        let key = key.clone();
+        let records = self
+            .read(move |index| {
+                index
+                    .vids
+                    .get(&view)
+                    .and_then(|shares| shares.get(&key))
+                    .copied()
+                    .into_iter()
+                    .collect()
+            })
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::Vid(proposal) => Some(proposal),
+            _ => None,
+        }))
+    }
+
+    async fn load_quorum_proposal(
+        &self,
+        view: TYPES::View,
+    ) -> Result<Option<Proposal<TYPES, QuorumProposal2<TYPES>>>> {
+        let records = self
+            .read(move |index| index.proposals2.get(&view).copied().into_iter().collect())
+            .await?;
+
+        Ok(records.into_iter().find_map(|record| match record {
+            Record::Proposal2(proposal) => Some(proposal),
+            _ => None,
+        }))
+    }
+
+    async fn append_decided_leaves(&self, leaves: &[Leaf2<TYPES>]) -> Result<()> {
+        // Syncing the last leaf makes the ones before it durable as well
+        for (i, leaf) in leaves.iter().enumerate() {
+            let sync = if i + 1 == leaves.len() {
+                self.sync.decided_leaves
+            } else {
+                SyncMode::None
+            };
+            self.append(Record::DecidedLeaf(leaf.clone()), sync).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn load_decided_leaves(
+        &self,
+        from: TYPES::View,
+        to: TYPES::View,
+        limit: usize,
+    ) -> Result<Vec<Leaf2<TYPES>>> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        let records = self
+            .read(move |index| {
+                let mut locations: Vec<_> = index
+                    .decided_leaves
+                    .range(from..=to)
+                    .rev()
+                    .take(limit)
+                    .map(|(_, location)| *location)
+                    .collect();
+                locations.reverse();
+                locations
+            })
+            .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::DecidedLeaf(leaf) => Some(leaf),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn load_decided_leaves_by_height(
+        &self,
+        from: u64,
+        to: u64,
+        limit: usize,
+    ) -> Result<Vec<Leaf2<TYPES>>> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        let records = self
+            .read(move |index| {
+                let mut locations: Vec<_> = index
+                    .decided_heights
+                    .range(from..=to)
+                    .rev()
+                    .filter_map(|(_, view)| index.decided_leaves.get(view).copied())
+                    .take(limit)
+                    .collect();
+                locations.reverse();
+                locations
+            })
+            .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::DecidedLeaf(leaf) => Some(leaf),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn append_equivocation_evidence(
+        &self,
+        evidence: &EquivocationEvidence<TYPES>,
+    ) -> Result<()> {
+        self.append(Record::Evidence(evidence.clone()), self.sync.evidence)
+            .await
+    }
+
+    async fn load_equivocation_evidence(&self) -> Result<Vec<EquivocationEvidence<TYPES>>> {
+        let records = self
+            .read(|index| {
+                index
+                    .evidence
+                    .values()
+                    .flat_map(HashMap::values)
+                    .copied()
+                    .collect()
+            })
+            .await?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Evidence(evidence) => Some(evidence),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn collect_garbage(&self, decided_view: TYPES::View) -> Result<()> {
+        let inner = Arc::clone(&self.inner);
+
+        spawn_blocking(move || inner.lock_blocking().collect_garbage(decided_view)).await?
+    }
+
+    async fn retained_from(&self) -> TYPES::View {
+        self.inner
+            .lock()
+            .await
+            .index
+            .retained_from
+            .map_or_else(TYPES::View::genesis, |(view, _)| view)
+    }
+
+    async fn load_consensus_state(&self) -> Result<PersistedConsensusState<TYPES>> {
+        let (undecided_leaves, undecided_state) =
+            self.load_undecided_state().await?.unwrap_or_default();
+
+        Ok(PersistedConsensusState {
+            anchor_leaf: self.load_anchor_leaf().await?,
+            high_qc: self.load_high_qc().await?,
+            last_actioned_view: self.last_actioned_view().await,
+            last_actioned_epoch: self.load_last_actioned_epoch().await?,
+            saved_proposals: self.load_quorum_proposals().await?,
+            undecided_leaves,
+            undecided_state,
+            decided_upgrade_certificate: self.load_decided_upgrade_certificate().await?,
+            drb_results: self.load_drb_results().await?,
+        })
+    }
+
+    async fn migrate_consensus(
+        &self,
+        convert_leaf: fn(Leaf<TYPES>) -> Leaf2<TYPES>,
+        convert_proposal: fn(
+            Proposal<TYPES, QuorumProposal<TYPES>>,
+        ) -> Proposal<TYPES, QuorumProposal2<TYPES>>,
+    ) -> Result<()> {
+        let legacy = self
+            .read(|index| {
+                index
+                    .proposals
+                    .iter()
+                    .filter(|(view, _)| !index.proposals2.contains_key(view))
+                    .map(|(_, location)| *location)
+                    .chain(
+                        index
+                            .undecided_state
+                            .filter(|_| index.undecided_state2.is_none()),
+                    )
+                    .collect()
+            })
+            .await?;
+
+        if legacy.is_empty() {
+            return Ok(());
+        }
+        debug!("Migrating {} legacy storage records", legacy.len());
+
+        for record in legacy {
+            match record {
+                Record::Proposal(proposal) => {
+                    self.append(
+                        Record::Proposal2(convert_proposal(proposal)),
+                        self.sync.proposal,
+                    )
+                    .await?;
+                }
+                Record::UndecidedState { leaves, state } => {
+                    let leaves = leaves
+                        .into_values()
+                        .map(|leaf| {
+                            let leaf = convert_leaf(leaf);
+                            (leaf.commit(), leaf)
+                        })
+                        .collect();
+                    self.append(
+                        Record::UndecidedState2 { leaves, state },
+                        self.sync.undecided_state,
+                    )
+                    .await?;
+                }
+                _ => bail!("Unexpected record found while migrating storage"),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -6,11 +6,15 @@
 
 mod event;
 mod handle;
+mod subscription;
+mod transaction_status;
 
-pub use event::{Event, EventType};
+pub use event::{Event, EventKind, EventType};
 pub use handle::SystemContextHandle;
 pub use hotshot_types::{
     message::Message,
     signature_key::{BLSPrivKey, BLSPubKey},
     traits::signature_key::SignatureKey,
 };
+pub use subscription::{LagPolicy, Subscription, SubscriptionFilter};
+pub use transaction_status::{TransactionReceipt, TransactionStatus, TransactionTracker};
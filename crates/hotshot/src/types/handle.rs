@@ -6,7 +6,7 @@
 
 //! Provides an event-streaming handle for a [`SystemContext`] running in the background
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use anyhow::{anyhow, Context, Ok, Result};
 use async_broadcast::{InactiveReceiver, Receiver, Sender};
@@ -35,7 +35,14 @@ use hotshot_types::{
 };
 use tracing::instrument;
 
-use crate::{traits::NodeImplementation, types::Event, SystemContext, Versions};
+use crate::{
+    traits::NodeImplementation,
+    types::{
+        Event, LagPolicy, Subscription, SubscriptionFilter, TransactionReceipt, TransactionStatus,
+        TransactionTracker,
+    },
+    SystemContext, Versions,
+};
 
 /// Event streaming handle for a [`SystemContext`] instance running in the background
 ///
@@ -73,6 +80,9 @@ pub struct SystemContextHandle<TYPES: NodeType, I: NodeImplementation<TYPES>, V:
 
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+
+    /// The status of the transactions submitted through this handle
+    pub(crate) transaction_tracker: Arc<RwLock<TransactionTracker<TYPES>>>,
 }
 
 impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static, V: Versions>
@@ -94,6 +104,31 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static, V: Versions>
         self.output_event_stream.1.activate_cloned()
     }
 
+    /// Subscribe to the events `filter` matches, with a buffer of its own so a slow subscriber
+    /// only holds back itself.
+    ///
+    /// Up to `capacity` events are buffered for the subscriber, and `policy` decides what happens
+    /// once it falls further behind. Events the subscriber misses are counted in the
+    /// `lagged_subscription_events` metric.
+    #[must_use]
+    pub fn subscribe(
+        &self,
+        filter: SubscriptionFilter<TYPES>,
+        capacity: usize,
+        policy: LagPolicy,
+    ) -> Subscription<TYPES> {
+        Subscription::new(
+            self.output_event_stream.1.activate_cloned(),
+            filter,
+            capacity,
+            policy,
+            self.hotshot
+                .metrics
+                .lagged_subscription_events
+                .create(vec![policy.name().to_string()]),
+        )
+    }
+
     /// Message other participants with a serialized message from the application
     /// Receivers of this message will get an `Event::ExternalMessageReceived` via
     /// the event stream.
@@ -268,7 +303,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static, V: Versions>
 
     /// Submits a transaction to the backing [`SystemContext`] instance.
     ///
-    /// The current node broadcasts the transaction to all nodes on the network.
+    /// The current node broadcasts the transaction to all nodes on the network. The returned
+    /// receipt can be passed to [`Self::transaction_status`] to follow the transaction.
     ///
     /// # Errors
     ///
@@ -277,8 +313,30 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static, V: Versions>
     pub async fn submit_transaction(
         &self,
         tx: TYPES::Transaction,
-    ) -> Result<(), HotShotError<TYPES>> {
-        self.hotshot.publish_transaction_async(tx).await
+    ) -> Result<TransactionReceipt<TYPES>, HotShotError<TYPES>> {
+        let commitment = tx.commit();
+        self.transaction_tracker
+            .write()
+            .await
+            .track(commitment, Instant::now());
+        self.hotshot
+            .publish_transaction_async(tx)
+            .await
+            .map(|()| TransactionReceipt { commitment })
+    }
+
+    /// The status of a transaction submitted through this handle.
+    ///
+    /// Returns `None` if the transaction is unknown, or reached a final status long enough ago
+    /// to be forgotten. Inclusion is only seen in blocks whose payload this node has.
+    pub async fn transaction_status(
+        &self,
+        receipt: &TransactionReceipt<TYPES>,
+    ) -> Option<TransactionStatus<TYPES>> {
+        self.transaction_tracker
+            .read()
+            .await
+            .status(&receipt.commitment)
     }
 
     /// Get the underlying consensus state for this [`SystemContext`]
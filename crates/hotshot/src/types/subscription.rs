@@ -0,0 +1,193 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Filtered subscriptions to the events of a [`SystemContext`](crate::SystemContext), each with
+//! its own bounded buffer
+
+use std::{
+    collections::BTreeSet,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_broadcast::{Receiver, RecvError, Sender, TrySendError};
+use futures::{Stream, StreamExt};
+use hotshot_types::{
+    event::{Event, EventKind, EventType},
+    traits::{metrics::Counter, node_implementation::NodeType},
+};
+use tokio::task::JoinHandle;
+
+/// The events a subscriber wants
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubscriptionFilter<TYPES: NodeType> {
+    /// Every event
+    All,
+    /// Events of these kinds
+    Kinds(BTreeSet<EventKind>),
+    /// DA, quorum and upgrade proposals sent by this leader
+    ProposalsFrom(TYPES::SignatureKey),
+}
+
+impl<TYPES: NodeType> SubscriptionFilter<TYPES> {
+    /// Decide events only
+    #[must_use]
+    pub fn decides() -> Self {
+        Self::Kinds([EventKind::Decide].into())
+    }
+
+    /// Error events only
+    #[must_use]
+    pub fn errors() -> Self {
+        Self::Kinds([EventKind::Error].into())
+    }
+
+    /// Messages from the applications of other nodes only
+    #[must_use]
+    pub fn external_messages() -> Self {
+        Self::Kinds([EventKind::ExternalMessageReceived].into())
+    }
+
+    /// Proposals sent by `leader` only
+    #[must_use]
+    pub fn proposals_from(leader: TYPES::SignatureKey) -> Self {
+        Self::ProposalsFrom(leader)
+    }
+
+    /// Whether the subscriber wants `event`
+    #[must_use]
+    pub fn matches(&self, event: &Event<TYPES>) -> bool {
+        match self {
+            Self::All => true,
+            Self::Kinds(kinds) => kinds.contains(&event.event.kind()),
+            Self::ProposalsFrom(leader) => matches!(
+                &event.event,
+                EventType::DaProposal { sender, .. }
+                | EventType::QuorumProposal { sender, .. }
+                | EventType::UpgradeProposal { sender, .. } if sender == leader
+            ),
+        }
+    }
+}
+
+/// What to do when the buffer of a subscriber is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Drop the oldest buffered event to make room for the new one
+    #[default]
+    DropOldest,
+    /// Wait for the subscriber to make room.
+    ///
+    /// The node itself never waits, so a subscriber that stays behind for long still misses
+    /// events once the event channel of the node overflows.
+    Block,
+    /// End the subscription, once the subscriber has the buffered events
+    Disconnect,
+}
+
+impl LagPolicy {
+    /// The name of the policy, as used in metrics
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DropOldest => "drop_oldest",
+            Self::Block => "block",
+            Self::Disconnect => "disconnect",
+        }
+    }
+}
+
+/// A stream of the events a [`SubscriptionFilter`] matches, from
+/// [`SystemContextHandle::subscribe`](crate::types::SystemContextHandle::subscribe)
+pub struct Subscription<TYPES: NodeType> {
+    /// The buffer of events for the subscriber
+    events: Receiver<Event<TYPES>>,
+    /// The task filling the buffer, which we stop when the subscriber goes away
+    forwarder: JoinHandle<()>,
+}
+
+impl<TYPES: NodeType> Subscription<TYPES> {
+    /// Subscribe to the events of `source` that `filter` matches, buffering up to `capacity` of
+    /// them, and counting the events the subscriber misses or is disconnected over in `lagged`
+    pub(crate) fn new(
+        source: Receiver<Event<TYPES>>,
+        filter: SubscriptionFilter<TYPES>,
+        capacity: usize,
+        policy: LagPolicy,
+        lagged: Box<dyn Counter>,
+    ) -> Self {
+        let (mut sender, events) = async_broadcast::broadcast(capacity.max(1));
+        sender.set_overflow(policy == LagPolicy::DropOldest);
+
+        Self {
+            events,
+            forwarder: tokio::spawn(forward_events(source, sender, filter, policy, lagged)),
+        }
+    }
+}
+
+impl<TYPES: NodeType> Stream for Subscription<TYPES> {
+    type Item = Event<TYPES>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+impl<TYPES: NodeType> Drop for Subscription<TYPES> {
+    fn drop(&mut self) {
+        self.forwarder.abort();
+    }
+}
+
+/// Move the events `filter` matches from `source` to the buffer of a subscriber, applying
+/// `policy` when the subscriber falls behind, until either side goes away
+async fn forward_events<TYPES: NodeType>(
+    mut source: Receiver<Event<TYPES>>,
+    sink: Sender<Event<TYPES>>,
+    filter: SubscriptionFilter<TYPES>,
+    policy: LagPolicy,
+    lagged: Box<dyn Counter>,
+) {
+    loop {
+        let event = match source.recv_direct().await {
+            Ok(event) => event,
+            // We fell behind the node, whatever room the subscriber has
+            Err(RecvError::Overflowed(skipped)) => {
+                lagged.add(usize::try_from(skipped).unwrap_or(usize::MAX));
+                if policy == LagPolicy::Disconnect {
+                    return;
+                }
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if !filter.matches(&event) {
+            continue;
+        }
+
+        match policy {
+            LagPolicy::DropOldest => match sink.try_broadcast(event) {
+                Ok(None) => {}
+                Ok(Some(_dropped)) => lagged.add(1),
+                Err(_) => return,
+            },
+            LagPolicy::Block => {
+                if sink.broadcast_direct(event).await.is_err() {
+                    return;
+                }
+            }
+            LagPolicy::Disconnect => match sink.try_broadcast(event) {
+                Ok(_) => {}
+                Err(TrySendError::Full(_)) => {
+                    lagged.add(1);
+                    return;
+                }
+                Err(_) => return,
+            },
+        }
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Tracks what happened to the transactions submitted through a [`SystemContextHandle`]
+//!
+//! [`SystemContextHandle`]: crate::types::SystemContextHandle
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use committable::Commitment;
+use hotshot_types::{
+    event::{EventType, LeafInfo},
+    traits::{
+        block_contents::{BlockHeader, BlockPayload},
+        node_implementation::NodeType,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// Proof that a transaction was submitted, used to ask what became of it
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TransactionReceipt<TYPES: NodeType> {
+    /// Commitment to the transaction
+    pub commitment: Commitment<TYPES::Transaction>,
+}
+
+/// Where a submitted transaction is in its lifecycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum TransactionStatus<TYPES: NodeType> {
+    /// Submitted, but not yet seen in a block
+    Pending,
+    /// Included in a proposed block which is not yet decided
+    Sequenced {
+        /// View of the proposal
+        view: TYPES::View,
+        /// Height of the block
+        block_height: u64,
+    },
+    /// Included in a decided block
+    Decided {
+        /// View of the decided leaf
+        view: TYPES::View,
+        /// Height of the block
+        block_height: u64,
+    },
+    /// Not decided within the maximum age of a transaction, so it was dropped
+    Expired,
+}
+
+impl<TYPES: NodeType> TransactionStatus<TYPES> {
+    /// Whether the transaction will not change status anymore
+    #[must_use]
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Decided { .. } | Self::Expired)
+    }
+}
+
+/// A transaction being tracked
+struct TrackedTransaction<TYPES: NodeType> {
+    /// Its current status
+    status: TransactionStatus<TYPES>,
+    /// When it got its current status
+    since: Instant,
+}
+
+/// The status of the transactions submitted by this node, updated from the events it emits.
+///
+/// Transactions that were not decided within `max_age` expire, and are forgotten `max_age` after
+/// they reached a final status.
+pub struct TransactionTracker<TYPES: NodeType> {
+    /// How long a transaction may take to be decided, and how long a final status is kept
+    max_age: Duration,
+    /// The transactions being tracked
+    transactions: HashMap<Commitment<TYPES::Transaction>, TrackedTransaction<TYPES>>,
+    /// The tracked transactions in each DA proposal we saw, until the proposal is decided
+    proposed: BTreeMap<TYPES::View, Vec<Commitment<TYPES::Transaction>>>,
+}
+
+impl<TYPES: NodeType> TransactionTracker<TYPES> {
+    /// Create a tracker expiring transactions that are not decided within `max_age`
+    #[must_use]
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            transactions: HashMap::new(),
+            proposed: BTreeMap::new(),
+        }
+    }
+
+    /// Start tracking the transaction with `commitment`, submitted at `now`.
+    ///
+    /// A transaction submitted again after it expired is pending again.
+    pub fn track(&mut self, commitment: Commitment<TYPES::Transaction>, now: Instant) {
+        let tracked = self
+            .transactions
+            .entry(commitment)
+            .or_insert(TrackedTransaction {
+                status: TransactionStatus::Pending,
+                since: now,
+            });
+        if tracked.status == TransactionStatus::Expired {
+            tracked.status = TransactionStatus::Pending;
+            tracked.since = now;
+        }
+    }
+
+    /// The status of the transaction with `commitment`, if it is being tracked
+    #[must_use]
+    pub fn status(
+        &self,
+        commitment: &Commitment<TYPES::Transaction>,
+    ) -> Option<TransactionStatus<TYPES>> {
+        self.transactions
+            .get(commitment)
+            .map(|tracked| tracked.status)
+    }
+
+    /// Give the tracked transactions among `commitments` the status `status`, unless they are
+    /// already decided
+    fn update(
+        &mut self,
+        commitments: &[Commitment<TYPES::Transaction>],
+        status: TransactionStatus<TYPES>,
+        now: Instant,
+    ) {
+        for commitment in commitments {
+            if let Some(tracked) = self.transactions.get_mut(commitment) {
+                if !matches!(tracked.status, TransactionStatus::Decided { .. }) {
+                    tracked.status = status;
+                    tracked.since = now;
+                }
+            }
+        }
+    }
+
+    /// Update the tracked transactions from an event emitted at `now`
+    pub fn handle_event(&mut self, event: &EventType<TYPES>, now: Instant) {
+        match event {
+            EventType::DaProposal { proposal, .. } => {
+                let metadata = &proposal.data.metadata;
+                let commitments: Vec<_> =
+                    TYPES::BlockPayload::from_bytes(&proposal.data.encoded_transactions, metadata)
+                        .transaction_commitments(metadata)
+                        .into_iter()
+                        .filter(|commitment| self.transactions.contains_key(commitment))
+                        .collect();
+                if !commitments.is_empty() {
+                    self.proposed.insert(proposal.data.view_number, commitments);
+                }
+            }
+            EventType::QuorumProposal { proposal, .. } => {
+                let view = proposal.data.view_number;
+                if let Some(commitments) = self.proposed.get(&view).cloned() {
+                    let block_height = proposal.data.block_header.block_number();
+                    self.update(
+                        &commitments,
+                        TransactionStatus::Sequenced { view, block_height },
+                        now,
+                    );
+                }
+            }
+            EventType::Decide { leaf_chain, .. } => {
+                for LeafInfo { leaf, .. } in leaf_chain.iter() {
+                    let view = leaf.view_number();
+                    // Use the payload if we have it, and otherwise the DA proposal we saw for it
+                    let commitments = match leaf.block_payload() {
+                        Some(payload) => {
+                            payload.transaction_commitments(leaf.block_header().metadata())
+                        }
+                        None => self.proposed.get(&view).cloned().unwrap_or_default(),
+                    };
+                    let block_height = leaf.height();
+                    self.update(
+                        &commitments,
+                        TransactionStatus::Decided { view, block_height },
+                        now,
+                    );
+                }
+                if let Some(LeafInfo { leaf, .. }) = leaf_chain.first() {
+                    self.proposed = self.proposed.split_off(&(leaf.view_number() + 1));
+                }
+            }
+            EventType::ViewFinished { .. } => self.expire(now),
+            _ => {}
+        }
+    }
+
+    /// Expire the transactions not decided within the maximum age, and forget those which reached
+    /// a final status long enough ago
+    pub fn expire(&mut self, now: Instant) {
+        let max_age = self.max_age;
+        self.transactions.retain(|_, tracked| {
+            if now.saturating_duration_since(tracked.since) < max_age {
+                return true;
+            }
+            if tracked.status.is_final() {
+                return false;
+            }
+            tracked.status = TransactionStatus::Expired;
+            tracked.since = now;
+            true
+        });
+    }
+}
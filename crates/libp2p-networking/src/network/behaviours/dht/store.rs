@@ -7,12 +7,24 @@ use std::marker::PhantomData;
 
 use delegate::delegate;
 use hotshot_types::traits::signature_key::SignatureKey;
-use libp2p::kad::store::{Error, RecordStore, Result};
+use libp2p::kad::{
+    store::{Error, RecordStore, Result},
+    Record,
+};
 use tracing::warn;
 
 use super::record::RecordValue;
 use crate::network::behaviours::dht::record::RecordKey;
 
+/// Why a [`ValidatedStore`] did not store a record
+#[derive(Debug)]
+pub enum PutRecordError {
+    /// The record does not decode, or is not signed for its key
+    Invalid,
+    /// The record is valid, but the underlying store did not take it, e.g. because it is full
+    Store(Error),
+}
+
 /// A `RecordStore` wrapper that validates records before storing them.
 pub struct ValidatedStore<R: RecordStore, K: SignatureKey> {
     /// The underlying store
@@ -30,6 +42,36 @@ impl<R: RecordStore, K: SignatureKey> ValidatedStore<R, K> {
             phantom: PhantomData,
         }
     }
+
+    /// Validate `record` and store it if it is valid.
+    ///
+    /// # Errors
+    /// Returns [`PutRecordError::Invalid`] if the record is invalid, and
+    /// [`PutRecordError::Store`] if the underlying store did not take it.
+    pub fn try_put(&mut self, record: Record) -> std::result::Result<(), PutRecordError> {
+        // Convert the record to the correct type
+        let Ok(record_value) = RecordValue::<K>::try_from(record.clone()) else {
+            warn!("Failed to decode record value");
+            return Err(PutRecordError::Invalid);
+        };
+
+        // Convert the key to the correct type
+        let Ok(record_key) = RecordKey::try_from_bytes(&record.key.to_vec()) else {
+            warn!("Failed to convert record key");
+            return Err(PutRecordError::Invalid);
+        };
+
+        // Only store the record if it is signed by the correct key
+        if !record_value.validate(&record_key) {
+            warn!("Failed to validate record");
+            return Err(PutRecordError::Invalid);
+        }
+
+        self.store.put(record).map_err(|err| {
+            warn!("Failed to store record: {:?}", err);
+            PutRecordError::Store(err)
+        })
+    }
 }
 
 /// Implement the `RecordStore` trait for `ValidatedStore`
@@ -62,29 +104,11 @@ where
     }
 
     /// Overwrite the `put` method to validate the record before storing it
-    fn put(&mut self, record: libp2p::kad::Record) -> Result<()> {
-        // Convert the record to the correct type
-        if let Ok(record_value) = RecordValue::<K>::try_from(record.clone()) {
-            // Convert the key to the correct type
-            let Ok(record_key) = RecordKey::try_from_bytes(&record.key.to_vec()) else {
-                warn!("Failed to convert record key");
-                return Err(Error::MaxRecords);
-            };
-
-            // If the record is signed by the correct key,
-            if record_value.validate(&record_key) {
-                // Store the record
-                if let Err(err) = self.store.put(record.clone()) {
-                    warn!("Failed to store record: {:?}", err);
-                    return Err(Error::MaxRecords);
-                }
-            } else {
-                warn!("Failed to validate record");
-                return Err(Error::MaxRecords);
-            }
-        }
-
-        Ok(())
+    fn put(&mut self, record: Record) -> Result<()> {
+        self.try_put(record).map_err(|err| match err {
+            PutRecordError::Invalid => Error::MaxRecords,
+            PutRecordError::Store(err) => err,
+        })
     }
 }
 
@@ -92,7 +116,10 @@ where
 mod test {
     use hotshot_types::signature_key::BLSPubKey;
     use libp2p::{
-        kad::{store::MemoryStore, Record},
+        kad::{
+            store::{MemoryStore, MemoryStoreConfig},
+            Record,
+        },
         PeerId,
     };
 
@@ -171,4 +198,75 @@ mod test {
             "Should not have stored record"
         );
     }
+
+    /// Test that a record that does not decode is rejected
+    #[test]
+    fn test_undecodable_not_stored() {
+        // Generate a staking keypair
+        let (public_key, _) = BLSPubKey::generated_from_seed_indexed([1; 32], 1337);
+
+        // Create a record key
+        let record_key = RecordKey::new(Namespace::Lookup, public_key.to_bytes());
+
+        // Initialize the store
+        let mut store: ValidatedStore<MemoryStore, BLSPubKey> =
+            ValidatedStore::new(MemoryStore::new(PeerId::random()));
+
+        // Make sure we are unable to store garbage
+        let record = Record::new(record_key.to_bytes(), vec![0xff; 3]);
+        assert!(store.put(record).is_err(), "Should not have stored record");
+
+        // Check that the record is not stored
+        let libp2p_record_key = libp2p::kad::RecordKey::new(&record_key.to_bytes());
+        assert!(
+            store.get(&libp2p_record_key).is_none(),
+            "Should not have stored record"
+        );
+    }
+
+    /// Test that a full store tells a valid record apart from an invalid one
+    #[test]
+    fn test_full_store_rejects_valid_record() {
+        // A signed record for each of two staking keypairs
+        let records: Vec<_> = [1, 2]
+            .into_iter()
+            .map(|index| {
+                let (public_key, private_key) =
+                    BLSPubKey::generated_from_seed_indexed([1; 32], index);
+                let record_key = RecordKey::new(Namespace::Lookup, public_key.to_bytes());
+                let record_value: RecordValue<BLSPubKey> =
+                    RecordValue::new_signed(&record_key, vec![5, 6, 7, 8], &private_key).unwrap();
+                Record::new(
+                    record_key.to_bytes(),
+                    bincode::serialize(&record_value).expect("Failed to serialize record value"),
+                )
+            })
+            .collect();
+
+        // Initialize a store with room for one record
+        let mut store: ValidatedStore<MemoryStore, BLSPubKey> =
+            ValidatedStore::new(MemoryStore::with_config(
+                PeerId::random(),
+                MemoryStoreConfig {
+                    max_records: 1,
+                    ..MemoryStoreConfig::default()
+                },
+            ));
+
+        // The second valid record does not fit, which is not the fault of the record
+        store
+            .try_put(records[0].clone())
+            .expect("Failed to store record");
+        assert!(matches!(
+            store.try_put(records[1].clone()),
+            Err(PutRecordError::Store(Error::MaxRecords))
+        ));
+
+        // An invalid record is still rejected as such
+        let invalid = Record::new(records[1].key.clone(), vec![0xff; 3]);
+        assert!(matches!(
+            store.try_put(invalid),
+            Err(PutRecordError::Invalid)
+        ));
+    }
 }
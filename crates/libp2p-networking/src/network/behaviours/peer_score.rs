@@ -0,0 +1,210 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use hotshot_types::traits::network::PeerMisbehaviour;
+use libp2p_identity::PeerId;
+
+/// Scores that decayed this close to zero are forgotten
+const FORGET_SCORE: f64 = -0.01;
+
+/// Configuration for scoring peers by their misbehaviour
+#[derive(Clone, Debug)]
+pub struct PeerScoreConfig {
+    /// Score lost for sending us something with an invalid signature
+    pub invalid_signature_penalty: f64,
+    /// Score lost for sending us a message we could not decode
+    pub undecodable_message_penalty: f64,
+    /// Score lost for asking us to store a DHT record that failed validation
+    pub invalid_dht_record_penalty: f64,
+    /// Time it takes a score to decay halfway back to zero
+    pub decay_half_life: Duration,
+    /// Score at or below which we disconnect from a peer
+    pub prune_threshold: f64,
+    /// Score at or below which we also refuse the peer's connections and drop its messages, until
+    /// its score decays back above the threshold
+    pub ban_threshold: f64,
+}
+
+impl Default for PeerScoreConfig {
+    fn default() -> Self {
+        Self {
+            invalid_signature_penalty: 10.0,
+            undecodable_message_penalty: 10.0,
+            invalid_dht_record_penalty: 10.0,
+            decay_half_life: Duration::from_secs(10 * 60),
+            prune_threshold: -50.0,
+            ban_threshold: -100.0,
+        }
+    }
+}
+
+impl PeerScoreConfig {
+    /// The score a peer loses for `misbehaviour`
+    fn penalty(&self, misbehaviour: PeerMisbehaviour) -> f64 {
+        match misbehaviour {
+            PeerMisbehaviour::InvalidSignature => self.invalid_signature_penalty,
+            PeerMisbehaviour::UndecodableMessage => self.undecodable_message_penalty,
+            PeerMisbehaviour::InvalidDhtRecord => self.invalid_dht_record_penalty,
+        }
+    }
+}
+
+/// What to do with a peer after it misbehaved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerScoreAction {
+    /// Disconnect from the peer
+    Prune,
+    /// Disconnect from the peer and refuse it until its score recovers
+    Ban,
+}
+
+/// The scores of the peers that misbehaved recently.
+///
+/// Every peer starts at zero, loses score when it misbehaves, and decays back towards zero over
+/// time. Peers that never misbehaved are not tracked.
+#[derive(Clone, Debug)]
+pub struct PeerScores {
+    /// How to score peers
+    config: PeerScoreConfig,
+    /// The score of each peer, as of when it was last updated
+    scores: HashMap<PeerId, (f64, Instant)>,
+}
+
+impl PeerScores {
+    /// Create an empty score table scoring peers according to `config`
+    #[must_use]
+    pub fn new(config: PeerScoreConfig) -> Self {
+        Self {
+            config,
+            scores: HashMap::new(),
+        }
+    }
+
+    /// `score`, last updated at `since`, decayed until `now`
+    fn decay(&self, score: f64, since: Instant, now: Instant) -> f64 {
+        let half_lives = now.saturating_duration_since(since).as_secs_f64()
+            / self.config.decay_half_life.as_secs_f64();
+        score * 0.5_f64.powf(half_lives)
+    }
+
+    /// The score of `peer` at `now`
+    #[must_use]
+    pub fn score(&self, peer: &PeerId, now: Instant) -> f64 {
+        self.scores
+            .get(peer)
+            .map_or(0.0, |&(score, since)| self.decay(score, since, now))
+    }
+
+    /// Whether `peer` is banned at `now`
+    #[must_use]
+    pub fn is_banned(&self, peer: &PeerId, now: Instant) -> bool {
+        self.score(peer, now) <= self.config.ban_threshold
+    }
+
+    /// Lower the score of `peer` for `misbehaviour` at `now`, returning what to do with it.
+    pub fn report(
+        &mut self,
+        peer: PeerId,
+        misbehaviour: PeerMisbehaviour,
+        now: Instant,
+    ) -> Option<PeerScoreAction> {
+        let score = self.score(&peer, now) - self.config.penalty(misbehaviour);
+        self.scores.insert(peer, (score, now));
+
+        // Forget the peers that made up for their misbehaviour
+        let scores = std::mem::take(&mut self.scores);
+        self.scores = scores
+            .into_iter()
+            .filter(|&(_, (score, since))| self.decay(score, since, now) < FORGET_SCORE)
+            .collect();
+
+        if score <= self.config.ban_threshold {
+            Some(PeerScoreAction::Ban)
+        } else if score <= self.config.prune_threshold {
+            Some(PeerScoreAction::Prune)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test that penalties add up to pruning and then banning
+    #[test]
+    fn test_thresholds() {
+        let mut scores = PeerScores::new(PeerScoreConfig::default());
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..4 {
+            assert_eq!(
+                scores.report(peer, PeerMisbehaviour::InvalidSignature, now),
+                None
+            );
+        }
+        assert_eq!(
+            scores.report(peer, PeerMisbehaviour::UndecodableMessage, now),
+            Some(PeerScoreAction::Prune)
+        );
+        assert!(!scores.is_banned(&peer, now));
+
+        for _ in 0..4 {
+            assert_eq!(
+                scores.report(peer, PeerMisbehaviour::InvalidDhtRecord, now),
+                Some(PeerScoreAction::Prune)
+            );
+        }
+        assert_eq!(
+            scores.report(peer, PeerMisbehaviour::InvalidDhtRecord, now),
+            Some(PeerScoreAction::Ban)
+        );
+        assert!(scores.is_banned(&peer, now));
+
+        // Other peers are unaffected
+        assert!(scores.score(&PeerId::random(), now).abs() < f64::EPSILON);
+    }
+
+    /// Test that scores decay back to zero
+    #[test]
+    fn test_decay() {
+        let config = PeerScoreConfig::default();
+        let half_life = config.decay_half_life;
+        let mut scores = PeerScores::new(config);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            scores.report(peer, PeerMisbehaviour::InvalidSignature, now);
+        }
+        assert!(scores.is_banned(&peer, now));
+
+        // After a half life the peer is no longer banned, and a single misbehaviour only prunes it
+        let later = now + half_life;
+        assert!((scores.score(&peer, later) + 50.0).abs() < 1e-9);
+        assert!(!scores.is_banned(&peer, later));
+        assert_eq!(
+            scores.report(peer, PeerMisbehaviour::UndecodableMessage, later),
+            Some(PeerScoreAction::Prune)
+        );
+
+        // Eventually the peer is forgotten
+        let much_later = later + 20 * half_life;
+        scores.report(
+            PeerId::random(),
+            PeerMisbehaviour::UndecodableMessage,
+            much_later,
+        );
+        assert!(!scores.scores.contains_key(&peer));
+    }
+}
@@ -19,7 +19,10 @@ pub mod cbor;
 use std::{collections::HashSet, fmt::Debug};
 
 use futures::channel::oneshot::Sender;
-use hotshot_types::traits::{network::NetworkError, node_implementation::NodeType};
+use hotshot_types::traits::{
+    network::{NetworkError, PeerMisbehaviour},
+    node_implementation::NodeType,
+};
 use libp2p::{
     build_multiaddr,
     core::{muxing::StreamMuxerBox, transport::Boxed},
@@ -71,6 +74,10 @@ pub enum ClientRequest {
     DirectResponse(ResponseChannel<Vec<u8>>, Vec<u8>),
     /// prune a peer
     Prune(PeerId),
+    /// lower the score of a misbehaving peer, pruning or banning it if it drops too low
+    ReportPeer(PeerId, PeerMisbehaviour),
+    /// never lower the score of these peers, so they are never pruned or banned
+    ProtectPeers(Vec<PeerId>),
     /// add vec of known peers or addresses
     AddKnownPeers(Vec<(PeerId, Multiaddr)>),
     /// Ignore peers. Only here for debugging purposes.
@@ -109,8 +116,8 @@ pub enum ClientRequest {
 /// to relay to the client
 #[derive(Debug)]
 pub enum NetworkEvent {
-    /// Recv-ed a broadcast
-    GossipMsg(Vec<u8>),
+    /// Recv-ed a broadcast, along with its publisher if it was signed
+    GossipMsg(Vec<u8>, Option<PeerId>),
     /// Recv-ed a direct message from a node
     DirectRequest(Vec<u8>, PeerId, ResponseChannel<Vec<u8>>),
     /// Recv-ed a direct response from a node (that hopefully was initiated by this node)
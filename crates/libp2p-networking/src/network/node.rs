@@ -13,14 +13,15 @@ mod handle;
 
 use std::{
     collections::{HashMap, HashSet},
-    iter,
+    io, iter,
     num::{NonZeroU32, NonZeroUsize},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures::{channel::mpsc, SinkExt, StreamExt};
 use hotshot_types::{
-    constants::KAD_DEFAULT_REPUB_INTERVAL_SEC, traits::node_implementation::NodeType,
+    constants::KAD_DEFAULT_REPUB_INTERVAL_SEC,
+    traits::{network::PeerMisbehaviour, node_implementation::NodeType},
 };
 use libp2p::{
     autonat,
@@ -34,9 +35,13 @@ use libp2p::{
         Info as IdentifyInfo,
     },
     identity::Keypair,
-    kad::{store::MemoryStore, Behaviour, Config, Mode, Record},
+    kad::{
+        store::{MemoryStore, RecordStore},
+        Behaviour, Config, Event as KademliaEvent, InboundRequest, Mode, Record, StoreInserts,
+    },
     request_response::{
-        Behaviour as RequestResponse, Config as Libp2pRequestResponseConfig, ProtocolSupport,
+        Behaviour as RequestResponse, Config as Libp2pRequestResponseConfig,
+        Event as DirectMessageEvent, InboundFailure, ProtocolSupport,
     },
     swarm::SwarmEvent,
     Multiaddr, StreamProtocol, Swarm, SwarmBuilder,
@@ -59,7 +64,7 @@ pub use self::{
 use super::{
     behaviours::dht::{
         bootstrap::{DHTBootstrapTask, InputEvent},
-        store::ValidatedStore,
+        store::{PutRecordError, ValidatedStore},
     },
     cbor::Cbor,
     gen_transport, BoxedTransport, ClientRequest, NetworkDef, NetworkError, NetworkEvent,
@@ -69,6 +74,7 @@ use crate::network::behaviours::{
     dht::{DHTBehaviour, DHTProgress, KadPutQuery, NUM_REPLICATED_TO_TRUST},
     direct_message::{DMBehaviour, DMRequest},
     exponential_backoff::ExponentialBackoff,
+    peer_score::{PeerScoreAction, PeerScores},
 };
 
 /// Maximum size of a message
@@ -96,6 +102,10 @@ pub struct NetworkNode<T: NodeType> {
     dht_handler: DHTBehaviour<T::SignatureKey>,
     /// Channel to resend requests, set to Some when we call `spawn_listeners`
     resend_tx: Option<UnboundedSender<ClientRequest>>,
+    /// Scores of the peers that misbehaved recently
+    peer_scores: PeerScores,
+    /// Peers that are never pruned or banned, however they behave
+    protected_peers: HashSet<PeerId>,
 }
 
 impl<T: NodeType> NetworkNode<T> {
@@ -243,7 +253,9 @@ impl<T: NodeType> NetworkNode<T> {
                 .set_parallelism(NonZeroUsize::new(5).unwrap())
                 .set_provider_publication_interval(Some(record_republication_interval))
                 .set_publication_interval(Some(record_republication_interval))
-                .set_record_ttl(ttl);
+                .set_record_ttl(ttl)
+                // Validate inbound records ourselves, so we know who sent the invalid ones
+                .set_record_filtering(StoreInserts::FilterBoth);
 
             // allowing panic here because something is very wrong if this fales
             #[allow(clippy::panic)]
@@ -321,9 +333,81 @@ impl<T: NodeType> NetworkNode<T> {
                     .unwrap_or(NonZeroUsize::new(4).unwrap()),
             ),
             resend_tx: None,
+            peer_scores: PeerScores::new(config.peer_score_config.clone()),
+            protected_peers: HashSet::new(),
         })
     }
 
+    /// Lower the score of `peer` for `misbehaviour`, disconnecting from it if it drops too low.
+    /// Protected peers are never disconnected.
+    fn report_peer(&mut self, peer: PeerId, misbehaviour: PeerMisbehaviour) {
+        if peer == self.peer_id || self.protected_peers.contains(&peer) {
+            return;
+        }
+
+        match self.peer_scores.report(peer, misbehaviour, Instant::now()) {
+            Some(PeerScoreAction::Ban) => {
+                warn!("Banning peer {:?} after {:?}", peer, misbehaviour);
+            }
+            Some(PeerScoreAction::Prune) => {
+                info!("Pruning peer {:?} after {:?}", peer, misbehaviour);
+            }
+            None => {
+                debug!("Peer {:?} misbehaved: {:?}", peer, misbehaviour);
+                return;
+            }
+        }
+        if self.swarm.disconnect_peer_id(peer).is_err() {
+            debug!("Misbehaving peer {:?} was not connected", peer);
+        }
+    }
+
+    /// Whether `peer` is banned, and everything it sends us should be dropped
+    fn is_banned(&self, peer: &PeerId) -> bool {
+        !self.protected_peers.contains(peer) && self.peer_scores.is_banned(peer, Instant::now())
+    }
+
+    /// Store the records and provider records other peers asked us to store, reporting the peers
+    /// whose records fail validation.
+    fn handle_inbound_dht_request(&mut self, request: InboundRequest) {
+        match request {
+            InboundRequest::PutRecord {
+                source,
+                connection: _,
+                record: Some(record),
+            } => {
+                if self.is_banned(&source) {
+                    return;
+                }
+                match self.swarm.behaviour_mut().dht.store_mut().try_put(record) {
+                    Ok(()) => {}
+                    // Only an invalid record is the fault of the peer
+                    Err(PutRecordError::Invalid) => {
+                        warn!("Rejected invalid DHT record from {:?}", source);
+                        self.report_peer(source, PeerMisbehaviour::InvalidDhtRecord);
+                    }
+                    Err(PutRecordError::Store(err)) => {
+                        warn!("Failed to store DHT record from {:?}: {:?}", source, err);
+                    }
+                }
+            }
+            InboundRequest::AddProvider {
+                record: Some(record),
+            } => {
+                if let Err(err) = self
+                    .swarm
+                    .behaviour_mut()
+                    .dht
+                    .store_mut()
+                    .add_provider(record)
+                {
+                    warn!("Failed to store DHT provider record: {:?}", err);
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Publish a key/value to the record store.
     ///
     /// # Panics
@@ -477,6 +561,12 @@ impl<T: NodeType> NetworkNode<T> {
                             warn!("Could not disconnect from {:?}", pid);
                         }
                     }
+                    ClientRequest::ReportPeer(pid, misbehaviour) => {
+                        self.report_peer(pid, misbehaviour);
+                    }
+                    ClientRequest::ProtectPeers(peers) => {
+                        self.protected_peers.extend(peers);
+                    }
                 }
             }
             None => {
@@ -519,6 +609,11 @@ impl<T: NodeType> NetworkNode<T> {
                     );
                 }
 
+                if self.is_banned(&peer_id) {
+                    debug!("Disconnecting from banned peer {:?}", peer_id);
+                    let _ = self.swarm.disconnect_peer_id(peer_id);
+                }
+
                 // Send the number of connected peers to the client
                 send_to_client
                     .send(NetworkEvent::ConnectedPeersUpdate(self.num_connected()))
@@ -576,6 +671,10 @@ impl<T: NodeType> NetworkNode<T> {
             } => {}
             SwarmEvent::Behaviour(b) => {
                 let maybe_event = match b {
+                    NetworkEventInternal::DHTEvent(KademliaEvent::InboundRequest { request }) => {
+                        self.handle_inbound_dht_request(request);
+                        None
+                    }
                     NetworkEventInternal::DHTEvent(e) => self
                         .dht_handler
                         .dht_handle_event(e, self.swarm.behaviour_mut().dht.store_mut()),
@@ -605,10 +704,16 @@ impl<T: NodeType> NetworkNode<T> {
                     }
                     NetworkEventInternal::GossipEvent(e) => match *e {
                         GossipEvent::Message {
-                            propagation_source: _peer_id,
+                            propagation_source,
                             message_id: _id,
                             message,
-                        } => Some(NetworkEvent::GossipMsg(message.data)),
+                        } => {
+                            if self.is_banned(&propagation_source) {
+                                None
+                            } else {
+                                Some(NetworkEvent::GossipMsg(message.data, message.source))
+                            }
+                        }
                         GossipEvent::Subscribed { peer_id, topic } => {
                             debug!("Peer {:?} subscribed to topic {:?}", peer_id, topic);
                             None
@@ -622,9 +727,34 @@ impl<T: NodeType> NetworkNode<T> {
                             None
                         }
                     },
-                    NetworkEventInternal::DMEvent(e) => self
-                        .direct_message_state
-                        .handle_dm_event(e, self.resend_tx.clone()),
+                    NetworkEventInternal::DMEvent(e) => {
+                        // The codec fails with these when it cannot decode a request
+                        if let DirectMessageEvent::InboundFailure {
+                            peer,
+                            error: InboundFailure::Io(ref err),
+                            ..
+                        } = e
+                        {
+                            if matches!(
+                                err.kind(),
+                                io::ErrorKind::InvalidData | io::ErrorKind::Unsupported
+                            ) {
+                                self.report_peer(peer, PeerMisbehaviour::UndecodableMessage);
+                            }
+                        }
+
+                        match self
+                            .direct_message_state
+                            .handle_dm_event(e, self.resend_tx.clone())
+                        {
+                            Some(NetworkEvent::DirectRequest(_, pid, _))
+                                if self.is_banned(&pid) =>
+                            {
+                                None
+                            }
+                            event => event,
+                        }
+                    }
                     NetworkEventInternal::AutonatEvent(e) => {
                         match e {
                             autonat::Event::InboundProbe(_) => {}
@@ -11,6 +11,7 @@ use libp2p::{identity::Keypair, Multiaddr};
 use libp2p_identity::PeerId;
 
 use super::MAX_GOSSIP_MSG_SIZE;
+use crate::network::behaviours::peer_score::PeerScoreConfig;
 
 /// The default Kademlia replication factor
 pub const DEFAULT_REPLICATION_FACTOR: Option<NonZeroUsize> = NonZeroUsize::new(10);
@@ -59,6 +60,10 @@ pub struct NetworkNodeConfig<T: NodeType> {
     #[builder(default)]
     /// The timeout for DHT lookups.
     pub dht_timeout: Option<Duration>,
+
+    #[builder(default)]
+    /// How to score misbehaving peers, and when to disconnect from them
+    pub peer_score_config: PeerScoreConfig,
 }
 
 /// Configuration for Libp2p's Gossipsub
@@ -6,7 +6,10 @@
 
 use std::{collections::HashSet, fmt::Debug, time::Duration};
 
-use hotshot_types::traits::{network::NetworkError, node_implementation::NodeType};
+use hotshot_types::traits::{
+    network::{NetworkError, PeerMisbehaviour},
+    node_implementation::NodeType,
+};
 use libp2p::{request_response::ResponseChannel, Multiaddr};
 use libp2p_identity::PeerId;
 use tokio::{
@@ -329,6 +332,15 @@ impl<T: NodeType> NetworkNodeHandle<T> {
         self.send_request(req)
     }
 
+    /// Protect `peers` from misbehaviour reports
+    /// e.g. never prune or ban them, however they behave
+    /// # Errors
+    /// - Will return [`NetworkError::ChannelSendError`] when underlying `NetworkNode` has been killed
+    pub fn protect_peers(&self, peers: Vec<PeerId>) -> Result<(), NetworkError> {
+        let req = ClientRequest::ProtectPeers(peers);
+        self.send_request(req)
+    }
+
     /// Make a direct request to `peer_id` containing `msg`
     /// # Errors
     /// - Will return [`NetworkError::ChannelSendError`] when underlying `NetworkNode` has been killed
@@ -379,6 +391,18 @@ impl<T: NodeType> NetworkNodeHandle<T> {
         self.send_request(req)
     }
 
+    /// Lower the score of `pid` for `misbehaviour`, disconnecting from it if it drops too low
+    /// # Errors
+    /// - Will return [`NetworkError::ChannelSendError`] when underlying `NetworkNode` has been killed
+    pub fn report_peer(
+        &self,
+        pid: PeerId,
+        misbehaviour: PeerMisbehaviour,
+    ) -> Result<(), NetworkError> {
+        let req = ClientRequest::ReportPeer(pid, misbehaviour);
+        self.send_request(req)
+    }
+
     /// Gossip a message to peers
     /// # Errors
     /// - Will return [`NetworkError::ChannelSendError`] when underlying `NetworkNode` has been killed
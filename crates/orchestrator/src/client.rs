@@ -145,6 +145,9 @@ pub struct ValidatorArgs {
     /// Allows for rejoining the network on a complete state loss
     #[arg(short, long)]
     pub network_config_file: Option<String>,
+    /// Optional address to serve Prometheus metrics on, at `/status/metrics`
+    #[arg(long)]
+    pub metrics_address: Option<SocketAddr>,
 }
 
 /// arguments to run multiple validators
@@ -160,6 +163,10 @@ pub struct MultiValidatorArgs {
     /// Allows for rejoining the network on a complete state loss
     #[arg(short, long)]
     pub network_config_file: Option<String>,
+    /// Optional address to serve Prometheus metrics on. Each validator uses the port of this
+    /// address plus its index
+    #[arg(long)]
+    pub metrics_address: Option<SocketAddr>,
 }
 
 /// Asynchronously retrieves a `NetworkConfig` from an orchestrator.
@@ -226,6 +233,10 @@ impl ValidatorArgs {
             network_config_file: multi_args
                 .network_config_file
                 .map(|s| format!("{s}-{node_index}")),
+            metrics_address: multi_args.metrics_address.map(|mut address| {
+                address.set_port(address.port().saturating_add(node_index));
+                address
+            }),
         }
     }
 }
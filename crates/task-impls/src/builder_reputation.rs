@@ -0,0 +1,248 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    cmp::Ordering,
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+/// How long a builder is left out after its first failure in a row
+const BUILDER_BACKOFF_BASE: Duration = Duration::from_millis(500);
+/// The longest a builder is left out, however many times in a row it failed
+const BUILDER_BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// Weight of the latest response in the average response time of a builder
+const LATENCY_SMOOTHING: f64 = 0.2;
+/// Age at which a success or failure counts half as much towards the reliability of a builder
+const RELIABILITY_HALF_LIFE: Duration = Duration::from_secs(10 * 60);
+
+/// What happened when we asked a builder for a block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuilderOutcome {
+    /// The builder offered blocks
+    Responded {
+        /// How long it took to respond
+        latency: Duration,
+        /// The highest fee it offered
+        offered_fee: u64,
+    },
+    /// The builder gave us the block we claimed
+    Claimed,
+    /// The builder did not respond in time
+    Timeout,
+    /// The builder failed to answer the query for available blocks
+    QueryFailed,
+    /// The builder failed to give us a block we claimed
+    ClaimFailed,
+    /// The builder sent a response with an invalid signature
+    InvalidSignature,
+}
+
+impl BuilderOutcome {
+    /// Whether the builder did what we asked
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Responded { .. } | Self::Claimed)
+    }
+
+    /// Short name of a failure, used as a metric label
+    #[must_use]
+    pub fn failure_reason(&self) -> Option<&'static str> {
+        match self {
+            Self::Responded { .. } | Self::Claimed => None,
+            Self::Timeout => Some("timeout"),
+            Self::QueryFailed => Some("query_failed"),
+            Self::ClaimFailed => Some("claim_failed"),
+            Self::InvalidSignature => Some("invalid_signature"),
+        }
+    }
+}
+
+/// What we know about how a builder has served us
+#[derive(Clone, Debug, Default)]
+pub struct BuilderStats {
+    /// Average time the builder took to offer blocks
+    pub latency: Option<Duration>,
+    /// Highest fee offered in its last response
+    pub offered_fee: u64,
+    /// Number of times the builder did what we asked, in all
+    pub successes: u64,
+    /// Number of times it did not respond in time
+    pub timeouts: u64,
+    /// Number of times it failed to answer a query for available blocks
+    pub query_failures: u64,
+    /// Number of times it failed to give us a block we claimed
+    pub claim_failures: u64,
+    /// Number of responses with an invalid signature
+    pub signature_failures: u64,
+    /// Number of failures since the last success
+    pub consecutive_failures: u32,
+    /// The builder is not asked for blocks until then
+    pub backoff_until: Option<Instant>,
+    /// Successes, each weighed by its age as of `weighed_at`
+    recent_successes: f64,
+    /// Failures, each weighed by its age as of `weighed_at`
+    recent_failures: f64,
+    /// When the builder last succeeded or failed
+    weighed_at: Option<Instant>,
+}
+
+impl BuilderStats {
+    /// Total number of failures
+    #[must_use]
+    pub fn failures(&self) -> u64 {
+        self.timeouts + self.query_failures + self.claim_failures + self.signature_failures
+    }
+
+    /// How much the recent successes and failures still count for at `now`
+    fn weight(&self, now: Instant) -> f64 {
+        self.weighed_at.map_or(1.0, |weighed_at| {
+            let age = now.saturating_duration_since(weighed_at);
+            0.5_f64.powf(age.as_secs_f64() / RELIABILITY_HALF_LIFE.as_secs_f64())
+        })
+    }
+
+    /// Count a success or failure at `now`, weighing down the earlier ones by their age
+    fn weigh(&mut self, success: bool, now: Instant) {
+        let weight = self.weight(now);
+        self.recent_successes *= weight;
+        self.recent_failures *= weight;
+        if success {
+            self.recent_successes += 1.0;
+        } else {
+            self.recent_failures += 1.0;
+        }
+        self.weighed_at = Some(now);
+    }
+
+    /// Estimated chance of the builder doing what we ask at `now`, starting at one half for a
+    /// builder we know nothing about. Recent successes and failures count for more than old ones.
+    #[must_use]
+    pub fn reliability(&self, now: Instant) -> f64 {
+        let weight = self.weight(now);
+        (self.recent_successes * weight + 1.0)
+            / ((self.recent_successes + self.recent_failures) * weight + 2.0)
+    }
+
+    /// Whether the builder is left out at `now`
+    #[must_use]
+    pub fn backing_off(&self, now: Instant) -> bool {
+        self.backoff_until.is_some_and(|until| until > now)
+    }
+
+    /// Order builders best first at `now`: most reliable, then fastest, then offering the highest
+    /// fee.
+    ///
+    /// Builders that never responded count as fast, so they get a chance to.
+    fn rank(&self, other: &Self, now: Instant) -> Ordering {
+        other
+            .reliability(now)
+            .total_cmp(&self.reliability(now))
+            .then_with(|| {
+                self.latency
+                    .unwrap_or_default()
+                    .cmp(&other.latency.unwrap_or_default())
+            })
+            .then_with(|| other.offered_fee.cmp(&self.offered_fee))
+    }
+}
+
+/// Reputation of the builders a leader asks for blocks, kept across views.
+///
+/// Builders are identified by their position in the list of builder URLs. A builder that fails is
+/// left out of queries for a time doubling with each failure in a row, unless every builder is.
+#[derive(Clone, Debug)]
+pub struct BuilderReputation {
+    /// The URL of each builder
+    urls: Vec<Url>,
+    /// What we know about each builder
+    stats: Vec<BuilderStats>,
+}
+
+impl BuilderReputation {
+    /// Create a reputation tracker for the builders at `urls`, knowing nothing about them yet
+    #[must_use]
+    pub fn new(urls: Vec<Url>) -> Self {
+        let stats = vec![BuilderStats::default(); urls.len()];
+
+        Self { urls, stats }
+    }
+
+    /// The URL of builder `builder_idx`
+    #[must_use]
+    pub fn url(&self, builder_idx: usize) -> Option<&Url> {
+        self.urls.get(builder_idx)
+    }
+
+    /// What we know about builder `builder_idx`
+    #[must_use]
+    pub fn stats(&self, builder_idx: usize) -> Option<&BuilderStats> {
+        self.stats.get(builder_idx)
+    }
+
+    /// Record what happened when we asked builder `builder_idx` for a block at `now`
+    pub fn record(&mut self, builder_idx: usize, outcome: BuilderOutcome, now: Instant) {
+        let Some(stats) = self.stats.get_mut(builder_idx) else {
+            return;
+        };
+
+        match outcome {
+            BuilderOutcome::Responded {
+                latency,
+                offered_fee,
+            } => {
+                stats.latency = Some(stats.latency.map_or(latency, |average| {
+                    average.mul_f64(1.0 - LATENCY_SMOOTHING) + latency.mul_f64(LATENCY_SMOOTHING)
+                }));
+                stats.offered_fee = offered_fee;
+            }
+            BuilderOutcome::Claimed => {}
+            BuilderOutcome::Timeout => stats.timeouts += 1,
+            BuilderOutcome::QueryFailed => stats.query_failures += 1,
+            BuilderOutcome::ClaimFailed => stats.claim_failures += 1,
+            BuilderOutcome::InvalidSignature => stats.signature_failures += 1,
+        }
+
+        stats.weigh(outcome.is_success(), now);
+        if outcome.is_success() {
+            stats.successes += 1;
+            stats.consecutive_failures = 0;
+            stats.backoff_until = None;
+        } else {
+            stats.consecutive_failures = stats.consecutive_failures.saturating_add(1);
+            let backoff = BUILDER_BACKOFF_BASE
+                .saturating_mul(1_u32 << stats.consecutive_failures.saturating_sub(1).min(16))
+                .min(BUILDER_BACKOFF_MAX);
+            stats.backoff_until = Some(now + backoff);
+        }
+    }
+
+    /// The builders to ask for a block at `now`, best first.
+    ///
+    /// Builders backing off are left out, unless all of them are.
+    #[must_use]
+    pub fn ordered(&self, now: Instant) -> Vec<usize> {
+        let mut builders: Vec<usize> = (0..self.stats.len())
+            .filter(|&builder_idx| !self.stats[builder_idx].backing_off(now))
+            .collect();
+        if builders.is_empty() {
+            builders = (0..self.stats.len()).collect();
+        }
+        builders.sort_by(|&l, &r| self.stats[l].rank(&self.stats[r], now));
+
+        builders
+    }
+
+    /// Compare builders `l` and `r` at `now`, best first
+    #[must_use]
+    pub fn compare(&self, l: usize, r: usize, now: Instant) -> Ordering {
+        match (self.stats.get(l), self.stats.get(r)) {
+            (Some(l), Some(r)) => l.rank(r, now),
+            _ => l.cmp(&r),
+        }
+    }
+}
@@ -4,7 +4,10 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_broadcast::Sender;
 use chrono::Utc;
@@ -55,6 +58,10 @@ pub(crate) async fn handle_quorum_vote_recv<
             vote.view_number() + 1
         )
     );
+    task_state
+        .first_vote_times
+        .entry(vote.view_number())
+        .or_insert_with(Instant::now);
 
     handle_vote(
         &mut task_state.vote_collectors,
@@ -182,6 +189,11 @@ pub(crate) async fn handle_view_change<
         .await
         .update_view(new_view_number)?;
 
+    // Forget the votes of views whose QC can no longer help us
+    task_state.first_vote_times = task_state
+        .first_vote_times
+        .split_off(&TYPES::View::new(new_view_number.saturating_sub(1)));
+
     // If we have a decided upgrade certificate, the protocol version may also have been upgraded.
     let decided_upgrade_certificate_read = task_state
         .upgrade_lock
@@ -4,10 +4,11 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
 
 use async_broadcast::{Receiver, Sender};
 use async_trait::async_trait;
+use either::Either;
 use hotshot_task::task::TaskState;
 use hotshot_types::{
     consensus::OuterConsensus,
@@ -85,6 +86,9 @@ pub struct ConsensusTaskState<TYPES: NodeType, I: NodeImplementation<TYPES>, V:
 
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+
+    /// When we received the first vote of each view we are collecting a QC for
+    pub first_vote_times: BTreeMap<TYPES::View, Instant>,
 }
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> ConsensusTaskState<TYPES, I, V> {
     /// Handles a consensus event received on the event stream
@@ -102,6 +106,16 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> ConsensusTaskSt
                     tracing::debug!("Failed to handle QuorumVoteRecv event; error = {e}");
                 }
             }
+            HotShotEvent::Qc2Formed(Either::Left(qc)) => {
+                if let Some(first_vote_time) = self.first_vote_times.remove(&qc.view_number) {
+                    self.consensus
+                        .read()
+                        .await
+                        .metrics
+                        .vote_to_qc_duration
+                        .add_point(first_vote_time.elapsed().as_secs_f64());
+                }
+            }
             HotShotEvent::TimeoutVoteRecv(ref vote) => {
                 if let Err(e) =
                     handle_timeout_vote_recv(vote, Arc::clone(&event), &sender, self).await
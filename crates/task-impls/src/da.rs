@@ -4,7 +4,7 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::{marker::PhantomData, sync::Arc};
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc, time::Instant};
 
 use async_broadcast::{Receiver, Sender};
 use async_lock::RwLock;
@@ -21,7 +21,7 @@ use hotshot_types::{
         block_contents::vid_commitment,
         election::Membership,
         network::ConnectedNetwork,
-        node_implementation::{NodeImplementation, NodeType, Versions},
+        node_implementation::{ConsensusTime, NodeImplementation, NodeType, Versions},
         signature_key::SignatureKey,
         storage::Storage,
     },
@@ -77,6 +77,9 @@ pub struct DaTaskState<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Version
 
     /// Lock for a decided upgrade
     pub upgrade_lock: UpgradeLock<TYPES, V>,
+
+    /// When we sent the DA proposals still waiting for a DAC, by view
+    pub proposal_send_times: BTreeMap<TYPES::View, Instant>,
 }
 
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> DaTaskState<TYPES, I, V> {
@@ -283,6 +286,16 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> DaTaskState<TYP
                 )
                 .await?;
             }
+            HotShotEvent::DacSend(cert, _) => {
+                if let Some(send_time) = self.proposal_send_times.remove(&cert.view_number) {
+                    self.consensus
+                        .read()
+                        .await
+                        .metrics
+                        .da_proposal_to_dac_duration
+                        .add_point(send_time.elapsed().as_secs_f64());
+                }
+            }
             HotShotEvent::ViewChange(view, epoch) => {
                 if *epoch > self.cur_epoch {
                     self.cur_epoch = *epoch;
@@ -298,6 +311,11 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> DaTaskState<TYP
                     tracing::info!("View changed by more than 1 going to view {:?}", view);
                 }
                 self.cur_view = view;
+
+                // Forget the proposals that never got a DAC
+                self.proposal_send_times = self
+                    .proposal_send_times
+                    .split_off(&TYPES::View::new(view.saturating_sub(1)));
             }
             HotShotEvent::BlockRecv(packed_bundle) => {
                 let PackedBundle::<TYPES> {
@@ -329,6 +347,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> DaTaskState<TYP
                     _pd: PhantomData,
                 };
 
+                self.proposal_send_times.insert(view_number, Instant::now());
                 broadcast_event(
                     Arc::new(HotShotEvent::DaProposalSend(
                         message.clone(),
@@ -0,0 +1,233 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_broadcast::{Receiver, Sender};
+use async_lock::RwLock;
+use async_trait::async_trait;
+use hotshot_task::task::TaskState;
+use hotshot_types::{
+    event::{Event, EventType},
+    evidence::{EquivocationEvidence, EquivocationKind, SignedMessage},
+    message::UpgradeLock,
+    traits::{
+        block_contents::BlockHeader,
+        election::Membership,
+        node_implementation::{ConsensusTime, NodeImplementation, NodeType, Versions},
+        signature_key::SignatureKey,
+        storage::Storage,
+    },
+    utils::epoch_from_block_number,
+    vote::{HasViewNumber, Vote},
+};
+use tracing::instrument;
+use utils::anytrace::*;
+
+use crate::{events::HotShotEvent, helpers::broadcast_event};
+
+/// How many views around the current one we keep the signed messages of
+pub const EQUIVOCATION_VIEW_WINDOW: u64 = 10;
+
+/// The first message of a kind a key signed in a view
+pub struct SeenMessage<TYPES: NodeType> {
+    /// The message
+    pub message: SignedMessage<TYPES>,
+    /// The bytes its signature is over
+    pub signed_bytes: Vec<u8>,
+    /// Whether we already reported the key for signing a conflicting message
+    pub reported: bool,
+}
+
+/// The first message of each kind each key signed in a view
+pub type SeenMessages<TYPES> =
+    HashMap<(EquivocationKind, <TYPES as NodeType>::SignatureKey), SeenMessage<TYPES>>;
+
+/// Watches the votes and proposals we receive for keys signing two conflicting messages of the
+/// same kind in a view, and persists and reports the evidence of each such key once.
+pub struct EquivocationTaskState<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> {
+    /// Output events to application
+    pub output_event_stream: async_broadcast::Sender<Event<TYPES>>,
+
+    /// View number this view is executing in.
+    pub cur_view: TYPES::View,
+
+    /// Epoch number this node is executing in.
+    pub cur_epoch: TYPES::Epoch,
+
+    /// Membership, to find the signer of proposals and check the stake of voters
+    pub membership: Arc<TYPES::Membership>,
+
+    /// This node's storage ref
+    pub storage: Arc<RwLock<I::Storage>>,
+
+    /// Lock for a decided upgrade
+    pub upgrade_lock: UpgradeLock<TYPES, V>,
+
+    /// Number of blocks in an epoch, zero means there are no epochs
+    pub epoch_height: u64,
+
+    /// This state's ID
+    pub id: u64,
+
+    /// The first message of each kind each key signed, by view
+    pub seen: BTreeMap<TYPES::View, SeenMessages<TYPES>>,
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>
+    EquivocationTaskState<TYPES, I, V>
+{
+    /// main task event handler
+    #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Equivocation task", level = "error", target = "EquivocationTaskState")]
+    pub async fn handle(&mut self, event: Arc<HotShotEvent<TYPES>>) -> Result<()> {
+        let (message, signer, has_stake) = match event.as_ref() {
+            HotShotEvent::QuorumVoteRecv(vote) => {
+                let signer = vote.signing_key();
+                let has_stake = self.membership.has_stake(&signer, self.cur_epoch);
+                (SignedMessage::QuorumVote(vote.clone()), signer, has_stake)
+            }
+            HotShotEvent::DaVoteRecv(vote) => {
+                let signer = vote.signing_key();
+                let has_stake = self.membership.has_da_stake(&signer, self.cur_epoch);
+                (SignedMessage::DaVote(vote.clone()), signer, has_stake)
+            }
+            HotShotEvent::QuorumProposalRecv(proposal, _) => {
+                // The proposal may be for the next epoch, whose leaders differ
+                let epoch = TYPES::Epoch::new(epoch_from_block_number(
+                    proposal.data.block_header.block_number(),
+                    self.epoch_height,
+                ));
+                let leader = self.membership.leader(proposal.data.view_number(), epoch)?;
+                (
+                    SignedMessage::QuorumProposal(proposal.clone()),
+                    leader,
+                    true,
+                )
+            }
+            HotShotEvent::DaProposalRecv(proposal, _) => {
+                let leader = self
+                    .membership
+                    .leader(proposal.data.view_number(), self.cur_epoch)?;
+                (SignedMessage::DaProposal(proposal.clone()), leader, true)
+            }
+            HotShotEvent::ViewChange(view, epoch) => {
+                if *view > self.cur_view {
+                    self.cur_view = *view;
+                    self.cur_epoch = *epoch;
+                    let horizon = TYPES::View::new(view.saturating_sub(EQUIVOCATION_VIEW_WINDOW));
+                    self.seen = self.seen.split_off(&horizon);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        ensure!(
+            has_stake,
+            debug!("Ignoring {:?} from a key without stake", message.kind())
+        );
+        self.record(message, signer).await
+    }
+
+    /// Remember `message` signed by `signer`, and report `signer` if it conflicts with a message
+    /// it signed before
+    async fn record(
+        &mut self,
+        message: SignedMessage<TYPES>,
+        signer: TYPES::SignatureKey,
+    ) -> Result<()> {
+        let view = message.view_number();
+        ensure!(
+            view + EQUIVOCATION_VIEW_WINDOW >= self.cur_view
+                && view <= self.cur_view + EQUIVOCATION_VIEW_WINDOW,
+            debug!(
+                "Ignoring {:?} for view {} out of the window",
+                message.kind(),
+                view
+            )
+        );
+
+        // Only a message the key really signed is evidence against it
+        let signed_bytes = message.signed_bytes(&self.upgrade_lock).await?;
+        ensure!(
+            signer.validate(&message.signature(), &signed_bytes),
+            debug!("Ignoring {:?} with an invalid signature", message.kind())
+        );
+
+        let first = match self
+            .seen
+            .entry(view)
+            .or_default()
+            .entry((message.kind(), signer.clone()))
+        {
+            Entry::Vacant(entry) => {
+                entry.insert(SeenMessage {
+                    message,
+                    signed_bytes,
+                    reported: false,
+                });
+                return Ok(());
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        };
+        if first.reported || first.signed_bytes == signed_bytes {
+            return Ok(());
+        }
+        first.reported = true;
+
+        let evidence = Arc::new(EquivocationEvidence {
+            offender: signer,
+            first: first.message.clone(),
+            second: message,
+        });
+        tracing::warn!(
+            "Key {} signed conflicting {:?} messages in view {}",
+            evidence.offender,
+            evidence.kind(),
+            view
+        );
+
+        self.storage
+            .write()
+            .await
+            .append_equivocation_evidence(&evidence)
+            .await
+            .wrap()
+            .context(error!("Failed to append equivocation evidence to storage"))?;
+
+        broadcast_event(
+            Event {
+                view_number: view,
+                event: EventType::Equivocation { evidence },
+            },
+            &self.output_event_stream,
+        )
+        .await;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TaskState
+    for EquivocationTaskState<TYPES, I, V>
+{
+    type Event = HotShotEvent<TYPES>;
+
+    async fn handle_event(
+        &mut self,
+        event: Arc<Self::Event>,
+        _sender: &Sender<Arc<Self::Event>>,
+        _receiver: &Receiver<Arc<Self::Event>>,
+    ) -> Result<()> {
+        self.handle(event).await
+    }
+
+    fn cancel_subtasks(&mut self) {}
+}
@@ -15,7 +15,7 @@ use hotshot_types::{
         VidDisperseShare,
     },
     message::Proposal,
-    request_response::ProposalRequestPayload,
+    request_response::{DecidedLeaves, ProposalRequestPayload},
     simple_certificate::{
         DaCertificate, QuorumCertificate, QuorumCertificate2, TimeoutCertificate,
         UpgradeCertificate, ViewSyncCommitCertificate2, ViewSyncFinalizeCertificate2,
@@ -26,13 +26,17 @@ use hotshot_types::{
         ViewSyncPreCommitVote,
     },
     traits::{
-        block_contents::BuilderFee, network::DataRequest, node_implementation::NodeType,
-        signature_key::SignatureKey, BlockPayload,
+        block_contents::BuilderFee,
+        network::{DataRequest, DenialReason, PeerMisbehaviour},
+        node_implementation::NodeType,
+        signature_key::SignatureKey,
+        BlockPayload,
     },
     utils::BuilderCommitment,
     vid::VidCommitment,
     vote::HasViewNumber,
 };
+use libp2p_identity::PeerId;
 use vec1::Vec1;
 
 use crate::view_sync::ViewSyncPhase;
@@ -232,17 +236,100 @@ pub enum HotShotEvent<TYPES: NodeType> {
         Proposal<TYPES, VidDisperseShare<TYPES>>,
     ),
 
+    /// Tell the node that sent a data request that we deny it, and why.
+    /// Includes nodes public key, recipient public key, the view of the request and the reason
+    RequestDeniedSend(
+        /// Sender key
+        TYPES::SignatureKey,
+        /// Recipient key
+        TYPES::SignatureKey,
+        /// View of the request
+        TYPES::View,
+        DenialReason,
+    ),
+
+    /// A node denied one of our data requests; received by the node that made the request.
+    RequestDeniedRecv(TYPES::SignatureKey, DenialReason),
+
     /// Receive a VID response from the network; received by the node that triggered the VID request.
     VidResponseRecv(
         TYPES::SignatureKey,
         Proposal<TYPES, VidDisperseShare<TYPES>>,
     ),
 
+    /// Send a request for decided leaves to the network; emitted to a node with stake.
+    /// Includes the data request, node's public key and the public key of the node we want to send to.
+    LeavesRequestSend(
+        DataRequest<TYPES>,
+        // Sender
+        TYPES::SignatureKey,
+        // Recipient
+        TYPES::SignatureKey,
+    ),
+
+    /// Receive a request for decided leaves from the network.
+    /// Includes the data request and the requesting node's public key.
+    LeavesRequestRecv(DataRequest<TYPES>, TYPES::SignatureKey),
+
+    /// Send decided leaves to the node that requested them.
+    LeavesResponseSend(
+        /// Sender key
+        TYPES::SignatureKey,
+        /// Recipient key
+        TYPES::SignatureKey,
+        DecidedLeaves<TYPES>,
+    ),
+
+    /// Receive decided leaves from the network; received by the node that requested them.
+    LeavesResponseRecv(TYPES::SignatureKey, DecidedLeaves<TYPES>),
+
+    /// Send a request for the VID shares of a view to the network, to recover its payload.
+    /// Includes the data request, node's public key and the public key of the node we want to send to.
+    VidSharesRequestSend(
+        DataRequest<TYPES>,
+        // Sender
+        TYPES::SignatureKey,
+        // Recipient
+        TYPES::SignatureKey,
+    ),
+
+    /// Receive a request for the VID shares of a view from the network.
+    /// Includes the data request and the requesting node's public key.
+    VidSharesRequestRecv(DataRequest<TYPES>, TYPES::SignatureKey),
+
+    /// Receive a request for the DA proposal of a view from the network.
+    /// Includes the data request and the requesting node's public key.
+    DaProposalRequestRecv(DataRequest<TYPES>, TYPES::SignatureKey),
+
+    /// Send the VID shares we have for a view to the node that requested them.
+    VidSharesResponseSend(
+        /// Sender key
+        TYPES::SignatureKey,
+        /// Recipient key
+        TYPES::SignatureKey,
+        Vec<VidDisperseShare<TYPES>>,
+    ),
+
+    /// Receive VID shares from the network; received by the node that requested them.
+    VidSharesResponseRecv(TYPES::SignatureKey, Vec<VidDisperseShare<TYPES>>),
+
     /// A replica send us a High QC
     HighQcRecv(QuorumCertificate2<TYPES>, TYPES::SignatureKey),
 
     /// Send our HighQc to the next leader, should go to the same leader as our vote
     HighQcSend(QuorumCertificate2<TYPES>, TYPES::SignatureKey),
+
+    /// A peer the network authenticated misbehaved, and should be reported to the network
+    PeerMisbehaved(PeerId, PeerMisbehaviour),
+
+    /// Leaves were decided, newest first
+    LeavesDecided(Vec<Leaf2<TYPES>>),
+
+    /// We fetched the decided leaves a decide skipped over, oldest first
+    MissingLeavesFetched(Vec<Leaf2<TYPES>>),
+
+    /// We gave up fetching the decided leaves a decide skipped over, up to the one of this view
+    LeafCatchupFailed(TYPES::View),
 }
 
 impl<TYPES: NodeType> HotShotEvent<TYPES> {
@@ -322,9 +409,27 @@ impl<TYPES: NodeType> HotShotEvent<TYPES> {
             | HotShotEvent::VidRequestRecv(request, _) => Some(request.view),
             HotShotEvent::VidResponseSend(_, _, proposal)
             | HotShotEvent::VidResponseRecv(_, proposal) => Some(proposal.data.view_number),
+            HotShotEvent::RequestDeniedSend(_, _, view_number, _) => Some(*view_number),
+            HotShotEvent::LeavesRequestSend(request, _, _)
+            | HotShotEvent::LeavesRequestRecv(request, _) => Some(request.view),
+            HotShotEvent::LeavesResponseSend(_, _, decided)
+            | HotShotEvent::LeavesResponseRecv(_, decided) => {
+                decided.leaves.last().map(Leaf2::view_number)
+            }
+            HotShotEvent::VidSharesRequestSend(request, _, _)
+            | HotShotEvent::VidSharesRequestRecv(request, _)
+            | HotShotEvent::DaProposalRequestRecv(request, _) => Some(request.view),
+            HotShotEvent::VidSharesResponseSend(_, _, shares)
+            | HotShotEvent::VidSharesResponseRecv(_, shares) => {
+                shares.first().map(|share| share.view_number)
+            }
             HotShotEvent::HighQcRecv(qc, _) | HotShotEvent::HighQcSend(qc, _) => {
                 Some(qc.view_number())
             }
+            HotShotEvent::PeerMisbehaved(..) | HotShotEvent::RequestDeniedRecv(..) => None,
+            HotShotEvent::LeavesDecided(leaves) => leaves.first().map(Leaf2::view_number),
+            HotShotEvent::MissingLeavesFetched(leaves) => leaves.last().map(Leaf2::view_number),
+            HotShotEvent::LeafCatchupFailed(view) => Some(*view),
         }
     }
 }
@@ -580,6 +685,15 @@ impl<TYPES: NodeType> Display for HotShotEvent<TYPES> {
                     proposal.data.view_number
                 )
             }
+            HotShotEvent::RequestDeniedSend(_, _, view_number, reason) => {
+                write!(
+                    f,
+                    "RequestDeniedSend(view_number={view_number:?}, reason={reason:?})"
+                )
+            }
+            HotShotEvent::RequestDeniedRecv(_, reason) => {
+                write!(f, "RequestDeniedRecv(reason={reason:?})")
+            }
             HotShotEvent::VidResponseRecv(_, proposal) => {
                 write!(
                     f,
@@ -587,12 +701,75 @@ impl<TYPES: NodeType> Display for HotShotEvent<TYPES> {
                     proposal.data.view_number
                 )
             }
+            HotShotEvent::LeavesRequestSend(request, _, _) => {
+                write!(f, "LeavesRequestSend(view_number={:?}", request.view)
+            }
+            HotShotEvent::LeavesRequestRecv(request, _) => {
+                write!(f, "LeavesRequestRecv(view_number={:?}", request.view)
+            }
+            HotShotEvent::LeavesResponseSend(_, _, decided) => {
+                write!(
+                    f,
+                    "LeavesResponseSend(view_number={:?}",
+                    decided.leaves.last().map(Leaf2::view_number)
+                )
+            }
+            HotShotEvent::LeavesResponseRecv(_, decided) => {
+                write!(
+                    f,
+                    "LeavesResponseRecv(view_number={:?}",
+                    decided.leaves.last().map(Leaf2::view_number)
+                )
+            }
+            HotShotEvent::VidSharesRequestSend(request, _, _) => {
+                write!(f, "VidSharesRequestSend(view_number={:?}", request.view)
+            }
+            HotShotEvent::VidSharesRequestRecv(request, _) => {
+                write!(f, "VidSharesRequestRecv(view_number={:?}", request.view)
+            }
+            HotShotEvent::DaProposalRequestRecv(request, _) => {
+                write!(f, "DaProposalRequestRecv(view_number={:?})", request.view)
+            }
+            HotShotEvent::VidSharesResponseSend(_, _, shares) => {
+                write!(
+                    f,
+                    "VidSharesResponseSend(view_number={:?}",
+                    shares.first().map(|share| share.view_number)
+                )
+            }
+            HotShotEvent::VidSharesResponseRecv(_, shares) => {
+                write!(
+                    f,
+                    "VidSharesResponseRecv(view_number={:?}",
+                    shares.first().map(|share| share.view_number)
+                )
+            }
             HotShotEvent::HighQcRecv(qc, _) => {
                 write!(f, "HighQcRecv(view_number={:?}", qc.view_number())
             }
             HotShotEvent::HighQcSend(qc, _) => {
                 write!(f, "HighQcSend(view_number={:?}", qc.view_number())
             }
+            HotShotEvent::PeerMisbehaved(_, misbehaviour) => {
+                write!(f, "PeerMisbehaved(misbehaviour={misbehaviour:?})")
+            }
+            HotShotEvent::LeavesDecided(leaves) => {
+                write!(
+                    f,
+                    "LeavesDecided(view_number={:?})",
+                    leaves.first().map(Leaf2::view_number)
+                )
+            }
+            HotShotEvent::MissingLeavesFetched(leaves) => {
+                write!(
+                    f,
+                    "MissingLeavesFetched(view_number={:?})",
+                    leaves.last().map(Leaf2::view_number)
+                )
+            }
+            HotShotEvent::LeafCatchupFailed(view) => {
+                write!(f, "LeafCatchupFailed(view_number={view:?})")
+            }
         }
     }
 }
@@ -7,6 +7,7 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
+    time::Instant,
 };
 
 use async_broadcast::{Receiver, SendError, Sender};
@@ -16,6 +17,7 @@ use hotshot_task::dependency::{Dependency, EventDependency};
 use hotshot_types::{
     consensus::OuterConsensus,
     data::{Leaf2, QuorumProposal2, ViewChangeEvidence},
+    drb::INITIAL_DRB_RESULT,
     event::{Event, EventType, LeafInfo},
     message::{Proposal, UpgradeLock},
     request_response::ProposalRequestPayload,
@@ -23,18 +25,25 @@ use hotshot_types::{
     traits::{
         block_contents::BlockHeader,
         election::Membership,
-        node_implementation::{NodeImplementation, NodeType, Versions},
+        network::{DataRequest, RequestKind},
+        node_implementation::{ConsensusTime, NodeImplementation, NodeType, Versions},
         signature_key::SignatureKey,
         BlockPayload, ValidatedState,
     },
     utils::{epoch_from_block_number, Terminator, View, ViewInner},
     vote::{Certificate, HasViewNumber},
 };
+use rand::{seq::SliceRandom, thread_rng};
+use sha2::{Digest, Sha256};
 use tokio::time::timeout;
 use tracing::instrument;
 use utils::anytrace::*;
 
-use crate::{events::HotShotEvent, quorum_proposal_recv::ValidationInfo, request::REQUEST_TIMEOUT};
+use crate::{
+    events::HotShotEvent,
+    quorum_proposal_recv::ValidationInfo,
+    request::{record_request_outcome, RequestOutcome, REQUEST_TIMEOUT},
+};
 
 /// Trigger a request to the network for a proposal for a view and wait for the response or timeout.
 #[instrument(skip_all)]
@@ -127,7 +136,7 @@ pub(crate) async fn fetch_proposal<TYPES: NodeType, V: Versions>(
     if !justify_qc
         .is_valid_cert(
             quorum_membership.stake_table(cur_epoch),
-            quorum_membership.success_threshold(),
+            quorum_membership.success_threshold(cur_epoch),
             upgrade_lock,
         )
         .await
@@ -153,6 +162,136 @@ pub(crate) async fn fetch_proposal<TYPES: NodeType, V: Versions>(
     Ok((leaf, view))
 }
 
+/// Most nodes we ask for one batch of missing leaves before giving up
+const LEAVES_REQUEST_ATTEMPTS: usize = 3;
+
+/// Fetch the leaves after `anchor_view` up to and including the leaf with `commitment` for `view`
+/// from other nodes, oldest first.
+///
+/// The leaves come in batches, newest first. A batch is only accepted if it is a chain whose
+/// newest leaf is the one we are missing, so every leaf we add is an ancestor of `commitment`.
+///
+/// # Errors
+/// Returns an error if no node gave us a valid batch of the leaves we are still missing.
+#[instrument(skip_all, fields(view = *view, anchor_view = *anchor_view))]
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+pub(crate) async fn fetch_leaves<TYPES: NodeType, V: Versions>(
+    mut commitment: Commitment<Leaf2<TYPES>>,
+    mut view: TYPES::View,
+    anchor_view: TYPES::View,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+    membership: &TYPES::Membership,
+    consensus: OuterConsensus<TYPES>,
+    public_key: &TYPES::SignatureKey,
+    private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    upgrade_lock: &UpgradeLock<TYPES, V>,
+) -> Result<Vec<Leaf2<TYPES>>> {
+    let consensus_reader = consensus.read().await;
+    let (cur_view, cur_epoch) = (consensus_reader.cur_view(), consensus_reader.cur_epoch());
+    let metrics = Arc::clone(&consensus_reader.metrics);
+    drop(consensus_reader);
+
+    // Spread the requests of all the nodes catching up over the whole committee
+    let mut peers: Vec<_> = membership
+        .committee_members(cur_view, cur_epoch)
+        .into_iter()
+        .filter(|key| key != public_key)
+        .collect();
+    ensure!(!peers.is_empty(), warn!("No peers to request leaves from"));
+    peers.shuffle(&mut thread_rng());
+    let mut peers = peers.into_iter().cycle();
+
+    // The batches we got, newest first
+    let mut batches = Vec::new();
+    while view > anchor_view {
+        let request = RequestKind::Leaves(anchor_view + 1, view);
+
+        let mut decided = None;
+        for peer in peers.by_ref().take(LEAVES_REQUEST_ATTEMPTS) {
+            // Peers deny requests signed for a view long past, and catching up can take a while,
+            // so we sign each attempt for the view we are in by then
+            let request_view = consensus.read().await.cur_view();
+            let signature = TYPES::SignatureKey::sign(
+                private_key,
+                &Sha256::digest(
+                    DataRequest::signed_data(&request, request_view, upgrade_lock)
+                        .await
+                        .wrap()?,
+                ),
+            )
+            .wrap()
+            .context(error!(
+                "Failed to sign leaves request. This should never happen."
+            ))?;
+            let data_request = DataRequest {
+                request: request.clone(),
+                view: request_view,
+                signature,
+            };
+
+            let sent_at = Instant::now();
+            broadcast_event(
+                HotShotEvent::LeavesRequestSend(data_request, public_key.clone(), peer.clone())
+                    .into(),
+                event_sender,
+            )
+            .await;
+
+            let response = timeout(
+                REQUEST_TIMEOUT,
+                EventDependency::new(
+                    event_receiver.clone(),
+                    Box::new(move |event: &Arc<HotShotEvent<TYPES>>| {
+                        matches!(
+                            event.as_ref(),
+                            HotShotEvent::LeavesResponseRecv(sender, _)
+                                | HotShotEvent::RequestDeniedRecv(sender, _) if *sender == peer
+                        )
+                    }),
+                )
+                .completed(),
+            )
+            .await;
+            let Ok(Some(event)) = response else {
+                record_request_outcome(&metrics, &request, RequestOutcome::Timeout);
+                continue;
+            };
+            let HotShotEvent::LeavesResponseRecv(_, response) = event.as_ref() else {
+                record_request_outcome(&metrics, &request, RequestOutcome::Denied);
+                continue;
+            };
+
+            if let Err(e) = response.validate(commitment) {
+                tracing::warn!("Invalid response to a request for leaves; error = {e:#}");
+                record_request_outcome(&metrics, &request, RequestOutcome::Invalid);
+                continue;
+            }
+            record_request_outcome(&metrics, &request, RequestOutcome::Hit(sent_at.elapsed()));
+            decided = Some(response.clone());
+            break;
+        }
+        let decided = decided.context(warn!(
+            "Failed to fetch the leaves from view {} to view {}",
+            *anchor_view + 1,
+            *view
+        ))?;
+
+        // The batch is not empty, and its oldest leaf links to the rest of the chain
+        let oldest = &decided.leaves[0];
+        commitment = oldest.parent_commitment();
+        view = oldest.justify_qc().view_number();
+        batches.push(decided.leaves);
+    }
+
+    Ok(batches
+        .into_iter()
+        .rev()
+        .flatten()
+        .filter(|leaf| leaf.view_number() > anchor_view)
+        .collect())
+}
+
 /// Helper type to give names and to the output values of the leaf chain traversal operation.
 #[derive(Debug)]
 pub struct LeafChainTraversalOutcome<TYPES: NodeType> {
@@ -578,6 +717,25 @@ pub async fn validate_proposal_safety_and_liveness<
             }
         );
 
+        // A proposal may carry the DRB result of the next epoch, which must match ours. We
+        // cannot vouch for a result we have not computed yet, so we do not vote for it either.
+        if proposal.data.drb_result != INITIAL_DRB_RESULT {
+            let next_epoch = TYPES::Epoch::new(proposal_epoch + 1);
+            let Some(drb_result) = consensus_reader.drb_result(next_epoch) else {
+                bail!(warn!(
+                    "Proposal carries a DRB result for epoch {:?}, which we do not have yet",
+                    next_epoch
+                ));
+            };
+            ensure!(
+                proposal.data.drb_result == drb_result,
+                error!(
+                    "Proposal carries a wrong DRB result for epoch {:?}",
+                    next_epoch
+                )
+            );
+        }
+
         // Liveness check.
         let liveness_check = justify_qc.view_number() > consensus_reader.locked_view();
 
@@ -686,7 +844,9 @@ pub(crate) async fn validate_proposal_view_and_certs<
                             validation_info
                                 .quorum_membership
                                 .stake_table(validation_info.cur_epoch),
-                            validation_info.quorum_membership.success_threshold(),
+                            validation_info
+                                .quorum_membership
+                                .success_threshold(validation_info.cur_epoch),
                             &validation_info.upgrade_lock
                         )
                         .await,
@@ -709,7 +869,9 @@ pub(crate) async fn validate_proposal_view_and_certs<
                             validation_info
                                 .quorum_membership
                                 .stake_table(validation_info.cur_epoch),
-                            validation_info.quorum_membership.success_threshold(),
+                            validation_info
+                                .quorum_membership
+                                .success_threshold(validation_info.cur_epoch),
                             &validation_info.upgrade_lock
                         )
                         .await,
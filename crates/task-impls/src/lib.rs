@@ -19,12 +19,21 @@ pub mod da;
 /// The task which implements all transaction handling
 pub mod transactions;
 
+/// The transactions a node has seen but not yet seen decided
+pub mod mempool;
+
+/// Blocks a leader builds from its mempool when no builder gives it one
+pub mod local_builder;
+
 /// Defines the events passed between tasks
 pub mod events;
 
 /// The task which implements the network.
 pub mod network;
 
+/// Per-peer rate limiting of the messages we receive
+pub mod rate_limit;
+
 /// Defines the types to run unit tests for a task.
 pub mod harness;
 
@@ -40,16 +49,25 @@ pub mod vote_collection;
 /// Task for handling upgrades
 pub mod upgrade;
 
+/// Task which detects keys signing conflicting votes or proposals for a view
+pub mod equivocation;
+
 /// Implementations for builder client
 /// Should contain builder task in the future
 pub mod builder;
 
+/// Reputation of the builders a leader asks for blocks
+pub mod builder_reputation;
+
 /// Helper functions used by any task
 pub mod helpers;
 
 /// Task which responses to requests from the network
 pub mod response;
 
+/// Recovery of the payloads of decided leaves from the VID shares of other nodes
+pub mod payload_recovery;
+
 /// Task for requesting the network for things
 pub mod request;
 
@@ -0,0 +1,91 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use hotshot_types::{
+    traits::{
+        block_contents::{precompute_vid_commitment, BuilderFee, EncodeBytes},
+        node_implementation::{NodeType, Versions},
+        signature_key::BuilderSignatureKey,
+        BlockPayload,
+    },
+    vid::VidCommitment,
+};
+use utils::anytrace::*;
+use vbs::version::{StaticVersionType, Version};
+
+use crate::transactions::BuilderResponse;
+
+/// Fee we pay ourselves for a block we built
+const LOCAL_BLOCK_FEE: u64 = 0;
+
+/// Fee data for a block we built ourselves, signed with the same key as the fee of a null block
+fn local_builder_fee<TYPES: NodeType, V: Versions>(
+    metadata: &<TYPES::BlockPayload as BlockPayload<TYPES>>::Metadata,
+    payload_commitment: &VidCommitment,
+    version: Version,
+    view_number: u64,
+) -> Option<BuilderFee<TYPES>> {
+    let (fee_account, private_key) =
+        <TYPES::BuilderSignatureKey as BuilderSignatureKey>::generated_from_seed_indexed(
+            [0_u8; 32], 0,
+        );
+
+    let fee_signature = if version >= V::Marketplace::VERSION {
+        TYPES::BuilderSignatureKey::sign_sequencing_fee_marketplace(
+            &private_key,
+            LOCAL_BLOCK_FEE,
+            view_number,
+        )
+    } else {
+        TYPES::BuilderSignatureKey::sign_fee(
+            &private_key,
+            LOCAL_BLOCK_FEE,
+            metadata,
+            payload_commitment,
+        )
+    }
+    .ok()?;
+
+    Some(BuilderFee {
+        fee_amount: LOCAL_BLOCK_FEE,
+        fee_account,
+        fee_signature,
+    })
+}
+
+/// Build a block of `transactions` ourselves, to propose when no builder gave us one in time.
+///
+/// # Errors
+///
+/// Returns an error if the payload cannot be built from the transactions, or its fee cannot be
+/// signed.
+pub async fn build_block<TYPES: NodeType, V: Versions>(
+    transactions: Vec<TYPES::Transaction>,
+    validated_state: &TYPES::ValidatedState,
+    instance_state: &TYPES::InstanceState,
+    num_storage_nodes: usize,
+    version: Version,
+    view_number: TYPES::View,
+) -> Result<BuilderResponse<TYPES>> {
+    let (block_payload, metadata) =
+        TYPES::BlockPayload::from_transactions(transactions, validated_state, instance_state)
+            .await
+            .wrap()
+            .context(warn!("Failed to build a block payload from our mempool"))?;
+
+    let (payload_commitment, precompute_data) =
+        precompute_vid_commitment(&block_payload.encode(), num_storage_nodes);
+
+    let fee = local_builder_fee::<TYPES, V>(&metadata, &payload_commitment, version, *view_number)
+        .context(warn!("Failed to sign the fee of a block we built"))?;
+
+    Ok(BuilderResponse {
+        fee,
+        block_payload,
+        metadata,
+        precompute_data: Some(precompute_data),
+    })
+}
@@ -0,0 +1,248 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::Arc,
+    time::Instant,
+};
+
+use committable::{Commitment, Committable};
+pub use hotshot_types::traits::mempool::{FifoPriority, TransactionPriority};
+use hotshot_types::{
+    mempool_config::MempoolConfig,
+    traits::{block_contents::Transaction, node_implementation::NodeType},
+};
+
+/// Number of decided transactions we remember, so late copies of them are not held again
+const DECIDED_CACHE_SIZE: usize = 10_000;
+
+/// Why a transaction was not added to the mempool
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MempoolRejection {
+    /// The mempool already holds the transaction
+    Duplicate,
+    /// The transaction was decided recently
+    Decided,
+    /// The mempool is full of transactions that take priority over this one
+    Full,
+}
+
+/// A transaction held in the mempool
+struct MempoolEntry<TYPES: NodeType> {
+    /// The transaction itself
+    transaction: TYPES::Transaction,
+    /// Its commitment
+    commitment: Commitment<TYPES::Transaction>,
+    /// Its priority
+    priority: u64,
+    /// Its size in a block
+    size: u64,
+    /// When we received it
+    received: Instant,
+}
+
+/// The transactions this node has seen but not yet seen decided.
+///
+/// Transactions are deduplicated by commitment, expire after a while, and are evicted lowest
+/// priority first once the mempool is full.
+pub struct Mempool<TYPES: NodeType> {
+    /// The limits of the mempool
+    config: MempoolConfig,
+    /// How transactions are ordered
+    priority: Arc<dyn TransactionPriority<TYPES>>,
+    /// The transactions held, by the order in which they were received
+    entries: BTreeMap<u64, MempoolEntry<TYPES>>,
+    /// The order of the transactions held: highest priority first, then first received
+    by_priority: BTreeSet<(Reverse<u64>, u64)>,
+    /// Where each transaction held is in `entries`
+    sequences: HashMap<Commitment<TYPES::Transaction>, u64>,
+    /// The transactions decided recently
+    decided: lru::LruCache<Commitment<TYPES::Transaction>, ()>,
+    /// Where the next transaction received goes in `entries`
+    next_sequence: u64,
+    /// The total size of the transactions held
+    total_bytes: u64,
+}
+
+impl<TYPES: NodeType> Mempool<TYPES> {
+    /// Create an empty mempool within the limits of `config`, ordered by `priority`
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn new(config: MempoolConfig, priority: Arc<dyn TransactionPriority<TYPES>>) -> Self {
+        Self {
+            config,
+            priority,
+            entries: BTreeMap::new(),
+            by_priority: BTreeSet::new(),
+            sequences: HashMap::new(),
+            decided: lru::LruCache::new(NonZeroUsize::new(DECIDED_CACHE_SIZE).unwrap()),
+            next_sequence: 0,
+            total_bytes: 0,
+        }
+    }
+
+    /// The limits of the mempool
+    #[must_use]
+    pub fn config(&self) -> &MempoolConfig {
+        &self.config
+    }
+
+    /// Number of transactions held
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no transactions are held
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total size of the transactions held
+    #[must_use]
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Whether the transaction with `commitment` is held
+    #[must_use]
+    pub fn contains(&self, commitment: &Commitment<TYPES::Transaction>) -> bool {
+        self.sequences.contains_key(commitment)
+    }
+
+    /// Add `transaction`, received at `now`, evicting transactions of a lower priority if the
+    /// mempool is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns why the transaction was not added, if it was not.
+    pub fn insert(
+        &mut self,
+        transaction: TYPES::Transaction,
+        now: Instant,
+    ) -> Result<(), MempoolRejection> {
+        self.expire(now);
+
+        let commitment = transaction.commit();
+        if self.sequences.contains_key(&commitment) {
+            return Err(MempoolRejection::Duplicate);
+        }
+        if self.decided.contains(&commitment) {
+            return Err(MempoolRejection::Decided);
+        }
+
+        let priority = self.priority.priority(&transaction);
+        let size = transaction.minimum_block_size();
+
+        // Find the transactions that would make room, as long as they all come after the new one
+        let mut count = self.entries.len();
+        let mut bytes = self.total_bytes;
+        let mut evicted = vec![];
+        for &(Reverse(lowest), sequence) in self.by_priority.iter().rev() {
+            if count < self.config.max_transactions
+                && bytes.saturating_add(size) <= self.config.max_bytes
+            {
+                break;
+            }
+            if lowest >= priority {
+                return Err(MempoolRejection::Full);
+            }
+            count -= 1;
+            bytes -= self.entries[&sequence].size;
+            evicted.push(sequence);
+        }
+        if count >= self.config.max_transactions
+            || bytes.saturating_add(size) > self.config.max_bytes
+        {
+            return Err(MempoolRejection::Full);
+        }
+
+        for sequence in evicted {
+            self.remove(sequence);
+        }
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.sequences.insert(commitment, sequence);
+        self.by_priority.insert((Reverse(priority), sequence));
+        self.total_bytes += size;
+        self.entries.insert(
+            sequence,
+            MempoolEntry {
+                transaction,
+                commitment,
+                priority,
+                size,
+                received: now,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Remove the transaction at `sequence`
+    fn remove(&mut self, sequence: u64) {
+        if let Some(entry) = self.entries.remove(&sequence) {
+            self.sequences.remove(&entry.commitment);
+            self.by_priority
+                .remove(&(Reverse(entry.priority), sequence));
+            self.total_bytes -= entry.size;
+        }
+    }
+
+    /// Remove the transactions that are too old at `now`
+    pub fn expire(&mut self, now: Instant) {
+        while let Some((&sequence, entry)) = self.entries.first_key_value() {
+            if now.saturating_duration_since(entry.received) < self.config.max_age {
+                break;
+            }
+            self.remove(sequence);
+        }
+    }
+
+    /// Remove the transactions with the given commitments, which were decided, and refuse them
+    /// from now on.
+    pub fn remove_decided(
+        &mut self,
+        commitments: impl IntoIterator<Item = Commitment<TYPES::Transaction>>,
+    ) {
+        for commitment in commitments {
+            if let Some(&sequence) = self.sequences.get(&commitment) {
+                self.remove(sequence);
+            }
+            self.decided.put(commitment, ());
+        }
+    }
+
+    /// The transactions to build a block of at most `max_bytes` from, in priority order, leaving
+    /// out those in `excluded`, such as the transactions of undecided blocks.
+    ///
+    /// The transactions stay in the mempool until they are decided or expire.
+    #[must_use]
+    pub fn select(
+        &self,
+        max_bytes: u64,
+        excluded: &HashSet<Commitment<TYPES::Transaction>>,
+    ) -> Vec<TYPES::Transaction> {
+        let mut bytes = 0;
+        let mut selected = vec![];
+        for (_, sequence) in &self.by_priority {
+            let entry = &self.entries[sequence];
+            if excluded.contains(&entry.commitment) {
+                continue;
+            }
+            if bytes.saturating_add(entry.size) <= max_bytes {
+                bytes += entry.size;
+                selected.push(entry.transaction.clone());
+            }
+        }
+        selected
+    }
+}
@@ -8,31 +8,35 @@ use std::{
     collections::{BTreeMap, HashMap},
     hash::{DefaultHasher, Hash, Hasher},
     sync::Arc,
+    time::Instant,
 };
 
 use async_broadcast::{Receiver, Sender};
 use async_lock::RwLock;
 use async_trait::async_trait;
+use committable::Committable;
 use hotshot_task::task::TaskState;
 use hotshot_types::{
-    consensus::OuterConsensus,
+    consensus::{ConsensusMetricsValue, OuterConsensus},
     data::{VidDisperse, VidDisperseShare},
     event::{Event, EventType, HotShotAction},
+    evidence::SignedMessage,
     message::{
         convert_proposal, DaConsensusMessage, DataMessage, GeneralConsensusMessage, Message,
-        MessageKind, Proposal, SequencingMessage, UpgradeLock,
+        MessageKind, MessagePurpose, MessageSource, Proposal, SequencingMessage, UpgradeLock,
     },
     traits::{
         election::Membership,
         network::{
-            BroadcastDelay, ConnectedNetwork, RequestKind, ResponseMessage, Topic, TransmitType,
-            ViewMessage,
+            BroadcastDelay, ConnectedNetwork, PeerMisbehaviour, RequestKind, ResponseMessage,
+            Topic, TransmitType, ViewMessage,
         },
         node_implementation::{ConsensusTime, NodeType, Versions},
         storage::Storage,
     },
     vote::{HasViewNumber, Vote},
 };
+use libp2p_identity::PeerId;
 use tokio::{spawn, task::JoinHandle};
 use tracing::instrument;
 use utils::anytrace::*;
@@ -40,11 +44,13 @@ use utils::anytrace::*;
 use crate::{
     events::{HotShotEvent, HotShotTaskCompleted},
     helpers::broadcast_event,
+    rate_limit::{RateLimitOutcome, RateLimiter},
+    response::valid_signature,
 };
 
 /// the network message task state
 #[derive(Clone)]
-pub struct NetworkMessageTaskState<TYPES: NodeType> {
+pub struct NetworkMessageTaskState<TYPES: NodeType, V: Versions> {
     /// Sender to send internal events this task generates to other tasks
     pub internal_event_stream: Sender<Arc<HotShotEvent<TYPES>>>,
 
@@ -56,16 +62,63 @@ pub struct NetworkMessageTaskState<TYPES: NodeType> {
 
     /// Transaction Cache to ignore previously seen transatctions
     pub transactions_cache: lru::LruCache<u64, ()>,
+
+    /// Limits on the messages we pass on, by the peer or key we know they came from
+    pub rate_limiter: RateLimiter<MessageSource<TYPES::SignatureKey>>,
+
+    /// Metrics to count the messages we drop in
+    pub metrics: Arc<ConsensusMetricsValue>,
+
+    /// Lock for a decided upgrade, which determines how data requests are signed
+    pub upgrade_lock: UpgradeLock<TYPES, V>,
 }
 
-impl<TYPES: NodeType> NetworkMessageTaskState<TYPES> {
+impl<TYPES: NodeType, V: Versions> NetworkMessageTaskState<TYPES, V> {
     #[instrument(skip_all, name = "Network message task", level = "trace")]
-    /// Handles a (deserialized) message from the network
-    pub async fn handle_message(&mut self, message: Message<TYPES>) {
+    /// Handles a (deserialized) message from the network, which authenticated it as coming from
+    /// `peer` if it could.
+    ///
+    /// Messages are rate limited by the peer the network authenticated or, for networks that do
+    /// not authenticate peers, by the key of their sender once we checked it signed them. The
+    /// sender a message claims is not otherwise authenticated, so limiting by it would let anyone
+    /// use up the limit of another.
+    pub async fn handle_message(&mut self, message: Message<TYPES>, peer: Option<PeerId>) {
         tracing::trace!("Received message from network:\n\n{message:?}");
 
+        let sender = message.sender.clone();
+        let signed_by_sender = peer.is_none() && self.signed_by_sender(&message).await;
+        let source = match peer {
+            Some(peer) => Some(MessageSource::Peer(peer)),
+            None if signed_by_sender => Some(MessageSource::Key(sender.clone())),
+            None => None,
+        };
+        if let Some(source) = source {
+            let purpose = message.kind.purpose();
+            match self.rate_limiter.check(&source, purpose, Instant::now()) {
+                RateLimitOutcome::Allowed => {}
+                RateLimitOutcome::Limited => {
+                    self.count_rate_limited(purpose);
+                    return;
+                }
+                RateLimitOutcome::NewlyLimited => {
+                    tracing::warn!(
+                        "Sender exceeded its rate limit, dropping its {purpose:?} messages; source = {source:?}"
+                    );
+                    self.count_rate_limited(purpose);
+                    broadcast_event(
+                        Event {
+                            view_number: message.kind.view_number(),
+                            event: EventType::MessageRateLimited { source, purpose },
+                        },
+                        &self.external_event_stream,
+                    )
+                    .await;
+                    return;
+                }
+            }
+        }
+
         // Match the message kind and send the appropriate event to the internal event stream
-        let sender = message.sender;
         match message.kind {
             // Handle consensus messages
             MessageKind::Consensus(consensus_message) => {
@@ -75,6 +128,11 @@ impl<TYPES: NodeType> NetworkMessageTaskState<TYPES> {
                             HotShotEvent::QuorumProposalRecv(convert_proposal(proposal), sender)
                         }
                         GeneralConsensusMessage::ProposalRequested(req, sig) => {
+                            if !req.key.validate(&sig, req.commit().as_ref()) {
+                                tracing::warn!("Invalid signature key on proposal request.");
+                                self.report(peer, PeerMisbehaviour::InvalidSignature).await;
+                                return;
+                            }
                             HotShotEvent::QuorumProposalRequestRecv(req, sig)
                         }
                         GeneralConsensusMessage::ProposalResponse(proposal) => {
@@ -148,31 +206,85 @@ impl<TYPES: NodeType> NetworkMessageTaskState<TYPES> {
                     )
                     .await;
                 }
-                DataMessage::DataResponse(response) => {
-                    if let ResponseMessage::Found(message) = response {
-                        match message {
-                            SequencingMessage::Da(da_message) => {
-                                if let DaConsensusMessage::VidDisperseMsg(proposal) = da_message {
-                                    broadcast_event(
-                                        Arc::new(HotShotEvent::VidResponseRecv(sender, proposal)),
-                                        &self.internal_event_stream,
-                                    )
-                                    .await;
-                                }
+                DataMessage::DataResponse(response) => match response {
+                    ResponseMessage::Found(message) => match message {
+                        SequencingMessage::Da(da_message) => {
+                            if let DaConsensusMessage::VidDisperseMsg(proposal) = da_message {
+                                broadcast_event(
+                                    Arc::new(HotShotEvent::VidResponseRecv(sender, proposal)),
+                                    &self.internal_event_stream,
+                                )
+                                .await;
                             }
-                            SequencingMessage::General(_) => {}
                         }
+                        SequencingMessage::General(_) => {}
+                    },
+                    ResponseMessage::Leaves(decided) => {
+                        broadcast_event(
+                            Arc::new(HotShotEvent::LeavesResponseRecv(sender, decided)),
+                            &self.internal_event_stream,
+                        )
+                        .await;
                     }
-                }
-                DataMessage::RequestData(data) => {
-                    let req_data = data.clone();
-                    if let RequestKind::Vid(_view_number, _key) = req_data.request {
+                    ResponseMessage::VidShares(shares) => {
+                        broadcast_event(
+                            Arc::new(HotShotEvent::VidSharesResponseRecv(sender, shares)),
+                            &self.internal_event_stream,
+                        )
+                        .await;
+                    }
+                    ResponseMessage::DeniedFor(reason) => {
                         broadcast_event(
-                            Arc::new(HotShotEvent::VidRequestRecv(data, sender)),
+                            Arc::new(HotShotEvent::RequestDeniedRecv(sender, reason)),
                             &self.internal_event_stream,
                         )
                         .await;
                     }
+                    // Peers that predate `DeniedFor` do not say why they denied us
+                    ResponseMessage::NotFound | ResponseMessage::Denied => {}
+                },
+                DataMessage::RequestData(data) => {
+                    // Requests with a bad signature are dropped here, where we still know the
+                    // peer they came from
+                    if !signed_by_sender
+                        && !valid_signature(&data, &sender, &self.upgrade_lock).await
+                    {
+                        tracing::warn!("Invalid signature on {} request.", data.request.name());
+                        self.report(peer, PeerMisbehaviour::InvalidSignature).await;
+                        return;
+                    }
+                    let req_data = data.clone();
+                    match req_data.request {
+                        RequestKind::Vid(_view_number, _key) => {
+                            broadcast_event(
+                                Arc::new(HotShotEvent::VidRequestRecv(data, sender)),
+                                &self.internal_event_stream,
+                            )
+                            .await;
+                        }
+                        RequestKind::Leaves(..) | RequestKind::LeavesByHeight(..) => {
+                            broadcast_event(
+                                Arc::new(HotShotEvent::LeavesRequestRecv(data, sender)),
+                                &self.internal_event_stream,
+                            )
+                            .await;
+                        }
+                        RequestKind::VidShares(_) => {
+                            broadcast_event(
+                                Arc::new(HotShotEvent::VidSharesRequestRecv(data, sender)),
+                                &self.internal_event_stream,
+                            )
+                            .await;
+                        }
+                        RequestKind::DaProposal(_) => {
+                            broadcast_event(
+                                Arc::new(HotShotEvent::DaProposalRequestRecv(data, sender)),
+                                &self.internal_event_stream,
+                            )
+                            .await;
+                        }
+                        RequestKind::Proposal(_) => {}
+                    }
                 }
             },
 
@@ -193,6 +305,62 @@ impl<TYPES: NodeType> NetworkMessageTaskState<TYPES> {
             }
         }
     }
+
+    /// Whether `message` is of a kind its sender signs, and the sender did sign it.
+    ///
+    /// Our own messages never count as signed here, so we never limit them.
+    async fn signed_by_sender(&self, message: &Message<TYPES>) -> bool {
+        let sender = &message.sender;
+        if *sender == self.public_key {
+            return false;
+        }
+
+        let signed = match &message.kind {
+            MessageKind::Consensus(SequencingMessage::General(
+                GeneralConsensusMessage::Proposal(proposal),
+            )) => SignedMessage::QuorumProposal(convert_proposal(proposal.clone())),
+            MessageKind::Consensus(SequencingMessage::General(GeneralConsensusMessage::Vote(
+                vote,
+            ))) => SignedMessage::QuorumVote(vote.clone().to_vote2()),
+            MessageKind::Consensus(SequencingMessage::Da(DaConsensusMessage::DaProposal(
+                proposal,
+            ))) => SignedMessage::DaProposal(proposal.clone()),
+            MessageKind::Consensus(SequencingMessage::Da(DaConsensusMessage::DaVote(vote))) => {
+                SignedMessage::DaVote(vote.clone())
+            }
+            MessageKind::Data(DataMessage::RequestData(request)) => {
+                return valid_signature(request, sender, &self.upgrade_lock).await;
+            }
+            _ => return false,
+        };
+        // Votes name the key that signed them, which must be the sender
+        signed.signing_key().map_or(true, |key| key == *sender)
+            && signed
+                .validate_signature(sender, &self.upgrade_lock)
+                .await
+                .is_ok()
+    }
+
+    /// Report that `peer` misbehaved, if the network authenticated it. The sender a message
+    /// claims is never reported, since anyone can claim to be anyone.
+    async fn report(&self, peer: Option<PeerId>, misbehaviour: PeerMisbehaviour) {
+        let Some(peer) = peer else {
+            return;
+        };
+        broadcast_event(
+            Arc::new(HotShotEvent::PeerMisbehaved(peer, misbehaviour)),
+            &self.internal_event_stream,
+        )
+        .await;
+    }
+
+    /// Count a message of `purpose` dropped for exceeding its sender's rate limit
+    fn count_rate_limited(&self, purpose: MessagePurpose) {
+        self.metrics
+            .rate_limited_messages
+            .create(vec![format!("{purpose:?}")])
+            .add(1);
+    }
 }
 
 /// network event task state
@@ -214,6 +382,8 @@ pub struct NetworkEventTaskState<
     pub storage: Arc<RwLock<S>>,
     /// Shared consensus state
     pub consensus: OuterConsensus<TYPES>,
+    /// Consensus metrics, recorded without taking the consensus lock
+    pub metrics: Arc<ConsensusMetricsValue>,
     /// Lock for a decided upgrade
     pub upgrade_lock: UpgradeLock<TYPES, V>,
     /// map view number to transmit tasks
@@ -282,8 +452,16 @@ impl<
                     DaConsensusMessage::VidDisperseMsg(proposal),
                 )),
             };
-            let serialized_message = match self.upgrade_lock.serialize(&message).await {
-                Ok(serialized) => serialized,
+            let serialized_message = match self.upgrade_lock.serialize_with_size(&message).await {
+                Ok((serialized, uncompressed_size)) => {
+                    record_compression_ratio(
+                        &self.metrics,
+                        &message.kind,
+                        uncompressed_size,
+                        serialized.len(),
+                    );
+                    serialized
+                }
                 Err(e) => {
                     tracing::error!("Failed to serialize message: {}", e);
                     continue;
@@ -317,7 +495,8 @@ impl<
         None
     }
 
-    /// Record `HotShotAction` if available
+    /// Record `HotShotAction` if available, in the epoch of the leaf of its view if we have it and
+    /// the current epoch otherwise
     async fn maybe_record_action(
         maybe_action: Option<HotShotAction>,
         storage: Arc<RwLock<S>>,
@@ -325,16 +504,27 @@ impl<
         view: <TYPES as NodeType>::View,
     ) -> std::result::Result<(), ()> {
         if let Some(mut action) = maybe_action {
-            if !consensus.write().await.update_action(action, view) {
-                tracing::warn!("Already actioned {:?} in view {:?}", action, view);
-                return Err(());
-            }
+            let epoch = {
+                let mut consensus_writer = consensus.write().await;
+                if !consensus_writer.update_action(action, view) {
+                    tracing::warn!("Already actioned {:?} in view {:?}", action, view);
+                    return Err(());
+                }
+                consensus_writer
+                    .epoch_of_view(view)
+                    .unwrap_or_else(|| consensus_writer.cur_epoch())
+            };
             // If the action was view sync record it as a vote, but we don't
             // want to limit to 1 View sync vote above so change the action here.
             if matches!(action, HotShotAction::ViewSyncVote) {
                 action = HotShotAction::Vote;
             }
-            match storage.write().await.record_action(view, action).await {
+            match storage
+                .write()
+                .await
+                .record_action(view, epoch, action)
+                .await
+            {
                 Ok(()) => Ok(()),
                 Err(e) => {
                     tracing::warn!("Not Sending {:?} because of storage error: {:?}", action, e);
@@ -650,6 +840,39 @@ impl<
                     TransmitType::Direct(to),
                 ))
             }
+            HotShotEvent::RequestDeniedSend(sender, to, _, reason) => Some((
+                sender,
+                MessageKind::Data(DataMessage::DataResponse(ResponseMessage::DeniedFor(
+                    reason,
+                ))),
+                TransmitType::Direct(to),
+            )),
+            HotShotEvent::LeavesRequestSend(req, sender, to) => Some((
+                sender,
+                MessageKind::Data(DataMessage::RequestData(req)),
+                TransmitType::Direct(to),
+            )),
+            HotShotEvent::LeavesResponseSend(sender, to, decided) => Some((
+                sender,
+                MessageKind::Data(DataMessage::DataResponse(ResponseMessage::Leaves(decided))),
+                TransmitType::Direct(to),
+            )),
+            HotShotEvent::VidSharesRequestSend(req, sender, to) => Some((
+                sender,
+                MessageKind::Data(DataMessage::RequestData(req)),
+                TransmitType::Direct(to),
+            )),
+            HotShotEvent::VidSharesResponseSend(sender, to, shares) => Some((
+                sender,
+                MessageKind::Data(DataMessage::DataResponse(ResponseMessage::VidShares(
+                    shares,
+                ))),
+                TransmitType::Direct(to),
+            )),
+            HotShotEvent::PeerMisbehaved(peer, misbehaviour) => {
+                self.network.report_peer(&peer, misbehaviour);
+                None
+            }
             _ => None,
         }
     }
@@ -681,12 +904,13 @@ impl<
         let network = Arc::clone(&self.network);
         let storage = Arc::clone(&self.storage);
         let consensus = OuterConsensus::new(Arc::clone(&self.consensus.inner_consensus));
+        let metrics = Arc::clone(&self.metrics);
         let upgrade_lock = self.upgrade_lock.clone();
         let handle = spawn(async move {
             if NetworkEventTaskState::<TYPES, V, NET, S>::maybe_record_action(
                 maybe_action,
                 Arc::clone(&storage),
-                consensus,
+                OuterConsensus::new(Arc::clone(&consensus.inner_consensus)),
                 view_number,
             )
             .await
@@ -703,8 +927,16 @@ impl<
                 }
             }
 
-            let serialized_message = match upgrade_lock.serialize(&message).await {
-                Ok(serialized) => serialized,
+            let serialized_message = match upgrade_lock.serialize_with_size(&message).await {
+                Ok((serialized, uncompressed_size)) => {
+                    record_compression_ratio(
+                        &metrics,
+                        &message.kind,
+                        uncompressed_size,
+                        serialized.len(),
+                    );
+                    serialized
+                }
                 Err(e) => {
                     tracing::error!("Failed to serialize message: {}", e);
                     return;
@@ -743,6 +975,20 @@ impl<
     }
 }
 
+/// Record how well a message of `kind` compressed on its way to the wire
+#[allow(clippy::cast_precision_loss)]
+fn record_compression_ratio<TYPES: NodeType>(
+    metrics: &ConsensusMetricsValue,
+    kind: &MessageKind<TYPES>,
+    uncompressed_size: usize,
+    size: usize,
+) {
+    metrics
+        .message_compression_ratio
+        .create(vec![format!("{:?}", kind.purpose())])
+        .add_point(uncompressed_size as f64 / size as f64);
+}
+
 /// A module with test helpers
 pub mod test {
     use std::ops::{Deref, DerefMut};
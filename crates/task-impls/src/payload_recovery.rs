@@ -0,0 +1,323 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use async_broadcast::{Receiver, Sender};
+use futures::future::join_all;
+use hotshot_types::{
+    data::{Leaf2, VidDisperseShare},
+    event::LeafInfo,
+    message::UpgradeLock,
+    traits::{
+        block_contents::BlockHeader,
+        election::Membership,
+        network::{DataRequest, RequestKind},
+        node_implementation::{NodeType, Versions},
+        signature_key::SignatureKey,
+        BlockPayload,
+    },
+    vid::{vid_recovery_threshold, vid_scheme, VidCommitment, VidCommon, VidSchemeType, VidShare},
+};
+use jf_vid::VidScheme;
+use rand::{seq::SliceRandom, thread_rng};
+use sha2::{Digest, Sha256};
+use tokio::time::timeout;
+use tracing::instrument;
+use utils::anytrace::*;
+
+use crate::{events::HotShotEvent, helpers::broadcast_event, request::REQUEST_TIMEOUT};
+
+/// Most members of the DA committee we ask for the shares of a payload before asking the others
+const DA_MEMBER_ATTEMPTS: usize = 3;
+
+/// The VID shares of a payload we collected so far, to recover it once we have enough
+pub struct PayloadRecovery {
+    /// The commitment in the header of the leaf whose payload we recover
+    payload_commitment: VidCommitment,
+    /// The distinct shares we verified against the commitment
+    shares: Vec<VidShare>,
+    /// The VID common data of the shares
+    common: Option<VidCommon>,
+}
+
+impl PayloadRecovery {
+    /// Start recovering the payload with `payload_commitment`
+    #[must_use]
+    pub fn new(payload_commitment: VidCommitment) -> Self {
+        Self {
+            payload_commitment,
+            shares: Vec::new(),
+            common: None,
+        }
+    }
+
+    /// Add `share`, if it is a valid share of the payload
+    ///
+    /// # Errors
+    /// Returns an error if the share is for another payload, or does not verify against the
+    /// payload commitment.
+    pub fn add_share<TYPES: NodeType>(&mut self, share: &VidDisperseShare<TYPES>) -> Result<()> {
+        ensure!(
+            share.payload_commitment == self.payload_commitment,
+            warn!(
+                "VID share for view {} is for another payload",
+                *share.view_number
+            )
+        );
+        let num_storage_nodes =
+            usize::try_from(VidSchemeType::get_num_storage_nodes(&share.common)).wrap()?;
+        // `verify_share` returns a nested `Result`, so we must check both of them
+        ensure!(
+            matches!(
+                vid_scheme(num_storage_nodes).verify_share(
+                    &share.share,
+                    &share.common,
+                    &self.payload_commitment
+                ),
+                Ok(Ok(()))
+            ),
+            warn!("Invalid VID share for view {}", *share.view_number)
+        );
+
+        if !self.shares.contains(&share.share) {
+            self.shares.push(share.share.clone());
+        }
+        self.common.get_or_insert_with(|| share.common.clone());
+
+        Ok(())
+    }
+
+    /// The number of distinct valid shares we have
+    #[must_use]
+    pub fn num_shares(&self) -> usize {
+        self.shares.len()
+    }
+
+    /// The number of shares we still need to recover the payload, once we have a share to tell
+    /// how many nodes it was dispersed to
+    #[must_use]
+    pub fn missing_shares(&self) -> Option<usize> {
+        let threshold = vid_recovery_threshold(self.num_storage_nodes()?)?;
+        Some(threshold.saturating_sub(self.shares.len()))
+    }
+
+    /// Whether we have enough shares to recover the payload
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.missing_shares() == Some(0)
+    }
+
+    /// The number of nodes the payload was dispersed to, once we have a share
+    fn num_storage_nodes(&self) -> Option<usize> {
+        usize::try_from(VidSchemeType::get_num_storage_nodes(self.common.as_ref()?)).ok()
+    }
+
+    /// Recover the payload from the shares, and fill `leaf` with it
+    ///
+    /// # Errors
+    /// Returns an error if we do not have enough shares, or the payload we recover from them is
+    /// not the one in the header of `leaf`.
+    pub fn fill<TYPES: NodeType>(&self, leaf: &mut Leaf2<TYPES>) -> Result<()> {
+        ensure!(
+            self.is_complete(),
+            debug!(
+                "Not enough VID shares to recover the payload for view {}",
+                *leaf.view_number()
+            )
+        );
+        let (Some(common), Some(num_storage_nodes)) = (&self.common, self.num_storage_nodes())
+        else {
+            bail!("Not enough VID shares to recover the payload");
+        };
+
+        let encoded_transactions = vid_scheme(num_storage_nodes)
+            .recover_payload(&self.shares, common)
+            .wrap()
+            .context(warn!(
+                "Failed to recover the payload for view {}",
+                *leaf.view_number()
+            ))?;
+        let payload =
+            TYPES::BlockPayload::from_bytes(&encoded_transactions, leaf.block_header().metadata());
+
+        leaf.fill_block_payload(payload, num_storage_nodes)
+            .wrap()
+            .context(warn!(
+                "Recovered payload for view {} does not match its header",
+                *leaf.view_number()
+            ))
+    }
+}
+
+/// Recover the payload of `leaf` from the VID shares of other nodes, and fill the leaf with it.
+///
+/// We start from `own_share`, if we have it. Then we ask a few members of the DA committee of the
+/// view one at a time, as each calculates every share from the payload it holds, and if none of
+/// them answers, only as many other nodes at once as we still need shares from. The request is
+/// signed for `cur_view`, since peers deny requests signed for a view long past.
+///
+/// # Errors
+/// Returns an error if we could not collect enough valid shares in time.
+#[instrument(skip_all, fields(view = *leaf.view_number()))]
+#[allow(clippy::too_many_arguments)]
+pub async fn recover_payload<TYPES: NodeType, V: Versions>(
+    leaf: &mut Leaf2<TYPES>,
+    own_share: Option<&VidDisperseShare<TYPES>>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+    membership: &TYPES::Membership,
+    cur_view: TYPES::View,
+    epoch: TYPES::Epoch,
+    public_key: &TYPES::SignatureKey,
+    private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    upgrade_lock: &UpgradeLock<TYPES, V>,
+) -> Result<()> {
+    let view = leaf.view_number();
+    let mut recovery = PayloadRecovery::new(leaf.payload_commitment());
+    if let Some(share) = own_share {
+        recovery.add_share(share)?;
+    }
+
+    let request = RequestKind::VidShares(view);
+    let signature = TYPES::SignatureKey::sign(
+        private_key,
+        &Sha256::digest(
+            DataRequest::signed_data(&request, cur_view, upgrade_lock)
+                .await
+                .wrap()?,
+        ),
+    )
+    .wrap()
+    .context(error!(
+        "Failed to sign VID shares request. This should never happen."
+    ))?;
+    let data_request = DataRequest {
+        request,
+        view: cur_view,
+        signature,
+    };
+
+    let mut da_members: Vec<_> = membership
+        .da_committee_members(view, epoch)
+        .into_iter()
+        .filter(|key| key != public_key)
+        .collect();
+    let mut others: Vec<_> = membership
+        .committee_members(view, epoch)
+        .into_iter()
+        .filter(|key| key != public_key && !da_members.contains(key))
+        .collect();
+    da_members.shuffle(&mut thread_rng());
+    da_members.truncate(DA_MEMBER_ATTEMPTS);
+    others.shuffle(&mut thread_rng());
+
+    while !recovery.is_complete() {
+        let recipients: BTreeSet<_> = if let Some(member) = da_members.pop() {
+            [member].into()
+        } else {
+            // Until we have a share, we do not know how many nodes the payload was dispersed to,
+            // so we ask a single node
+            let wanted = recovery.missing_shares().unwrap_or(1).max(1);
+            others
+                .split_off(others.len().saturating_sub(wanted))
+                .into_iter()
+                .collect()
+        };
+        if recipients.is_empty() {
+            break;
+        }
+
+        // Listen before we ask, so we do not miss a response
+        let mut receiver = event_receiver.clone();
+        for recipient in &recipients {
+            broadcast_event(
+                HotShotEvent::VidSharesRequestSend(
+                    data_request.clone(),
+                    public_key.clone(),
+                    recipient.clone(),
+                )
+                .into(),
+                event_sender,
+            )
+            .await;
+        }
+
+        // Collect shares until we have enough, or nobody else answers in time
+        let _ = timeout(REQUEST_TIMEOUT, async {
+            while !recovery.is_complete() {
+                let Ok(event) = receiver.recv_direct().await else {
+                    return;
+                };
+                let HotShotEvent::VidSharesResponseRecv(sender, shares) = event.as_ref() else {
+                    continue;
+                };
+                if !recipients.contains(sender) {
+                    continue;
+                }
+                for share in shares.iter().filter(|share| share.view_number == view) {
+                    if let Err(e) = recovery.add_share(share) {
+                        tracing::warn!("Ignoring a VID share from {sender}; error = {e:#}");
+                    }
+                }
+            }
+        })
+        .await;
+    }
+
+    recovery.fill(leaf)
+}
+
+/// Fill the decided leaves that lack their payload with the payload recovered from VID shares,
+/// all at once.
+///
+/// Leaves of views we are on the DA committee of are skipped: we get their payload from the DA
+/// proposal instead.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn recover_payloads<TYPES: NodeType, V: Versions>(
+    leaf_views: &mut [LeafInfo<TYPES>],
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+    membership: &TYPES::Membership,
+    cur_view: TYPES::View,
+    epoch: TYPES::Epoch,
+    public_key: &TYPES::SignatureKey,
+    private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    upgrade_lock: &UpgradeLock<TYPES, V>,
+) {
+    join_all(
+        leaf_views
+            .iter_mut()
+            .filter(|info| {
+                info.leaf.block_payload().is_none()
+                    && !membership
+                        .da_committee_members(info.leaf.view_number(), epoch)
+                        .contains(public_key)
+            })
+            .map(|info| async move {
+                if let Err(e) = recover_payload(
+                    &mut info.leaf,
+                    info.vid_share.as_ref(),
+                    event_sender,
+                    event_receiver,
+                    membership,
+                    cur_view,
+                    epoch,
+                    public_key,
+                    private_key,
+                    upgrade_lock,
+                )
+                .await
+                {
+                    tracing::warn!(
+                        "Deciding view {} without its payload; error = {e:#}",
+                        *info.leaf.view_number()
+                    );
+                }
+            }),
+    )
+    .await;
+}
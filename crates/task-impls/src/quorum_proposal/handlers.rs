@@ -20,7 +20,7 @@ use committable::Committable;
 use hotshot_task::dependency_task::HandleDepOutput;
 use hotshot_types::{
     consensus::{CommitmentAndMetadata, OuterConsensus},
-    data::{Leaf2, QuorumProposal, VidDisperse, ViewChangeEvidence},
+    data::{Leaf2, QuorumProposal, QuorumProposal2, VidDisperse, ViewChangeEvidence},
     message::Proposal,
     simple_certificate::{QuorumCertificate2, UpgradeCertificate},
     traits::{
@@ -29,6 +29,7 @@ use hotshot_types::{
         node_implementation::{ConsensusTime, NodeType},
         signature_key::SignatureKey,
     },
+    utils::epoch_from_block_number,
     vote::{Certificate, HasViewNumber},
 };
 use tracing::instrument;
@@ -128,7 +129,8 @@ impl<TYPES: NodeType, V: Versions> ProposalDependencyHandle<TYPES, V> {
                         // TODO take epoch from `qc`
                         // https://github.com/EspressoSystems/HotShot/issues/3917
                         self.quorum_membership.stake_table(TYPES::Epoch::new(0)),
-                        self.quorum_membership.success_threshold(),
+                        self.quorum_membership
+                            .success_threshold(TYPES::Epoch::new(0)),
                         &self.upgrade_lock,
                     )
                     .await
@@ -295,7 +297,7 @@ impl<TYPES: NodeType, V: Versions> ProposalDependencyHandle<TYPES, V> {
             .context(warn!("Failed to construct marketplace block header"))?
         };
 
-        let proposal = QuorumProposal {
+        let mut proposal: QuorumProposal2<TYPES> = QuorumProposal {
             block_header,
             view_number: self.view_number,
             justify_qc: parent_qc.to_qc(),
@@ -304,6 +306,19 @@ impl<TYPES: NodeType, V: Versions> ProposalDependencyHandle<TYPES, V> {
         }
         .into();
 
+        // Attach the DRB result of the next epoch once we have it, so nodes that could not
+        // compute it learn it from the decided chain
+        if version >= V::Epochs::VERSION {
+            let consensus_reader = self.consensus.read().await;
+            let epoch = epoch_from_block_number(
+                proposal.block_header.block_number(),
+                consensus_reader.epoch_height,
+            );
+            if let Some(drb_result) = consensus_reader.drb_result(TYPES::Epoch::new(epoch + 1)) {
+                proposal.drb_result = drb_result;
+            }
+        }
+
         let proposed_leaf = Leaf2::from_quorum_proposal(&proposal);
         ensure!(
             proposed_leaf.parent_commitment() == parent_leaf.commit(),
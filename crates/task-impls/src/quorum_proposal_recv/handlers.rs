@@ -157,7 +157,9 @@ pub(crate) async fn handle_quorum_proposal_recv<
             validation_info
                 .quorum_membership
                 .stake_table(validation_info.cur_epoch),
-            validation_info.quorum_membership.success_threshold(),
+            validation_info
+                .quorum_membership
+                .success_threshold(validation_info.cur_epoch),
             &validation_info.upgrade_lock,
         )
         .await
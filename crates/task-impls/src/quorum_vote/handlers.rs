@@ -4,16 +4,20 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use async_broadcast::{InactiveReceiver, Sender};
+use async_broadcast::{InactiveReceiver, Receiver, Sender};
 use async_lock::RwLock;
 use chrono::Utc;
 use committable::Committable;
 use hotshot_types::{
     consensus::OuterConsensus,
     data::{Leaf2, QuorumProposal2, VidDisperseShare},
-    event::{Event, EventType},
+    drb::{compute_drb_result, drb_seed_input, DrbResult, DRB_EPOCHS_AHEAD, INITIAL_DRB_RESULT},
+    event::{Event, EventType, LeafInfo},
     message::{Proposal, UpgradeLock},
     simple_vote::{QuorumData2, QuorumVote2},
     traits::{
@@ -23,23 +27,457 @@ use hotshot_types::{
         storage::Storage,
         ValidatedState,
     },
+    utils::epoch_from_block_number,
     vote::HasViewNumber,
 };
+use tokio::{spawn, task::spawn_blocking, time::sleep};
 use tracing::instrument;
 use utils::anytrace::*;
-use vbs::version::StaticVersionType;
+use vbs::version::{StaticVersionType, Version};
 
-use super::QuorumVoteTaskState;
+use super::{HeldDecide, MissingLeaves, QuorumVoteTaskState};
 use crate::{
     events::HotShotEvent,
     helpers::{
-        broadcast_event, decide_from_proposal, decide_from_proposal_2, fetch_proposal,
-        LeafChainTraversalOutcome,
+        broadcast_event, decide_from_proposal, decide_from_proposal_2, fetch_leaves,
+        fetch_proposal, LeafChainTraversalOutcome,
     },
+    payload_recovery::recover_payloads,
     quorum_vote::Versions,
 };
 
+/// Starts the DRB computations seeded by newly decided leaves.
+///
+/// The justify QC of the first block of epoch `e` seeds the DRB of epoch `e + DRB_EPOCHS_AHEAD`.
+/// Every node decides the same leaves, so every node derives the same seed and, once the
+/// computation finishes, the same leaders for that epoch.
+async fn start_drb_computations<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    leaf_views: &[LeafInfo<TYPES>],
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+) {
+    let epoch_height = task_state.epoch_height;
+    if epoch_height == 0 {
+        return;
+    }
+
+    for LeafInfo { leaf, .. } in leaf_views {
+        let block_number = leaf.height();
+        if block_number == 0 || (block_number - 1) % epoch_height != 0 {
+            continue;
+        }
+        let Some(drb_seed_input) = drb_seed_input(&leaf.justify_qc()) else {
+            continue;
+        };
+        let epoch = TYPES::Epoch::new(
+            epoch_from_block_number(block_number, epoch_height) + DRB_EPOCHS_AHEAD,
+        );
+        {
+            // We may already have the result from storage or a decided leaf
+            let mut consensus_writer = task_state.consensus.write().await;
+            if consensus_writer.drb_result(epoch).is_some()
+                || !consensus_writer.add_drb_seed(epoch, drb_seed_input)
+            {
+                continue;
+            }
+        }
+
+        tracing::info!("Starting the DRB computation for epoch {epoch}");
+        let consensus = OuterConsensus::new(Arc::clone(&task_state.consensus.inner_consensus));
+        let membership = Arc::clone(&task_state.membership);
+        let storage = Arc::clone(&task_state.storage);
+        let computation = spawn(async move {
+            let drb_result = match spawn_blocking(move || {
+                compute_drb_result(drb_seed_input, TYPES::DRB_DIFFICULTY)
+            })
+            .await
+            {
+                Ok(drb_result) => drb_result,
+                Err(e) => {
+                    tracing::error!("DRB computation for epoch {epoch} failed; error = {e:#}");
+                    return;
+                }
+            };
+
+            store_drb_result::<TYPES, I>(epoch, drb_result, &consensus, &membership, &storage)
+                .await;
+            tracing::info!("Finished the DRB computation for epoch {epoch}");
+        });
+
+        task_state
+            .drb_computations
+            .retain(|_, computation| !computation.is_finished());
+        task_state.drb_computations.insert(epoch, computation);
+    }
+}
+
+/// Records the DRB result for `epoch` in consensus, the membership and storage, unless we already
+/// have one.
+///
+/// We get each result from our own computation, or from a decided leaf if we never had the seed
+/// to compute it ourselves.
+async fn store_drb_result<TYPES: NodeType, I: NodeImplementation<TYPES>>(
+    epoch: TYPES::Epoch,
+    drb_result: DrbResult,
+    consensus: &OuterConsensus<TYPES>,
+    membership: &TYPES::Membership,
+    storage: &RwLock<I::Storage>,
+) {
+    {
+        let mut consensus_writer = consensus.write().await;
+        if let Some(known) = consensus_writer.drb_result(epoch) {
+            if known != drb_result {
+                tracing::error!("Got two different DRB results for epoch {epoch}");
+            }
+            return;
+        }
+        consensus_writer.add_drb_result(epoch, drb_result);
+    }
+    membership.add_drb_result(epoch, drb_result);
+
+    if let Err(e) = storage
+        .write()
+        .await
+        .add_drb_result(epoch, drb_result)
+        .await
+    {
+        tracing::warn!("Failed to store the DRB result for epoch {epoch}; error = {e:#}");
+    }
+}
+
+/// Checks the DRB results carried by newly decided leaves against our own.
+///
+/// A leaf of epoch `e` may carry the DRB result of epoch `e + 1`. Honest nodes only vote for a
+/// result they computed themselves, so a decided result was computed by at least one honest node.
+/// We still never let it replace or preempt our own computation, and only adopt it when we never
+/// had the seed, e.g. because we restarted before the computation finished.
+async fn add_decided_drb_results<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    leaf_views: &[LeafInfo<TYPES>],
+    task_state: &QuorumVoteTaskState<TYPES, I, V>,
+) {
+    let epoch_height = task_state.epoch_height;
+    if epoch_height == 0 {
+        return;
+    }
+
+    for LeafInfo { leaf, .. } in leaf_views {
+        if leaf.drb_result == INITIAL_DRB_RESULT {
+            continue;
+        }
+        let epoch = TYPES::Epoch::new(epoch_from_block_number(leaf.height(), epoch_height) + 1);
+        {
+            let consensus_reader = task_state.consensus.read().await;
+            if let Some(drb_result) = consensus_reader.drb_result(epoch) {
+                if drb_result != leaf.drb_result {
+                    tracing::error!("Decided a wrong DRB result for epoch {epoch}");
+                }
+                continue;
+            }
+            // Our own computation is still running and will store the result
+            if consensus_reader.has_drb_seed(epoch) {
+                continue;
+            }
+        }
+        store_drb_result::<TYPES, I>(
+            epoch,
+            leaf.drb_result,
+            &task_state.consensus,
+            &task_state.membership,
+            &task_state.storage,
+        )
+        .await;
+    }
+}
+
+/// Tells the membership about newly decided epoch roots.
+///
+/// The first block of epoch `e` is the epoch root of epoch `e + 1`, which fixes the stake table
+/// of that epoch at the same point of the decided chain on every node.
+fn add_epoch_roots<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    leaf_views: &[LeafInfo<TYPES>],
+    task_state: &QuorumVoteTaskState<TYPES, I, V>,
+) {
+    let epoch_height = task_state.epoch_height;
+    if epoch_height == 0 {
+        return;
+    }
+
+    // Oldest first, as the membership ignores roots older than the newest it has seen
+    for LeafInfo { leaf, .. } in leaf_views.iter().rev() {
+        let block_number = leaf.height();
+        if block_number == 0 || (block_number - 1) % epoch_height != 0 {
+            continue;
+        }
+        let epoch = TYPES::Epoch::new(epoch_from_block_number(block_number, epoch_height) + 1);
+        tracing::debug!("Decided the epoch root of epoch {epoch}");
+        task_state.membership.add_epoch_root(epoch);
+    }
+}
+
+/// How long we wait before we try again to fetch the decided leaves we missed
+const LEAF_CATCHUP_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest we hold back the events of a decide for the leaves it skipped over, before we send
+/// them with a gap
+const MAX_DECIDE_HOLD: Duration = Duration::from_secs(60);
+
+/// Most decides we hold back, before we send the oldest of them with a gap
+const MAX_HELD_DECIDES: usize = 64;
+
+/// Holds back the events of a decide until those of the decides before it were sent and we have
+/// the leaves it skipped over, then sends the events of every decide we can.
+async fn queue_decide<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    decide: HeldDecide<TYPES>,
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+) {
+    task_state.held_decides.push_back(decide);
+    release_decides(task_state, event_sender, event_receiver).await;
+}
+
+/// Applies and sends the events of the held decides, oldest first, up to the first one that still
+/// misses leaves, whose leaves we then fetch.
+///
+/// A decide we hold back is only applied once we have the leaves before it, so the membership
+/// sees the epoch roots in the order of the chain. We give up on the missing leaves of a decide we held back for `MAX_DECIDE_HOLD`, or while we
+/// hold more than `MAX_HELD_DECIDES`, and send its events with a gap.
+async fn release_decides<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+) {
+    while let Some(mut decide) = task_state.held_decides.pop_front() {
+        if let Some(missing) = decide.missing {
+            if decide.held_since.elapsed() < MAX_DECIDE_HOLD
+                && task_state.held_decides.len() < MAX_HELD_DECIDES
+            {
+                if task_state.leaf_catchup.is_none() {
+                    let deadline = decide.held_since + MAX_DECIDE_HOLD;
+                    start_leaf_catchup(missing, deadline, task_state, event_sender, event_receiver);
+                }
+                task_state.held_decides.push_front(decide);
+                return;
+            }
+
+            if let Some(handle) = task_state.leaf_catchup.take() {
+                handle.abort();
+            }
+            give_up_missing_leaves(&mut decide);
+        }
+
+        apply_decided_leaves(&decide.leaf_views, task_state).await;
+        send_decide(decide, task_state, event_sender, event_receiver).await;
+    }
+}
+
+/// Stops waiting for the missing leaves of `decide`, so its events go out with a gap
+fn give_up_missing_leaves<TYPES: NodeType>(decide: &mut HeldDecide<TYPES>) {
+    if let Some(missing) = decide.missing.take() {
+        tracing::warn!(
+            "Giving up on the leaves from view {} to view {}, deciding view {} with a gap",
+            *missing.last_decided_view + 1,
+            *missing.view,
+            *decide.decided_view_number
+        );
+        decide.gap = Some(missing);
+    }
+}
+
+/// Starts fetching the `missing` leaves, and keeps trying until we have them or `deadline` has
+/// passed.
+///
+/// Once we have them, the task of the catchup sends `MissingLeavesFetched`, and if it gives up,
+/// `LeafCatchupFailed`.
+fn start_leaf_catchup<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    missing: MissingLeaves<TYPES>,
+    deadline: Instant,
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+) {
+    let MissingLeaves {
+        commitment,
+        view,
+        last_decided_view,
+    } = missing;
+    tracing::info!(
+        "Missing the leaves from view {} to view {}, fetching them",
+        *last_decided_view + 1,
+        *view
+    );
+    let event_sender = event_sender.clone();
+    let event_receiver = event_receiver.clone().deactivate();
+    let membership = Arc::clone(&task_state.membership);
+    let consensus = Arc::clone(&task_state.consensus.inner_consensus);
+    let public_key = task_state.public_key.clone();
+    let private_key = task_state.private_key.clone();
+    let upgrade_lock = task_state.upgrade_lock.clone();
+    task_state.leaf_catchup = Some(spawn(async move {
+        loop {
+            match fetch_leaves(
+                commitment,
+                view,
+                last_decided_view,
+                &event_sender,
+                &event_receiver.activate_cloned(),
+                &membership,
+                OuterConsensus::new(Arc::clone(&consensus)),
+                &public_key,
+                &private_key,
+                &upgrade_lock,
+            )
+            .await
+            {
+                Ok(leaves) => {
+                    broadcast_event(
+                        Arc::new(HotShotEvent::MissingLeavesFetched(leaves)),
+                        &event_sender,
+                    )
+                    .await;
+                    return;
+                }
+                Err(e) if Instant::now() + LEAF_CATCHUP_RETRY_DELAY >= deadline => {
+                    tracing::warn!("Failed to fetch the missing leaves, giving up; error = {e:#}");
+                    broadcast_event(
+                        Arc::new(HotShotEvent::LeafCatchupFailed(view)),
+                        &event_sender,
+                    )
+                    .await;
+                    return;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to fetch the missing leaves, retrying; error = {e:#}");
+                    sleep(LEAF_CATCHUP_RETRY_DELAY).await;
+                }
+            }
+        }
+    }));
+}
+
+/// Handles the `MissingLeavesFetched` event, adding the leaves to the oldest held decide, which
+/// skipped over them, and applying and sending the decides we no longer hold back.
+///
+/// We have no validated state for the fetched leaves, so their state is only what their headers
+/// tell.
+pub(crate) async fn handle_missing_leaves_fetched<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES>,
+    V: Versions,
+>(
+    leaves: &[Leaf2<TYPES>],
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+) {
+    let Some(decide) = task_state.held_decides.front() else {
+        return;
+    };
+    let Some(missing) = &decide.missing else {
+        return;
+    };
+    // Only one catchup runs at a time, for the oldest held decide
+    if leaves.last().map(Leaf2::view_number) != Some(missing.view) {
+        return;
+    }
+    task_state.leaf_catchup = None;
+
+    // Newest first, like the leaves of the decide
+    let leaf_views: Vec<_> = leaves
+        .iter()
+        .rev()
+        .map(|leaf| {
+            let state = Arc::new(
+                <TYPES::ValidatedState as ValidatedState<TYPES>>::from_header(leaf.block_header()),
+            );
+            LeafInfo::new(leaf.clone(), state, None, None)
+        })
+        .collect();
+
+    if let Some(decide) = task_state.held_decides.front_mut() {
+        decide.leaf_views.extend(leaf_views);
+        decide.missing = None;
+    }
+
+    release_decides(task_state, event_sender, event_receiver).await;
+}
+
+/// Handles the `LeafCatchupFailed` event, sending the events of the oldest held decide with a
+/// gap where the leaves we could not fetch would be.
+pub(crate) async fn handle_leaf_catchup_failed<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES>,
+    V: Versions,
+>(
+    view: TYPES::View,
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+) {
+    let Some(decide) = task_state.held_decides.front_mut() else {
+        return;
+    };
+    if decide.missing.map(|missing| missing.view) != Some(view) {
+        return;
+    }
+    task_state.leaf_catchup = None;
+    give_up_missing_leaves(decide);
+
+    release_decides(task_state, event_sender, event_receiver).await;
+}
+
+/// Applies what newly decided leaves, newest first, tell us about the epochs to come, and keeps
+/// the leaves to serve the nodes catching up.
+///
+/// The leaves must follow every leaf applied before, as the membership ignores an epoch root
+/// older than the newest it has seen.
+async fn apply_decided_leaves<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    leaf_views: &[LeafInfo<TYPES>],
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+) {
+    add_epoch_roots(leaf_views, task_state);
+    add_decided_drb_results(leaf_views, task_state).await;
+    start_drb_computations(leaf_views, task_state).await;
+
+    let leaves: Vec<_> = leaf_views.iter().map(|info| info.leaf.clone()).collect();
+    if let Err(e) = task_state
+        .storage
+        .write()
+        .await
+        .append_decided_leaves(&leaves)
+        .await
+    {
+        tracing::warn!("Failed to store decided leaves; error = {e:#}");
+    }
+}
+
+/// Walks the leaf chain back from the parent of `proposal` to our last decided leaf, with the
+/// rules of the protocol `version`.
+async fn traverse_leaf_chain<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    proposal: &QuorumProposal2<TYPES>,
+    task_state: &QuorumVoteTaskState<TYPES, I, V>,
+    version: Version,
+) -> LeafChainTraversalOutcome<TYPES> {
+    if version >= V::Epochs::VERSION {
+        decide_from_proposal_2(
+            proposal,
+            OuterConsensus::new(Arc::clone(&task_state.consensus.inner_consensus)),
+            Arc::clone(&task_state.upgrade_lock.decided_upgrade_certificate),
+            &task_state.public_key,
+        )
+        .await
+    } else {
+        decide_from_proposal(
+            proposal,
+            OuterConsensus::new(Arc::clone(&task_state.consensus.inner_consensus)),
+            Arc::clone(&task_state.upgrade_lock.decided_upgrade_certificate),
+            &task_state.public_key,
+        )
+        .await
+    }
+}
+
 /// Handles the `QuorumProposalValidated` event.
+#[allow(clippy::too_many_lines)]
 #[instrument(skip_all, fields(id = task_state.id, view = *proposal.view_number))]
 pub(crate) async fn handle_quorum_proposal_validated<
     TYPES: NodeType,
@@ -48,6 +486,8 @@ pub(crate) async fn handle_quorum_proposal_validated<
 >(
     proposal: &QuorumProposal2<TYPES>,
     task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
 ) -> Result<()> {
     let version = task_state
         .upgrade_lock
@@ -61,23 +501,7 @@ pub(crate) async fn handle_quorum_proposal_validated<
         leaf_views,
         included_txns,
         decided_upgrade_cert,
-    } = if version >= V::Epochs::VERSION {
-        decide_from_proposal_2(
-            proposal,
-            OuterConsensus::new(Arc::clone(&task_state.consensus.inner_consensus)),
-            Arc::clone(&task_state.upgrade_lock.decided_upgrade_certificate),
-            &task_state.public_key,
-        )
-        .await
-    } else {
-        decide_from_proposal(
-            proposal,
-            OuterConsensus::new(Arc::clone(&task_state.consensus.inner_consensus)),
-            Arc::clone(&task_state.upgrade_lock.decided_upgrade_certificate),
-            &task_state.public_key,
-        )
-        .await
-    };
+    } = traverse_leaf_chain(proposal, task_state, version).await;
 
     if let Some(cert) = decided_upgrade_cert.clone() {
         let mut decided_certificate_lock = task_state
@@ -108,6 +532,17 @@ pub(crate) async fn handle_quorum_proposal_validated<
         let old_decided_view = consensus_writer.last_decided_view();
         consensus_writer.collect_garbage(old_decided_view, decided_view_number);
 
+        // After a long outage, the chain we decide stops short of our last decided leaf, where
+        // we missed its leaves
+        let missing = leaf_views.last().and_then(|oldest| {
+            let view = oldest.leaf.justify_qc().view_number();
+            (view > old_decided_view).then(|| MissingLeaves {
+                commitment: oldest.leaf.parent_commitment(),
+                view,
+                last_decided_view: old_decided_view,
+            })
+        });
+
         // Set the new decided view.
         consensus_writer.update_last_decided_view(decided_view_number)?;
 
@@ -126,6 +561,17 @@ pub(crate) async fn handle_quorum_proposal_validated<
             .metrics
             .number_of_views_per_decide_event
             .add_point(cur_number_of_views_per_decide_event as f64);
+        for LeafInfo { leaf, .. } in &leaf_views {
+            if let Some(recv_time) = task_state.proposal_recv_times.get(&leaf.view_number()) {
+                consensus_writer
+                    .metrics
+                    .proposal_to_decide_duration
+                    .add_point(recv_time.elapsed().as_secs_f64());
+            }
+        }
+        task_state.proposal_recv_times = task_state
+            .proposal_recv_times
+            .split_off(&(decided_view_number + 1));
 
         tracing::debug!(
             "Sending Decide for view {:?}",
@@ -135,24 +581,153 @@ pub(crate) async fn handle_quorum_proposal_validated<
         // We don't need to hold this while we broadcast
         drop(consensus_writer);
 
-        // First, send an update to everyone saying that we've reached a decide
+        // Persist the new anchor, so a restart picks up consensus from here.
+        if let Some(decided) = leaf_views.first() {
+            if let Err(e) = task_state
+                .storage
+                .write()
+                .await
+                .update_anchor_leaf(decided.leaf.clone())
+                .await
+            {
+                tracing::warn!("Failed to store anchor leaf; error = {e:#}");
+            }
+        }
+
+        // Prune persisted data the retention policy no longer covers.
+        if let Err(e) = task_state
+            .storage
+            .write()
+            .await
+            .collect_garbage(decided_view_number)
+            .await
+        {
+            tracing::warn!("Failed to collect garbage in storage; error = {e:#}");
+        }
+
+        queue_decide(
+            HeldDecide {
+                leaf_views,
+                decided_view_number,
+                // This is never *not* none if we've reached a new decide, so this is safe to unwrap.
+                qc: new_decide_qc.unwrap(),
+                block_size: included_txns.map(|txns| txns.len().try_into().unwrap()),
+                missing,
+                gap: None,
+                held_since: Instant::now(),
+            },
+            task_state,
+            event_sender,
+            event_receiver,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Sends the events of `decide`.
+///
+/// If we recover payloads, we first fill in the payloads the leaves lack, in a task of its own so
+/// the decide does not hold up voting. The events of each decide still follow those of the
+/// decide before.
+async fn send_decide<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    mut decide: HeldDecide<TYPES>,
+    task_state: &mut QuorumVoteTaskState<TYPES, I, V>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    event_receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
+) {
+    if !task_state.recover_payloads {
+        broadcast_decide(decide, event_sender, &task_state.output_event_stream).await;
+        return;
+    }
+
+    let previous = task_state.payload_recovery.take();
+    let consensus_reader = task_state.consensus.read().await;
+    let (cur_view, cur_epoch) = (consensus_reader.cur_view(), consensus_reader.cur_epoch());
+    drop(consensus_reader);
+    let event_sender = event_sender.clone();
+    let event_receiver = event_receiver.clone().deactivate();
+    let output_event_stream = task_state.output_event_stream.clone();
+    let membership = Arc::clone(&task_state.membership);
+    let public_key = task_state.public_key.clone();
+    let private_key = task_state.private_key.clone();
+    let upgrade_lock = task_state.upgrade_lock.clone();
+    task_state.payload_recovery = Some(spawn(async move {
+        // Unless we are on the DA committee, we only have our own share of the payloads we
+        // decide, so recover them from the shares of the others.
+        recover_payloads(
+            &mut decide.leaf_views,
+            &event_sender,
+            &event_receiver.activate_cloned(),
+            &membership,
+            cur_view,
+            cur_epoch,
+            &public_key,
+            &private_key,
+            &upgrade_lock,
+        )
+        .await;
+
+        if let Some(previous) = previous {
+            let _ = previous.await;
+        }
+        broadcast_decide(decide, &event_sender, &output_event_stream).await;
+    }));
+}
+
+/// Tells the other tasks and the application that the leaves of `decide` were decided, after
+/// telling the application about the leaves before them we could not fetch, if any.
+async fn broadcast_decide<TYPES: NodeType>(
+    decide: HeldDecide<TYPES>,
+    event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    output_event_stream: &Sender<Event<TYPES>>,
+) {
+    let HeldDecide {
+        leaf_views,
+        decided_view_number,
+        qc,
+        block_size,
+        gap,
+        ..
+    } = decide;
+
+    broadcast_event(
+        Arc::new(HotShotEvent::LeavesDecided(
+            leaf_views.iter().map(|info| info.leaf.clone()).collect(),
+        )),
+        event_sender,
+    )
+    .await;
+
+    if let Some(gap) = gap {
         broadcast_event(
             Event {
                 view_number: decided_view_number,
-                event: EventType::Decide {
-                    leaf_chain: Arc::new(leaf_views),
-                    // This is never *not* none if we've reached a new decide, so this is safe to unwrap.
-                    qc: Arc::new(new_decide_qc.unwrap()),
-                    block_size: included_txns.map(|txns| txns.len().try_into().unwrap()),
+                event: EventType::DecideGap {
+                    last_decided_view: gap.last_decided_view,
+                    missing_until: gap.view,
                 },
             },
-            &task_state.output_event_stream,
+            output_event_stream,
         )
         .await;
-        tracing::debug!("Successfully sent decide event");
     }
 
-    Ok(())
+    // First, send an update to everyone saying that we've reached a decide
+    broadcast_event(
+        Event {
+            view_number: decided_view_number,
+            event: EventType::Decide {
+                leaf_chain: Arc::new(leaf_views),
+                qc: Arc::new(qc),
+                block_size,
+            },
+        },
+        output_event_stream,
+    )
+    .await;
+    tracing::debug!("Successfully sent decide event");
 }
 
 /// Updates the shared consensus state with the new voting data.
@@ -4,12 +4,16 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::Instant,
+};
 
 use async_broadcast::{InactiveReceiver, Receiver, Sender};
 use async_lock::RwLock;
 use async_trait::async_trait;
-use committable::Committable;
+use committable::{Commitment, Committable};
 use hotshot_task::{
     dependency::{AndDependency, EventDependency},
     dependency_task::{DependencyTask, HandleDepOutput},
@@ -18,8 +22,9 @@ use hotshot_task::{
 use hotshot_types::{
     consensus::OuterConsensus,
     data::{Leaf2, QuorumProposal2},
-    event::Event,
+    event::{Event, LeafInfo},
     message::{Proposal, UpgradeLock},
+    simple_certificate::QuorumCertificate2,
     traits::{
         block_contents::BlockHeader,
         election::Membership,
@@ -40,10 +45,13 @@ use vbs::version::StaticVersionType;
 use crate::{
     events::HotShotEvent,
     helpers::broadcast_event,
-    quorum_vote::handlers::{handle_quorum_proposal_validated, submit_vote, update_shared_state},
+    quorum_vote::handlers::{
+        handle_leaf_catchup_failed, handle_missing_leaves_fetched,
+        handle_quorum_proposal_validated, submit_vote, update_shared_state,
+    },
 };
 
-/// Event handlers for `QuorumProposalValidated`.
+/// Event handlers for `QuorumProposalValidated`, `MissingLeavesFetched` and `LeafCatchupFailed`.
 mod handlers;
 
 /// Vote dependency types.
@@ -244,6 +252,44 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static, V: Versions> Handl
     }
 }
 
+/// A decide we hold back until the decides before it were sent and we have the leaves it skipped
+/// over, so the application and the membership get every decided leaf, in order.
+pub struct HeldDecide<TYPES: NodeType> {
+    /// The decided leaves, newest first
+    leaf_views: Vec<LeafInfo<TYPES>>,
+
+    /// The view of the newest decided leaf
+    decided_view_number: TYPES::View,
+
+    /// The QC that decided the leaves
+    qc: QuorumCertificate2<TYPES>,
+
+    /// The size of the block of the newest leaf, if we know it
+    block_size: Option<u64>,
+
+    /// The leaves between the decide before and this one, if we have yet to fetch them
+    missing: Option<MissingLeaves<TYPES>>,
+
+    /// The leaves between the decide before and this one, if we gave up fetching them
+    gap: Option<MissingLeaves<TYPES>>,
+
+    /// When we started holding back the decide
+    held_since: Instant,
+}
+
+/// The decided leaves between our last decided leaf and the oldest leaf of a decide
+#[derive(Clone, Copy)]
+pub struct MissingLeaves<TYPES: NodeType> {
+    /// The commitment of the newest missing leaf, the parent of the oldest leaf of the decide
+    commitment: Commitment<Leaf2<TYPES>>,
+
+    /// The view of the newest missing leaf
+    view: TYPES::View,
+
+    /// The view of the decided leaf before the oldest missing leaf
+    last_decided_view: TYPES::View,
+}
+
 /// The state for the quorum vote task.
 ///
 /// Contains all of the information for the quorum vote.
@@ -286,6 +332,24 @@ pub struct QuorumVoteTaskState<TYPES: NodeType, I: NodeImplementation<TYPES>, V:
 
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+
+    /// DRB computations in progress, by the epoch whose leaders they determine
+    pub drb_computations: BTreeMap<TYPES::Epoch, JoinHandle<()>>,
+
+    /// The fetch of the decided leaves we missed, while it runs
+    pub leaf_catchup: Option<JoinHandle<()>>,
+
+    /// The decides we hold back until we fetched the leaves we missed, oldest first
+    pub held_decides: VecDeque<HeldDecide<TYPES>>,
+
+    /// Whether we recover the payloads of the leaves we decide from the VID shares of others
+    pub recover_payloads: bool,
+
+    /// The recovery of the payloads of the latest decide, which sends its decide events once done
+    pub payload_recovery: Option<JoinHandle<()>>,
+
+    /// When we received the quorum proposals of the undecided views, by view
+    pub proposal_recv_times: BTreeMap<TYPES::View, Instant>,
 }
 
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> QuorumVoteTaskState<TYPES, I, V> {
@@ -422,6 +486,21 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> QuorumVoteTaskS
         event_sender: Sender<Arc<HotShotEvent<TYPES>>>,
     ) -> Result<()> {
         match event.as_ref() {
+            HotShotEvent::QuorumProposalRecv(proposal, _) => {
+                self.proposal_recv_times
+                    .entry(proposal.data.view_number())
+                    .or_insert_with(Instant::now);
+            }
+            HotShotEvent::QuorumVoteSend(vote) | HotShotEvent::ExtendedQuorumVoteSend(vote) => {
+                if let Some(recv_time) = self.proposal_recv_times.get(&vote.view_number()) {
+                    self.consensus
+                        .read()
+                        .await
+                        .metrics
+                        .proposal_to_vote_duration
+                        .add_point(recv_time.elapsed().as_secs_f64());
+                }
+            }
             HotShotEvent::QuorumProposalValidated(proposal, parent_leaf) => {
                 tracing::trace!(
                     "Received Proposal for view {}",
@@ -429,7 +508,14 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> QuorumVoteTaskS
                 );
 
                 // Handle the event before creating the dependency task.
-                if let Err(e) = handle_quorum_proposal_validated(&proposal.data, self).await {
+                if let Err(e) = handle_quorum_proposal_validated(
+                    &proposal.data,
+                    self,
+                    &event_sender,
+                    &event_receiver,
+                )
+                .await
+                {
                     tracing::debug!(
                         "Failed to handle QuorumProposalValidated event; error = {e:#}"
                     );
@@ -463,6 +549,12 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> QuorumVoteTaskS
                     );
                 }
             }
+            HotShotEvent::MissingLeavesFetched(leaves) => {
+                handle_missing_leaves_fetched(leaves, self, &event_sender, &event_receiver).await;
+            }
+            HotShotEvent::LeafCatchupFailed(view) => {
+                handle_leaf_catchup_failed(*view, self, &event_sender, &event_receiver).await;
+            }
             HotShotEvent::DaCertificateRecv(cert) => {
                 let view = cert.view_number;
 
@@ -478,7 +570,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> QuorumVoteTaskS
                 ensure!(
                     cert.is_valid_cert(
                         self.membership.da_stake_table(cur_epoch),
-                        self.membership.da_success_threshold(),
+                        self.membership.da_success_threshold(cur_epoch),
                         &self.upgrade_lock
                     )
                     .await,
@@ -722,5 +814,15 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TaskState
         while let Some((_, handle)) = self.vote_dependencies.pop_last() {
             handle.abort();
         }
+        while let Some((_, handle)) = self.drb_computations.pop_last() {
+            handle.abort();
+        }
+        if let Some(handle) = self.leaf_catchup.take() {
+            handle.abort();
+        }
+        self.held_decides.clear();
+        if let Some(handle) = self.payload_recovery.take() {
+            handle.abort();
+        }
     }
 }
@@ -0,0 +1,134 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{hash::Hash, num::NonZeroUsize, time::Instant};
+
+use hotshot_types::{
+    message::MessagePurpose,
+    rate_limit_config::{RateLimit, RateLimitConfig},
+};
+
+/// Number of (peer, purpose) buckets we keep at most. New peer identities cost nothing to make,
+/// so anyone can make us track a new one; the least recently used are forgotten first.
+const MAX_BUCKETS: usize = 10_000;
+
+/// What to do with a message, according to [`RateLimiter::check`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitOutcome {
+    /// The message is within the peer's limit
+    Allowed,
+    /// The message is over the peer's limit, and the peer just went over it
+    NewlyLimited,
+    /// The message is over the peer's limit, which the peer was already over
+    Limited,
+}
+
+/// The tokens left to one peer for one message purpose
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    /// Messages the peer may still send right now
+    tokens: f64,
+    /// When `tokens` was last topped up
+    last_refill: Instant,
+    /// Whether the last message was dropped
+    limited: bool,
+}
+
+impl TokenBucket {
+    /// A bucket holding a full burst
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.burst),
+            last_refill: now,
+            limited: false,
+        }
+    }
+
+    /// Top the bucket up for the time passed, then take a token from it if there is one.
+    fn take(&mut self, limit: RateLimit, now: Instant) -> RateLimitOutcome {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * f64::from(limit.per_second)).min(f64::from(limit.burst));
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.limited = false;
+            RateLimitOutcome::Allowed
+        } else if self.limited {
+            RateLimitOutcome::Limited
+        } else {
+            self.limited = true;
+            RateLimitOutcome::NewlyLimited
+        }
+    }
+}
+
+/// Token-bucket rate limiting of the messages each peer sends us, by message purpose
+#[derive(Clone, Debug)]
+pub struct RateLimiter<K: Hash + Eq + Clone> {
+    /// The limits to enforce
+    config: RateLimitConfig,
+    /// The buckets of the peers we heard from recently
+    buckets: lru::LruCache<(K, MessagePurpose), TokenBucket>,
+}
+
+impl<K: Hash + Eq + Clone> RateLimiter<K> {
+    /// Create a rate limiter enforcing `config`
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: lru::LruCache::new(NonZeroUsize::new(MAX_BUCKETS).unwrap()),
+        }
+    }
+
+    /// Account for a message of `purpose` from `peer` received at `now`.
+    pub fn check(&mut self, peer: &K, purpose: MessagePurpose, now: Instant) -> RateLimitOutcome {
+        let Some(&limit) = self.config.limits.get(&purpose) else {
+            return RateLimitOutcome::Allowed;
+        };
+
+        self.buckets
+            .get_or_insert_mut((peer.clone(), purpose), || TokenBucket::full(limit, now))
+            .take(limit, now)
+    }
+}
+
+/// Token-bucket quotas on the data requests each peer makes of us, across all kinds of request
+#[derive(Clone, Debug)]
+pub struct RequestQuotas<K: Hash + Eq + Clone> {
+    /// The quota of each peer, if requests are limited at all
+    quota: Option<RateLimit>,
+    /// The buckets of the peers that asked us for data recently
+    buckets: lru::LruCache<K, TokenBucket>,
+}
+
+impl<K: Hash + Eq + Clone> RequestQuotas<K> {
+    /// Create quotas granting each peer `quota`, or unlimited requests if `None`
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn new(quota: Option<RateLimit>) -> Self {
+        Self {
+            quota,
+            buckets: lru::LruCache::new(NonZeroUsize::new(MAX_BUCKETS).unwrap()),
+        }
+    }
+
+    /// Account for a request from `peer` received at `now`.
+    pub fn check(&mut self, peer: &K, now: Instant) -> RateLimitOutcome {
+        let Some(quota) = self.quota else {
+            return RateLimitOutcome::Allowed;
+        };
+
+        self.buckets
+            .get_or_insert_mut(peer.clone(), || TokenBucket::full(quota, now))
+            .take(quota, now)
+    }
+}
@@ -5,26 +5,26 @@
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::Hash,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use async_broadcast::{Receiver, Sender};
+use async_lock::RwLock;
 use async_trait::async_trait;
-use hotshot_task::{
-    dependency::{Dependency, EventDependency},
-    task::TaskState,
-};
+use hotshot_task::task::TaskState;
 use hotshot_types::{
-    consensus::OuterConsensus,
+    consensus::{ConsensusMetricsValue, OuterConsensus},
+    message::UpgradeLock,
     traits::{
         election::Membership,
         network::{ConnectedNetwork, DataRequest, RequestKind},
-        node_implementation::{NodeImplementation, NodeType},
+        node_implementation::{NodeImplementation, NodeType, Versions},
         signature_key::SignatureKey,
     },
     vote::HasViewNumber,
@@ -41,14 +41,129 @@ use utils::anytrace::Result;
 
 use crate::{events::HotShotEvent, helpers::broadcast_event};
 
-/// Amount of time to try for a request before timing out.
+/// Amount of time to wait for a response from a peer we have not heard from before.
 pub const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
 
+/// Least amount of time to wait for a response, however fast the peers we ask usually answer.
+pub const MIN_REQUEST_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Most amount of time to wait for a response, however slow the peers we ask usually answer.
+pub const MAX_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of DA members we send each request to at once. The first valid response wins.
+pub const REQUEST_FANOUT: usize = 3;
+
+/// The round trip times of the requests we made of one peer, smoothed as in RFC 6298
+#[derive(Clone, Copy, Debug)]
+struct PeerLatency {
+    /// Smoothed round trip time, in seconds
+    smoothed: f64,
+    /// Smoothed deviation of the round trip time, in seconds
+    variation: f64,
+}
+
+impl PeerLatency {
+    /// How long to wait for the peer: its round trip time with a margin for its deviation
+    fn timeout(self) -> Duration {
+        Duration::from_secs_f64(self.smoothed + 4.0 * self.variation)
+    }
+}
+
+/// The latencies we observed of the peers we request data from, to adapt how long we wait for
+/// each of them
+#[derive(Clone, Debug)]
+pub struct PeerLatencies<K: Hash + Eq> {
+    /// The latency of each peer that answered us
+    peers: HashMap<K, PeerLatency>,
+}
+
+impl<K: Hash + Eq> Default for PeerLatencies<K> {
+    fn default() -> Self {
+        Self {
+            peers: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> PeerLatencies<K> {
+    /// Account for `peer` taking `rtt` to answer a request
+    pub fn record(&mut self, peer: K, rtt: Duration) {
+        let rtt = rtt.as_secs_f64();
+        self.peers
+            .entry(peer)
+            .and_modify(|latency| {
+                latency.variation =
+                    0.75 * latency.variation + 0.25 * (latency.smoothed - rtt).abs();
+                latency.smoothed = 0.875 * latency.smoothed + 0.125 * rtt;
+            })
+            .or_insert(PeerLatency {
+                smoothed: rtt,
+                variation: rtt / 2.0,
+            });
+    }
+
+    /// How long to wait for a response from any of `peers`: long enough for the slowest of them,
+    /// or [`REQUEST_TIMEOUT`] if we have not heard from one of them yet.
+    #[must_use]
+    pub fn timeout<'a>(&self, peers: impl IntoIterator<Item = &'a K>) -> Duration
+    where
+        K: 'a,
+    {
+        peers
+            .into_iter()
+            .map(|peer| {
+                self.peers
+                    .get(peer)
+                    .map_or(REQUEST_TIMEOUT, |latency| latency.timeout())
+            })
+            .max()
+            .unwrap_or(REQUEST_TIMEOUT)
+            .clamp(MIN_REQUEST_TIMEOUT, MAX_REQUEST_TIMEOUT)
+    }
+}
+
+/// How a request for data we made of some peers ended
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum RequestOutcome {
+    /// A peer gave us valid data after this long
+    Hit(Duration),
+    /// Every peer denied the request
+    Denied,
+    /// The responses we got were invalid
+    Invalid,
+    /// No peer answered in time
+    Timeout,
+}
+
+/// Count the outcome of a request of `kind`, and the latency of a hit
+pub(crate) fn record_request_outcome<TYPES: NodeType>(
+    metrics: &ConsensusMetricsValue,
+    kind: &RequestKind<TYPES>,
+    outcome: RequestOutcome,
+) {
+    let label = match outcome {
+        RequestOutcome::Hit(latency) => {
+            metrics
+                .data_request_latency
+                .create(vec![kind.name().to_string()])
+                .add_point(latency.as_secs_f64());
+            "hit"
+        }
+        RequestOutcome::Denied => "denied",
+        RequestOutcome::Invalid => "invalid",
+        RequestOutcome::Timeout => "timeout",
+    };
+    metrics
+        .data_requests
+        .create(vec![kind.name().to_string(), label.to_string()])
+        .add(1);
+}
+
 /// Long running task which will request information after a proposal is received.
-/// The task will wait a it's `delay` and then send a request iteratively to peers
+/// The task will wait a it's `delay` and then send a request to a few peers at a time
 /// for any data they don't have related to the proposal.  For now it's just requesting VID
 /// shares.
-pub struct NetworkRequestState<TYPES: NodeType, I: NodeImplementation<TYPES>> {
+pub struct NetworkRequestState<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> {
     /// Network to send requests over
     /// The underlying network
     pub network: Arc<I::Network>,
@@ -71,9 +186,15 @@ pub struct NetworkRequestState<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     pub shutdown_flag: Arc<AtomicBool>,
     /// A flag indicating that `HotShotEvent::Shutdown` has been received
     pub spawned_tasks: BTreeMap<TYPES::View, Vec<JoinHandle<()>>>,
+    /// The latencies of the peers we requested data from, shared by all our requests
+    pub latencies: Arc<RwLock<PeerLatencies<TYPES::SignatureKey>>>,
+    /// Lock for a decided upgrade, which determines how we sign requests
+    pub upgrade_lock: UpgradeLock<TYPES, V>,
 }
 
-impl<TYPES: NodeType, I: NodeImplementation<TYPES>> Drop for NetworkRequestState<TYPES, I> {
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> Drop
+    for NetworkRequestState<TYPES, I, V>
+{
     fn drop(&mut self) {
         self.cancel_subtasks();
     }
@@ -84,7 +205,9 @@ type Signature<TYPES> =
     <<TYPES as NodeType>::SignatureKey as SignatureKey>::PureAssembledSignatureType;
 
 #[async_trait]
-impl<TYPES: NodeType, I: NodeImplementation<TYPES>> TaskState for NetworkRequestState<TYPES, I> {
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TaskState
+    for NetworkRequestState<TYPES, I, V>
+{
     type Event = HotShotEvent<TYPES>;
 
     #[instrument(skip_all, target = "NetworkRequestState", fields(id = self.id))]
@@ -108,7 +231,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> TaskState for NetworkRequest
                         .vid_shares()
                         .contains_key(&prop_view)
                 {
-                    self.spawn_requests(prop_view, cur_epoch, sender, receiver);
+                    self.spawn_requests(prop_view, cur_epoch, sender, receiver)
+                        .await;
                 }
                 Ok(())
             }
@@ -138,9 +262,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> TaskState for NetworkRequest
     }
 }
 
-impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I> {
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> NetworkRequestState<TYPES, I, V> {
     /// Creates and signs the payload, then will create a request task
-    fn spawn_requests(
+    async fn spawn_requests(
         &mut self,
         view: TYPES::View,
         epoch: TYPES::Epoch,
@@ -150,7 +274,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
         let request = RequestKind::Vid(view, self.public_key.clone());
 
         // First sign the request for the VID shares.
-        if let Some(signature) = self.serialize_and_sign(&request) {
+        if let Some(signature) = self.serialize_and_sign(&request, view).await {
             self.create_vid_request_task(
                 request,
                 signature,
@@ -162,7 +286,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
         }
     }
 
-    /// Creates a task that will request a VID share from a DA member and wait for the `HotShotEvent::VidResponseRecv`event
+    /// Creates a task that will request a VID share from a few DA members at a time and wait for
+    /// the first valid `HotShotEvent::VidResponseRecv`.
     /// If we get the VID disperse share, broadcast `HotShotEvent::VidShareRecv` and terminate task
     fn create_vid_request_task(
         &mut self,
@@ -176,6 +301,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
         let consensus = OuterConsensus::new(Arc::clone(&self.consensus.inner_consensus));
         let network = Arc::clone(&self.network);
         let shutdown_flag = Arc::clone(&self.shutdown_flag);
+        let latencies = Arc::clone(&self.latencies);
         let delay = self.delay;
         let public_key = self.public_key.clone();
 
@@ -185,14 +311,15 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
             da_committee_for_view.insert(leader);
         }
 
-        // Get committee members for view
+        // Get committee members for view, no need to send a message to ourselves
         let mut recipients: Vec<TYPES::SignatureKey> = self
             .membership
             .da_committee_members(view, epoch)
             .into_iter()
+            .filter(|key| *key != public_key)
             .collect();
-        // Randomize the recipients so all replicas don't overload the same 1 recipients
-        // and so we don't implicitly rely on the same replica all the time.
+        // Randomize the recipients so all replicas don't overload the same few recipients
+        // and so we don't implicitly rely on the same replicas all the time.
         recipients.shuffle(&mut thread_rng());
 
         // prepare request
@@ -206,10 +333,41 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
             if !network.is_primary_down() {
                 sleep(delay).await;
             }
+            let metrics = Arc::clone(&consensus.read().await.metrics);
+
+            // Ask a few DA members at a time, until one of them gives us the share
+            for peers in recipients.chunks(REQUEST_FANOUT) {
+                // First check if we got the data before continuing
+                if Self::cancel_vid_request_task(
+                    &consensus,
+                    &sender,
+                    &public_key,
+                    &view,
+                    &shutdown_flag,
+                )
+                .await
+                {
+                    return;
+                }
+
+                let outcome = Self::handle_vid_request_task(
+                    &sender,
+                    &receiver,
+                    &data_request,
+                    peers,
+                    &da_committee_for_view,
+                    &public_key,
+                    view,
+                    &latencies,
+                )
+                .await;
+                record_request_outcome(&metrics, &data_request.request, outcome);
+                if matches!(outcome, RequestOutcome::Hit(_)) {
+                    return;
+                }
+            }
 
-            let mut recipients_it = recipients.iter();
-            // First check if we got the data before continuing
-            while !Self::cancel_vid_request_task(
+            if !Self::cancel_vid_request_task(
                 &consensus,
                 &sender,
                 &public_key,
@@ -218,113 +376,103 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
             )
             .await
             {
-                // Cycle da members we send the request to each time
-                if let Some(recipient) = recipients_it.next() {
-                    if *recipient == public_key {
-                        // no need to send a message to ourselves.
-                        // just check for the data at start of loop in `cancel_vid_request_task`
-                        continue;
-                    }
-                    // If we got the data after we make the request then we are done
-                    if Self::handle_vid_request_task(
-                        &sender,
-                        &receiver,
-                        &data_request,
-                        recipient,
-                        &da_committee_for_view,
-                        &public_key,
-                        view,
-                    )
-                    .await
-                    {
-                        return;
-                    }
-                } else {
-                    // This shouldnt be possible `recipients_it.next()` should clone original and start over if `None`
-                    tracing::warn!(
-                        "Sent VID request to all available DA members and got no response for view: {:?}",
-                        view
-                    );
-                    return;
-                }
+                tracing::warn!(
+                    "Sent VID request to all available DA members and got no response for view: {:?}",
+                    view
+                );
             }
         });
         self.spawned_tasks.entry(view).or_default().push(handle);
     }
 
     /// Handles main logic for the Request / Response of a vid share
-    /// Make the request to get VID share to a DA member and wait for the response.
-    /// Returns true if response received, otherwise false
+    /// Make the request to get VID share to each of `peers` and wait for the first valid
+    /// response, for as long as the slowest of them usually takes to answer.
+    #[allow(clippy::too_many_arguments)]
     async fn handle_vid_request_task(
         sender: &Sender<Arc<HotShotEvent<TYPES>>>,
         receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
         data_request: &DataRequest<TYPES>,
-        recipient: &TYPES::SignatureKey,
+        peers: &[TYPES::SignatureKey],
         da_committee_for_view: &BTreeSet<<TYPES as NodeType>::SignatureKey>,
         public_key: &<TYPES as NodeType>::SignatureKey,
         view: TYPES::View,
-    ) -> bool {
-        // First send request to a random DA member for the view
-        broadcast_event(
-            HotShotEvent::VidRequestSend(
-                data_request.clone(),
-                public_key.clone(),
-                recipient.clone(),
+        latencies: &RwLock<PeerLatencies<TYPES::SignatureKey>>,
+    ) -> RequestOutcome {
+        // Listen before we ask, so we do not miss a response
+        let mut receiver = receiver.clone();
+        let wait = latencies.read().await.timeout(peers);
+        let sent_at = Instant::now();
+        for peer in peers {
+            broadcast_event(
+                HotShotEvent::VidRequestSend(
+                    data_request.clone(),
+                    public_key.clone(),
+                    peer.clone(),
+                )
+                .into(),
+                sender,
             )
-            .into(),
-            sender,
-        )
-        .await;
+            .await;
+        }
 
         // Wait for a response
-        let result = timeout(
-            REQUEST_TIMEOUT,
-            Self::handle_event_dependency(receiver, da_committee_for_view.clone(), view),
-        )
+        let mut denied = BTreeSet::new();
+        let result = timeout(wait, async {
+            loop {
+                let Ok(event) = receiver.recv_direct().await else {
+                    return RequestOutcome::Timeout;
+                };
+                match event.as_ref() {
+                    HotShotEvent::VidResponseRecv(sender_key, proposal)
+                        if peers.contains(sender_key)
+                            && proposal.data.view_number() == view
+                            && da_committee_for_view.contains(sender_key)
+                            && sender_key.validate(
+                                &proposal.signature,
+                                proposal.data.payload_commitment.as_ref(),
+                            ) =>
+                    {
+                        latencies
+                            .write()
+                            .await
+                            .record(sender_key.clone(), sent_at.elapsed());
+                        broadcast_event(
+                            Arc::new(HotShotEvent::VidShareRecv(
+                                sender_key.clone(),
+                                proposal.clone(),
+                            )),
+                            sender,
+                        )
+                        .await;
+                        return RequestOutcome::Hit(sent_at.elapsed());
+                    }
+                    // Denials do not say which request they are for, so a denial of another of
+                    // our requests may end the wait early. We then just ask the next peers.
+                    HotShotEvent::RequestDeniedRecv(sender_key, _)
+                        if peers.contains(sender_key) =>
+                    {
+                        denied.insert(sender_key.clone());
+                        if denied.len() == peers.len() {
+                            return RequestOutcome::Denied;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        })
         .await;
 
-        // Check if we got a result, if not we timed out
-        if let Ok(Some(event)) = result {
-            if let HotShotEvent::VidResponseRecv(sender_pub_key, proposal) = event.as_ref() {
-                broadcast_event(
-                    Arc::new(HotShotEvent::VidShareRecv(
-                        sender_pub_key.clone(),
-                        proposal.clone(),
-                    )),
-                    sender,
-                )
-                .await;
-                return true;
+        let Ok(outcome) = result else {
+            // Peers that did not answer in time count as answering at the timeout, so we wait
+            // longer for them next time
+            let mut latencies = latencies.write().await;
+            for peer in peers.iter().filter(|peer| !denied.contains(*peer)) {
+                latencies.record(peer.clone(), wait);
             }
-        }
-        false
-    }
-
-    /// Create event dependency and wait for `VidResponseRecv` after we send out the request
-    /// Returns an optional with `VidResponseRecv` if received, otherwise None
-    async fn handle_event_dependency(
-        receiver: &Receiver<Arc<HotShotEvent<TYPES>>>,
-        da_members_for_view: BTreeSet<<TYPES as NodeType>::SignatureKey>,
-        view: TYPES::View,
-    ) -> Option<Arc<HotShotEvent<TYPES>>> {
-        EventDependency::new(
-            receiver.clone(),
-            Box::new(move |event: &Arc<HotShotEvent<TYPES>>| {
-                let event = event.as_ref();
-                if let HotShotEvent::VidResponseRecv(sender_key, proposal) = event {
-                    proposal.data.view_number() == view
-                        && da_members_for_view.contains(sender_key)
-                        && sender_key.validate(
-                            &proposal.signature,
-                            proposal.data.payload_commitment.as_ref(),
-                        )
-                } else {
-                    false
-                }
-            }),
-        )
-        .completed()
-        .await
+            return RequestOutcome::Timeout;
+        };
+        outcome
     }
 
     /// Returns true if we got the data we wanted, a shutdown even was received, or the view has moved on.
@@ -364,9 +512,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> NetworkRequestState<TYPES, I
         cancel
     }
 
-    /// Sign the serialized version of the request
-    fn serialize_and_sign(&self, request: &RequestKind<TYPES>) -> Option<Signature<TYPES>> {
-        let Ok(data) = bincode::serialize(&request) else {
+    /// Sign the serialized version of the request for `view`
+    async fn serialize_and_sign(
+        &self,
+        request: &RequestKind<TYPES>,
+        view: TYPES::View,
+    ) -> Option<Signature<TYPES>> {
+        let Ok(data) = DataRequest::signed_data(request, view, &self.upgrade_lock).await else {
             tracing::error!("Failed to serialize request!");
             return None;
         };
@@ -4,33 +4,56 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_broadcast::{Receiver, Sender};
+use async_lock::{OnceCell, RwLock};
 use committable::Committable;
 use hotshot_types::{
     consensus::{Consensus, LockedConsensusState, OuterConsensus},
-    data::VidDisperseShare,
-    message::Proposal,
+    data::{QuorumProposal2, VidDisperse, VidDisperseShare},
+    message::{Proposal, UpgradeLock},
+    rate_limit_config::RateLimit,
+    request_response::DecidedLeaves,
     traits::{
-        election::Membership, network::DataRequest, node_implementation::NodeType,
+        election::Membership,
+        network::{DataRequest, DenialReason, RequestKind},
+        node_implementation::{NodeImplementation, NodeType, Versions},
         signature_key::SignatureKey,
+        storage::Storage,
     },
+    vid::vid_recovery_threshold,
 };
 use sha2::{Digest, Sha256};
 use tokio::{spawn, task::JoinHandle, time::sleep};
 use tracing::instrument;
 
-use crate::{events::HotShotEvent, helpers::broadcast_event};
+use crate::{
+    events::HotShotEvent,
+    helpers::broadcast_event,
+    rate_limit::{RateLimitOutcome, RequestQuotas},
+};
 /// Time to wait for txns before sending `ResponseMessage::NotFound`
 const TXNS_TIMEOUT: Duration = Duration::from_millis(100);
+/// Most leaves we send in response to one request for leaves
+const MAX_LEAVES_PER_RESPONSE: usize = 100;
+/// Number of views we keep every VID share of, for the nodes recovering their payloads
+const VID_DISPERSAL_CACHE_SIZE: usize = 8;
+/// Number of views a request may be signed for before our current view and still be served
+const MAX_REQUEST_AGE: u64 = 10;
 
 /// Task state for the Network Request Task. The task is responsible for handling
 /// requests sent to this node by the network.  It will validate the sender,
 /// parse the request, and try to find the data request in the consensus stores.
-pub struct NetworkResponseState<TYPES: NodeType> {
+pub struct NetworkResponseState<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> {
     /// Locked consensus state
     consensus: LockedConsensusState<TYPES>,
+    /// Persisted data, for requests consensus already garbage collected
+    storage: Arc<RwLock<I::Storage>>,
     /// Quorum membership for checking if requesters have state
     quorum: Arc<TYPES::Membership>,
     /// This replicas public key
@@ -39,29 +62,44 @@ pub struct NetworkResponseState<TYPES: NodeType> {
     private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
     /// The node's id
     id: u64,
+    /// How many more requests we serve each requester
+    quotas: RequestQuotas<TYPES::SignatureKey>,
+    /// Every VID share of the payloads of recent views, by view, once calculated
+    vid_dispersals: BTreeMap<TYPES::View, Arc<OnceCell<Vec<VidDisperseShare<TYPES>>>>>,
+    /// Lock for a decided upgrade, which determines how requests are signed
+    upgrade_lock: UpgradeLock<TYPES, V>,
 }
 
-impl<TYPES: NodeType> NetworkResponseState<TYPES> {
-    /// Create the network request state with the info it needs
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> NetworkResponseState<TYPES, I, V> {
+    /// Create the network request state with the info it needs, serving each requester
+    /// `request_quota`, or any number of requests if `None`
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         consensus: LockedConsensusState<TYPES>,
+        storage: Arc<RwLock<I::Storage>>,
         quorum: Arc<TYPES::Membership>,
         pub_key: TYPES::SignatureKey,
         private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
         id: u64,
+        request_quota: Option<RateLimit>,
+        upgrade_lock: UpgradeLock<TYPES, V>,
     ) -> Self {
         Self {
             consensus,
+            storage,
             quorum,
             pub_key,
             private_key,
             id,
+            quotas: RequestQuotas::new(request_quota),
+            vid_dispersals: BTreeMap::new(),
+            upgrade_lock,
         }
     }
 
     /// Process request events or loop until a `HotShotEvent::Shutdown` is received.
     async fn run_response_loop(
-        self,
+        mut self,
         mut receiver: Receiver<Arc<HotShotEvent<TYPES>>>,
         event_sender: Sender<Arc<HotShotEvent<TYPES>>>,
     ) {
@@ -71,13 +109,15 @@ impl<TYPES: NodeType> NetworkResponseState<TYPES> {
                     // break loop when false, this means shutdown received
                     match event.as_ref() {
                         HotShotEvent::VidRequestRecv(request, sender) => {
-                            // Verify request is valid
-                            if !self.valid_sender(sender, self.consensus.read().await.cur_epoch())
-                                || !valid_signature::<TYPES>(request, sender)
-                            {
+                            if !self.check_request(request, sender, &event_sender).await {
                                 continue;
                             }
-                            if let Some(proposal) =
+                            if self.is_pruned(request.view).await {
+                                // We will never have this share again, so say so rather than
+                                // leaving the requester to time out.
+                                self.deny(request, sender, DenialReason::Pruned, &event_sender)
+                                    .await;
+                            } else if let Some(proposal) =
                                 self.get_or_calc_vid_share(request.view, sender).await
                             {
                                 broadcast_event(
@@ -92,24 +132,67 @@ impl<TYPES: NodeType> NetworkResponseState<TYPES> {
                                 .await;
                             }
                         }
+                        HotShotEvent::LeavesRequestRecv(request, sender) => {
+                            if !self.check_request(request, sender, &event_sender).await {
+                                continue;
+                            }
+                            if let Some(decided) = self.get_decided_leaves(&request.request).await {
+                                broadcast_event(
+                                    HotShotEvent::LeavesResponseSend(
+                                        self.pub_key.clone(),
+                                        sender.clone(),
+                                        decided,
+                                    )
+                                    .into(),
+                                    &event_sender,
+                                )
+                                .await;
+                            }
+                        }
+                        HotShotEvent::VidSharesRequestRecv(request, sender) => {
+                            if !self.check_request(request, sender, &event_sender).await {
+                                continue;
+                            }
+                            let RequestKind::VidShares(view) = request.request else {
+                                continue;
+                            };
+                            self.send_vid_shares(view, sender, &event_sender).await;
+                        }
+                        HotShotEvent::DaProposalRequestRecv(request, sender) => {
+                            if !self.check_request(request, sender, &event_sender).await {
+                                continue;
+                            }
+                            // We do not serve DA proposals, but save the requester waiting on us
+                            // for one we no longer have
+                            if self.is_pruned(request.view).await {
+                                self.deny(request, sender, DenialReason::Pruned, &event_sender)
+                                    .await;
+                            }
+                        }
                         HotShotEvent::QuorumProposalRequestRecv(req, signature) => {
-                            // Make sure that this request came from who we think it did
+                            // Make sure that this request came from who we think it did. The
+                            // network task reports the peer that sent a bad signature.
                             if !req.key.validate(signature, req.commit().as_ref()) {
                                 tracing::warn!("Invalid signature key on proposal request.");
-                                return;
+                                continue;
                             }
 
-                            if let Some(quorum_proposal) = self
-                                .consensus
-                                .read()
-                                .await
-                                .last_proposals()
-                                .get(&req.view_number)
+                            if self.is_pruned(req.view_number).await {
+                                self.deny_kind(
+                                    "Proposal",
+                                    req.view_number,
+                                    &req.key,
+                                    DenialReason::Pruned,
+                                    &event_sender,
+                                )
+                                .await;
+                            } else if let Some(quorum_proposal) =
+                                self.get_quorum_proposal(req.view_number).await
                             {
                                 broadcast_event(
                                     HotShotEvent::QuorumProposalResponseSend(
                                         req.key.clone(),
-                                        quorum_proposal.clone(),
+                                        quorum_proposal,
                                     )
                                     .into(),
                                     &event_sender,
@@ -130,7 +213,177 @@ impl<TYPES: NodeType> NetworkResponseState<TYPES> {
         }
     }
 
-    /// Get the VID share from consensus storage, or calculate it from the payload for
+    /// Whether we serve `request`: it must be signed by `sender` for a recent view, and `sender`
+    /// must have stake and be within its quota. Otherwise we tell `sender` why we deny the
+    /// request, once per stretch of requests over its quota.
+    ///
+    /// Requests with an invalid signature are dropped without a word, since `sender` need not
+    /// have made them. The network task reports the peer that did. Requests signed for a view
+    /// long past are denied before they count against the quota of `sender`, since anyone could
+    /// replay them.
+    async fn check_request(
+        &mut self,
+        request: &DataRequest<TYPES>,
+        sender: &TYPES::SignatureKey,
+        event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    ) -> bool {
+        if !valid_signature(request, sender, &self.upgrade_lock).await {
+            tracing::warn!("Invalid signature on {} request.", request.request.name());
+            return false;
+        }
+
+        let consensus_reader = self.consensus.read().await;
+        let (cur_view, cur_epoch) = (consensus_reader.cur_view(), consensus_reader.cur_epoch());
+        drop(consensus_reader);
+
+        let reason = if request.view + MAX_REQUEST_AGE < cur_view {
+            // The request may be a replay, but the requester may also just be behind, so we tell
+            // it to sign the request again for its current view
+            DenialReason::Expired
+        } else if !self.valid_sender(sender, cur_epoch) {
+            DenialReason::NoStake
+        } else {
+            match self.quotas.check(sender, Instant::now()) {
+                RateLimitOutcome::Allowed => return true,
+                RateLimitOutcome::NewlyLimited => DenialReason::RateLimited,
+                // We already told the requester it is over its quota
+                RateLimitOutcome::Limited => {
+                    self.count_denied(request.request.name(), DenialReason::RateLimited)
+                        .await;
+                    return false;
+                }
+            }
+        };
+
+        self.deny(request, sender, reason, event_sender).await;
+        false
+    }
+
+    /// Tell `sender` we deny its `request` for `reason`
+    async fn deny(
+        &self,
+        request: &DataRequest<TYPES>,
+        sender: &TYPES::SignatureKey,
+        reason: DenialReason,
+        event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    ) {
+        self.deny_kind(
+            request.request.name(),
+            request.view,
+            sender,
+            reason,
+            event_sender,
+        )
+        .await;
+    }
+
+    /// Tell `sender` we deny its request of kind `kind` for the data of `view` for `reason`
+    async fn deny_kind(
+        &self,
+        kind: &'static str,
+        view: TYPES::View,
+        sender: &TYPES::SignatureKey,
+        reason: DenialReason,
+        event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    ) {
+        tracing::debug!(
+            "Denying {kind} request for view {} from {sender}; reason = {reason:?}",
+            *view
+        );
+        self.count_denied(kind, reason).await;
+        broadcast_event(
+            HotShotEvent::RequestDeniedSend(self.pub_key.clone(), sender.clone(), view, reason)
+                .into(),
+            event_sender,
+        )
+        .await;
+    }
+
+    /// Count a request of kind `kind` we deny for `reason`
+    async fn count_denied(&self, kind: &'static str, reason: DenialReason) {
+        self.consensus
+            .read()
+            .await
+            .metrics
+            .denied_data_requests
+            .create(vec![kind.to_string(), format!("{reason:?}")])
+            .add(1);
+    }
+
+    /// Whether the data of `view` was pruned from storage, and so can no longer be served
+    async fn is_pruned(&self, view: TYPES::View) -> bool {
+        view < self.storage.read().await.retained_from().await
+    }
+
+    /// Get the quorum proposal for `view` from consensus, falling back to storage once consensus
+    /// has garbage collected it
+    async fn get_quorum_proposal(
+        &self,
+        view: TYPES::View,
+    ) -> Option<Proposal<TYPES, QuorumProposal2<TYPES>>> {
+        if let Some(proposal) = self.consensus.read().await.last_proposals().get(&view) {
+            return Some(proposal.clone());
+        }
+
+        match self.storage.read().await.load_quorum_proposal(view).await {
+            Ok(proposal) => proposal,
+            Err(e) => {
+                tracing::warn!("Failed to load quorum proposal from storage; error = {e:#}");
+                None
+            }
+        }
+    }
+
+    /// Get the newest decided leaves `request` asks for from storage, oldest first.
+    ///
+    /// Storage finds the leaves by view or height, so we serve any decided leaves we still keep,
+    /// however far back they are. We only send the newest leaves that form a chain, every leaf the
+    /// parent of the next, as the requester checks.
+    #[instrument(skip_all, target = "NetworkResponseState", fields(id = self.id))]
+    async fn get_decided_leaves(
+        &self,
+        request: &RequestKind<TYPES>,
+    ) -> Option<DecidedLeaves<TYPES>> {
+        let storage = self.storage.read().await;
+        let leaves = match *request {
+            RequestKind::Leaves(from, to) => {
+                storage
+                    .load_decided_leaves(from, to, MAX_LEAVES_PER_RESPONSE)
+                    .await
+            }
+            RequestKind::LeavesByHeight(from, to) => {
+                storage
+                    .load_decided_leaves_by_height(from, to, MAX_LEAVES_PER_RESPONSE)
+                    .await
+            }
+            RequestKind::Vid(..)
+            | RequestKind::DaProposal(_)
+            | RequestKind::Proposal(_)
+            | RequestKind::VidShares(_) => return None,
+        };
+        drop(storage);
+        let mut leaves = match leaves {
+            Ok(leaves) => leaves,
+            Err(e) => {
+                tracing::warn!("Failed to load decided leaves from storage; error = {e:#}");
+                return None;
+            }
+        };
+
+        // Leave out everything before a leaf we are missing
+        let chain_start = leaves
+            .windows(2)
+            .rposition(|pair| pair[1].parent_commitment() != pair[0].commit())
+            .map_or(0, |gap| gap + 1);
+        let leaves = leaves.split_off(chain_start);
+
+        if leaves.is_empty() {
+            return None;
+        }
+        Some(DecidedLeaves { leaves })
+    }
+
+    /// Get the VID share from consensus, then from storage, or calculate it from the payload for
     /// the view, if we have the payload.  Stores all the shares calculated from the payload
     /// if the calculation was done
     #[instrument(skip_all, target = "NetworkResponseState", fields(id = self.id))]
@@ -149,6 +402,12 @@ impl<TYPES: NodeType> NetworkResponseState<TYPES> {
         let cur_epoch = consensus_reader.cur_epoch();
         drop(consensus_reader);
 
+        match self.storage.read().await.load_vid_share(view, key).await {
+            Ok(Some(share)) => return Some(share),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to load VID share from storage; error = {e:#}"),
+        }
+
         if Consensus::calculate_and_update_vid(
             OuterConsensus::new(Arc::clone(&self.consensus)),
             view,
@@ -180,18 +439,106 @@ impl<TYPES: NodeType> NetworkResponseState<TYPES> {
             .cloned();
     }
 
+    /// Send `sender` enough of the VID shares of `view` to recover its payload, if we have them.
+    ///
+    /// With the payload, as a DA member has it, we calculate every share, and keep them for the
+    /// requests of the other nodes recovering the payload. The calculation runs in a task of its
+    /// own, so it does not hold up our other responses. Without the payload, we only have our own
+    /// share.
+    #[instrument(skip_all, target = "NetworkResponseState", fields(id = self.id))]
+    async fn send_vid_shares(
+        &mut self,
+        view: TYPES::View,
+        sender: &TYPES::SignatureKey,
+        event_sender: &Sender<Arc<HotShotEvent<TYPES>>>,
+    ) {
+        let consensus_reader = self.consensus.read().await;
+        let cur_epoch = consensus_reader.cur_epoch();
+        let payload = consensus_reader.saved_payloads().get(&view).cloned();
+        let own_share = consensus_reader
+            .vid_shares()
+            .get(&view)
+            .and_then(|shares| shares.get(&self.pub_key))
+            .map(|share| share.data.clone());
+        drop(consensus_reader);
+
+        if let Some(payload) = payload {
+            let dispersal = Arc::clone(
+                self.vid_dispersals
+                    .entry(view)
+                    .or_insert_with(|| Arc::new(OnceCell::new())),
+            );
+            while self.vid_dispersals.len() > VID_DISPERSAL_CACHE_SIZE {
+                self.vid_dispersals.pop_first();
+            }
+
+            let threshold =
+                vid_recovery_threshold(self.quorum.total_nodes(cur_epoch)).unwrap_or_default();
+            let quorum = Arc::clone(&self.quorum);
+            let pub_key = self.pub_key.clone();
+            let sender = sender.clone();
+            let event_sender = event_sender.clone();
+            spawn(async move {
+                let shares = dispersal
+                    .get_or_init(|| async {
+                        let vid_disperse = VidDisperse::calculate_vid_disperse(
+                            payload, &quorum, view, cur_epoch, None,
+                        )
+                        .await;
+                        VidDisperseShare::from_vid_disperse(vid_disperse)
+                    })
+                    .await
+                    .iter()
+                    .take(threshold)
+                    .cloned()
+                    .collect();
+                broadcast_event(
+                    HotShotEvent::VidSharesResponseSend(pub_key, sender, shares).into(),
+                    &event_sender,
+                )
+                .await;
+            });
+            return;
+        }
+
+        let mut shares: Vec<_> = own_share.into_iter().collect();
+        if shares.is_empty() {
+            match self
+                .storage
+                .read()
+                .await
+                .load_vid_share(view, &self.pub_key)
+                .await
+            {
+                Ok(Some(share)) => shares.push(share.data),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to load VID share from storage; error = {e:#}"),
+            }
+        }
+
+        if !shares.is_empty() {
+            broadcast_event(
+                HotShotEvent::VidSharesResponseSend(self.pub_key.clone(), sender.clone(), shares)
+                    .into(),
+                event_sender,
+            )
+            .await;
+        }
+    }
+
     /// Makes sure the sender is allowed to send a request in the given epoch.
     fn valid_sender(&self, sender: &TYPES::SignatureKey, epoch: TYPES::Epoch) -> bool {
         self.quorum.has_stake(sender, epoch)
     }
 }
 
-/// Check the signature
-fn valid_signature<TYPES: NodeType>(
+/// Whether `req` is signed by `sender`
+pub(crate) async fn valid_signature<TYPES: NodeType, V: Versions>(
     req: &DataRequest<TYPES>,
     sender: &TYPES::SignatureKey,
+    upgrade_lock: &UpgradeLock<TYPES, V>,
 ) -> bool {
-    let Ok(data) = bincode::serialize(&req.request) else {
+    let Ok(data) = DataRequest::signed_data(&req.request, req.view, upgrade_lock).await else {
         return false;
     };
     sender.validate(&req.signature, &Sha256::digest(data))
@@ -200,8 +547,8 @@ fn valid_signature<TYPES: NodeType>(
 /// Spawn the network response task to handle incoming request for data
 /// from other nodes.  It will shutdown when it gets `HotshotEvent::Shutdown`
 /// on the `event_stream` arg.
-pub fn run_response_task<TYPES: NodeType>(
-    task_state: NetworkResponseState<TYPES>,
+pub fn run_response_task<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions>(
+    task_state: NetworkResponseState<TYPES, I, V>,
     event_stream: Receiver<Arc<HotShotEvent<TYPES>>>,
     sender: Sender<Arc<HotShotEvent<TYPES>>>,
 ) -> JoinHandle<()> {
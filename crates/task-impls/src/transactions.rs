@@ -5,23 +5,26 @@
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
 use std::{
+    collections::HashSet,
+    ops::Bound::{Excluded, Unbounded},
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use async_broadcast::{Receiver, Sender};
 use async_trait::async_trait;
+use committable::Commitment;
 use futures::{future::join_all, stream::FuturesUnordered, StreamExt};
 use hotshot_builder_api::v0_1::block_info::AvailableBlockInfo;
 use hotshot_task::task::TaskState;
 use hotshot_types::{
-    consensus::OuterConsensus,
+    consensus::{Consensus, OuterConsensus},
     data::{null_block, PackedBundle},
     event::{Event, EventType},
     message::UpgradeLock,
     traits::{
         auction_results_provider::AuctionResultsProvider,
-        block_contents::{precompute_vid_commitment, BuilderFee, EncodeBytes},
+        block_contents::{precompute_vid_commitment, BlockHeader, BuilderFee, EncodeBytes},
         election::Membership,
         node_implementation::{ConsensusTime, HasUrls, NodeImplementation, NodeType, Versions},
         signature_key::{BuilderSignatureKey, SignatureKey},
@@ -40,9 +43,13 @@ use vec1::Vec1;
 use crate::{
     builder::{
         v0_1::BuilderClient as BuilderClientBase, v0_99::BuilderClient as BuilderClientMarketplace,
+        BuilderClientError,
     },
+    builder_reputation::{BuilderOutcome, BuilderReputation},
     events::{HotShotEvent, HotShotTaskCompleted},
     helpers::broadcast_event,
+    local_builder,
+    mempool::Mempool,
 };
 
 // Parameters for builder querying algorithm
@@ -99,6 +106,9 @@ pub struct TransactionTaskState<TYPES: NodeType, I: NodeImplementation<TYPES>, V
     /// Builder 0.1 API clients
     pub builder_clients: Vec<BuilderClientBase<TYPES>>,
 
+    /// How well each of the builders in `builder_clients` has served us
+    pub builder_reputation: BuilderReputation,
+
     /// This Nodes Public Key
     pub public_key: TYPES::SignatureKey,
 
@@ -119,6 +129,9 @@ pub struct TransactionTaskState<TYPES: NodeType, I: NodeImplementation<TYPES>, V
 
     /// fallback builder url
     pub fallback_builder_url: Url,
+
+    /// The transactions we have seen but not yet seen decided, if we keep a mempool
+    pub mempool: Option<Mempool<TYPES>>,
 }
 
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTaskState<TYPES, I, V> {
@@ -160,7 +173,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
             }
         };
 
-        // Request a block from the builder unless we are between versions.
+        // Request a block from the builder unless we are between versions, and build one
+        // ourselves if none of them gives us one in time.
         let block = {
             if self
                 .upgrade_lock
@@ -171,8 +185,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                 .is_some_and(|cert| cert.upgrading_in(block_view))
             {
                 None
+            } else if let Some(block) = self.wait_for_block(block_view).await {
+                Some(block)
             } else {
-                self.wait_for_block(block_view).await
+                self.local_block(block_view, version).await
             }
         };
 
@@ -349,6 +365,52 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
         ))
     }
 
+    /// Build a block from the transactions in our mempool, if we keep one and may build blocks
+    /// from it, and it holds any transactions.
+    ///
+    /// Transactions stay in the mempool until decided, so we leave out those of the blocks
+    /// proposed since the last decide.
+    async fn local_block(
+        &self,
+        block_view: TYPES::View,
+        version: Version,
+    ) -> Option<BuilderResponse<TYPES>> {
+        let mempool = self.mempool.as_ref()?;
+        let max_bytes = mempool.config().fallback_block_bytes?;
+
+        let consensus_reader = self.consensus.read().await;
+        let undecided = undecided_transactions(&consensus_reader);
+        let validated_state = consensus_reader.decided_state();
+        drop(consensus_reader);
+
+        let transactions = mempool.select(max_bytes, &undecided);
+        if transactions.is_empty() {
+            return None;
+        }
+
+        match local_builder::build_block::<TYPES, V>(
+            transactions,
+            &validated_state,
+            &self.instance_state,
+            self.membership.total_nodes(self.cur_epoch),
+            version,
+            block_view,
+        )
+        .await
+        {
+            Ok(block) => {
+                tracing::info!(
+                    "No builder gave us a block for view {block_view:?}, proposing one we built"
+                );
+                Some(block)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to build a block for view {block_view:?}: {e}");
+                None
+            }
+        }
+    }
+
     /// Produce a null block
     pub fn null_block(
         &self,
@@ -457,6 +519,15 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
     ) -> Result<()> {
         match event.as_ref() {
             HotShotEvent::TransactionsRecv(transactions) => {
+                if let Some(mempool) = &mut self.mempool {
+                    let now = Instant::now();
+                    for transaction in transactions {
+                        if let Err(rejection) = mempool.insert(transaction.clone(), now) {
+                            tracing::trace!("Not adding transaction to the mempool: {rejection:?}");
+                        }
+                    }
+                }
+
                 broadcast_event(
                     Event {
                         view_number: self.cur_view,
@@ -480,11 +551,25 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                     )
                 );
                 self.cur_view = view;
+                if let Some(mempool) = &mut self.mempool {
+                    mempool.expire(Instant::now());
+                }
                 if self.membership.leader(view, self.cur_epoch)? == self.public_key {
                     self.handle_view_change(&event_stream, view).await;
                     return Ok(());
                 }
             }
+            HotShotEvent::LeavesDecided(leaves) => {
+                if let Some(mempool) = &mut self.mempool {
+                    for leaf in leaves {
+                        if let Some(payload) = leaf.block_payload() {
+                            mempool.remove_decided(
+                                payload.transaction_commitments(leaf.block_header().metadata()),
+                            );
+                        }
+                    }
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -552,7 +637,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
     }
 
     #[instrument(skip_all, fields(id = self.id, cur_view = *self.cur_view, block_view = *block_view), name = "wait_for_block", level = "error")]
-    async fn wait_for_block(&self, block_view: TYPES::View) -> Option<BuilderResponse<TYPES>> {
+    async fn wait_for_block(&mut self, block_view: TYPES::View) -> Option<BuilderResponse<TYPES>> {
         let task_start_time = Instant::now();
 
         // Find commitment to the block we want to build upon
@@ -578,6 +663,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
             }
         };
 
+        let request_start = Instant::now();
         while task_start_time.elapsed() < self.builder_timeout {
             match timeout(
                 self.builder_timeout
@@ -588,6 +674,12 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
             {
                 // We got a block
                 Ok(Ok(block)) => {
+                    self.consensus
+                        .read()
+                        .await
+                        .metrics
+                        .builder_response_duration
+                        .add_point(request_start.elapsed().as_secs_f64());
                     return Some(block);
                 }
 
@@ -611,37 +703,59 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
         None
     }
 
+    /// Record what happened when we asked builder `builder_idx` for a block, in its reputation
+    /// and in the metrics
+    async fn record_builder_outcome(&mut self, builder_idx: usize, outcome: BuilderOutcome) {
+        self.builder_reputation
+            .record(builder_idx, outcome, Instant::now());
+
+        let Some(url) = self.builder_reputation.url(builder_idx) else {
+            return;
+        };
+        let consensus_reader = self.consensus.read().await;
+        if let BuilderOutcome::Responded { latency, .. } = outcome {
+            consensus_reader
+                .metrics
+                .builder_latency
+                .create(vec![url.to_string()])
+                .add_point(latency.as_secs_f64());
+        } else if let Some(reason) = outcome.failure_reason() {
+            consensus_reader
+                .metrics
+                .builder_failures
+                .create(vec![url.to_string(), reason.to_string()])
+                .add(1);
+        }
+    }
+
     /// Query the builders for available blocks. Queries only fraction of the builders
-    /// based on the response time.
+    /// based on the response time, leaving out those backing off after failures.
     async fn get_available_blocks(
-        &self,
+        &mut self,
         parent_comm: VidCommitment,
         view_number: TYPES::View,
         parent_comm_sig: &<<TYPES as NodeType>::SignatureKey as SignatureKey>::PureAssembledSignatureType,
     ) -> Vec<(AvailableBlockInfo<TYPES>, usize)> {
-        let tasks = self
-            .builder_clients
+        let builders = self.builder_reputation.ordered(Instant::now());
+        let public_key = &self.public_key;
+        let builder_clients = &self.builder_clients;
+        let query_start = Instant::now();
+        let tasks = builders
             .iter()
-            .enumerate()
-            .map(|(builder_idx, client)| async move {
-                client
+            .map(|&builder_idx| async move {
+                let result = builder_clients[builder_idx]
                     .available_blocks(
                         parent_comm,
                         view_number.u64(),
-                        self.public_key.clone(),
+                        public_key.clone(),
                         parent_comm_sig,
                     )
-                    .await
-                    .map(move |blocks| {
-                        blocks
-                            .into_iter()
-                            .map(move |block_info| (block_info, builder_idx))
-                    })
+                    .await;
+                (builder_idx, query_start.elapsed(), result)
             })
             .collect::<FuturesUnordered<_>>();
-        let mut results = Vec::with_capacity(self.builder_clients.len());
-        let query_start = Instant::now();
-        let threshold = (self.builder_clients.len() * BUILDER_MAIN_BATCH_THRESHOLD_DIVIDEND)
+        let mut results = Vec::with_capacity(builders.len());
+        let threshold = (builders.len() * BUILDER_MAIN_BATCH_THRESHOLD_DIVIDEND)
             .div_ceil(BUILDER_MAIN_BATCH_THRESHOLD_DIVISOR);
         let mut tasks = tasks.take(threshold);
         while let Some(result) = tasks.next().await {
@@ -661,29 +775,69 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
         while let Some(result) = tasks.next().await {
             results.push(result);
         }
-        results
-            .into_iter()
-            .filter_map(|result| match result {
-                Ok(value) => Some(value),
+        drop(tasks);
+
+        let mut available_blocks = vec![];
+        let mut responded = vec![false; self.builder_clients.len()];
+        for (builder_idx, latency, result) in results {
+            responded[builder_idx] = true;
+            match result {
+                Ok(blocks) => {
+                    let offered_fee = blocks
+                        .iter()
+                        .map(|block_info| block_info.offered_fee)
+                        .max()
+                        .unwrap_or_default();
+                    self.record_builder_outcome(
+                        builder_idx,
+                        BuilderOutcome::Responded {
+                            latency,
+                            offered_fee,
+                        },
+                    )
+                    .await;
+                    available_blocks.extend(
+                        blocks
+                            .into_iter()
+                            .map(|block_info| (block_info, builder_idx)),
+                    );
+                }
+                // The builder may just not have built on this parent yet
+                Err(BuilderClientError::BlockNotFound) => {
+                    tracing::debug!("Builder {builder_idx} has no blocks available");
+                }
                 Err(err) => {
-                    tracing::warn!(%err,"Error getting available blocks");
-                    None
+                    tracing::warn!(%err, "Error getting available blocks");
+                    self.record_builder_outcome(builder_idx, BuilderOutcome::QueryFailed)
+                        .await;
                 }
-            })
-            .flatten()
-            .collect::<Vec<_>>()
+            }
+        }
+        // Builders we stopped waiting for because others answered quickly are merely slower than
+        // those, and only count as timed out if they missed the cutoff of the first batch.
+        if query_start.elapsed() >= BUILDER_MAIN_BATCH_CUTOFF {
+            for builder_idx in builders {
+                if !responded[builder_idx] {
+                    self.record_builder_outcome(builder_idx, BuilderOutcome::Timeout)
+                        .await;
+                }
+            }
+        }
+
+        available_blocks
     }
 
     /// Get a block from builder.
     /// Queries the sufficiently fast builders for available blocks and chooses the one with the
-    /// best fee/byte ratio, re-trying with the next best one in case of failure.
+    /// best fee/byte ratio, re-trying with the next best one in case of failure. Blocks with the
+    /// same ratio are tried from the builder with the best reputation first.
     ///
     /// # Errors
     /// If none of the builder reports any available blocks or claiming block fails for all of the
     /// builders.
     #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "block_from_builder", level = "error")]
     async fn block_from_builder(
-        &self,
+        &mut self,
         parent_comm: VidCommitment,
         view_number: TYPES::View,
         parent_comm_sig: &<<TYPES as NodeType>::SignatureKey as SignatureKey>::PureAssembledSignatureType,
@@ -692,7 +846,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
             .get_available_blocks(parent_comm, view_number, parent_comm_sig)
             .await;
 
-        available_blocks.sort_by(|(l, _), (r, _)| {
+        let now = Instant::now();
+        available_blocks.sort_by(|(l, l_idx), (r, r_idx)| {
             // We want the block with the highest fee per byte of data we're going to have to
             // process, thus our comparison function is:
             //      (l.offered_fee / l.block_size) < (r.offered_fee / r.block_size)
@@ -702,6 +857,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
             // We cast up to u128 to avoid overflow.
             (u128::from(l.offered_fee) * u128::from(r.block_size))
                 .cmp(&(u128::from(r.offered_fee) * u128::from(l.block_size)))
+                .then_with(|| self.builder_reputation.compare(*l_idx, *r_idx, now))
         });
 
         if available_blocks.is_empty() {
@@ -724,6 +880,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                 &block_info.block_hash,
             ) {
                 tracing::warn!("Failed to verify available block info response message signature");
+                self.record_builder_outcome(builder_idx, BuilderOutcome::InvalidSignature)
+                    .await;
                 continue;
             }
 
@@ -759,6 +917,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                     Ok(block_data) => block_data,
                     Err(err) => {
                         tracing::warn!(%err, "Error claiming block data");
+                        self.record_builder_outcome(builder_idx, BuilderOutcome::ClaimFailed)
+                            .await;
                         continue;
                     }
                 };
@@ -767,6 +927,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                     Ok(block_data) => block_data,
                     Err(err) => {
                         tracing::warn!(%err, "Error claiming header input");
+                        self.record_builder_outcome(builder_idx, BuilderOutcome::ClaimFailed)
+                            .await;
                         continue;
                     }
                 };
@@ -776,6 +938,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                     tracing::warn!(
                         "Failed to verify available block data response message signature"
                     );
+                    self.record_builder_outcome(builder_idx, BuilderOutcome::InvalidSignature)
+                        .await;
                     continue;
                 }
 
@@ -784,6 +948,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                     tracing::warn!(
                     "Failed to verify available block header input data response message signature"
                 );
+                    self.record_builder_outcome(builder_idx, BuilderOutcome::InvalidSignature)
+                        .await;
                     continue;
                 }
 
@@ -801,6 +967,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
                 }
             };
 
+            self.record_builder_outcome(builder_idx, BuilderOutcome::Claimed)
+                .await;
+
             return Ok(response);
         }
 
@@ -808,6 +977,32 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TransactionTask
     }
 }
 
+/// The commitments of the transactions in the blocks proposed since the last decide, as far as
+/// we have their payloads
+fn undecided_transactions<TYPES: NodeType>(
+    consensus: &Consensus<TYPES>,
+) -> HashSet<Commitment<TYPES::Transaction>> {
+    let mut commitments = HashSet::new();
+    for (view, view_data) in consensus
+        .validated_state_map()
+        .range((Excluded(consensus.last_decided_view()), Unbounded))
+    {
+        let ViewInner::Leaf { leaf, .. } = &view_data.view_inner else {
+            continue;
+        };
+        let (Some(leaf), Some(encoded)) = (
+            consensus.saved_leaves().get(leaf),
+            consensus.saved_payloads().get(view),
+        ) else {
+            continue;
+        };
+        let metadata = leaf.block_header().metadata();
+        let payload = TYPES::BlockPayload::from_bytes(encoded, metadata);
+        commitments.extend(payload.transaction_commitments(metadata));
+    }
+    commitments
+}
+
 #[async_trait]
 /// task state implementation for Transactions Task
 impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TaskState
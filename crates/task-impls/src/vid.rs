@@ -4,7 +4,7 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, sync::Arc, time::Instant};
 
 use async_broadcast::{Receiver, Sender};
 use async_trait::async_trait;
@@ -76,6 +76,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> VidTaskState<TYPES, I> {
                 let payload =
                     <TYPES as NodeType>::BlockPayload::from_bytes(encoded_transactions, metadata);
                 let builder_commitment = payload.builder_commitment(metadata);
+                let disperse_start = Instant::now();
                 let vid_disperse = VidDisperse::calculate_vid_disperse(
                     Arc::clone(encoded_transactions),
                     &Arc::clone(&self.membership),
@@ -92,6 +93,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> VidTaskState<TYPES, I> {
                         consensus_writer.update_vid_shares(*view_number, disperse);
                     }
                 }
+                consensus_writer
+                    .metrics
+                    .vid_disperse_duration
+                    .add_point(disperse_start.elapsed().as_secs_f64());
                 drop(consensus_writer);
 
                 // send the commitment and metadata to consensus for block building
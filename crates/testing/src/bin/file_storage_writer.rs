@@ -0,0 +1,57 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Appends quorum proposals and actions to the file storage in the given directory until it is
+//! killed.
+//!
+//! Spawned by the `test_file_storage_killed_writer` test, which kills it mid-write and checks that
+//! the storage reopens to a consistent state.
+
+use futures::StreamExt;
+use hotshot::traits::implementations::{FileStorage, FileStorageConfig};
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes, TestVersions};
+use hotshot_testing::{helpers::build_system_handle, view_generator::TestViewGenerator};
+use hotshot_types::{
+    data::{EpochNumber, ViewNumber},
+    event::HotShotAction,
+    traits::{node_implementation::ConsensusTime, storage::Storage},
+};
+
+#[tokio::main]
+async fn main() {
+    let dir = std::env::args()
+        .nth(1)
+        .expect("Usage: file-storage-writer <storage directory>");
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(1)
+        .await
+        .0;
+    let mut generator = TestViewGenerator::generate((*handle.hotshot.memberships).clone());
+    let proposal = generator
+        .next()
+        .await
+        .expect("Failed to generate a proposal")
+        .quorum_proposal;
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir))
+        .expect("Failed to open the storage");
+    for view in 1.. {
+        let mut proposal = proposal.clone();
+        proposal.data.view_number = ViewNumber::new(view);
+        storage
+            .append_proposal2(&proposal)
+            .await
+            .expect("Failed to append a proposal");
+        storage
+            .record_action(
+                ViewNumber::new(view),
+                EpochNumber::new(1),
+                HotShotAction::Propose,
+            )
+            .await
+            .expect("Failed to record an action");
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 use anyhow::Context;
 use async_lock::RwLock;
 use async_trait::async_trait;
+use committable::Commitment;
 use hotshot::{
     tasks::EventTransformerState,
     types::{SignatureKey, SystemContextHandle},
@@ -21,8 +22,11 @@ use hotshot_types::{
     consensus::{Consensus, OuterConsensus},
     data::QuorumProposal2,
     message::{Proposal, UpgradeLock},
-    simple_vote::QuorumVote2,
-    traits::node_implementation::{ConsensusTime, NodeImplementation, NodeType, Versions},
+    simple_vote::{QuorumData2, QuorumVote2},
+    traits::{
+        network::{DataRequest, RequestKind},
+        node_implementation::{ConsensusTime, NodeImplementation, NodeType, Versions},
+    },
 };
 
 #[derive(Debug)]
@@ -102,6 +106,51 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> EventTransforme
     }
 }
 
+#[derive(Debug)]
+/// An `EventHandlerState` that follows every `QuorumVoteSend` with a correctly signed vote for a
+/// different leaf in the same view
+pub struct EquivocatingVoter;
+
+#[async_trait]
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> EventTransformerState<TYPES, I, V>
+    for EquivocatingVoter
+{
+    async fn recv_handler(&mut self, event: &HotShotEvent<TYPES>) -> Vec<HotShotEvent<TYPES>> {
+        vec![event.clone()]
+    }
+
+    async fn send_handler(
+        &mut self,
+        event: &HotShotEvent<TYPES>,
+        public_key: &TYPES::SignatureKey,
+        private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+        upgrade_lock: &UpgradeLock<TYPES, V>,
+        _consensus: Arc<RwLock<Consensus<TYPES>>>,
+    ) -> Vec<HotShotEvent<TYPES>> {
+        if let HotShotEvent::QuorumVoteSend(vote) = event {
+            let mut leaf_commit: [u8; 32] = vote.data.leaf_commit.into();
+            leaf_commit[0] ^= 1;
+            let conflicting_vote = QuorumVote2::<TYPES>::create_signed_vote(
+                QuorumData2 {
+                    leaf_commit: Commitment::from_raw(leaf_commit),
+                },
+                vote.view_number,
+                public_key,
+                private_key,
+                upgrade_lock,
+            )
+            .await
+            .context("Failed to sign vote")
+            .unwrap();
+            return vec![
+                event.clone(),
+                HotShotEvent::QuorumVoteSend(conflicting_vote),
+            ];
+        }
+        vec![event.clone()]
+    }
+}
+
 #[derive(Debug)]
 /// An `EventHandlerState` that modifies justify_qc on `QuorumProposalSend` to that of a previous view to mock dishonest leader
 pub struct DishonestLeader<TYPES: NodeType> {
@@ -348,6 +397,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + std::fmt::Debug, V: Version
             membership,
             storage: Arc::clone(&handle.storage()),
             consensus: OuterConsensus::new(handle.consensus()),
+            metrics: handle.hotshot.metrics(),
             upgrade_lock: handle.hotshot.upgrade_lock.clone(),
             transmit_tasks: BTreeMap::new(),
         };
@@ -430,3 +480,42 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + std::fmt::Debug, V: Version
         vec![event.clone()]
     }
 }
+
+#[derive(Debug)]
+/// An `EventHandlerState` that asks the leader of every proposal it receives for a VID share,
+/// with a request that is not properly signed
+pub struct BadSignatureRequester;
+
+#[async_trait]
+impl<TYPES: NodeType, I: NodeImplementation<TYPES> + std::fmt::Debug, V: Versions>
+    EventTransformerState<TYPES, I, V> for BadSignatureRequester
+{
+    async fn recv_handler(&mut self, event: &HotShotEvent<TYPES>) -> Vec<HotShotEvent<TYPES>> {
+        vec![event.clone()]
+    }
+
+    async fn send_handler(
+        &mut self,
+        event: &HotShotEvent<TYPES>,
+        public_key: &TYPES::SignatureKey,
+        private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+        _upgrade_lock: &UpgradeLock<TYPES, V>,
+        _consensus: Arc<RwLock<Consensus<TYPES>>>,
+    ) -> Vec<HotShotEvent<TYPES>> {
+        if let HotShotEvent::QuorumProposalRecv(proposal, leader) = event {
+            let view = proposal.data.view_number;
+            let request = DataRequest {
+                request: RequestKind::Vid(view, public_key.clone()),
+                view,
+                // Sign something other than the request
+                signature: TYPES::SignatureKey::sign(private_key, b"not the request")
+                    .expect("Failed to sign"),
+            };
+            return vec![
+                event.clone(),
+                HotShotEvent::VidRequestSend(request, public_key.clone(), leader.clone()),
+            ];
+        }
+        vec![event.clone()]
+    }
+}
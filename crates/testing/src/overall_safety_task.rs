@@ -78,6 +78,9 @@ pub enum OverallSafetyTaskErr<TYPES: NodeType> {
 
     #[error("View timed out")]
     ViewTimeout,
+
+    #[error("Node {0} equivocated but no node reported it")]
+    UnreportedEquivocation(u64),
 }
 
 /// Data availability task state
@@ -172,6 +175,10 @@ impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>, V: Versions> TestTas
                     }
                 }
             }
+            EventType::Equivocation { evidence } => {
+                self.ctx.equivocators.insert(evidence.offender.clone());
+                return Ok(());
+            }
             EventType::ReplicaViewTimeout { view_number } => {
                 let error = Arc::new(HotShotError::<TYPES>::ViewTimedOut {
                     view_number,
@@ -245,6 +252,7 @@ impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>, V: Versions> TestTas
             threshold_calculator: _,
             transaction_threshold: _,
             expected_views_to_fail,
+            expected_equivocators,
         }: OverallSafetyPropertiesDescription<TYPES> = self.properties.clone();
 
         let views_count = self.ctx.failed_views.len() + self.ctx.successful_views.len();
@@ -288,6 +296,16 @@ impl<TYPES: NodeType, I: TestableNodeImplementation<TYPES>, V: Versions> TestTas
             ));
         }
 
+        for node in self.handles.read().await.iter() {
+            if expected_equivocators.contains(&node.node_id)
+                && !self.ctx.equivocators.contains(&node.handle.public_key())
+            {
+                return TestResult::Fail(Box::new(
+                    OverallSafetyTaskErr::<TYPES>::UnreportedEquivocation(node.node_id),
+                ));
+            }
+        }
+
         // We should really be able to include a check like this:
         //
         //        if self.ctx.failed_views.len() < num_failed_rounds_total {
@@ -352,6 +370,7 @@ impl<TYPES: NodeType> Default for RoundCtx<TYPES> {
             round_results: HashMap::default(),
             failed_views: HashSet::default(),
             successful_views: HashSet::default(),
+            equivocators: HashSet::default(),
         }
     }
 }
@@ -369,6 +388,8 @@ pub struct RoundCtx<TYPES: NodeType> {
     pub failed_views: HashSet<TYPES::View>,
     /// successful views
     pub successful_views: HashSet<TYPES::View>,
+    /// keys any node reported for equivocating
+    pub equivocators: HashSet<TYPES::SignatureKey>,
 }
 
 impl<TYPES: NodeType> RoundCtx<TYPES> {
@@ -571,6 +592,8 @@ pub struct OverallSafetyPropertiesDescription<TYPES: NodeType> {
     pub threshold_calculator: Arc<dyn Fn(usize, usize) -> usize + Send + Sync>,
     /// pass in the views that we expect to fail
     pub expected_views_to_fail: HashMap<TYPES::View, bool>,
+    /// the nodes we expect some node to report for equivocating
+    pub expected_equivocators: HashSet<u64>,
 }
 
 impl<TYPES: NodeType> std::fmt::Debug for OverallSafetyPropertiesDescription<TYPES> {
@@ -582,6 +605,7 @@ impl<TYPES: NodeType> std::fmt::Debug for OverallSafetyPropertiesDescription<TYP
             .field("num_failed_rounds_total", &self.num_failed_views)
             .field("transaction_threshold", &self.transaction_threshold)
             .field("expected views to fail", &self.expected_views_to_fail)
+            .field("expected equivocators", &self.expected_equivocators)
             .finish_non_exhaustive()
     }
 }
@@ -597,6 +621,7 @@ impl<TYPES: NodeType> Default for OverallSafetyPropertiesDescription<TYPES> {
             // very strict
             threshold_calculator: Arc::new(|_num_live, num_total| 2 * num_total / 3 + 1),
             expected_views_to_fail: HashMap::new(),
+            expected_equivocators: HashSet::new(),
         }
     }
 }
@@ -282,3 +282,13 @@ where
     });
     Box::new(EventPredicate { check, info })
 }
+
+pub fn leaves_decided<TYPES>() -> Box<EventPredicate<TYPES>>
+where
+    TYPES: NodeType,
+{
+    let info = "LeavesDecided".to_string();
+    let check: EventCallback<TYPES> =
+        Arc::new(move |e: Arc<HotShotEvent<TYPES>>| matches!(e.as_ref(), LeavesDecided(..)));
+    Box::new(EventPredicate { check, info })
+}
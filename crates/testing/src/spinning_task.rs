@@ -5,7 +5,7 @@
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -28,10 +28,11 @@ use hotshot_types::{
     constants::EVENT_CHANNEL_SIZE,
     data::Leaf2,
     event::Event,
-    simple_certificate::{QuorumCertificate, QuorumCertificate2},
+    simple_certificate::QuorumCertificate2,
     traits::{
         network::{AsyncGenerator, ConnectedNetwork},
         node_implementation::{ConsensusTime, NodeImplementation, NodeType, Versions},
+        signature_key::SignatureKey,
     },
     vote::HasViewNumber,
     ValidatorConfig,
@@ -163,6 +164,7 @@ where
                                             None,
                                             Vec::new(),
                                             BTreeMap::new(),
+                                            BTreeMap::new(),
                                         );
                                         // We assign node's public key and stake value rather than read from config file since it's a test
                                         let validator_config =
@@ -234,26 +236,13 @@ where
                                 let marketplace_config =
                                     node.handle.hotshot.marketplace_config.clone();
                                 let read_storage = storage.read().await;
-                                let initializer = HotShotInitializer::<TYPES>::from_reload(
-                                    self.last_decided_leaf.clone(),
+                                let initializer = HotShotInitializer::<TYPES>::from_storage::<V>(
+                                    &*read_storage,
                                     TestInstanceState::new(self.async_delay_config.clone()),
-                                    None,
-                                    read_storage.last_actioned_view().await,
-                                    read_storage.last_actioned_epoch().await,
-                                    read_storage.last_actioned_view().await,
-                                    read_storage.proposals_cloned().await,
-                                    read_storage.high_qc_cloned().await.unwrap_or(
-                                        QuorumCertificate::genesis::<V>(
-                                            &TestValidatedState::default(),
-                                            &TestInstanceState::default(),
-                                        )
-                                        .await
-                                        .to_qc2(),
-                                    ),
-                                    read_storage.decided_upgrade_certificate().await,
-                                    Vec::new(),
-                                    BTreeMap::new(),
-                                );
+                                    config.epoch_height,
+                                )
+                                .await
+                                .unwrap();
                                 // We assign node's public key and stake value rather than read from config file since it's a test
                                 let validator_config = ValidatorConfig::generated_from_seed_indexed(
                                     [0u8; 32],
@@ -316,6 +305,30 @@ where
                                 handle.network.pause();
                             }
                         }
+                        NodeAction::RestrictLinks(reachable) => {
+                            if let Some(handle) = self.handles.write().await.get(idx) {
+                                tracing::error!("Node {} only reaching nodes {:?}", idx, reachable);
+                                // A node always reaches itself
+                                let reachable: HashSet<_> = reachable
+                                    .iter()
+                                    .chain([&idx])
+                                    .map(|&node_id| {
+                                        TYPES::SignatureKey::generated_from_seed_indexed(
+                                            [0u8; 32],
+                                            node_id.try_into().unwrap(),
+                                        )
+                                        .0
+                                    })
+                                    .collect();
+                                handle.network.set_reachable_peers(Some(reachable));
+                            }
+                        }
+                        NodeAction::HealLinks => {
+                            if let Some(handle) = self.handles.write().await.get(idx) {
+                                tracing::error!("Node {} reaching all nodes again", idx);
+                                handle.network.set_reachable_peers(None);
+                            }
+                        }
                     }
                 }
             }
@@ -393,6 +406,10 @@ pub enum NodeAction {
     /// Start a node up again after it's been shutdown for restart.  This
     /// should only be created following a `RestartDown`
     RestartUp,
+    /// Only deliver the messages the node sends to the nodes with these indices
+    RestrictLinks(Vec<usize>),
+    /// Deliver the messages the node sends to every node again
+    HealLinks,
 }
 
 /// denotes a change in node state
@@ -411,3 +428,57 @@ pub struct SpinningTaskDescription {
     /// the changes in node status, time -> changes
     pub node_changes: Vec<(u64, Vec<ChangeNode>)>,
 }
+
+impl SpinningTaskDescription {
+    /// Partition the network into `groups` of node indices at `view`, so each node only reaches
+    /// the nodes in its own group. Nodes that are not in any group are left alone.
+    #[must_use]
+    pub fn with_partition(mut self, view: u64, groups: &[Vec<usize>]) -> Self {
+        let changes = groups
+            .iter()
+            .flat_map(|group| {
+                group.iter().map(|&idx| ChangeNode {
+                    idx,
+                    updown: NodeAction::RestrictLinks(group.clone()),
+                })
+            })
+            .collect();
+        self.node_changes.push((view, changes));
+        self
+    }
+
+    /// Cut the links from node `from` to the nodes in `to` at `view`, out of `num_nodes` nodes.
+    ///
+    /// Only the messages `from` sends are dropped, so the nodes in `to` can still reach it.
+    #[must_use]
+    pub fn with_cut_links(
+        mut self,
+        view: u64,
+        from: usize,
+        to: &[usize],
+        num_nodes: usize,
+    ) -> Self {
+        let reachable = (0..num_nodes).filter(|idx| !to.contains(idx)).collect();
+        self.node_changes.push((
+            view,
+            vec![ChangeNode {
+                idx: from,
+                updown: NodeAction::RestrictLinks(reachable),
+            }],
+        ));
+        self
+    }
+
+    /// Heal every partition and cut link of the first `num_nodes` nodes at `view`
+    #[must_use]
+    pub fn with_heal(mut self, view: u64, num_nodes: usize) -> Self {
+        let changes = (0..num_nodes)
+            .map(|idx| ChangeNode {
+                idx,
+                updown: NodeAction::HealLinks,
+            })
+            .collect();
+        self.node_changes.push((view, changes));
+        self
+    }
+}
@@ -4,7 +4,13 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::{collections::HashMap, num::NonZeroUsize, rc::Rc, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{ensure, Result};
 use hotshot::{
@@ -19,6 +25,8 @@ use hotshot_example_types::{
 };
 use hotshot_types::{
     consensus::ConsensusMetricsValue,
+    mempool_config::MempoolConfig,
+    rate_limit_config::RateLimitConfig,
     traits::node_implementation::{NodeType, Versions},
     HotShotConfig, ValidatorConfig,
 };
@@ -100,6 +108,10 @@ pub struct TestDescription<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Ver
     pub validate_transactions: TransactionValidator,
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+    /// Limits of the mempool of each node, if they keep one
+    pub mempool: Option<MempoolConfig>,
+    /// Whether nodes recover the payloads of decided leaves from the VID shares of others
+    pub recover_payloads: bool,
 }
 
 pub fn nonempty_block_threshold(threshold: (u64, u64)) -> TransactionValidator {
@@ -310,6 +322,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TestDescription
                 transaction_threshold: 0,
                 threshold_calculator: Arc::new(|_active, total| (2 * total / 3 + 1)),
                 expected_views_to_fail: HashMap::new(),
+                expected_equivocators: HashSet::new(),
             },
             timing_data: TimingData {
                 next_view_timeout: 2000,
@@ -337,6 +350,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> TestDescription
                 transaction_threshold: 0,
                 threshold_calculator: Arc::new(|_active, total| (2 * total / 3 + 1)),
                 expected_views_to_fail: HashMap::new(),
+                expected_equivocators: HashSet::new(),
             },
             timing_data: TimingData {
                 ..TimingData::default()
@@ -418,6 +432,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, V: Versions> Default
             start_solver: true,
             validate_transactions: Arc::new(|_| Ok(())),
             epoch_height: 0,
+            mempool: None,
+            recover_payloads: false,
         }
     }
 }
@@ -456,6 +472,8 @@ where
             da_staked_committee_size,
             unreliable_network,
             epoch_height,
+            mempool,
+            recover_payloads,
             ..
         } = self.clone();
 
@@ -513,6 +531,9 @@ where
             start_voting_time: u64::MAX,
             stop_voting_time: 0,
             epoch_height,
+            rate_limits: RateLimitConfig::default(),
+            mempool,
+            recover_payloads,
         };
         let TimingData {
             next_view_timeout,
@@ -15,9 +15,13 @@ use hotshot::{
     traits::TestableNodeImplementation,
     types::{Event, Message},
 };
-use hotshot_task_impls::{events::HotShotEvent, network::NetworkMessageTaskState};
+use hotshot_task_impls::{
+    events::HotShotEvent, network::NetworkMessageTaskState, rate_limit::RateLimiter,
+};
 use hotshot_types::{
+    consensus::ConsensusMetricsValue,
     message::UpgradeLock,
+    rate_limit_config::RateLimitConfig,
     traits::{
         network::ConnectedNetwork,
         node_implementation::{NodeType, Versions},
@@ -164,11 +168,14 @@ pub async fn add_network_message_test_task<
     public_key: TYPES::SignatureKey,
 ) -> JoinHandle<()> {
     let net = Arc::clone(&channel);
-    let network_state: NetworkMessageTaskState<_> = NetworkMessageTaskState {
+    let network_state: NetworkMessageTaskState<_, _> = NetworkMessageTaskState {
         internal_event_stream: internal_event_stream.clone(),
         external_event_stream: external_event_stream.clone(),
         public_key,
         transactions_cache: lru::LruCache::new(NonZeroUsize::new(100_000).unwrap()),
+        rate_limiter: RateLimiter::new(RateLimitConfig::default()),
+        metrics: Arc::new(ConsensusMetricsValue::default()),
+        upgrade_lock: upgrade_lock.clone(),
     };
 
     let network = Arc::clone(&net);
@@ -177,8 +184,8 @@ pub async fn add_network_message_test_task<
     spawn(async move {
         loop {
             // Get the next message from the network
-            let message = match network.recv_message().await {
-                Ok(message) => message,
+            let (message, peer) = match network.recv_message_from().await {
+                Ok(received) => received,
                 Err(e) => {
                     error!("Failed to receive message: {:?}", e);
                     continue;
@@ -196,7 +203,7 @@ pub async fn add_network_message_test_task<
                 };
 
             // Handle the message
-            state.handle_message(deserialized_message).await;
+            state.handle_message(deserialized_message, peer).await;
         }
     })
 }
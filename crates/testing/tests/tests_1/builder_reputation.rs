@@ -0,0 +1,98 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::time::{Duration, Instant};
+
+use hotshot_task_impls::builder_reputation::{BuilderOutcome, BuilderReputation};
+use url::Url;
+
+/// A reputation tracker for `count` builders
+fn reputation(count: u16) -> BuilderReputation {
+    BuilderReputation::new(
+        (0..count)
+            .map(|i| Url::parse(&format!("http://localhost:{}", 9000 + i)).unwrap())
+            .collect(),
+    )
+}
+
+/// A response from a builder which took `millis` milliseconds
+fn responded(millis: u64) -> BuilderOutcome {
+    BuilderOutcome::Responded {
+        latency: Duration::from_millis(millis),
+        offered_fee: 1,
+    }
+}
+
+#[test]
+fn test_builder_reputation_orders_by_reliability_then_latency() {
+    let mut reputation = reputation(3);
+    let now = Instant::now();
+
+    reputation.record(0, responded(200), now);
+    reputation.record(1, responded(100), now);
+    reputation.record(2, responded(50), now);
+    // Builder 2 is the fastest, but it failed once, and got over its backoff since
+    reputation.record(2, BuilderOutcome::ClaimFailed, now);
+    let later = now + Duration::from_secs(1);
+
+    assert_eq!(reputation.ordered(later), vec![1, 0, 2]);
+    assert_eq!(reputation.stats(2).unwrap().claim_failures, 1);
+}
+
+#[test]
+fn test_builder_reputation_backs_off_failing_builders() {
+    let mut reputation = reputation(2);
+    let now = Instant::now();
+
+    reputation.record(1, BuilderOutcome::Timeout, now);
+    assert_eq!(reputation.ordered(now), vec![0]);
+    assert_eq!(reputation.ordered(now + Duration::from_secs(1)), vec![0, 1]);
+
+    // The backoff doubles with each failure in a row
+    reputation.record(1, BuilderOutcome::InvalidSignature, now);
+    reputation.record(1, BuilderOutcome::QueryFailed, now);
+    assert_eq!(reputation.ordered(now + Duration::from_secs(1)), vec![0]);
+    assert_eq!(reputation.ordered(now + Duration::from_secs(3)), vec![0, 1]);
+
+    // A success ends the backoff
+    reputation.record(1, responded(10), now);
+    assert_eq!(reputation.ordered(now), vec![0, 1]);
+    assert_eq!(reputation.stats(1).unwrap().consecutive_failures, 0);
+}
+
+#[test]
+fn test_builder_reputation_never_leaves_out_every_builder() {
+    let mut reputation = reputation(2);
+    let now = Instant::now();
+
+    reputation.record(0, BuilderOutcome::Timeout, now);
+    reputation.record(1, BuilderOutcome::Timeout, now);
+    reputation.record(1, BuilderOutcome::Timeout, now);
+
+    assert_eq!(reputation.ordered(now), vec![0, 1]);
+}
+
+#[test]
+fn test_builder_reputation_forgets_old_failures() {
+    let mut reputation = reputation(2);
+    let now = Instant::now();
+
+    // Builder 0 failed a lot an hour ago, but has served us since
+    reputation.record(0, responded(50), now);
+    for _ in 0..5 {
+        reputation.record(0, BuilderOutcome::Timeout, now);
+    }
+    let later = now + Duration::from_secs(60 * 60);
+    reputation.record(0, responded(50), later);
+    // Builder 1 has only just started failing
+    reputation.record(1, responded(50), later);
+    reputation.record(1, BuilderOutcome::ClaimFailed, later);
+
+    let after_backoff = later + Duration::from_secs(1);
+    assert_eq!(reputation.ordered(after_backoff), vec![0, 1]);
+    // The old failures still count in the totals
+    assert_eq!(reputation.stats(0).unwrap().timeouts, 5);
+}
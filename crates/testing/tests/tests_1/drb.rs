@@ -0,0 +1,165 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use futures::StreamExt;
+use hotshot::traits::election::dynamic::DynamicCommittee;
+use hotshot_example_types::node_types::{
+    MemoryImpl, TestTypes, TestTypesDynamicCommittee, TestVersions,
+};
+use hotshot_testing::{helpers::build_system_handle, view_generator::TestViewGenerator};
+use hotshot_types::{
+    data::{EpochNumber, ViewNumber},
+    drb::{
+        compute_drb_result, difficulty_level, drb_seed_input, DrbResult, DRB_EPOCHS_AHEAD,
+        INITIAL_DRB_RESULT,
+    },
+    signature_key::BLSPubKey,
+    traits::{
+        election::Membership, node_implementation::ConsensusTime, signature_key::SignatureKey,
+    },
+    PeerConfig, ValidatorConfig,
+};
+use sha2::{Digest, Sha256};
+
+/// Difficulty of the DRB computations in these tests
+const TEST_DIFFICULTY: u64 = 1000;
+
+/// Peer configs of nodes with the given stakes, all of them on the DA committee
+fn peers(stakes: &[u64]) -> Vec<PeerConfig<BLSPubKey>> {
+    stakes
+        .iter()
+        .enumerate()
+        .map(|(index, &stake)| {
+            ValidatorConfig::<BLSPubKey>::generated_from_seed_indexed(
+                [0u8; 32],
+                index as u64,
+                stake,
+                true,
+            )
+            .public_config()
+        })
+        .collect()
+}
+
+/// The leaders of the first `num_views` views of `epoch`
+fn leaders(
+    membership: &DynamicCommittee<TestTypesDynamicCommittee>,
+    epoch: EpochNumber,
+    num_views: u64,
+) -> Vec<BLSPubKey> {
+    (0..num_views)
+        .map(|view| membership.leader(ViewNumber::new(view), epoch).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_drb_difficulty_and_result() {
+    // One view's worth of hashing at the calibrated rate, for each view of the calculation.
+    assert_eq!(difficulty_level(), 300_000_000);
+
+    let seed = [7u8; 32];
+    assert_eq!(compute_drb_result(seed, 0), seed);
+
+    let twice: DrbResult = Sha256::digest(Sha256::digest(seed)).into();
+    assert_eq!(compute_drb_result(seed, 2), twice);
+    assert_eq!(
+        compute_drb_result(seed, TEST_DIFFICULTY),
+        compute_drb_result(seed, TEST_DIFFICULTY)
+    );
+    assert_ne!(
+        compute_drb_result(seed, TEST_DIFFICULTY),
+        compute_drb_result(seed, TEST_DIFFICULTY + 1)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_drb_seed_from_decided_qc() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let membership = (*handle.hotshot.memberships).clone();
+    let views: Vec<_> = TestViewGenerator::generate(membership)
+        .take(3)
+        .collect()
+        .await;
+
+    // The genesis QC has no signatures to derive a seed from.
+    assert!(drb_seed_input(&views[0].quorum_proposal.data.justify_qc).is_none());
+
+    let seed = drb_seed_input(&views[1].quorum_proposal.data.justify_qc).unwrap();
+    let other = drb_seed_input(&views[2].quorum_proposal.data.justify_qc).unwrap();
+    assert_ne!(seed, other);
+
+    // Every node decides the same leaf, so every node derives the same seed.
+    let leaf = views[1].leaf.clone();
+    assert_eq!(drb_seed_input(&leaf.justify_qc()), Some(seed));
+}
+
+#[test]
+fn test_drb_leaders_identical_on_every_node() {
+    let peers = peers(&[1, 2, 3, 4, 10]);
+    let nodes: Vec<_> = (0..4)
+        .map(|_| DynamicCommittee::<TestTypesDynamicCommittee>::new(peers.clone(), peers.clone()))
+        .collect();
+    let epoch = EpochNumber::new(DRB_EPOCHS_AHEAD + 1);
+
+    // The first epochs are led from the initial DRB result.
+    for node in &nodes {
+        assert_eq!(
+            node.drb_result(EpochNumber::new(DRB_EPOCHS_AHEAD)),
+            Some(INITIAL_DRB_RESULT)
+        );
+        assert_eq!(
+            leaders(node, EpochNumber::new(1), 100),
+            leaders(&nodes[0], EpochNumber::new(1), 100)
+        );
+        // Nobody knows the leaders of a later epoch before its DRB result is in.
+        assert!(node.leader(ViewNumber::new(0), epoch).is_err());
+    }
+
+    let drb_result = compute_drb_result([42u8; 32], TEST_DIFFICULTY);
+    for node in &nodes {
+        // Clones share the results, like the memberships held by each task.
+        node.clone().add_drb_result(epoch, drb_result);
+    }
+
+    let expected = leaders(&nodes[0], epoch, 1000);
+    for node in &nodes[1..] {
+        assert_eq!(leaders(node, epoch, 1000), expected);
+    }
+
+    // A different DRB result rotates the leaders differently.
+    let next_epoch = epoch + 1;
+    for node in &nodes {
+        node.add_drb_result(next_epoch, compute_drb_result([43u8; 32], TEST_DIFFICULTY));
+    }
+    assert_ne!(leaders(&nodes[0], next_epoch, 1000), expected);
+}
+
+#[test]
+fn test_drb_leaders_weighted_by_stake() {
+    let stakes = [1, 0, 9];
+    let peers = peers(&stakes);
+    let membership =
+        DynamicCommittee::<TestTypesDynamicCommittee>::new(peers.clone(), peers.clone());
+    let epoch = EpochNumber::new(DRB_EPOCHS_AHEAD + 1);
+    membership.add_drb_result(epoch, compute_drb_result([1u8; 32], TEST_DIFFICULTY));
+
+    let leaders = leaders(&membership, epoch, 10_000);
+    let count = |peer: &PeerConfig<BLSPubKey>| {
+        let key = BLSPubKey::public_key(&peer.stake_table_entry);
+        leaders.iter().filter(|leader| **leader == key).count()
+    };
+
+    assert_eq!(count(&peers[1]), 0);
+    let share = count(&peers[2]) as f64 / leaders.len() as f64;
+    assert!(
+        (share - 0.9).abs() < 0.02,
+        "node with 90% of the stake led {share:.3}"
+    );
+}
@@ -0,0 +1,227 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use hotshot::traits::election::epoch_committee::{
+    EpochCommittee, StakeTableUpdate, RETAINED_EPOCHS,
+};
+use hotshot_example_types::node_types::TestTypesEpochCommittee;
+use hotshot_types::{
+    data::{EpochNumber, ViewNumber},
+    signature_key::BLSPubKey,
+    traits::{election::Membership, node_implementation::ConsensusTime},
+    utils::epoch_from_block_number,
+    ValidatorConfig,
+};
+
+/// Blocks per epoch in these tests
+const EPOCH_HEIGHT: u64 = 10;
+
+/// The validator config of node `index`
+fn validator(index: u64, stake: u64) -> ValidatorConfig<BLSPubKey> {
+    ValidatorConfig::generated_from_seed_indexed([0u8; 32], index, stake, true)
+}
+
+/// A committee of four nodes with a stake of one each, all of them on the DA committee
+fn committee() -> EpochCommittee<TestTypesEpochCommittee> {
+    let peers: Vec<_> = (0..4)
+        .map(|index| validator(index, 1).public_config())
+        .collect();
+    EpochCommittee::new(peers.clone(), peers)
+}
+
+/// Decide blocks `1..=last_block`, fixing the stake table of every epoch whose root they include
+fn decide_through(membership: &EpochCommittee<TestTypesEpochCommittee>, last_block: u64) {
+    for block in (1..=last_block).filter(|block| (block - 1) % EPOCH_HEIGHT == 0) {
+        membership.add_epoch_root(EpochNumber::new(
+            epoch_from_block_number(block, EPOCH_HEIGHT) + 1,
+        ));
+    }
+}
+
+#[test]
+fn test_epoch_committee_updates_take_effect_at_epoch() {
+    let membership = committee();
+    let joining = validator(4, 6);
+    let leaving = validator(0, 1);
+
+    membership
+        .schedule_update(
+            EpochNumber::new(3),
+            StakeTableUpdate::Register {
+                peer: joining.public_config(),
+                da: false,
+            },
+        )
+        .unwrap();
+    membership
+        .schedule_update(
+            EpochNumber::new(3),
+            StakeTableUpdate::Deregister(leaving.public_key),
+        )
+        .unwrap();
+    decide_through(&membership, 21);
+
+    for epoch in [1, 2] {
+        let epoch = EpochNumber::new(epoch);
+        assert_eq!(membership.total_nodes(epoch), 4);
+        assert!(membership.has_stake(&leaving.public_key, epoch));
+        assert!(!membership.has_stake(&joining.public_key, epoch));
+        assert_eq!(membership.success_threshold(epoch).get(), 3);
+    }
+
+    // Both updates apply together, and stay in effect for later epochs.
+    for epoch in [3, 4, 7] {
+        let epoch = EpochNumber::new(epoch);
+        assert_eq!(membership.total_nodes(epoch), 4);
+        assert!(!membership.has_stake(&leaving.public_key, epoch));
+        assert!(!membership.has_da_stake(&leaving.public_key, epoch));
+        assert!(membership.has_stake(&joining.public_key, epoch));
+        assert!(!membership.has_da_stake(&joining.public_key, epoch));
+        assert_eq!(membership.da_total_nodes(epoch), 3);
+        // Three nodes with a stake of one, and one with a stake of six.
+        assert_eq!(membership.success_threshold(epoch).get(), 7);
+    }
+}
+
+#[test]
+fn test_epoch_committee_switches_at_epoch_boundary() {
+    let membership = committee();
+    let joining = validator(4, 1000);
+
+    // Schedule the change for the epoch after the one holding block 25.
+    let next_epoch = EpochNumber::new(epoch_from_block_number(25, EPOCH_HEIGHT) + 1);
+    membership
+        .schedule_update(
+            next_epoch,
+            StakeTableUpdate::Register {
+                peer: joining.public_config(),
+                da: true,
+            },
+        )
+        .unwrap();
+    decide_through(&membership, 40);
+
+    for block in 21..=40 {
+        let epoch = EpochNumber::new(epoch_from_block_number(block, EPOCH_HEIGHT));
+        let expected = block > 30;
+        assert_eq!(
+            membership.has_stake(&joining.public_key, epoch),
+            expected,
+            "unexpected committee at block {block}"
+        );
+
+        // With almost all of the stake, the new node leads nearly every view once it joins.
+        let leads = (0..100)
+            .filter(|view| {
+                membership.leader(ViewNumber::new(*view), epoch).unwrap() == joining.public_key
+            })
+            .count();
+        assert_eq!(leads > 90, expected, "unexpected leaders at block {block}");
+    }
+}
+
+#[test]
+fn test_epoch_committee_rejects_updates_for_fixed_epochs() {
+    let membership = committee();
+    let joining = validator(4, 1);
+    let update = StakeTableUpdate::Register {
+        peer: joining.public_config(),
+        da: false,
+    };
+
+    // Looking up an epoch does not fix its stake table, only deciding its epoch root does.
+    assert_eq!(membership.total_nodes(EpochNumber::new(100)), 4);
+    assert_eq!(membership.last_fixed_epoch(), EpochNumber::new(1));
+    decide_through(&membership, 31);
+    assert_eq!(membership.last_fixed_epoch(), EpochNumber::new(5));
+    for epoch in [0, 3, 5] {
+        assert!(membership
+            .schedule_update(EpochNumber::new(epoch), update.clone())
+            .is_err());
+    }
+    assert_eq!(membership.total_nodes(EpochNumber::new(5)), 4);
+
+    membership
+        .schedule_update(EpochNumber::new(6), update)
+        .unwrap();
+    // The update only takes effect once the epoch root of epoch 6 is decided.
+    assert_eq!(membership.total_nodes(EpochNumber::new(6)), 4);
+    decide_through(&membership, 41);
+    assert_eq!(membership.total_nodes(EpochNumber::new(5)), 4);
+    assert_eq!(membership.total_nodes(EpochNumber::new(6)), 5);
+
+    // Clones share the stake tables.
+    let clone = membership.clone();
+    assert!(clone.has_stake(&joining.public_key, EpochNumber::new(6)));
+}
+
+#[test]
+fn test_epoch_committee_prunes_old_epochs() {
+    let membership = committee();
+    let joining = validator(4, 1);
+    membership
+        .schedule_update(
+            EpochNumber::new(3),
+            StakeTableUpdate::Register {
+                peer: joining.public_config(),
+                da: false,
+            },
+        )
+        .unwrap();
+    decide_through(&membership, 101);
+
+    // We only keep the stake tables from a few epochs before the newest fixed one, and the
+    // oldest of them is still the one in effect then.
+    let oldest = 11 - RETAINED_EPOCHS;
+    for epoch in [oldest, 10, 11] {
+        assert!(membership.has_stake(&joining.public_key, EpochNumber::new(epoch)));
+    }
+    assert_eq!(membership.last_fixed_epoch(), EpochNumber::new(11));
+}
+
+#[test]
+fn test_epoch_committee_rebuilds_after_restart() {
+    let joining = validator(4, 1);
+    let leaving = validator(0, 1);
+    let updates = [
+        (
+            EpochNumber::new(3),
+            StakeTableUpdate::Register {
+                peer: joining.public_config(),
+                da: false,
+            },
+        ),
+        (
+            EpochNumber::new(6),
+            StakeTableUpdate::Deregister(leaving.public_key),
+        ),
+    ];
+    let membership = committee();
+    for (epoch, update) in updates.clone() {
+        membership.schedule_update(epoch, update).unwrap();
+    }
+    decide_through(&membership, 51);
+
+    // After a restart, the updates are scheduled again and the epoch roots up to the anchor leaf
+    // replayed, which fixes the same stake tables.
+    let restarted = committee();
+    for (epoch, update) in updates {
+        restarted.schedule_update(epoch, update).unwrap();
+    }
+    decide_through(&restarted, 51);
+
+    assert_eq!(restarted.last_fixed_epoch(), membership.last_fixed_epoch());
+    for epoch in (7 - RETAINED_EPOCHS..=8).map(EpochNumber::new) {
+        assert_eq!(restarted.total_nodes(epoch), membership.total_nodes(epoch));
+        for key in [&joining.public_key, &leaving.public_key] {
+            assert_eq!(
+                restarted.has_stake(key, epoch),
+                membership.has_stake(key, epoch)
+            );
+        }
+    }
+    assert!(!restarted.has_stake(&leaving.public_key, EpochNumber::new(6)));
+}
@@ -0,0 +1,208 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::sync::Arc;
+
+use committable::Commitment;
+use futures::StreamExt;
+use hotshot::{tasks::task_state::CreateTaskState, types::SystemContextHandle};
+use hotshot_example_types::{
+    block_types::TestTransaction,
+    node_types::{MemoryImpl, TestTypes, TestVersions},
+};
+use hotshot_task_impls::{equivocation::EquivocationTaskState, events::HotShotEvent};
+use hotshot_testing::{
+    helpers::{build_system_handle, key_pair_for_id},
+    view_generator::TestViewGenerator,
+};
+use hotshot_types::{
+    data::{EpochNumber, ViewNumber},
+    event::EventType,
+    evidence::{EquivocationEvidence, EquivocationKind, SignedMessage},
+    simple_vote::{QuorumData2, QuorumVote2},
+    traits::{node_implementation::ConsensusTime, storage::Storage},
+};
+
+/// A quorum vote by node `node_id` in `view` for the leaf with commitment `leaf_byte` repeated
+async fn quorum_vote(
+    handle: &SystemContextHandle<TestTypes, MemoryImpl, TestVersions>,
+    node_id: u64,
+    view: u64,
+    leaf_byte: u8,
+) -> QuorumVote2<TestTypes> {
+    let (private_key, public_key) = key_pair_for_id::<TestTypes>(node_id);
+
+    QuorumVote2::create_signed_vote(
+        QuorumData2 {
+            leaf_commit: Commitment::from_raw([leaf_byte; 32]),
+        },
+        ViewNumber::new(view),
+        &public_key,
+        &private_key,
+        &handle.hotshot.upgrade_lock,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_equivocation_evidence_validation() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let membership = &*handle.hotshot.memberships;
+    let upgrade_lock = &handle.hotshot.upgrade_lock;
+    let epoch = EpochNumber::genesis();
+
+    let first = quorum_vote(&handle, 1, 2, 1).await;
+    let evidence = EquivocationEvidence {
+        offender: key_pair_for_id::<TestTypes>(1).1,
+        first: SignedMessage::QuorumVote(first.clone()),
+        second: SignedMessage::QuorumVote(quorum_vote(&handle, 1, 2, 2).await),
+    };
+    evidence
+        .validate(membership, epoch, upgrade_lock)
+        .await
+        .unwrap();
+    assert_eq!(evidence.kind(), EquivocationKind::QuorumVote);
+    assert_eq!(evidence.view_number(), ViewNumber::new(2));
+
+    // The same vote twice is not equivocation
+    let repeated = EquivocationEvidence {
+        second: SignedMessage::QuorumVote(first.clone()),
+        ..evidence.clone()
+    };
+    assert!(repeated
+        .validate(membership, epoch, upgrade_lock)
+        .await
+        .is_err());
+
+    // Neither are votes for different views
+    let other_view = EquivocationEvidence {
+        second: SignedMessage::QuorumVote(quorum_vote(&handle, 1, 3, 2).await),
+        ..evidence.clone()
+    };
+    assert!(other_view
+        .validate(membership, epoch, upgrade_lock)
+        .await
+        .is_err());
+
+    // A vote claiming to be from the offender, but signed by another node, proves nothing
+    let mut forged = quorum_vote(&handle, 3, 2, 2).await;
+    forged.signature.0 = key_pair_for_id::<TestTypes>(1).1;
+    let forged = EquivocationEvidence {
+        second: SignedMessage::QuorumVote(forged),
+        ..evidence
+    };
+    assert!(forged
+        .validate(membership, epoch, upgrade_lock)
+        .await
+        .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_equivocation_task_reports_conflicting_messages() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let membership = (*handle.hotshot.memberships).clone();
+    let (output_sender, mut output_receiver) = async_broadcast::broadcast(16);
+    let mut state =
+        EquivocationTaskState::<TestTypes, MemoryImpl, TestVersions>::create_from(&handle).await;
+    state.output_event_stream = output_sender;
+
+    // Two leaders' worth of views, one proposing a block with a transaction and one without
+    let mut empty = TestViewGenerator::generate(membership.clone());
+    let _genesis = empty.next().await.unwrap();
+    let empty_view = empty.next().await.unwrap();
+    let mut full = TestViewGenerator::generate(membership);
+    let _genesis = full.next().await.unwrap();
+    full.add_transactions(vec![TestTransaction::new(vec![1])]);
+    let full_view = full.next().await.unwrap();
+    assert_eq!(empty_view.view_number, full_view.view_number);
+
+    let vote = quorum_vote(&handle, 1, 2, 1).await;
+    let conflicting_vote = quorum_vote(&handle, 1, 2, 2).await;
+    let mut forged_vote = quorum_vote(&handle, 3, 2, 3).await;
+    forged_vote.signature.0 = key_pair_for_id::<TestTypes>(1).1;
+    let leader = empty_view.leader_public_key;
+
+    for event in [
+        HotShotEvent::QuorumVoteRecv(vote.clone()),
+        // A repeated vote is fine
+        HotShotEvent::QuorumVoteRecv(vote.clone()),
+        // A forged vote is ignored
+        HotShotEvent::QuorumVoteRecv(forged_vote),
+        HotShotEvent::QuorumVoteRecv(conflicting_vote.clone()),
+        // The node is only reported once per view
+        HotShotEvent::QuorumVoteRecv(quorum_vote(&handle, 1, 2, 3).await),
+        HotShotEvent::DaProposalRecv(empty_view.da_proposal.clone(), leader),
+        HotShotEvent::DaProposalRecv(full_view.da_proposal.clone(), leader),
+        HotShotEvent::QuorumProposalRecv(empty_view.quorum_proposal.clone(), leader),
+        HotShotEvent::QuorumProposalRecv(full_view.quorum_proposal.clone(), leader),
+    ] {
+        let _ = state.handle(Arc::new(event)).await;
+    }
+
+    let expected = [
+        EquivocationEvidence {
+            offender: key_pair_for_id::<TestTypes>(1).1,
+            first: SignedMessage::QuorumVote(vote),
+            second: SignedMessage::QuorumVote(conflicting_vote),
+        },
+        EquivocationEvidence {
+            offender: leader,
+            first: SignedMessage::DaProposal(empty_view.da_proposal.clone()),
+            second: SignedMessage::DaProposal(full_view.da_proposal.clone()),
+        },
+        EquivocationEvidence {
+            offender: leader,
+            first: SignedMessage::QuorumProposal(empty_view.quorum_proposal.clone()),
+            second: SignedMessage::QuorumProposal(full_view.quorum_proposal.clone()),
+        },
+    ];
+
+    for expected in &expected {
+        let event = output_receiver.try_recv().unwrap();
+        assert_eq!(event.view_number, ViewNumber::new(2));
+        let EventType::Equivocation { evidence } = event.event else {
+            panic!("Unexpected event {:?}", event.event);
+        };
+        assert_eq!(&*evidence, expected);
+        evidence
+            .validate(
+                &handle.hotshot.memberships,
+                EpochNumber::genesis(),
+                &handle.hotshot.upgrade_lock,
+            )
+            .await
+            .unwrap();
+    }
+    assert!(output_receiver.try_recv().is_err());
+
+    let stored = handle
+        .storage()
+        .read()
+        .await
+        .load_equivocation_evidence()
+        .await
+        .unwrap();
+    assert_eq!(stored, expected);
+
+    // Messages for views we moved past are forgotten
+    state
+        .handle(Arc::new(HotShotEvent::ViewChange(
+            ViewNumber::new(20),
+            EpochNumber::genesis(),
+        )))
+        .await
+        .unwrap();
+    assert!(state.seen.is_empty());
+}
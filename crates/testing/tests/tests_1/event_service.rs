@@ -0,0 +1,266 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use hotshot_event_service::{
+    filter::EventFilter,
+    sse::define_sse_app,
+    streamer::{EventStreamError, EventStreamer},
+};
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    data::ViewNumber,
+    event::{Event, EventKind, EventType},
+    traits::node_implementation::ConsensusTime,
+};
+use tokio::time::timeout;
+
+/// A `ViewFinished` and a `ViewTimeout` event for `view`
+fn events_of_view(view: u64) -> [Event<TestTypes>; 2] {
+    let view_number = ViewNumber::new(view);
+    [
+        Event {
+            view_number,
+            event: EventType::ViewFinished { view_number },
+        },
+        Event {
+            view_number,
+            event: EventType::ViewTimeout { view_number },
+        },
+    ]
+}
+
+/// The view and kind of each event, to compare streams of events
+fn summary(events: &[Event<TestTypes>]) -> Vec<(u64, EventKind)> {
+    events
+        .iter()
+        .map(|event| (*event.view_number, event.event.kind()))
+        .collect()
+}
+
+#[test]
+fn test_event_filter() {
+    let filter = EventFilter::<TestTypes>::parse(
+        Some("view_finished,decide"),
+        Some(ViewNumber::new(2)),
+        Some(ViewNumber::new(3)),
+    )
+    .unwrap();
+    let matching: Vec<_> = (1..=4).flat_map(events_of_view).collect();
+    assert_eq!(
+        summary(
+            &matching
+                .into_iter()
+                .filter(|event| filter.matches(event))
+                .collect::<Vec<_>>()
+        ),
+        [(2, EventKind::ViewFinished), (3, EventKind::ViewFinished)]
+    );
+    assert!(!filter.is_past(ViewNumber::new(3)));
+    assert!(filter.is_past(ViewNumber::new(4)));
+
+    assert_eq!(
+        EventFilter::<TestTypes>::parse(Some("all"), None, None).unwrap(),
+        EventFilter::default()
+    );
+    assert!(EventFilter::<TestTypes>::parse(Some("view_finished,bogus"), None, None).is_err());
+    assert!(EventFilter::<TestTypes>::parse(
+        None,
+        Some(ViewNumber::new(3)),
+        Some(ViewNumber::new(2))
+    )
+    .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_event_streamer_replays_and_resumes() {
+    let streamer = EventStreamer::<TestTypes>::new(4, 16);
+    for view in 1..=3 {
+        for event in events_of_view(view) {
+            streamer.handle_event(event).await;
+        }
+    }
+
+    // View 1 is no longer buffered, so we cannot resume from it
+    let from = |view| EventFilter {
+        from: Some(ViewNumber::new(view)),
+        ..EventFilter::default()
+    };
+    assert!(matches!(
+        streamer.subscribe(from(1)).await.err(),
+        Some(EventStreamError::Evicted {
+            from: 1,
+            oldest: Some(2)
+        })
+    ));
+    assert_eq!(
+        summary(&streamer.replay(&from(3)).await.unwrap()),
+        [(3, EventKind::ViewFinished), (3, EventKind::ViewTimeout)]
+    );
+
+    // Resuming after an event replays exactly the events after it, unless some were evicted
+    let after: Vec<_> = timeout(
+        Duration::from_secs(5),
+        streamer
+            .subscribe_sequenced(EventFilter::default(), Some(3))
+            .await
+            .unwrap()
+            .take(2)
+            .collect(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        after
+            .into_iter()
+            .map(|event| event.unwrap().sequence)
+            .collect::<Vec<_>>(),
+        [4, 5]
+    );
+    assert!(matches!(
+        streamer
+            .subscribe_sequenced(EventFilter::default(), Some(0))
+            .await
+            .err(),
+        Some(EventStreamError::EvictedAfter {
+            after: 0,
+            oldest: Some(2)
+        })
+    ));
+
+    // Resuming gets the buffered events, then the new ones, without gaps or duplicates
+    let mut resumed = streamer.subscribe(from(2)).await.unwrap();
+    let mut live = streamer
+        .subscribe(EventFilter {
+            kinds: Some([EventKind::ViewTimeout].into()),
+            to: Some(ViewNumber::new(4)),
+            ..EventFilter::default()
+        })
+        .await
+        .unwrap();
+    for view in 4..=5 {
+        for event in events_of_view(view) {
+            streamer.handle_event(event).await;
+        }
+    }
+
+    let resumed: Vec<_> = timeout(Duration::from_secs(5), resumed.by_ref().take(8).collect())
+        .await
+        .unwrap();
+    let resumed: Vec<_> = resumed.into_iter().map(Result::unwrap).collect();
+    assert_eq!(
+        summary(&resumed),
+        (2..=5)
+            .flat_map(|view| [
+                (view, EventKind::ViewFinished),
+                (view, EventKind::ViewTimeout)
+            ])
+            .collect::<Vec<_>>()
+    );
+
+    // A subscription without a start sends new events only, and ends after its last view
+    let live: Vec<_> = timeout(Duration::from_secs(5), live.collect())
+        .await
+        .unwrap();
+    let live: Vec<_> = live.into_iter().map(Result::unwrap).collect();
+    assert_eq!(summary(&live), [(4, EventKind::ViewTimeout)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_event_streamer_disconnects_lagging_clients() {
+    let streamer = EventStreamer::<TestTypes>::new(16, 2);
+    let mut events = streamer.subscribe(EventFilter::default()).await.unwrap();
+    for view in 1..=3 {
+        for event in events_of_view(view) {
+            streamer.handle_event(event).await;
+        }
+    }
+
+    let events: Vec<_> = timeout(Duration::from_secs(5), events.by_ref().collect())
+        .await
+        .unwrap();
+    assert!(matches!(
+        events.as_slice(),
+        [Err(EventStreamError::Lagged {
+            skipped: 4,
+            last_view: None
+        })]
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_event_service_streams_server_sent_events() {
+    let streamer = EventStreamer::<TestTypes>::new(4, 16);
+    for view in 1..=3 {
+        for event in events_of_view(view) {
+            streamer.handle_event(event).await;
+        }
+    }
+
+    let port = portpicker::pick_unused_port().unwrap();
+    tokio::spawn(define_sse_app(streamer.clone()).listen(format!("127.0.0.1:{port}")));
+    let url = |path: &str| format!("http://127.0.0.1:{port}/hotshot-events/sse/{path}");
+
+    // Wait for the server to start, checking that bad filters and evicted views are rejected
+    let mut response = None;
+    for _ in 0..50 {
+        if let Ok(rejected) = reqwest::get(url("bogus")).await {
+            response = Some(rejected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(response.unwrap().status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(
+        reqwest::get(url("all/from/1")).await.unwrap().status(),
+        reqwest::StatusCode::GONE
+    );
+
+    // Resuming needs an event we buffer and sent, after which nothing was evicted
+    let resume = |id: &'static str| {
+        reqwest::Client::new()
+            .get(url("view_timeout/from/2"))
+            .header("Last-Event-ID", id)
+            .send()
+    };
+    assert_eq!(
+        resume("0").await.unwrap().status(),
+        reqwest::StatusCode::GONE
+    );
+    assert_eq!(
+        resume("42").await.unwrap().status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+
+    // Resuming after an event ID replays the events after that one, rather than from the path's
+    // view. Event 3 is the timeout of view 2, which the client already has.
+    let mut response = resume("3").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    for event in events_of_view(4) {
+        streamer.handle_event(event).await;
+    }
+
+    // Each event ends with a blank line
+    let mut body = String::new();
+    while body.matches("\n\n").count() < 2 {
+        let chunk = timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        body.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let field = |name: &str| {
+        body.lines()
+            .filter_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(field("event:"), ["view_timeout", "view_timeout"], "{body}");
+    assert_eq!(field("id:"), ["5", "7"], "{body}");
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use hotshot::types::{Event, EventType, LagPolicy, SubscriptionFilter};
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes, TestVersions};
+use hotshot_testing::{
+    helpers::{build_system_handle, key_pair_for_id},
+    view_generator::TestViewGenerator,
+};
+use hotshot_types::{
+    data::ViewNumber, signature_key::BLSPubKey, traits::node_implementation::ConsensusTime,
+};
+use tokio::time::{sleep, timeout};
+
+/// An external message from `sender`, whose data is `byte`
+fn external_message(sender: BLSPubKey, byte: u8) -> Event<TestTypes> {
+    Event {
+        view_number: ViewNumber::new(1),
+        event: EventType::ExternalMessageReceived {
+            sender,
+            data: vec![byte],
+        },
+    }
+}
+
+/// The data of the external messages in `events`
+fn message_data(events: &[Event<TestTypes>]) -> Vec<u8> {
+    events
+        .iter()
+        .filter_map(|event| match &event.event {
+            EventType::ExternalMessageReceived { data, .. } => data.first().copied(),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_subscription_filters() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let sender = handle.external_channel_sender();
+    let leader = key_pair_for_id::<TestTypes>(1).1;
+    let other = key_pair_for_id::<TestTypes>(3).1;
+
+    let mut messages = handle.subscribe(
+        SubscriptionFilter::external_messages(),
+        16,
+        LagPolicy::DropOldest,
+    );
+    let mut proposals = handle.subscribe(
+        SubscriptionFilter::proposals_from(leader),
+        16,
+        LagPolicy::DropOldest,
+    );
+
+    let mut generator = TestViewGenerator::generate((*handle.hotshot.memberships).clone());
+    let view = generator.next().await.unwrap();
+    for sender_key in [other, leader] {
+        sender
+            .broadcast(Event {
+                view_number: view.view_number,
+                event: EventType::QuorumProposal {
+                    proposal: view.quorum_proposal.clone(),
+                    sender: sender_key,
+                },
+            })
+            .await
+            .unwrap();
+    }
+    sender.broadcast(external_message(other, 7)).await.unwrap();
+
+    let message = timeout(Duration::from_secs(5), messages.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(message_data(&[message]), [7]);
+
+    let proposal = timeout(Duration::from_secs(5), proposals.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        proposal.event,
+        EventType::QuorumProposal { sender, .. } if sender == leader
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_subscription_lag_policies() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let sender = handle.external_channel_sender();
+    let key = key_pair_for_id::<TestTypes>(1).1;
+
+    let subscribe = |policy| handle.subscribe(SubscriptionFilter::external_messages(), 2, policy);
+    let mut drop_oldest = subscribe(LagPolicy::DropOldest);
+    let mut block = subscribe(LagPolicy::Block);
+    let disconnect = subscribe(LagPolicy::Disconnect);
+
+    for byte in 1..=4 {
+        sender.broadcast(external_message(key, byte)).await.unwrap();
+    }
+    // Let the subscriptions take in every event before we read any
+    sleep(Duration::from_millis(200)).await;
+
+    // The oldest events made room for the newest ones
+    let events: Vec<_> = timeout(
+        Duration::from_secs(5),
+        drop_oldest.by_ref().take(2).collect(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(message_data(&events), [3, 4]);
+
+    // We wait for the subscriber to make room, so it misses nothing
+    let events: Vec<_> = timeout(Duration::from_secs(5), block.by_ref().take(4).collect())
+        .await
+        .unwrap();
+    assert_eq!(message_data(&events), [1, 2, 3, 4]);
+
+    // The subscriber gets what we buffered before it fell behind, then the subscription ends
+    let events: Vec<_> = timeout(Duration::from_secs(5), disconnect.collect())
+        .await
+        .unwrap();
+    assert_eq!(message_data(&events), [1, 2]);
+}
@@ -0,0 +1,454 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use hotshot::traits::implementations::{FileStorage, FileStorageConfig, SyncMode, SyncPolicy};
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes, TestVersions};
+use hotshot_testing::{
+    helpers::{build_system_handle, key_pair_for_id},
+    view_generator::TestViewGenerator,
+};
+use hotshot_types::{
+    data::{EpochNumber, Leaf2, QuorumProposal2, ViewNumber},
+    event::HotShotAction,
+    evidence::{EquivocationEvidence, SignedMessage},
+    message::Proposal,
+    traits::{
+        node_implementation::ConsensusTime,
+        storage::{RetentionPolicy, Storage},
+    },
+};
+
+/// Generate a few quorum proposals to store
+async fn quorum_proposals(count: usize) -> Vec<Proposal<TestTypes, QuorumProposal2<TestTypes>>> {
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(1)
+        .await
+        .0;
+    let generator = TestViewGenerator::generate((*handle.hotshot.memberships).clone());
+
+    generator
+        .take(count)
+        .map(|view| view.quorum_proposal)
+        .collect()
+        .await
+}
+
+/// The only segment file in `dir`
+fn only_segment(dir: &Path) -> PathBuf {
+    let mut segments: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(segments.len(), 1, "expected a single segment");
+    segments.pop().unwrap()
+}
+
+/// Copy the segment files of `from` into a fresh directory, cutting the last one to `len` bytes
+fn copy_truncated(from: &Path, len: usize) -> tempfile::TempDir {
+    let to = tempfile::tempdir().unwrap();
+    let segment = only_segment(from);
+    let bytes = fs::read(&segment).unwrap();
+    fs::write(to.path().join(segment.file_name().unwrap()), &bytes[..len]).unwrap();
+    to
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_reload() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposals = quorum_proposals(3).await;
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    for proposal in &proposals {
+        storage.append_proposal2(proposal).await.unwrap();
+        storage
+            .update_high_qc2(proposal.data.justify_qc.clone())
+            .await
+            .unwrap();
+        storage
+            .record_action(
+                proposal.data.view_number,
+                EpochNumber::new(1),
+                HotShotAction::Vote,
+            )
+            .await
+            .unwrap();
+    }
+    // Neither of these should move the last actioned view backwards or sideways.
+    storage
+        .record_action(ViewNumber::new(0), EpochNumber::new(2), HotShotAction::Vote)
+        .await
+        .unwrap();
+    storage
+        .record_action(
+            ViewNumber::new(100),
+            EpochNumber::new(2),
+            HotShotAction::DaVote,
+        )
+        .await
+        .unwrap();
+    storage
+        .update_decided_upgrade_certificate(None)
+        .await
+        .unwrap();
+    storage
+        .update_anchor_leaf(Leaf2::from_quorum_proposal(&proposals[1].data))
+        .await
+        .unwrap();
+    // An older decide must not replace the anchor.
+    storage
+        .update_anchor_leaf(Leaf2::from_quorum_proposal(&proposals[0].data))
+        .await
+        .unwrap();
+    drop(storage);
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    let loaded = storage.load_quorum_proposals().await.unwrap();
+    assert_eq!(loaded.len(), proposals.len());
+    for proposal in &proposals {
+        assert_eq!(loaded.get(&proposal.data.view_number), Some(proposal));
+    }
+    assert_eq!(
+        storage.load_high_qc().await.unwrap(),
+        Some(proposals.last().unwrap().data.justify_qc.clone())
+    );
+    assert_eq!(
+        storage.last_actioned_view().await,
+        Some(proposals.last().unwrap().data.view_number)
+    );
+    assert_eq!(
+        storage.load_last_actioned_epoch().await.unwrap(),
+        Some(EpochNumber::new(1))
+    );
+    assert!(storage
+        .load_decided_upgrade_certificate()
+        .await
+        .unwrap()
+        .is_none());
+
+    let state = storage.load_consensus_state().await.unwrap();
+    assert_eq!(
+        state.anchor_leaf,
+        Some(Leaf2::from_quorum_proposal(&proposals[1].data))
+    );
+    assert_eq!(state.saved_proposals, loaded);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_torn_append() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposals = quorum_proposals(2).await;
+    let config = |path: &Path| FileStorageConfig {
+        sync: SyncPolicy::uniform(SyncMode::None),
+        ..FileStorageConfig::new(path)
+    };
+
+    let storage = FileStorage::<TestTypes>::open(config(dir.path())).unwrap();
+    storage.append_proposal2(&proposals[0]).await.unwrap();
+    storage
+        .record_action(
+            proposals[0].data.view_number,
+            EpochNumber::new(1),
+            HotShotAction::Propose,
+        )
+        .await
+        .unwrap();
+    let committed_len = fs::metadata(only_segment(dir.path())).unwrap().len() as usize;
+
+    // The write that the crash interrupts.
+    storage.append_proposal2(&proposals[1]).await.unwrap();
+    let full_len = fs::metadata(only_segment(dir.path())).unwrap().len() as usize;
+    drop(storage);
+
+    // Cut the last frame at every possible point, as if the writer had been killed mid-append.
+    for len in committed_len..full_len {
+        let crashed = copy_truncated(dir.path(), len);
+
+        let storage = FileStorage::<TestTypes>::open(config(crashed.path())).unwrap();
+        let loaded = storage.load_quorum_proposals().await.unwrap();
+        assert_eq!(loaded.len(), 1, "torn proposal survived a cut at {len}");
+        assert_eq!(
+            storage.last_actioned_view().await,
+            Some(proposals[0].data.view_number)
+        );
+
+        // The torn tail is gone, so new writes land after the last complete frame.
+        assert_eq!(
+            fs::metadata(only_segment(crashed.path())).unwrap().len() as usize,
+            committed_len
+        );
+        storage.append_proposal2(&proposals[1]).await.unwrap();
+        drop(storage);
+
+        let storage = FileStorage::<TestTypes>::open(config(crashed.path())).unwrap();
+        assert_eq!(storage.load_quorum_proposals().await.unwrap().len(), 2);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_corrupted_tail() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposals = quorum_proposals(2).await;
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    storage.append_proposal2(&proposals[0]).await.unwrap();
+    storage.append_proposal2(&proposals[1]).await.unwrap();
+    drop(storage);
+
+    // Flip the last byte, as if the final sector only made it to disk partially.
+    let segment = only_segment(dir.path());
+    let mut bytes = fs::read(&segment).unwrap();
+    *bytes.last_mut().unwrap() ^= 0xff;
+    fs::write(&segment, &bytes).unwrap();
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    let loaded = storage.load_quorum_proposals().await.unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(
+        loaded.get(&proposals[0].data.view_number),
+        Some(&proposals[0])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_compaction() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposals = quorum_proposals(1).await;
+    let config = FileStorageConfig {
+        max_segment_size: 1024,
+        ..FileStorageConfig::new(dir.path())
+    };
+
+    let storage = FileStorage::<TestTypes>::open(config.clone()).unwrap();
+    storage.append_proposal2(&proposals[0]).await.unwrap();
+    for view in 1..=500 {
+        storage
+            .record_action(
+                ViewNumber::new(view),
+                EpochNumber::new(1),
+                HotShotAction::Vote,
+            )
+            .await
+            .unwrap();
+    }
+    drop(storage);
+
+    // Superseded actions are dropped as segments fill up, and the proposal is carried along.
+    let segments = fs::read_dir(dir.path()).unwrap().count();
+    assert!(segments <= 4, "{segments} segments left after compaction");
+
+    let storage = FileStorage::<TestTypes>::open(config).unwrap();
+    assert_eq!(
+        storage.last_actioned_view().await,
+        Some(ViewNumber::new(500))
+    );
+    assert_eq!(
+        storage
+            .load_quorum_proposals()
+            .await
+            .unwrap()
+            .values()
+            .next(),
+        Some(&proposals[0])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_evidence_not_duplicated() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposals = quorum_proposals(2).await;
+    let evidence = EquivocationEvidence {
+        offender: key_pair_for_id::<TestTypes>(0).1,
+        first: SignedMessage::QuorumProposal(proposals[0].clone()),
+        second: SignedMessage::QuorumProposal(proposals[1].clone()),
+    };
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    storage
+        .append_equivocation_evidence(&evidence)
+        .await
+        .unwrap();
+    storage
+        .append_equivocation_evidence(&evidence)
+        .await
+        .unwrap();
+    drop(storage);
+
+    // Copy the segment, as if a compaction had copied its frames and crashed before removing it.
+    let segment = only_segment(dir.path());
+    fs::copy(&segment, segment.with_file_name(format!("{:020}.seg", 1))).unwrap();
+
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    assert_eq!(
+        storage.load_equivocation_evidence().await.unwrap(),
+        vec![evidence]
+    );
+}
+
+/// Copies of `proposal` for each of `views`
+fn proposals_for_views(
+    proposal: &Proposal<TestTypes, QuorumProposal2<TestTypes>>,
+    views: impl Iterator<Item = u64>,
+) -> Vec<Proposal<TestTypes, QuorumProposal2<TestTypes>>> {
+    views
+        .map(|view| {
+            let mut proposal = proposal.clone();
+            proposal.data.view_number = ViewNumber::new(view);
+            proposal
+        })
+        .collect()
+}
+
+/// The views of the stored quorum proposals
+async fn stored_views(storage: &FileStorage<TestTypes>) -> Vec<u64> {
+    storage
+        .load_quorum_proposals()
+        .await
+        .unwrap()
+        .keys()
+        .map(|view| **view)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_retain_views() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposal = quorum_proposals(1).await.remove(0);
+    let config = FileStorageConfig {
+        retention: RetentionPolicy::Views(3),
+        ..FileStorageConfig::new(dir.path())
+    };
+
+    let storage = FileStorage::<TestTypes>::open(config.clone()).unwrap();
+    for proposal in proposals_for_views(&proposal, 1..=10) {
+        storage.append_proposal2(&proposal).await.unwrap();
+    }
+    assert_eq!(storage.retained_from().await, ViewNumber::genesis());
+
+    storage.collect_garbage(ViewNumber::new(8)).await.unwrap();
+    assert_eq!(storage.retained_from().await, ViewNumber::new(6));
+    assert_eq!(stored_views(&storage).await, (6..=10).collect::<Vec<_>>());
+    assert!(storage
+        .load_quorum_proposal(ViewNumber::new(5))
+        .await
+        .unwrap()
+        .is_none());
+
+    // Late data for a pruned view is not stored, and an older decide does not bring anything back.
+    storage
+        .append_proposal2(&proposals_for_views(&proposal, 2..=2)[0])
+        .await
+        .unwrap();
+    storage.collect_garbage(ViewNumber::new(4)).await.unwrap();
+    drop(storage);
+
+    let storage = FileStorage::<TestTypes>::open(config).unwrap();
+    assert_eq!(storage.retained_from().await, ViewNumber::new(6));
+    assert_eq!(stored_views(&storage).await, (6..=10).collect::<Vec<_>>());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_retain_bytes() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposal = quorum_proposals(1).await.remove(0);
+
+    // Find out how much space a single proposal takes up.
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+    storage.append_proposal2(&proposal).await.unwrap();
+    let frame_size = fs::metadata(only_segment(dir.path())).unwrap().len();
+    drop(storage);
+
+    let dir = tempfile::tempdir().unwrap();
+    let storage = FileStorage::<TestTypes>::open(FileStorageConfig {
+        retention: RetentionPolicy::Bytes(frame_size * 5 / 2),
+        ..FileStorageConfig::new(dir.path())
+    })
+    .unwrap();
+    for proposal in proposals_for_views(&proposal, 1..=6) {
+        storage.append_proposal2(&proposal).await.unwrap();
+    }
+
+    // Only two decided views fit in the budget. Undecided views are never pruned.
+    storage.collect_garbage(ViewNumber::new(4)).await.unwrap();
+    assert_eq!(storage.retained_from().await, ViewNumber::new(3));
+    assert_eq!(stored_views(&storage).await, (3..=6).collect::<Vec<_>>());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_pruned_segments_reclaimed() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+    let proposal = quorum_proposals(1).await.remove(0);
+    let config = FileStorageConfig {
+        max_segment_size: 4096,
+        retention: RetentionPolicy::Views(1),
+        ..FileStorageConfig::new(dir.path())
+    };
+
+    let storage = FileStorage::<TestTypes>::open(config).unwrap();
+    for proposal in proposals_for_views(&proposal, 1..=200) {
+        let view = proposal.data.view_number;
+        storage.append_proposal2(&proposal).await.unwrap();
+        storage.collect_garbage(view).await.unwrap();
+    }
+
+    assert_eq!(stored_views(&storage).await, vec![200]);
+    let segments = fs::read_dir(dir.path()).unwrap().count();
+    assert!(segments <= 4, "{segments} segments left after pruning");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_storage_killed_writer() {
+    hotshot::helpers::initialize_logging();
+
+    let dir = tempfile::tempdir().unwrap();
+
+    for _ in 0..3 {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_file-storage-writer"))
+            .arg(dir.path())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        // Every action is recorded after the proposal for its view, so whatever was cut off, the
+        // proposals must cover every view up to the last action.
+        let storage = FileStorage::<TestTypes>::open(FileStorageConfig::new(dir.path())).unwrap();
+        let proposals = storage.load_quorum_proposals().await.unwrap();
+        if let Some(last_action) = storage.last_actioned_view().await {
+            for view in 1..=*last_action {
+                assert!(
+                    proposals.contains_key(&ViewNumber::new(view)),
+                    "missing proposal for view {view}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{sync::Arc, time::Instant};
+
+use either::Either;
+use futures::StreamExt;
+use hotshot::{
+    tasks::task_state::CreateTaskState, traits::implementations::PrometheusMetrics,
+    types::SystemContextHandle,
+};
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes, TestVersions};
+use hotshot_task_impls::{
+    consensus::ConsensusTaskState, da::DaTaskState, events::HotShotEvent,
+    quorum_vote::QuorumVoteTaskState, vid::VidTaskState,
+};
+use hotshot_testing::{helpers::build_system_handle, view_generator::TestViewGenerator};
+use hotshot_types::{
+    consensus::ConsensusMetricsValue,
+    data::{null_block, EpochNumber, PackedBundle},
+    traits::{
+        election::Membership,
+        metrics::Metrics,
+        node_implementation::{ConsensusTime, Versions},
+    },
+};
+use vbs::version::StaticVersionType;
+use vec1::vec1;
+
+/// Build node 2 with its consensus metrics backed by a Prometheus registry
+async fn build_handle_with_metrics() -> (
+    SystemContextHandle<TestTypes, MemoryImpl, TestVersions>,
+    PrometheusMetrics,
+) {
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let metrics = PrometheusMetrics::new();
+    handle.hotshot.consensus().write().await.metrics = Arc::new(ConsensusMetricsValue::new(
+        &*metrics.subgroup("consensus".into()),
+    ));
+
+    (handle, metrics)
+}
+
+/// Number of points in the consensus histogram `name`
+fn count(metrics: &PrometheusMetrics, name: &str) -> u64 {
+    let prefix = format!("consensus_{name}_count ");
+    metrics
+        .export()
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .unwrap_or_else(|| panic!("histogram {name} is not exported"))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_proposal_to_vote_latency() {
+    hotshot::helpers::initialize_logging();
+
+    let (handle, metrics) = build_handle_with_metrics().await;
+    let (sender, receiver) = async_broadcast::broadcast(1024);
+    let views: Vec<_> = TestViewGenerator::generate((*handle.hotshot.memberships).clone())
+        .take(2)
+        .collect()
+        .await;
+
+    let mut state =
+        QuorumVoteTaskState::<TestTypes, MemoryImpl, TestVersions>::create_from(&handle).await;
+
+    // A vote without a proposal we saw arrive is not measured.
+    let vote = views[0].create_quorum_vote(&handle).await;
+    state
+        .handle(
+            Arc::new(HotShotEvent::QuorumVoteSend(vote)),
+            receiver.clone(),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(count(&metrics, "proposal_to_vote_duration"), 0);
+
+    state
+        .handle(
+            Arc::new(HotShotEvent::QuorumProposalRecv(
+                views[1].quorum_proposal.clone(),
+                views[1].leader_public_key,
+            )),
+            receiver.clone(),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+    let vote = views[1].create_quorum_vote(&handle).await;
+    state
+        .handle(
+            Arc::new(HotShotEvent::QuorumVoteSend(vote)),
+            receiver.clone(),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(count(&metrics, "proposal_to_vote_duration"), 1);
+
+    // The receive time is kept until the view is decided.
+    assert!(state
+        .proposal_recv_times
+        .contains_key(&views[1].view_number));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_vote_to_qc_latency() {
+    hotshot::helpers::initialize_logging();
+
+    let (handle, metrics) = build_handle_with_metrics().await;
+    let (sender, _receiver) = async_broadcast::broadcast(1024);
+    let views: Vec<_> = TestViewGenerator::generate((*handle.hotshot.memberships).clone())
+        .take(3)
+        .collect()
+        .await;
+
+    let mut state =
+        ConsensusTaskState::<TestTypes, MemoryImpl, TestVersions>::create_from(&handle).await;
+    let qc = views[2].quorum_proposal.data.justify_qc.clone();
+    state
+        .first_vote_times
+        .insert(qc.view_number, Instant::now());
+
+    state
+        .handle(
+            Arc::new(HotShotEvent::Qc2Formed(Either::Left(qc.clone()))),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(count(&metrics, "vote_to_qc_duration"), 1);
+    assert!(state.first_vote_times.is_empty());
+
+    // The same QC again has nothing left to measure.
+    state
+        .handle(Arc::new(HotShotEvent::Qc2Formed(Either::Left(qc))), sender)
+        .await
+        .unwrap();
+    assert_eq!(count(&metrics, "vote_to_qc_duration"), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_da_proposal_to_dac_latency() {
+    hotshot::helpers::initialize_logging();
+
+    let (handle, metrics) = build_handle_with_metrics().await;
+    let (sender, _receiver) = async_broadcast::broadcast(1024);
+    let views: Vec<_> = TestViewGenerator::generate((*handle.hotshot.memberships).clone())
+        .take(2)
+        .collect()
+        .await;
+
+    let mut state = DaTaskState::<TestTypes, MemoryImpl, TestVersions>::create_from(&handle).await;
+    state
+        .proposal_send_times
+        .insert(views[1].view_number, Instant::now());
+
+    // A DAC for a proposal we did not send is not measured.
+    state
+        .handle(
+            Arc::new(HotShotEvent::DacSend(
+                views[0].da_certificate.clone(),
+                handle.public_key(),
+            )),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(count(&metrics, "da_proposal_to_dac_duration"), 0);
+
+    state
+        .handle(
+            Arc::new(HotShotEvent::DacSend(
+                views[1].da_certificate.clone(),
+                handle.public_key(),
+            )),
+            sender,
+        )
+        .await
+        .unwrap();
+    assert_eq!(count(&metrics, "da_proposal_to_dac_duration"), 1);
+    assert!(state.proposal_send_times.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_vid_disperse_latency() {
+    hotshot::helpers::initialize_logging();
+
+    let (handle, metrics) = build_handle_with_metrics().await;
+    let (sender, _receiver) = async_broadcast::broadcast(1024);
+    let membership = (*handle.hotshot.memberships).clone();
+    let views: Vec<_> = TestViewGenerator::generate(membership.clone())
+        .take(2)
+        .collect()
+        .await;
+    let da_proposal = &views[1].da_proposal.data;
+
+    let mut state = VidTaskState::<TestTypes, MemoryImpl>::create_from(&handle).await;
+    state
+        .handle(
+            Arc::new(HotShotEvent::BlockRecv(PackedBundle::new(
+                Arc::clone(&da_proposal.encoded_transactions),
+                da_proposal.metadata,
+                da_proposal.view_number,
+                vec1![null_block::builder_fee::<TestTypes, TestVersions>(
+                    membership.total_nodes(EpochNumber::new(0)),
+                    <TestVersions as Versions>::Base::VERSION,
+                    *da_proposal.view_number,
+                )
+                .unwrap()],
+                None,
+                None,
+            ))),
+            sender,
+        )
+        .await;
+    assert_eq!(count(&metrics, "vid_disperse_duration"), 1);
+
+    // Every latency histogram is exported, measured or not.
+    for name in [
+        "proposal_to_vote_duration",
+        "vote_to_qc_duration",
+        "da_proposal_to_dac_duration",
+        "builder_response_duration",
+        "proposal_to_decide_duration",
+    ] {
+        assert_eq!(count(&metrics, name), 0);
+    }
+}
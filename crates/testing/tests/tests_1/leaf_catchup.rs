@@ -0,0 +1,263 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{sync::Arc, time::Duration};
+
+use committable::Committable;
+use futures::StreamExt;
+use hotshot::tasks::task_state::CreateTaskState;
+use hotshot_example_types::{
+    node_types::{MemoryImpl, TestTypes, TestVersions},
+    state_types::TestValidatedState,
+};
+use hotshot_task::task::TaskState;
+use hotshot_task_impls::{
+    events::HotShotEvent,
+    quorum_vote::QuorumVoteTaskState,
+    response::{run_response_task, NetworkResponseState},
+};
+use hotshot_testing::{
+    helpers::{build_system_handle, key_pair_for_id},
+    view_generator::{TestView, TestViewGenerator},
+};
+use hotshot_types::{
+    data::{EpochNumber, ViewNumber},
+    drb::DRB_EPOCHS_AHEAD,
+    message::UpgradeLock,
+    request_response::DecidedLeaves,
+    signature_key::BLSPubKey,
+    traits::{
+        network::{DataRequest, RequestKind},
+        node_implementation::ConsensusTime,
+        signature_key::SignatureKey,
+        storage::Storage,
+    },
+    utils::epoch_from_block_number,
+};
+use sha2::{Digest, Sha256};
+use tokio::time::timeout;
+
+/// The decided leaves of `views`
+fn decided_leaves(views: &[TestView]) -> DecidedLeaves<TestTypes> {
+    DecidedLeaves {
+        leaves: views.iter().map(|view| view.leaf.clone()).collect(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_decided_leaves_validation() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let views: Vec<TestView> = TestViewGenerator::generate((*handle.hotshot.memberships).clone())
+        .take(5)
+        .collect()
+        .await;
+
+    decided_leaves(&views[1..4])
+        .validate(views[3].leaf.commit())
+        .unwrap();
+
+    // The newest leaf must be the one we asked for
+    assert!(decided_leaves(&views[1..4])
+        .validate(views[4].leaf.commit())
+        .is_err());
+
+    // Each leaf must be the parent of the next
+    let gap = [views[1].clone(), views[3].clone()];
+    assert!(decided_leaves(&gap)
+        .validate(views[3].leaf.commit())
+        .is_err());
+
+    // There must be leaves
+    assert!(decided_leaves(&[])
+        .validate(views[3].leaf.commit())
+        .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_response_task_serves_decided_leaves() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let membership = Arc::clone(&handle.hotshot.memberships);
+    let views: Vec<TestView> = TestViewGenerator::generate((*membership).clone())
+        .take(6)
+        .collect()
+        .await;
+
+    // We stored the decided leaves of views 1 to 3, and of view 5, but not of view 4
+    let storage = handle.storage();
+    let decided: Vec<_> = views[0..3]
+        .iter()
+        .chain(&views[4..5])
+        .map(|view| view.leaf.clone())
+        .collect();
+    storage
+        .write()
+        .await
+        .append_decided_leaves(&decided)
+        .await
+        .unwrap();
+
+    let (private_key, public_key) = key_pair_for_id::<TestTypes>(1);
+    let (sender, receiver) = async_broadcast::broadcast(16);
+    let mut output = receiver.clone();
+    let (own_private_key, own_public_key) = key_pair_for_id::<TestTypes>(2);
+    let response_task = run_response_task(
+        NetworkResponseState::<TestTypes, MemoryImpl, TestVersions>::new(
+            handle.hotshot.consensus(),
+            storage,
+            membership,
+            own_public_key,
+            own_private_key,
+            2,
+            None,
+            UpgradeLock::new(),
+        ),
+        receiver,
+        sender.clone(),
+    );
+
+    // Only the stored leaves in the range are served, and only those after the last one missing
+    for (request, expected) in [
+        (
+            RequestKind::Leaves(ViewNumber::new(2), ViewNumber::new(3)),
+            decided_leaves(&views[1..3]),
+        ),
+        (
+            RequestKind::LeavesByHeight(views[0].leaf.height(), views[0].leaf.height()),
+            decided_leaves(&views[0..1]),
+        ),
+        (
+            RequestKind::Leaves(ViewNumber::new(2), ViewNumber::new(6)),
+            decided_leaves(&views[4..5]),
+        ),
+    ] {
+        let signature = BLSPubKey::sign(
+            &private_key,
+            &Sha256::digest(
+                DataRequest::signed_data(
+                    &request,
+                    ViewNumber::new(6),
+                    &UpgradeLock::<TestTypes, TestVersions>::new(),
+                )
+                .await
+                .unwrap(),
+            ),
+        )
+        .unwrap();
+        sender
+            .broadcast(Arc::new(HotShotEvent::LeavesRequestRecv(
+                DataRequest {
+                    request,
+                    view: ViewNumber::new(6),
+                    signature,
+                },
+                public_key,
+            )))
+            .await
+            .unwrap();
+
+        let decided = timeout(Duration::from_secs(5), async {
+            loop {
+                if let HotShotEvent::LeavesResponseSend(_, to, decided) =
+                    output.recv().await.unwrap().as_ref()
+                {
+                    assert_eq!(*to, public_key);
+                    return decided.clone();
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(decided, expected);
+    }
+
+    response_task.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fetched_epoch_roots_apply_before_newer_ones() {
+    hotshot::helpers::initialize_logging();
+
+    /// Blocks per epoch, so the blocks of views 4 and 7 are epoch roots
+    const EPOCH_HEIGHT: u64 = 3;
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let views: Vec<TestView> = TestViewGenerator::generate((*handle.hotshot.memberships).clone())
+        .take(10)
+        .collect()
+        .await;
+
+    // We missed the leaves of views 1 to 4, so deciding view 7 leaves a gap with an epoch root
+    let consensus = handle.hotshot.consensus();
+    let mut consensus_writer = consensus.write().await;
+    for view in &views[4..9] {
+        consensus_writer
+            .update_leaf(
+                view.leaf.clone(),
+                Arc::new(TestValidatedState::default()),
+                None,
+            )
+            .unwrap();
+    }
+    drop(consensus_writer);
+
+    let mut state =
+        QuorumVoteTaskState::<TestTypes, MemoryImpl, TestVersions>::create_from(&handle).await;
+    state.epoch_height = EPOCH_HEIGHT;
+    let (mut sender, receiver) = async_broadcast::broadcast(1024);
+    sender.set_overflow(true);
+
+    // Whether the epoch root at `height` was applied, which seeds the DRB of a later epoch
+    let seeded = |height: u64| {
+        let consensus = Arc::clone(&consensus);
+        async move {
+            consensus.read().await.has_drb_seed(EpochNumber::new(
+                epoch_from_block_number(height, EPOCH_HEIGHT) + DRB_EPOCHS_AHEAD,
+            ))
+        }
+    };
+
+    state
+        .handle(
+            Arc::new(HotShotEvent::QuorumProposalValidated(
+                views[9].quorum_proposal.clone(),
+                views[8].leaf.clone(),
+            )),
+            receiver.clone(),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+
+    // The decide waits for the leaves it skipped, and so does its epoch root
+    assert!(!seeded(views[3].leaf.height()).await);
+    assert!(!seeded(views[6].leaf.height()).await);
+
+    state
+        .handle(
+            Arc::new(HotShotEvent::MissingLeavesFetched(
+                views[0..4].iter().map(|view| view.leaf.clone()).collect(),
+            )),
+            receiver.clone(),
+            sender.clone(),
+        )
+        .await
+        .unwrap();
+
+    assert!(seeded(views[3].leaf.height()).await);
+    assert!(seeded(views[6].leaf.height()).await);
+
+    state.cancel_subtasks();
+}
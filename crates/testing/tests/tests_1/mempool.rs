@@ -0,0 +1,147 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use committable::Committable;
+use hotshot_example_types::{block_types::TestTransaction, node_types::TestTypes};
+use hotshot_task_impls::mempool::{FifoPriority, Mempool, MempoolRejection, TransactionPriority};
+use hotshot_types::mempool_config::MempoolConfig;
+
+/// Prioritizes transactions by their first byte
+struct FirstBytePriority;
+
+impl TransactionPriority<TestTypes> for FirstBytePriority {
+    fn priority(&self, transaction: &TestTransaction) -> u64 {
+        transaction
+            .bytes()
+            .first()
+            .copied()
+            .unwrap_or_default()
+            .into()
+    }
+}
+
+/// A config holding at most `max_transactions` transactions of at most `max_bytes` bytes
+fn config(max_transactions: usize, max_bytes: u64) -> MempoolConfig {
+    MempoolConfig {
+        max_transactions,
+        max_bytes,
+        max_age: Duration::from_secs(60),
+        ..MempoolConfig::default()
+    }
+}
+
+#[test]
+fn test_mempool_deduplicates_and_forgets_decided() {
+    let mut mempool = Mempool::<TestTypes>::new(config(10, 1000), Arc::new(FifoPriority));
+    let now = Instant::now();
+    let transaction = TestTransaction::new(vec![1, 2, 3]);
+
+    assert_eq!(mempool.insert(transaction.clone(), now), Ok(()));
+    assert_eq!(
+        mempool.insert(transaction.clone(), now),
+        Err(MempoolRejection::Duplicate)
+    );
+    assert_eq!(mempool.len(), 1);
+    assert_eq!(mempool.total_bytes(), 3);
+
+    // Once decided, the transaction is removed and late copies of it are refused
+    mempool.remove_decided([transaction.commit()]);
+    assert!(mempool.is_empty());
+    assert_eq!(mempool.total_bytes(), 0);
+    assert_eq!(
+        mempool.insert(transaction, now),
+        Err(MempoolRejection::Decided)
+    );
+}
+
+#[test]
+fn test_mempool_expires_old_transactions() {
+    let mut mempool = Mempool::<TestTypes>::new(config(10, 1000), Arc::new(FifoPriority));
+    let start = Instant::now();
+    let old = TestTransaction::new(vec![1]);
+    let new = TestTransaction::new(vec![2]);
+
+    mempool.insert(old.clone(), start).unwrap();
+    mempool
+        .insert(new.clone(), start + Duration::from_secs(30))
+        .unwrap();
+
+    mempool.expire(start + Duration::from_secs(60));
+    assert!(!mempool.contains(&old.commit()));
+    assert!(mempool.contains(&new.commit()));
+
+    mempool.expire(start + Duration::from_secs(90));
+    assert!(mempool.is_empty());
+}
+
+#[test]
+fn test_mempool_evicts_lowest_priority() {
+    let mut mempool = Mempool::<TestTypes>::new(config(2, 1000), Arc::new(FirstBytePriority));
+    let now = Instant::now();
+    let low = TestTransaction::new(vec![1]);
+    let middle = TestTransaction::new(vec![5]);
+    let high = TestTransaction::new(vec![9]);
+
+    mempool.insert(middle.clone(), now).unwrap();
+    mempool.insert(low.clone(), now).unwrap();
+
+    // A full mempool makes room for a higher priority transaction, but not for an equal one
+    assert_eq!(mempool.insert(high.clone(), now), Ok(()));
+    assert!(!mempool.contains(&low.commit()));
+    assert_eq!(
+        mempool.insert(TestTransaction::new(vec![5, 0]), now),
+        Err(MempoolRejection::Full)
+    );
+    assert_eq!(mempool.insert(low, now), Err(MempoolRejection::Full));
+
+    assert_eq!(
+        mempool.select(u64::MAX, &HashSet::new()),
+        vec![high, middle]
+    );
+}
+
+#[test]
+fn test_mempool_size_limits() {
+    let mut mempool = Mempool::<TestTypes>::new(config(10, 10), Arc::new(FifoPriority));
+    let now = Instant::now();
+    let first = TestTransaction::new(vec![0; 4]);
+    let second = TestTransaction::new(vec![1; 4]);
+    let third = TestTransaction::new(vec![2; 2]);
+
+    mempool.insert(first.clone(), now).unwrap();
+    mempool.insert(second.clone(), now).unwrap();
+    mempool.insert(third.clone(), now).unwrap();
+    // Transactions received first take priority over later ones of the same priority
+    assert_eq!(
+        mempool.insert(TestTransaction::new(vec![3]), now),
+        Err(MempoolRejection::Full)
+    );
+    assert_eq!(
+        mempool.insert(TestTransaction::new(vec![4; 11]), now),
+        Err(MempoolRejection::Full)
+    );
+
+    // Selection skips transactions that do not fit, but keeps filling the block
+    assert_eq!(
+        mempool.select(6, &HashSet::new()),
+        vec![first.clone(), third.clone()]
+    );
+    assert_eq!(mempool.select(1, &HashSet::new()), vec![]);
+    // Transactions of undecided blocks make room for the ones after them
+    assert_eq!(
+        mempool.select(6, &[first.commit()].into()),
+        vec![second, third]
+    );
+    // Selected transactions stay until they are decided
+    assert_eq!(mempool.len(), 3);
+    assert!(mempool.contains(&first.commit()));
+}
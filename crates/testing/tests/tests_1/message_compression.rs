@@ -0,0 +1,180 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use committable::Committable;
+use hotshot_example_types::{
+    block_types::TestMetadata,
+    node_types::{CompressionUpgradeTestVersions, TestTypes},
+};
+use hotshot_types::{
+    data::{DaProposal, ViewNumber},
+    message::{
+        DaConsensusMessage, GeneralConsensusMessage, Message, MessageKind, MessagePurpose,
+        Proposal, SequencingMessage, UpgradeLock, COMPRESSION_THRESHOLD,
+    },
+    signature_key::BLSPubKey,
+    simple_certificate::{SimpleCertificate, UpgradeCertificate},
+    simple_vote::{UpgradeProposalData, ViewSyncCommitData},
+    traits::{
+        node_implementation::{ConsensusTime, Versions},
+        signature_key::SignatureKey,
+    },
+};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use vbs::version::{StaticVersionType, Version};
+
+/// The first view of the version that compresses messages
+const UPGRADE_VIEW: u64 = 10;
+
+/// An upgrade lock that has decided to move to the compressing version at [`UPGRADE_VIEW`]
+fn upgraded_lock() -> UpgradeLock<TestTypes, CompressionUpgradeTestVersions> {
+    let data = UpgradeProposalData {
+        old_version: <CompressionUpgradeTestVersions as Versions>::Base::VERSION,
+        new_version: <CompressionUpgradeTestVersions as Versions>::Upgrade::VERSION,
+        decide_by: ViewNumber::new(UPGRADE_VIEW),
+        new_version_hash: vec![],
+        old_version_last_view: ViewNumber::new(UPGRADE_VIEW - 1),
+        new_version_first_view: ViewNumber::new(UPGRADE_VIEW),
+    };
+    let certificate: UpgradeCertificate<TestTypes> = SimpleCertificate::new(
+        data.clone(),
+        data.commit(),
+        ViewNumber::new(1),
+        None,
+        PhantomData,
+    );
+
+    UpgradeLock::from_certificate(&Some(certificate))
+}
+
+/// A DA proposal for `view` carrying `payload`
+fn da_proposal_message(view: u64, payload: Vec<u8>) -> Message<TestTypes> {
+    let (sender, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0);
+    let data = DaProposal {
+        encoded_transactions: Arc::from(payload),
+        metadata: TestMetadata {
+            num_transactions: 1,
+        },
+        view_number: ViewNumber::new(view),
+    };
+
+    Message {
+        sender,
+        kind: MessageKind::from_consensus_message(SequencingMessage::Da(
+            DaConsensusMessage::DaProposal(Proposal {
+                data,
+                signature: BLSPubKey::sign(&private_key, &[]).unwrap(),
+                _pd: PhantomData,
+            }),
+        )),
+    }
+}
+
+/// Length of the version prefix of a serialized message
+fn header_len(serialized_message: &[u8]) -> usize {
+    serialized_message.len() - Version::deserialize(serialized_message).unwrap().1.len()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_large_messages_are_compressed_after_upgrade() {
+    let upgrade_lock = upgraded_lock();
+    let payload = vec![7u8; 16 * COMPRESSION_THRESHOLD];
+
+    // Before the upgrade, messages go out exactly as before.
+    let message = da_proposal_message(UPGRADE_VIEW - 1, payload.clone());
+    let (serialized, uncompressed_size) = upgrade_lock.serialize_with_size(&message).await.unwrap();
+    assert_eq!(serialized.len(), uncompressed_size);
+    let deserialized: Message<TestTypes> = upgrade_lock.deserialize(&serialized).await.unwrap();
+    assert_eq!(deserialized, message);
+
+    // From the upgrade on, they are compressed and still round-trip.
+    let message = da_proposal_message(UPGRADE_VIEW, payload);
+    let (serialized, uncompressed_size) = upgrade_lock.serialize_with_size(&message).await.unwrap();
+    assert!(serialized.len() * 4 < uncompressed_size);
+    assert_eq!(
+        Version::deserialize(&serialized).unwrap().0,
+        <CompressionUpgradeTestVersions as Versions>::Compression::VERSION
+    );
+    let deserialized: Message<TestTypes> = upgrade_lock.deserialize(&serialized).await.unwrap();
+    assert_eq!(deserialized, message);
+    assert_eq!(upgrade_lock.serialize(&message).await.unwrap(), serialized);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_small_messages_are_not_compressed() {
+    let upgrade_lock = upgraded_lock();
+    let view_number = ViewNumber::new(UPGRADE_VIEW);
+    let data: ViewSyncCommitData<TestTypes> = ViewSyncCommitData {
+        relay: 37,
+        round: view_number,
+    };
+    let message = Message {
+        sender: BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0,
+        kind: MessageKind::Consensus(SequencingMessage::General(
+            GeneralConsensusMessage::ViewSyncCommitCertificate(SimpleCertificate::new(
+                data.clone(),
+                data.commit(),
+                view_number,
+                None,
+                PhantomData,
+            )),
+        )),
+    };
+
+    // Only the compression flag is added.
+    let (serialized, uncompressed_size) = upgrade_lock.serialize_with_size(&message).await.unwrap();
+    assert!(uncompressed_size < COMPRESSION_THRESHOLD);
+    assert_eq!(serialized.len(), uncompressed_size + 1);
+    let deserialized: Message<TestTypes> = upgrade_lock.deserialize(&serialized).await.unwrap();
+    assert_eq!(deserialized, message);
+
+    // Incompressible payloads are sent as is, even when large.
+    let mut payload = vec![0u8; 16 * COMPRESSION_THRESHOLD];
+    StdRng::seed_from_u64(0).fill_bytes(&mut payload);
+    let message = da_proposal_message(UPGRADE_VIEW, payload);
+    let (serialized, uncompressed_size) = upgrade_lock.serialize_with_size(&message).await.unwrap();
+    assert_eq!(serialized.len(), uncompressed_size + 1);
+    let deserialized: Message<TestTypes> = upgrade_lock.deserialize(&serialized).await.unwrap();
+    assert_eq!(deserialized, message);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_malformed_compressed_messages_are_rejected() {
+    let upgrade_lock = upgraded_lock();
+    let message = da_proposal_message(UPGRADE_VIEW, vec![7u8; 16 * COMPRESSION_THRESHOLD]);
+    let serialized = upgrade_lock.serialize(&message).await.unwrap();
+    let header_len = header_len(&serialized);
+
+    // An unknown compression flag
+    let mut unknown_flag = serialized.clone();
+    unknown_flag[header_len] = 0xff;
+    assert!(upgrade_lock
+        .deserialize::<Message<TestTypes>>(&unknown_flag)
+        .await
+        .is_err());
+
+    // A missing compression flag
+    assert!(upgrade_lock
+        .deserialize::<Message<TestTypes>>(&serialized[..header_len])
+        .await
+        .is_err());
+
+    // A corrupted deflate stream
+    let mut corrupted = serialized[..=header_len].to_vec();
+    corrupted.extend_from_slice(&[0xff; 64]);
+    assert!(upgrade_lock
+        .deserialize::<Message<TestTypes>>(&corrupted)
+        .await
+        .is_err());
+}
+
+#[test]
+fn test_message_purpose() {
+    let message = da_proposal_message(1, vec![]);
+    assert_eq!(message.kind.purpose(), MessagePurpose::Proposal);
+}
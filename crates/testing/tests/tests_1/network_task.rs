@@ -68,6 +68,7 @@ async fn test_network_task() {
             upgrade_lock: upgrade_lock.clone(),
             storage,
             consensus,
+            metrics: handle.hotshot.metrics(),
             transmit_tasks: BTreeMap::new(),
         };
     let (tx, rx) = async_broadcast::broadcast(10);
@@ -237,6 +238,7 @@ async fn test_network_storage_fail() {
             upgrade_lock: upgrade_lock.clone(),
             storage,
             consensus,
+            metrics: handle.hotshot.metrics(),
             transmit_tasks: BTreeMap::new(),
         };
     let (tx, rx) = async_broadcast::broadcast(10);
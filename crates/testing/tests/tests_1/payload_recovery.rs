@@ -0,0 +1,174 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use hotshot_example_types::{
+    block_types::TestTransaction,
+    node_types::{MemoryImpl, TestTypes, TestVersions},
+};
+use hotshot_task_impls::{
+    events::HotShotEvent,
+    payload_recovery::PayloadRecovery,
+    response::{run_response_task, NetworkResponseState},
+};
+use hotshot_testing::{
+    helpers::{build_system_handle, key_pair_for_id},
+    view_generator::{TestView, TestViewGenerator},
+};
+use hotshot_types::{
+    data::{Leaf2, ViewNumber},
+    message::UpgradeLock,
+    signature_key::BLSPubKey,
+    traits::{
+        network::{DataRequest, RequestKind},
+        node_implementation::ConsensusTime,
+        signature_key::SignatureKey,
+    },
+    vid::vid_recovery_threshold,
+};
+use sha2::{Digest, Sha256};
+use tokio::time::timeout;
+
+#[test]
+fn test_vid_recovery_threshold() {
+    // No payload can be dispersed to no nodes, so there is no threshold to recover it
+    assert_eq!(vid_recovery_threshold(0), None);
+    assert_eq!(vid_recovery_threshold(1), Some(1));
+    assert_eq!(vid_recovery_threshold(10), Some(8));
+    assert_eq!(vid_recovery_threshold(16), Some(16));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payload_recovery_from_vid_shares() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let mut generator = TestViewGenerator::generate((*handle.hotshot.memberships).clone());
+    let genesis = generator.next().await.unwrap();
+    generator.add_transactions(vec![TestTransaction::new(vec![1, 2, 3])]);
+    let view = generator.next().await.unwrap();
+
+    // Without its payload, as a node outside the DA committee decides it
+    let mut leaf = Leaf2::from_quorum_proposal(&view.quorum_proposal.data);
+    let mut recovery = PayloadRecovery::new(leaf.payload_commitment());
+
+    // A share of another payload is rejected
+    assert!(recovery.add_share(&genesis.vid_proposal.0[0].data).is_err());
+
+    let shares = &view.vid_proposal.0;
+    for share in shares {
+        assert!(!recovery.is_complete());
+        recovery.add_share(&share.data).unwrap();
+        // The same share twice does not count
+        recovery.add_share(&share.data).unwrap();
+        if recovery.is_complete() {
+            break;
+        }
+    }
+    assert!(recovery.is_complete());
+
+    recovery.fill(&mut leaf).unwrap();
+    assert_eq!(leaf.block_payload(), view.leaf.block_payload());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_response_task_serves_vid_shares() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let membership = Arc::clone(&handle.hotshot.memberships);
+    let mut generator = TestViewGenerator::generate((*membership).clone());
+    let transactions = vec![TestTransaction::new(vec![4, 5, 6])];
+    generator.add_transactions(transactions.clone());
+    let views: Vec<TestView> = generator.take(2).collect().await;
+    let view = &views[1];
+
+    // As a DA member, we hold the payload of the view
+    let consensus = handle.hotshot.consensus();
+    consensus
+        .write()
+        .await
+        .update_saved_payloads(
+            view.view_number,
+            Arc::from(TestTransaction::encode(&transactions)),
+        )
+        .unwrap();
+
+    let (private_key, public_key) = key_pair_for_id::<TestTypes>(1);
+    let (sender, receiver) = async_broadcast::broadcast(16);
+    let mut output = receiver.clone();
+    let (own_private_key, own_public_key) = key_pair_for_id::<TestTypes>(2);
+    let response_task = run_response_task(
+        NetworkResponseState::<TestTypes, MemoryImpl, TestVersions>::new(
+            consensus,
+            handle.storage(),
+            membership,
+            own_public_key,
+            own_private_key,
+            2,
+            None,
+            UpgradeLock::new(),
+        ),
+        receiver,
+        sender.clone(),
+    );
+
+    let request = RequestKind::VidShares(view.view_number);
+    let signature = BLSPubKey::sign(
+        &private_key,
+        &Sha256::digest(
+            DataRequest::signed_data(
+                &request,
+                ViewNumber::new(2),
+                &UpgradeLock::<TestTypes, TestVersions>::new(),
+            )
+            .await
+            .unwrap(),
+        ),
+    )
+    .unwrap();
+    sender
+        .broadcast(Arc::new(HotShotEvent::VidSharesRequestRecv(
+            DataRequest {
+                request,
+                view: ViewNumber::new(2),
+                signature,
+            },
+            public_key,
+        )))
+        .await
+        .unwrap();
+
+    let shares = timeout(Duration::from_secs(5), async {
+        loop {
+            if let HotShotEvent::VidSharesResponseSend(_, to, shares) =
+                output.recv().await.unwrap().as_ref()
+            {
+                assert_eq!(*to, public_key);
+                return shares.clone();
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    // The shares we get are enough to recover the payload on their own
+    let mut leaf = Leaf2::from_quorum_proposal(&view.quorum_proposal.data);
+    let mut recovery = PayloadRecovery::new(leaf.payload_commitment());
+    for share in &shares {
+        recovery.add_share(share).unwrap();
+    }
+    recovery.fill(&mut leaf).unwrap();
+    assert_eq!(leaf.block_payload(), view.leaf.block_payload());
+
+    response_task.abort();
+}
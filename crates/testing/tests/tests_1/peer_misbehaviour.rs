@@ -0,0 +1,149 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{num::NonZeroUsize, sync::Arc};
+
+use hotshot::traits::implementations::PrometheusMetrics;
+use hotshot_example_types::node_types::{TestTypes, TestVersions};
+use hotshot_task_impls::{
+    events::HotShotEvent, network::NetworkMessageTaskState, rate_limit::RateLimiter,
+};
+use hotshot_types::{
+    consensus::ConsensusMetricsValue,
+    data::ViewNumber,
+    message::{DataMessage, Message, MessageKind, UpgradeLock},
+    rate_limit_config::RateLimitConfig,
+    signature_key::BLSPubKey,
+    traits::{
+        metrics::Metrics,
+        network::{DataRequest, DenialReason, PeerMisbehaviour, RequestKind, ResponseMessage},
+        node_implementation::ConsensusTime,
+        signature_key::SignatureKey,
+    },
+};
+use libp2p_identity::PeerId;
+use sha2::{Digest, Sha256};
+
+/// The public key of the node with index `index`
+fn key(index: u64) -> BLSPubKey {
+    BLSPubKey::generated_from_seed_indexed([0u8; 32], index).0
+}
+
+/// A response from the node with index `sender`
+fn response_message(sender: u64, response: ResponseMessage<TestTypes>) -> Message<TestTypes> {
+    Message {
+        sender: key(sender),
+        kind: MessageKind::Data(DataMessage::DataResponse(response)),
+    }
+}
+
+/// A request for VID shares that claims to come from the node with index `sender`, signed by the
+/// node with index `signer`
+async fn request_message(sender: u64, signer: u64) -> Message<TestTypes> {
+    let request = RequestKind::VidShares(ViewNumber::new(1));
+    let private_key = BLSPubKey::generated_from_seed_indexed([0u8; 32], signer).1;
+    let upgrade_lock = UpgradeLock::<TestTypes, TestVersions>::new();
+    let data = DataRequest::signed_data(&request, ViewNumber::new(1), &upgrade_lock)
+        .await
+        .unwrap();
+
+    Message {
+        sender: key(sender),
+        kind: MessageKind::Data(DataMessage::RequestData(DataRequest {
+            signature: BLSPubKey::sign(&private_key, &Sha256::digest(data)).unwrap(),
+            request,
+            view: ViewNumber::new(1),
+        })),
+    }
+}
+
+/// The state of a network message task, and the receiver of the events it sends to other tasks
+fn network_message_task() -> (
+    NetworkMessageTaskState<TestTypes, TestVersions>,
+    async_broadcast::Receiver<Arc<HotShotEvent<TestTypes>>>,
+) {
+    let (internal_sender, internal_receiver) = async_broadcast::broadcast(16);
+    let (external_sender, _external_receiver) = async_broadcast::broadcast(16);
+    let metrics = PrometheusMetrics::new();
+    let state = NetworkMessageTaskState::<TestTypes, TestVersions> {
+        internal_event_stream: internal_sender,
+        external_event_stream: external_sender,
+        public_key: key(1),
+        transactions_cache: lru::LruCache::new(NonZeroUsize::new(100).unwrap()),
+        rate_limiter: RateLimiter::new(RateLimitConfig::default()),
+        metrics: Arc::new(ConsensusMetricsValue::new(
+            &*metrics.subgroup("consensus".into()),
+        )),
+        upgrade_lock: UpgradeLock::new(),
+    };
+
+    (state, internal_receiver)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_denied_responses_are_not_reported() {
+    let (mut state, mut internal_receiver) = network_message_task();
+    let peer = PeerId::random();
+
+    // Honest peers deny requests they cannot serve, so denials do not count against the peer
+    for reason in [
+        DenialReason::NoStake,
+        DenialReason::RateLimited,
+        DenialReason::Pruned,
+    ] {
+        state
+            .handle_message(
+                response_message(0, ResponseMessage::DeniedFor(reason)),
+                Some(peer),
+            )
+            .await;
+        let event = internal_receiver.try_recv().unwrap();
+        assert!(matches!(
+            event.as_ref(),
+            HotShotEvent::RequestDeniedRecv(sender, denied)
+                if *sender == key(0) && *denied == reason
+        ));
+        assert!(internal_receiver.try_recv().is_err());
+    }
+
+    // Nor is not having the data, or a denial that does not say why
+    state
+        .handle_message(response_message(0, ResponseMessage::NotFound), Some(peer))
+        .await;
+    state
+        .handle_message(response_message(0, ResponseMessage::Denied), Some(peer))
+        .await;
+    assert!(internal_receiver.try_recv().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_badly_signed_requests_report_the_peer() {
+    let (mut state, mut internal_receiver) = network_message_task();
+    let peer = PeerId::random();
+
+    // A request claiming to come from node 0 but signed by node 2 is dropped, and the peer that
+    // sent it is reported rather than node 0
+    state
+        .handle_message(request_message(0, 2).await, Some(peer))
+        .await;
+    let event = internal_receiver.try_recv().unwrap();
+    assert!(matches!(
+        event.as_ref(),
+        HotShotEvent::PeerMisbehaved(reported, PeerMisbehaviour::InvalidSignature)
+            if *reported == peer
+    ));
+    assert!(internal_receiver.try_recv().is_err());
+
+    // A properly signed request is passed on
+    state
+        .handle_message(request_message(0, 0).await, Some(peer))
+        .await;
+    let event = internal_receiver.try_recv().unwrap();
+    assert!(matches!(
+        event.as_ref(),
+        HotShotEvent::VidSharesRequestRecv(_, sender) if *sender == key(0)
+    ));
+}
@@ -0,0 +1,126 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use hotshot::traits::implementations::PrometheusMetrics;
+use hotshot_types::{consensus::ConsensusMetricsValue, traits::metrics::Metrics};
+use tokio::net::TcpStream;
+
+#[test]
+fn test_prometheus_metrics_export() {
+    let metrics = PrometheusMetrics::new();
+
+    let counter = metrics.create_counter("messages".into(), None);
+    counter.add(3);
+    counter.add(4);
+
+    let gauge = metrics.create_gauge("peers".into(), Some("nodes".into()));
+    gauge.set(10);
+    gauge.update(-3);
+
+    let histogram = metrics.create_histogram("latency".into(), Some("s".into()));
+    histogram.add_point(0.5);
+    histogram.add_point(1.5);
+
+    metrics.create_text("hotshot".into());
+
+    let export = metrics.export().unwrap();
+    assert!(export.contains("messages 7"), "{export}");
+    assert!(export.contains("# HELP peers peers (nodes)"), "{export}");
+    assert!(export.contains("peers 7"), "{export}");
+    assert!(export.contains("latency_count 2"), "{export}");
+    assert!(export.contains("latency_sum 2"), "{export}");
+    assert!(export.contains("hotshot 1"), "{export}");
+}
+
+#[test]
+fn test_prometheus_metrics_subgroups_and_families() {
+    let metrics = PrometheusMetrics::new();
+    let consensus = metrics.subgroup("consensus".into());
+    let network = consensus.subgroup("network-v2".into());
+
+    consensus.create_gauge("current_view".into(), None).set(42);
+    network.create_counter("dropped".into(), None).add(1);
+
+    let sent = metrics.counter_family("sent".into(), vec!["purpose".into()]);
+    sent.create(vec!["vote".into()]).add(2);
+    sent.create(vec!["proposal".into()]).add(5);
+    // A metric with the wrong number of label values is a no-op.
+    sent.create(vec![]).add(100);
+
+    metrics
+        .text_family("version".into(), vec!["semver".into(), "rev".into()])
+        .create(vec!["0.1.0".into(), "891c5baa5".into()]);
+
+    let export = metrics.export().unwrap();
+    assert!(export.contains("consensus_current_view 42"), "{export}");
+    assert!(
+        export.contains("consensus_network_v2_dropped 1"),
+        "{export}"
+    );
+    assert!(export.contains(r#"sent{purpose="vote"} 2"#), "{export}");
+    assert!(export.contains(r#"sent{purpose="proposal"} 5"#), "{export}");
+    assert!(!export.contains("100"), "{export}");
+    assert!(
+        export.contains(r#"version{rev="891c5baa5",semver="0.1.0"} 1"#),
+        "{export}"
+    );
+}
+
+#[test]
+fn test_prometheus_metrics_shared() {
+    let metrics = PrometheusMetrics::new();
+
+    // Creating a metric twice, or through a clone, gives a handle to the same metric.
+    metrics.create_counter("decides".into(), None).add(1);
+    metrics
+        .clone()
+        .create_counter("decides".into(), None)
+        .add(1);
+
+    // A name taken by a metric of another kind gives a no-op.
+    metrics.create_gauge("decides".into(), None).set(100);
+
+    // The consensus metrics register against the registry like any other component.
+    let consensus = ConsensusMetricsValue::new(&*metrics.subgroup("consensus".into()));
+    consensus.last_decided_view.set(7);
+
+    let export = metrics.export().unwrap();
+    assert!(export.contains("decides 2"), "{export}");
+    assert!(!export.contains("decides 100"), "{export}");
+    assert!(export.contains("consensus_last_decided_view 7"), "{export}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_prometheus_metrics_server() {
+    let metrics = PrometheusMetrics::new();
+    metrics.create_counter("scraped".into(), None).add(1);
+
+    let server = metrics.serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let address = server.local_addr();
+    assert_ne!(address.port(), 0);
+
+    let url = format!("http://{address}/status/metrics");
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("scraped 1\n"), "{body}");
+
+    // Metrics created after the server started are served too.
+    metrics.create_gauge("late".into(), None).set(3);
+    let body = reqwest::get(&url).await.unwrap().text().await.unwrap();
+    assert!(body.contains("late 3"), "{body}");
+
+    let response = reqwest::get(format!("http://{address}/status/unknown"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // Dropping the server stops it.
+    drop(server);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(TcpStream::connect(address).await.is_err());
+}
@@ -0,0 +1,207 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hotshot::traits::implementations::PrometheusMetrics;
+use hotshot_example_types::{
+    block_types::TestMetadata,
+    node_types::{TestTypes, TestVersions},
+};
+use hotshot_task_impls::{
+    events::HotShotEvent,
+    network::NetworkMessageTaskState,
+    rate_limit::{RateLimitOutcome, RateLimiter},
+};
+use hotshot_types::{
+    consensus::ConsensusMetricsValue,
+    data::{DaProposal, ViewNumber},
+    event::EventType,
+    message::{
+        DaConsensusMessage, Message, MessageKind, MessagePurpose, MessageSource, Proposal,
+        SequencingMessage, UpgradeLock,
+    },
+    rate_limit_config::{RateLimit, RateLimitConfig},
+    signature_key::BLSPubKey,
+    traits::{metrics::Metrics, node_implementation::ConsensusTime, signature_key::SignatureKey},
+};
+use libp2p_identity::PeerId;
+use sha2::{Digest, Sha256};
+
+/// A config limiting proposals to `burst` at once and `per_second` after that
+fn proposal_limit(burst: u32, per_second: u32) -> RateLimitConfig {
+    RateLimitConfig {
+        limits: HashMap::from([(MessagePurpose::Proposal, RateLimit { burst, per_second })]),
+        request_quota: None,
+    }
+}
+
+/// The public key of the node with index `index`
+fn key(index: u64) -> BLSPubKey {
+    BLSPubKey::generated_from_seed_indexed([0u8; 32], index).0
+}
+
+/// A DA proposal for `view` from the node with index `sender`
+fn da_proposal_message(sender: u64, view: u64) -> Message<TestTypes> {
+    let (sender, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], sender);
+
+    Message {
+        sender,
+        kind: MessageKind::from_consensus_message(SequencingMessage::Da(
+            DaConsensusMessage::DaProposal(Proposal {
+                data: DaProposal {
+                    encoded_transactions: Arc::from(vec![]),
+                    metadata: TestMetadata {
+                        num_transactions: 0,
+                    },
+                    view_number: ViewNumber::new(view),
+                },
+                signature: BLSPubKey::sign(&private_key, &Sha256::digest(b"")).unwrap(),
+                _pd: PhantomData,
+            }),
+        )),
+    }
+}
+
+#[test]
+fn test_rate_limiter_token_bucket() {
+    let mut limiter = RateLimiter::new(proposal_limit(2, 4));
+    let start = Instant::now();
+
+    // A full burst is allowed, after which the peer is limited until its bucket refills.
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, start),
+        RateLimitOutcome::Allowed
+    );
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, start),
+        RateLimitOutcome::Allowed
+    );
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, start),
+        RateLimitOutcome::NewlyLimited
+    );
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, start),
+        RateLimitOutcome::Limited
+    );
+
+    // Other peers and purposes without a limit are unaffected.
+    assert_eq!(
+        limiter.check(&1, MessagePurpose::Proposal, start),
+        RateLimitOutcome::Allowed
+    );
+    for _ in 0..100 {
+        assert_eq!(
+            limiter.check(&0, MessagePurpose::Vote, start),
+            RateLimitOutcome::Allowed
+        );
+    }
+
+    // A quarter second later, one token is back.
+    let later = start + Duration::from_millis(250);
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, later),
+        RateLimitOutcome::Allowed
+    );
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, later),
+        RateLimitOutcome::NewlyLimited
+    );
+
+    // The bucket never holds more than a burst, however long the peer was quiet.
+    let much_later = start + Duration::from_secs(60);
+    for _ in 0..2 {
+        assert_eq!(
+            limiter.check(&0, MessagePurpose::Proposal, much_later),
+            RateLimitOutcome::Allowed
+        );
+    }
+    assert_eq!(
+        limiter.check(&0, MessagePurpose::Proposal, much_later),
+        RateLimitOutcome::NewlyLimited
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_network_message_task_drops_rate_limited_messages() {
+    let (internal_sender, mut internal_receiver) = async_broadcast::broadcast(16);
+    let (external_sender, mut external_receiver) = async_broadcast::broadcast(16);
+    let metrics = PrometheusMetrics::new();
+    let mut state = NetworkMessageTaskState::<TestTypes, TestVersions> {
+        internal_event_stream: internal_sender,
+        external_event_stream: external_sender,
+        public_key: key(1),
+        transactions_cache: lru::LruCache::new(NonZeroUsize::new(100).unwrap()),
+        rate_limiter: RateLimiter::new(proposal_limit(1, 0)),
+        metrics: Arc::new(ConsensusMetricsValue::new(
+            &*metrics.subgroup("consensus".into()),
+        )),
+        upgrade_lock: UpgradeLock::new(),
+    };
+
+    let (limited_peer, other_peer) = (PeerId::random(), PeerId::random());
+    for view in 1..=3 {
+        state
+            .handle_message(da_proposal_message(0, view), Some(limited_peer))
+            .await;
+    }
+    // The limit is per peer, so another peer claiming the same sender is unaffected.
+    state
+        .handle_message(da_proposal_message(0, 4), Some(other_peer))
+        .await;
+    // Messages the network could not authenticate are limited by the key that signed them, but
+    // never our own.
+    for sender in [1, 2] {
+        for view in 1..=3 {
+            state
+                .handle_message(da_proposal_message(sender, view), None)
+                .await;
+        }
+    }
+
+    let mut received = vec![];
+    while let Ok(event) = internal_receiver.try_recv() {
+        let HotShotEvent::DaProposalRecv(proposal, sender) = event.as_ref() else {
+            panic!("Unexpected event {event:?}");
+        };
+        let from = (0..3).find(|index| *sender == key(*index)).unwrap();
+        received.push((from, *proposal.data.view_number));
+    }
+    assert_eq!(
+        received,
+        vec![(0, 1), (0, 4), (1, 1), (1, 2), (1, 3), (2, 1)]
+    );
+
+    // Operators hear about each sender once, but every dropped message is counted.
+    for source in [
+        MessageSource::Peer(limited_peer),
+        MessageSource::Key(key(2)),
+    ] {
+        let event = external_receiver.try_recv().unwrap();
+        assert_eq!(*event.view_number, 2);
+        assert!(matches!(
+            event.event,
+            EventType::MessageRateLimited {
+                source: ref limited,
+                purpose: MessagePurpose::Proposal,
+            } if *limited == source
+        ));
+    }
+    assert!(external_receiver.try_recv().is_err());
+
+    let export = metrics.export().unwrap();
+    assert!(
+        export.contains(r#"consensus_rate_limited_messages{purpose="Proposal"} 4"#),
+        "{export}"
+    );
+}
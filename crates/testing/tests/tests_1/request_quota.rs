@@ -0,0 +1,187 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{sync::Arc, time::Duration};
+
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes, TestVersions};
+use hotshot_task_impls::{
+    events::HotShotEvent,
+    request::{PeerLatencies, MIN_REQUEST_TIMEOUT, REQUEST_TIMEOUT},
+    response::{run_response_task, NetworkResponseState},
+};
+use hotshot_testing::helpers::{build_system_handle, key_pair_for_id};
+use hotshot_types::{
+    data::ViewNumber,
+    message::UpgradeLock,
+    rate_limit_config::RateLimit,
+    signature_key::BLSPubKey,
+    traits::{
+        network::{DataRequest, DenialReason, RequestKind},
+        node_implementation::ConsensusTime,
+        signature_key::SignatureKey,
+    },
+};
+use sha2::{Digest, Sha256};
+use tokio::time::timeout;
+
+/// `request` for `view`, signed by `private_key`
+async fn data_request(
+    request: &RequestKind<TestTypes>,
+    private_key: &<BLSPubKey as SignatureKey>::PrivateKey,
+    view: ViewNumber,
+) -> DataRequest<TestTypes> {
+    let upgrade_lock = UpgradeLock::<TestTypes, TestVersions>::new();
+    let data = DataRequest::signed_data(request, view, &upgrade_lock)
+        .await
+        .unwrap();
+    DataRequest {
+        request: request.clone(),
+        view,
+        signature: BLSPubKey::sign(private_key, &Sha256::digest(data)).unwrap(),
+    }
+}
+
+#[test]
+fn test_peer_latencies_adapt_timeouts() {
+    let mut latencies = PeerLatencies::<u64>::default();
+    assert_eq!(latencies.timeout(&[1]), REQUEST_TIMEOUT);
+
+    latencies.record(1, Duration::from_millis(100));
+    assert_eq!(latencies.timeout(&[1]).as_millis(), 300);
+
+    // However fast a peer is, we give it some time
+    latencies.record(2, Duration::from_millis(10));
+    assert_eq!(latencies.timeout(&[2]), MIN_REQUEST_TIMEOUT);
+
+    // We wait for the slowest of the peers we ask, and the default for peers we do not know
+    assert_eq!(latencies.timeout(&[1, 2]).as_millis(), 300);
+    assert_eq!(latencies.timeout(&[1, 3]), REQUEST_TIMEOUT);
+
+    // A peer that gets slower gets more time
+    latencies.record(1, Duration::from_millis(500));
+    assert!(latencies.timeout(&[1]).as_millis() > 300);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_response_task_denies_requests() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let (sender, receiver) = async_broadcast::broadcast(16);
+    let mut output = receiver.clone();
+    let (own_private_key, own_public_key) = key_pair_for_id::<TestTypes>(2);
+    let response_task = run_response_task(
+        NetworkResponseState::<TestTypes, MemoryImpl, TestVersions>::new(
+            handle.hotshot.consensus(),
+            handle.storage(),
+            Arc::clone(&handle.hotshot.memberships),
+            own_public_key,
+            own_private_key,
+            2,
+            Some(RateLimit {
+                burst: 1,
+                per_second: 0,
+            }),
+            UpgradeLock::new(),
+        ),
+        receiver,
+        sender.clone(),
+    );
+
+    let request = RequestKind::Leaves(ViewNumber::new(1), ViewNumber::new(2));
+    let (private_key, public_key) = key_pair_for_id::<TestTypes>(1);
+    let (unstaked_private_key, unstaked_public_key) = key_pair_for_id::<TestTypes>(100);
+    let view = ViewNumber::new(2);
+
+    for (request, sender_key) in [
+        // Within the quota
+        (data_request(&request, &private_key, view).await, public_key),
+        // Over the quota, which we say once
+        (data_request(&request, &private_key, view).await, public_key),
+        (data_request(&request, &private_key, view).await, public_key),
+        // Signed by another key, which we drop without a word since the key need not have sent it
+        (
+            data_request(&request, &unstaked_private_key, view).await,
+            public_key,
+        ),
+        (
+            data_request(&request, &unstaked_private_key, view).await,
+            unstaked_public_key,
+        ),
+    ] {
+        sender
+            .broadcast(Arc::new(HotShotEvent::LeavesRequestRecv(
+                request, sender_key,
+            )))
+            .await
+            .unwrap();
+    }
+
+    let denials = timeout(Duration::from_secs(5), async {
+        let mut denials = Vec::new();
+        while denials.len() < 2 {
+            if let HotShotEvent::RequestDeniedSend(_, to, _, reason) =
+                output.recv().await.unwrap().as_ref()
+            {
+                denials.push((*to, *reason));
+            }
+        }
+        denials
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        denials,
+        [
+            (public_key, DenialReason::RateLimited),
+            (unstaked_public_key, DenialReason::NoStake),
+        ]
+    );
+
+    // A request signed for a view long past may be a replay, so we deny it before it counts
+    // against any quota, and tell the requester to sign it again for its current view
+    let cur_view = ViewNumber::new(20);
+    handle
+        .hotshot
+        .consensus()
+        .write()
+        .await
+        .update_view(cur_view)
+        .unwrap();
+    for view in [view, cur_view] {
+        sender
+            .broadcast(Arc::new(HotShotEvent::LeavesRequestRecv(
+                data_request(&request, &unstaked_private_key, view).await,
+                unstaked_public_key,
+            )))
+            .await
+            .unwrap();
+    }
+    let denials = timeout(Duration::from_secs(5), async {
+        let mut denials = Vec::new();
+        while denials.len() < 2 {
+            if let HotShotEvent::RequestDeniedSend(_, _, view, reason) =
+                output.recv().await.unwrap().as_ref()
+            {
+                denials.push((*view, *reason));
+            }
+        }
+        denials
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        denials,
+        [
+            (view, DenialReason::Expired),
+            (cur_view, DenialReason::NoStake),
+        ]
+    );
+
+    response_task.abort();
+}
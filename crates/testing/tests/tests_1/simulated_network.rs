@@ -0,0 +1,182 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{sync::Arc, time::Duration};
+
+use hotshot::traits::implementations::{
+    SimulatedLink, SimulatedNetwork, SimulatedNetworkConfig, SimulatedTopology, Simulator,
+};
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    signature_key::BLSPubKey,
+    traits::{
+        network::{BroadcastDelay, ConnectedNetwork, Topic},
+        signature_key::SignatureKey,
+    },
+};
+use tokio::time::{timeout, Instant};
+
+/// The public key of the node with index `index`
+fn key(index: u64) -> BLSPubKey {
+    BLSPubKey::generated_from_seed_indexed([0u8; 32], index).0
+}
+
+/// Create the first `num_nodes` nodes of a network simulated according to `config`
+async fn nodes(
+    config: SimulatedNetworkConfig,
+    num_nodes: u64,
+) -> Vec<Arc<SimulatedNetwork<BLSPubKey>>> {
+    let generator = SimulatedNetwork::generator_with_config::<TestTypes>(config, 1);
+    let mut nodes = vec![];
+    for node_id in 0..num_nodes {
+        nodes.push(generator(node_id).await);
+    }
+    nodes
+}
+
+/// Receive messages on `node` until none arrives for a second, along with when they arrived
+async fn drain(node: &SimulatedNetwork<BLSPubKey>, start: Instant) -> Vec<(Duration, Vec<u8>)> {
+    let mut received = vec![];
+    while let Ok(message) = timeout(Duration::from_secs(1), node.recv_message()).await {
+        received.push((start.elapsed(), message.unwrap()));
+    }
+    received
+}
+
+/// Run some traffic over a lossy, jittery network seeded with `seed`, returning what the last
+/// node received and when
+async fn lossy_run(seed: u64) -> Vec<(Duration, Vec<u8>)> {
+    let link = SimulatedLink {
+        latency: Duration::from_millis(10),
+        jitter: Duration::from_millis(20),
+        bandwidth: Some(10_000),
+        loss: 0.3,
+    };
+    let config = SimulatedNetworkConfig {
+        seed,
+        topology: SimulatedTopology::uniform(link),
+    };
+    let nodes = nodes(config, 3).await;
+    let start = Instant::now();
+
+    for i in 0..32u8 {
+        for sender in 0..2u8 {
+            let message = vec![sender, i];
+            nodes[usize::from(sender)]
+                .direct_message(message.clone(), key(2))
+                .await
+                .unwrap();
+            nodes[usize::from(sender)]
+                .broadcast_message(message, Topic::Global, BroadcastDelay::None)
+                .await
+                .unwrap();
+        }
+    }
+
+    drain(&nodes[2], start).await
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_network_is_reproducible() {
+    let first = lossy_run(7).await;
+    assert!(!first.is_empty());
+    // Some messages were lost
+    assert!(first.len() < 128);
+
+    // The same seed gives exactly the same run, and a different one does not
+    assert_eq!(lossy_run(7).await, first);
+    assert_ne!(lossy_run(8).await, first);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_network_topology() {
+    let slow = SimulatedLink {
+        latency: Duration::from_millis(100),
+        ..SimulatedLink::PERFECT
+    };
+    let fast = SimulatedLink {
+        latency: Duration::from_millis(10),
+        ..SimulatedLink::PERFECT
+    };
+    let narrow = SimulatedLink {
+        bandwidth: Some(1000),
+        ..SimulatedLink::PERFECT
+    };
+    let config = SimulatedNetworkConfig {
+        seed: 0,
+        topology: SimulatedTopology::uniform(SimulatedLink::PERFECT)
+            .with_link(0, 3, slow)
+            .with_link(1, 3, fast)
+            .with_link(2, 3, narrow),
+    };
+    let nodes = nodes(config, 4).await;
+    let start = Instant::now();
+
+    // Messages arrive according to the latency of their link, not the order they were sent in
+    nodes[0].direct_message(vec![0], key(3)).await.unwrap();
+    nodes[1].direct_message(vec![1], key(3)).await.unwrap();
+    // Messages on a narrow link queue up behind each other
+    nodes[2].direct_message(vec![2; 500], key(3)).await.unwrap();
+    nodes[2].direct_message(vec![3; 500], key(3)).await.unwrap();
+
+    let received = drain(&nodes[3], start).await;
+    assert_eq!(
+        received,
+        vec![
+            (Duration::from_millis(10), vec![1]),
+            (Duration::from_millis(100), vec![0]),
+            (Duration::from_millis(500), vec![2; 500]),
+            (Duration::from_millis(1000), vec![3; 500]),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_network_pause() {
+    let config = SimulatedNetworkConfig {
+        seed: 0,
+        topology: SimulatedTopology::default(),
+    };
+    let nodes = nodes(config, 2).await;
+    let start = Instant::now();
+
+    // A paused node neither sends nor receives
+    nodes[1].pause();
+    nodes[0].direct_message(vec![0], key(1)).await.unwrap();
+    nodes[1].direct_message(vec![1], key(0)).await.unwrap();
+    assert!(drain(&nodes[0], start).await.is_empty());
+    assert!(drain(&nodes[1], start).await.is_empty());
+
+    nodes[1].resume();
+    nodes[0].direct_message(vec![2], key(1)).await.unwrap();
+    let received = drain(&nodes[1], start).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].1, vec![2]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulator_is_dropped_with_its_nodes() {
+    let simulator = Simulator::new(SimulatedNetworkConfig {
+        seed: 0,
+        topology: SimulatedTopology::default(),
+    });
+    let nodes: Vec<_> = (0..2)
+        .map(|index| SimulatedNetwork::new(&key(index), index, &simulator, &[Topic::Global]))
+        .collect();
+    let simulator = Arc::downgrade(&simulator);
+
+    // A node that went away no longer receives messages
+    nodes[0].direct_message(vec![0], key(1)).await.unwrap();
+    let mut nodes = nodes.into_iter();
+    let first = nodes.next().unwrap();
+    drop(nodes);
+    assert!(first.direct_message(vec![1], key(1)).await.is_err());
+
+    // Once every node is gone, so is the simulator
+    assert!(simulator.upgrade().is_some());
+    drop(first);
+    assert!(simulator.upgrade().is_none());
+}
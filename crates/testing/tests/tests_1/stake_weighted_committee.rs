@@ -0,0 +1,129 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::collections::HashMap;
+
+use hotshot::traits::election::stake_weighted_committee::StakeWeightedCommittee;
+use hotshot_example_types::node_types::TestTypesStakeWeightedLeader;
+use hotshot_types::{
+    data::{EpochNumber, ViewNumber},
+    signature_key::BLSPubKey,
+    traits::{election::Membership, node_implementation::ConsensusTime},
+    PeerConfig, ValidatorConfig,
+};
+
+/// Number of views sampled when checking leader frequencies
+const NUM_VIEWS: u64 = 100_000;
+
+/// Peer configs with the given stakes, and the public keys in the same order
+fn peers(stakes: &[u64]) -> (Vec<PeerConfig<BLSPubKey>>, Vec<BLSPubKey>) {
+    stakes
+        .iter()
+        .enumerate()
+        .map(|(index, &stake)| {
+            let config = ValidatorConfig::<BLSPubKey>::generated_from_seed_indexed(
+                [0u8; 32],
+                index as u64,
+                stake,
+                false,
+            );
+            (config.public_config(), config.public_key)
+        })
+        .unzip()
+}
+
+/// How often each key leads over the first [`NUM_VIEWS`] views
+fn leader_counts(
+    membership: &StakeWeightedCommittee<TestTypesStakeWeightedLeader>,
+) -> HashMap<BLSPubKey, u64> {
+    let mut counts = HashMap::new();
+    for view in 0..NUM_VIEWS {
+        let leader = membership
+            .leader(ViewNumber::new(view), EpochNumber::genesis())
+            .unwrap();
+        *counts.entry(leader).or_default() += 1;
+    }
+    counts
+}
+
+#[test]
+fn test_stake_weighted_leader_frequencies() {
+    let stakes = [1, 2, 3, 4, 10];
+    let total: u64 = stakes.iter().sum();
+    let (peers, keys) = peers(&stakes);
+    let membership =
+        StakeWeightedCommittee::<TestTypesStakeWeightedLeader>::new(peers.clone(), peers);
+
+    let counts = leader_counts(&membership);
+    for (key, stake) in keys.iter().zip(stakes) {
+        let expected = stake as f64 / total as f64;
+        let observed = counts.get(key).copied().unwrap_or_default() as f64 / NUM_VIEWS as f64;
+        assert!(
+            (observed - expected).abs() < 0.01,
+            "node with stake {stake} led {observed:.4} of views, expected {expected:.4}"
+        );
+    }
+}
+
+#[test]
+fn test_stake_weighted_leader_deterministic() {
+    let (peers, keys) = peers(&[5, 0, 1, 7]);
+    let membership =
+        StakeWeightedCommittee::<TestTypesStakeWeightedLeader>::new(peers.clone(), peers.clone());
+    let other = StakeWeightedCommittee::<TestTypesStakeWeightedLeader>::new(peers.clone(), peers);
+
+    // Every node computes the same leader for a view.
+    for view in 0..1000 {
+        assert_eq!(
+            membership
+                .leader(ViewNumber::new(view), EpochNumber::genesis())
+                .unwrap(),
+            other
+                .leader(ViewNumber::new(view), EpochNumber::genesis())
+                .unwrap()
+        );
+    }
+
+    // A node without stake never leads.
+    assert!(!leader_counts(&membership).contains_key(&keys[1]));
+}
+
+#[test]
+fn test_stake_weighted_thresholds() {
+    let (peers, _) = peers(&[1, 2, 3, 4, 10]);
+    let membership =
+        StakeWeightedCommittee::<TestTypesStakeWeightedLeader>::new(peers.clone(), peers);
+
+    // Thresholds are fractions of the total stake of 20, not of the 5 nodes.
+    let epoch = EpochNumber::genesis();
+    assert_eq!(membership.success_threshold(epoch).get(), 14);
+    assert_eq!(membership.failure_threshold(epoch).get(), 7);
+    assert_eq!(membership.upgrade_threshold(epoch).get(), 18);
+}
+
+#[test]
+fn test_stake_weighted_thresholds_with_decimals() {
+    // One token with 18 decimals
+    const TOKEN: u64 = 1_000_000_000_000_000_000;
+
+    // 10 tokens still fit in a u64, and the thresholds are exact fractions of them.
+    let (small, _) = peers(&[TOKEN, 2 * TOKEN, 3 * TOKEN, 4 * TOKEN]);
+    let membership =
+        StakeWeightedCommittee::<TestTypesStakeWeightedLeader>::try_new(small.clone(), small)
+            .unwrap();
+    let epoch = EpochNumber::genesis();
+    let total = 10 * TOKEN;
+    assert_eq!(membership.success_threshold(epoch).get(), total / 3 * 2 + 1);
+    assert_eq!(membership.failure_threshold(epoch).get(), total / 3 + 1);
+    assert_eq!(membership.upgrade_threshold(epoch).get(), total / 10 * 9);
+
+    // 20 tokens do not, so rather than saturate the thresholds we reject the stake table.
+    let (large, _) = peers(&[5 * TOKEN; 4]);
+    assert!(
+        StakeWeightedCommittee::<TestTypesStakeWeightedLeader>::try_new(large.clone(), large)
+            .is_err()
+    );
+}
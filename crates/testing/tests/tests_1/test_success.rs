@@ -8,8 +8,9 @@ use std::time::Duration;
 
 use hotshot_example_types::{
     node_types::{
-        EpochsTestVersions, Libp2pImpl, MemoryImpl, PushCdnImpl, TestConsecutiveLeaderTypes,
-        TestTypes, TestTypesRandomizedLeader, TestVersions,
+        CombinedMemoryImpl, EpochsTestVersions, Libp2pImpl, MemoryImpl, PushCdnImpl, SimulatedImpl,
+        TestConsecutiveLeaderTypes, TestTypes, TestTypesDynamicCommittee, TestTypesEpochCommittee,
+        TestTypesRandomizedLeader, TestTypesStakeWeightedLeader, TestVersions,
     },
     testable_delay::{DelayConfig, DelayOptions, DelaySettings, SupportedTraitTypesForAsyncDelay},
 };
@@ -22,10 +23,12 @@ use hotshot_testing::{
     view_sync_task::ViewSyncTaskDescription,
 };
 
+// The simulated network gives these runs seeded link delays and losses, but the runs do not
+// replay from the seed, see the docs of `simulated_network`
 cross_tests!(
     TestName: test_success,
-    Impls: [MemoryImpl, Libp2pImpl, PushCdnImpl],
-    Types: [TestTypes, TestTypesRandomizedLeader],
+    Impls: [MemoryImpl, Libp2pImpl, PushCdnImpl, SimulatedImpl, CombinedMemoryImpl],
+    Types: [TestTypes, TestTypesRandomizedLeader, TestTypesStakeWeightedLeader],
     Versions: [TestVersions],
     Ignore: false,
     Metadata: {
@@ -137,7 +140,7 @@ cross_tests!(
 cross_tests!(
     TestName: test_epoch_end,
     Impls: [MemoryImpl],
-    Types: [TestTypes],
+    Types: [TestTypes, TestTypesEpochCommittee, TestTypesDynamicCommittee],
     Versions: [EpochsTestVersions],
     Ignore: false,
     Metadata: {
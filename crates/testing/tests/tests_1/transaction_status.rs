@@ -0,0 +1,131 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use committable::Committable;
+use futures::StreamExt;
+use hotshot::types::{TransactionStatus, TransactionTracker};
+use hotshot_example_types::{
+    block_types::TestTransaction,
+    node_types::{MemoryImpl, TestTypes, TestVersions},
+    state_types::TestValidatedState,
+};
+use hotshot_testing::{helpers::build_system_handle, view_generator::TestViewGenerator};
+use hotshot_types::{
+    event::{EventType, LeafInfo},
+    traits::block_contents::BlockHeader,
+};
+
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+#[test]
+fn test_transaction_status_expires() {
+    let mut tracker = TransactionTracker::<TestTypes>::new(MAX_AGE);
+    let start = Instant::now();
+    let commitment = TestTransaction::new(vec![1]).commit();
+
+    assert_eq!(tracker.status(&commitment), None);
+    tracker.track(commitment, start);
+    assert_eq!(
+        tracker.status(&commitment),
+        Some(TransactionStatus::Pending)
+    );
+
+    tracker.expire(start + MAX_AGE);
+    assert_eq!(
+        tracker.status(&commitment),
+        Some(TransactionStatus::Expired)
+    );
+
+    // Resubmitting an expired transaction makes it pending again
+    tracker.track(commitment, start + MAX_AGE);
+    assert_eq!(
+        tracker.status(&commitment),
+        Some(TransactionStatus::Pending)
+    );
+
+    // A final status is forgotten once it is old enough
+    tracker.expire(start + MAX_AGE * 2);
+    tracker.expire(start + MAX_AGE * 3);
+    assert_eq!(tracker.status(&commitment), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_transaction_status_follows_events() {
+    hotshot::helpers::initialize_logging();
+
+    let handle = build_system_handle::<TestTypes, MemoryImpl, TestVersions>(2)
+        .await
+        .0;
+    let membership = (*handle.hotshot.memberships).clone();
+
+    let transaction = TestTransaction::new(vec![1, 2, 3]);
+    let untracked = TestTransaction::new(vec![4]);
+    let mut generator = TestViewGenerator::generate(membership);
+    let _genesis = generator.next().await.unwrap();
+    generator.add_transactions(vec![transaction.clone(), untracked.clone()]);
+    let view = generator.next().await.unwrap();
+
+    let start = Instant::now();
+    let mut tracker = TransactionTracker::<TestTypes>::new(MAX_AGE);
+    tracker.track(transaction.commit(), start);
+
+    tracker.handle_event(
+        &EventType::DaProposal {
+            proposal: view.da_proposal.clone(),
+            sender: view.leader_public_key,
+        },
+        start,
+    );
+    assert_eq!(
+        tracker.status(&transaction.commit()),
+        Some(TransactionStatus::Pending)
+    );
+
+    tracker.handle_event(
+        &EventType::QuorumProposal {
+            proposal: view.quorum_proposal.clone(),
+            sender: view.leader_public_key,
+        },
+        start,
+    );
+    let block_height = view.quorum_proposal.data.block_header.block_number();
+    assert_eq!(
+        tracker.status(&transaction.commit()),
+        Some(TransactionStatus::Sequenced {
+            view: view.view_number,
+            block_height,
+        })
+    );
+
+    tracker.handle_event(
+        &EventType::Decide {
+            leaf_chain: Arc::new(vec![LeafInfo::new(
+                view.leaf.clone(),
+                Arc::new(TestValidatedState::default()),
+                None,
+                None,
+            )]),
+            qc: Arc::new(view.quorum_proposal.data.justify_qc.clone()),
+            block_size: None,
+        },
+        start,
+    );
+    let decided = TransactionStatus::Decided {
+        view: view.view_number,
+        block_height,
+    };
+    assert_eq!(tracker.status(&transaction.commit()), Some(decided));
+    assert_eq!(tracker.status(&untracked.commit()), None);
+
+    // A decided transaction does not expire
+    tracker.expire(start + MAX_AGE / 2);
+    assert_eq!(tracker.status(&transaction.commit()), Some(decided));
+}
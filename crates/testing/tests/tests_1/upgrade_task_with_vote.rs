@@ -150,6 +150,7 @@ async fn test_upgrade_task_with_vote() {
                 exact(VidShareValidated(vids[3].0[0].clone())),
                 exact(ViewChange(ViewNumber::new(5), EpochNumber::new(0))),
                 quorum_vote_send(),
+                leaves_decided(),
             ],
             vec![no_decided_upgrade_certificate()],
         ),
@@ -159,10 +160,14 @@ async fn test_upgrade_task_with_vote() {
                 exact(VidShareValidated(vids[4].0[0].clone())),
                 exact(ViewChange(ViewNumber::new(6), EpochNumber::new(0))),
                 quorum_vote_send(),
+                leaves_decided(),
             ],
             vec![no_decided_upgrade_certificate()],
         ),
-        Expectations::from_outputs_and_task_states(vec![], vec![decided_upgrade_certificate()]),
+        Expectations::from_outputs_and_task_states(
+            all_predicates![leaves_decided()],
+            vec![decided_upgrade_certificate()],
+        ),
     ];
 
     let vote_state =
@@ -14,8 +14,8 @@ use hotshot_macros::cross_tests;
 use hotshot_testing::{
     block_builder::SimpleBuilderImplementation,
     byzantine::byzantine_behaviour::{
-        BadProposalViewDos, DishonestDa, DishonestLeader, DishonestVoter, DishonestVoting,
-        DoubleProposeVote,
+        BadProposalViewDos, BadSignatureRequester, DishonestDa, DishonestLeader, DishonestVoter,
+        DishonestVoting, DoubleProposeVote, EquivocatingVoter,
     },
     completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
     test_builder::{Behaviour, TestDescription},
@@ -56,6 +56,36 @@ cross_tests!(
     },
 );
 
+// Test where node 2 follows each of its quorum votes with a conflicting one, which the next
+// leader reports as equivocation without consensus being affected
+cross_tests!(
+    TestName: equivocating_voter,
+    Impls: [MemoryImpl],
+    Types: [TestTypes],
+    Versions: [TestVersions],
+    Ignore: false,
+    Metadata: {
+        let behaviour = Rc::new(|node_id| { match node_id {
+          2 => Behaviour::Byzantine(Box::new(EquivocatingVoter)),
+          _ => Behaviour::Standard,
+          } });
+
+        let mut metadata = TestDescription {
+            // allow more time to pass in CI
+            completion_task_description: CompletionTaskDescription::TimeBasedCompletionTaskBuilder(
+                                             TimeBasedCompletionTaskDescription {
+                                                 duration: Duration::from_secs(60),
+                                             },
+                                         ),
+            behaviour,
+            ..TestDescription::default()
+        };
+
+        metadata.overall_safety_properties.expected_equivocators = HashSet::from([2]);
+        metadata
+    },
+);
+
 // Test where node 4 sends out the correct quorum proposal and additionally spams the network with an extra 99 malformed proposals
 cross_tests!(
     TestName: multiple_bad_proposals,
@@ -249,3 +279,31 @@ cross_tests!(
         metadata
     },
 );
+
+// Test where node 3 pesters every leader with badly signed VID requests, which must not stall
+// consensus. That a badly signed request gets its sender reported is covered by
+// `test_badly_signed_requests_report_the_peer`.
+cross_tests!(
+    TestName: bad_signature_requests,
+    Impls: [MemoryImpl, Libp2pImpl],
+    Types: [TestTypes],
+    Versions: [TestVersions],
+    Ignore: false,
+    Metadata: {
+        let behaviour = Rc::new(|node_id| match node_id {
+            3 => Behaviour::Byzantine(Box::new(BadSignatureRequester)),
+            _ => Behaviour::Standard,
+        });
+
+        TestDescription {
+            // allow more time to pass in CI
+            completion_task_description: CompletionTaskDescription::TimeBasedCompletionTaskBuilder(
+                TimeBasedCompletionTaskDescription {
+                    duration: Duration::from_secs(60),
+                },
+            ),
+            behaviour,
+            ..TestDescription::default()
+        }
+    },
+);
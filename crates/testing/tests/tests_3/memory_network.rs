@@ -27,6 +27,7 @@ use hotshot_types::{
     message::{DataMessage, Message, MessageKind, UpgradeLock},
     signature_key::{BLSPubKey, BuilderKey},
     traits::{
+        mempool::FifoPriority,
         network::{BroadcastDelay, ConnectedNetwork, TestableNetworkingImplementation, Topic},
         node_implementation::{ConsensusTime, NodeType},
     },
@@ -72,6 +73,7 @@ impl NodeImplementation<Test> for TestImpl {
     type Network = MemoryNetwork<<Test as NodeType>::SignatureKey>;
     type Storage = TestStorage<Test>;
     type AuctionResultsProvider = TestAuctionResultsProvider<Test>;
+    type TransactionPriority = FifoPriority;
 }
 
 /// fake Eq
@@ -0,0 +1,87 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::time::Duration;
+
+use hotshot_example_types::node_types::{CombinedImpl, MemoryImpl, TestTypes, TestVersions};
+use hotshot_macros::cross_tests;
+use hotshot_testing::{
+    block_builder::SimpleBuilderImplementation,
+    completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
+    test_builder::{TestDescription, TimingData},
+};
+
+// Split the network into a quorum and a minority, then heal it. The overall safety task checks
+// that no two nodes decide conflicting leaves while the network is split, and that views keep
+// being decided once the minority can talk to the quorum again.
+cross_tests!(
+    TestName: test_network_partition_heals,
+    Impls: [MemoryImpl, CombinedImpl],
+    Types: [TestTypes],
+    Versions: [TestVersions],
+    Ignore: false,
+    Metadata: {
+        let mut metadata = TestDescription {
+            timing_data: TimingData {
+                next_view_timeout: 5_000,
+                ..Default::default()
+            },
+            completion_task_description: CompletionTaskDescription::TimeBasedCompletionTaskBuilder(
+                TimeBasedCompletionTaskDescription {
+                    duration: Duration::from_secs(240),
+                },
+            ),
+            ..TestDescription::default_multiple_rounds()
+        };
+        let num_nodes = metadata.num_nodes_with_stake;
+
+        // 7 of the 10 nodes are still a quorum, so only the minority's views fail
+        metadata.spinning_properties = metadata
+            .spinning_properties
+            .with_partition(5, &[(0..7).collect(), (7..num_nodes).collect()])
+            .with_heal(15, num_nodes);
+
+        metadata.overall_safety_properties.num_failed_views = 15;
+        metadata.overall_safety_properties.num_successful_views = 25;
+
+        metadata
+    }
+);
+
+// Cut every link out of one node while it can still hear everyone else, then heal it
+cross_tests!(
+    TestName: test_network_asymmetric_partition_heals,
+    Impls: [MemoryImpl, CombinedImpl],
+    Types: [TestTypes],
+    Versions: [TestVersions],
+    Ignore: false,
+    Metadata: {
+        let mut metadata = TestDescription {
+            timing_data: TimingData {
+                next_view_timeout: 5_000,
+                ..Default::default()
+            },
+            completion_task_description: CompletionTaskDescription::TimeBasedCompletionTaskBuilder(
+                TimeBasedCompletionTaskDescription {
+                    duration: Duration::from_secs(240),
+                },
+            ),
+            ..TestDescription::default_multiple_rounds()
+        };
+        let num_nodes = metadata.num_nodes_with_stake;
+        let muted = num_nodes - 1;
+
+        metadata.spinning_properties = metadata
+            .spinning_properties
+            .with_cut_links(5, muted, &(0..muted).collect::<Vec<_>>(), num_nodes)
+            .with_heal(15, num_nodes);
+
+        metadata.overall_safety_properties.num_failed_views = 10;
+        metadata.overall_safety_properties.num_successful_views = 25;
+
+        metadata
+    }
+);
@@ -4,7 +4,7 @@
 // You should have received a copy of the MIT License
 // along with the HotShot repository. If not, see <https://mit-license.org/>.
 
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use hotshot_example_types::node_types::{MemoryImpl, PushCdnImpl, TestTypes, TestVersions};
 use hotshot_macros::cross_tests;
@@ -13,6 +13,7 @@ use hotshot_testing::{
     test_builder::{BuilderChange, BuilderDescription, TestDescription},
     txn_task::TxnTaskDescription,
 };
+use hotshot_types::mempool_config::MempoolConfig;
 
 // Test one node leaving the network.
 cross_tests!(
@@ -74,3 +75,35 @@ cross_tests!(
         metadata
     }
 );
+
+// Test leaders building blocks from their mempool while every builder is down.
+cross_tests!(
+    TestName: test_with_all_builders_down,
+    Impls: [MemoryImpl, PushCdnImpl],
+    Types: [TestTypes],
+    Versions: [TestVersions],
+    Ignore: false,
+    Metadata: {
+        let mut metadata = TestDescription::default_multiple_rounds();
+        // Every block should contain at least one transaction, even while no builder is up
+        metadata.overall_safety_properties.transaction_threshold = 1;
+        metadata.txn_description = TxnTaskDescription::RoundRobinTimeBased(Duration::from_millis(1));
+        metadata.mempool = Some(MempoolConfig {
+            fallback_block_bytes: Some(1_000_000),
+            ..MempoolConfig::default()
+        });
+
+        // Both builders are down from view 4 to view 12
+        let changes: HashMap<u64, BuilderChange> =
+            [(4, BuilderChange::Down), (12, BuilderChange::Up)].into_iter().collect();
+        metadata.builders = vec1::vec1![
+            BuilderDescription {
+               changes: changes.clone(),
+            },
+            BuilderDescription {
+               changes,
+            },
+        ];
+        metadata
+    }
+);
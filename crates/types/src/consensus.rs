@@ -22,13 +22,14 @@ use vec1::Vec1;
 pub use crate::utils::{View, ViewInner};
 use crate::{
     data::{Leaf2, QuorumProposal2, VidDisperse, VidDisperseShare},
+    drb::{DrbResult, DrbSeedInput},
     error::HotShotError,
     event::{HotShotAction, LeafInfo},
     message::Proposal,
     simple_certificate::{DaCertificate, QuorumCertificate2},
     traits::{
         block_contents::BuilderFee,
-        metrics::{Counter, Gauge, Histogram, Metrics, NoMetrics},
+        metrics::{Counter, CounterFamily, Gauge, Histogram, HistogramFamily, Metrics, NoMetrics},
         node_implementation::{ConsensusTime, NodeType},
         signature_key::SignatureKey,
         BlockPayload, ValidatedState,
@@ -322,6 +323,12 @@ pub struct Consensus<TYPES: NodeType> {
 
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+
+    /// DRB seeds we derived from decided QCs, by the epoch whose leaders they determine
+    drb_seeds: BTreeMap<TYPES::Epoch, DrbSeedInput>,
+
+    /// Finished DRB computations, by the epoch whose leaders they determine
+    drb_results: BTreeMap<TYPES::Epoch, DrbResult>,
 }
 
 /// Contains several `ConsensusMetrics` that we're interested in from the consensus interfaces
@@ -355,6 +362,35 @@ pub struct ConsensusMetricsValue {
     pub number_of_empty_blocks_proposed: Box<dyn Counter>,
     /// Number of events in the hotshot event queue
     pub internal_event_queue_len: Box<dyn Gauge>,
+    /// Seconds from receiving a quorum proposal to voting on it
+    pub proposal_to_vote_duration: Box<dyn Histogram>,
+    /// Seconds from receiving the first vote of a view to forming its QC, as leader
+    pub vote_to_qc_duration: Box<dyn Histogram>,
+    /// Seconds from sending a DA proposal to forming its DAC, as DA leader
+    pub da_proposal_to_dac_duration: Box<dyn Histogram>,
+    /// Seconds spent computing and signing the VID disperse of a block, as leader
+    pub vid_disperse_duration: Box<dyn Histogram>,
+    /// Seconds from asking the builders for a block to getting one, as leader
+    pub builder_response_duration: Box<dyn Histogram>,
+    /// Seconds from receiving a quorum proposal to deciding its leaf
+    pub proposal_to_decide_duration: Box<dyn Histogram>,
+    /// Uncompressed over on-the-wire size of the messages we send, by message purpose
+    pub message_compression_ratio: Box<dyn HistogramFamily>,
+    /// Number of messages dropped for exceeding a peer's rate limit, by message purpose
+    pub rate_limited_messages: Box<dyn CounterFamily>,
+    /// Seconds a builder took to offer blocks, as leader, by builder URL
+    pub builder_latency: Box<dyn HistogramFamily>,
+    /// Number of times a builder failed us, as leader, by builder URL and reason
+    pub builder_failures: Box<dyn CounterFamily>,
+    /// Number of data requests we made, by kind of request and outcome
+    pub data_requests: Box<dyn CounterFamily>,
+    /// Seconds from making a data request to getting a valid response, by kind of request
+    pub data_request_latency: Box<dyn HistogramFamily>,
+    /// Number of data requests we denied, by kind of request and reason
+    pub denied_data_requests: Box<dyn CounterFamily>,
+    /// Number of events subscribers to the event stream missed or were disconnected over, by lag
+    /// policy
+    pub lagged_subscription_events: Box<dyn CounterFamily>,
 }
 
 impl ConsensusMetricsValue {
@@ -385,6 +421,58 @@ impl ConsensusMetricsValue {
                 .create_counter(String::from("number_of_empty_blocks_proposed"), None),
             internal_event_queue_len: metrics
                 .create_gauge(String::from("internal_event_queue_len"), None),
+            proposal_to_vote_duration: metrics.create_histogram(
+                String::from("proposal_to_vote_duration"),
+                Some(String::from("s")),
+            ),
+            vote_to_qc_duration: metrics
+                .create_histogram(String::from("vote_to_qc_duration"), Some(String::from("s"))),
+            da_proposal_to_dac_duration: metrics.create_histogram(
+                String::from("da_proposal_to_dac_duration"),
+                Some(String::from("s")),
+            ),
+            vid_disperse_duration: metrics.create_histogram(
+                String::from("vid_disperse_duration"),
+                Some(String::from("s")),
+            ),
+            builder_response_duration: metrics.create_histogram(
+                String::from("builder_response_duration"),
+                Some(String::from("s")),
+            ),
+            proposal_to_decide_duration: metrics.create_histogram(
+                String::from("proposal_to_decide_duration"),
+                Some(String::from("s")),
+            ),
+            message_compression_ratio: metrics.histogram_family(
+                String::from("message_compression_ratio"),
+                vec![String::from("purpose")],
+            ),
+            rate_limited_messages: metrics.counter_family(
+                String::from("rate_limited_messages"),
+                vec![String::from("purpose")],
+            ),
+            builder_latency: metrics
+                .histogram_family(String::from("builder_latency"), vec![String::from("url")]),
+            builder_failures: metrics.counter_family(
+                String::from("builder_failures"),
+                vec![String::from("url"), String::from("reason")],
+            ),
+            data_requests: metrics.counter_family(
+                String::from("data_requests"),
+                vec![String::from("kind"), String::from("outcome")],
+            ),
+            data_request_latency: metrics.histogram_family(
+                String::from("data_request_latency"),
+                vec![String::from("kind")],
+            ),
+            denied_data_requests: metrics.counter_family(
+                String::from("denied_data_requests"),
+                vec![String::from("kind"), String::from("reason")],
+            ),
+            lagged_subscription_events: metrics.counter_family(
+                String::from("lagged_subscription_events"),
+                vec![String::from("policy")],
+            ),
         }
     }
 }
@@ -427,6 +515,8 @@ impl<TYPES: NodeType> Consensus<TYPES> {
             high_qc,
             metrics,
             epoch_height,
+            drb_seeds: BTreeMap::new(),
+            drb_results: BTreeMap::new(),
         }
     }
 
@@ -487,6 +577,33 @@ impl<TYPES: NodeType> Consensus<TYPES> {
         &self.last_proposals
     }
 
+    /// Get the DRB result for an epoch, if its computation has finished.
+    pub fn drb_result(&self, epoch: TYPES::Epoch) -> Option<DrbResult> {
+        self.drb_results.get(&epoch).copied()
+    }
+
+    /// Whether we have the DRB seed for an epoch, and so computed or are computing its result.
+    pub fn has_drb_seed(&self, epoch: TYPES::Epoch) -> bool {
+        self.drb_seeds.contains_key(&epoch)
+    }
+
+    /// Record the DRB seed for an epoch.
+    ///
+    /// Returns false if we already had a seed for the epoch, in which case the DRB computation
+    /// was started before and the seed is left unchanged.
+    pub fn add_drb_seed(&mut self, epoch: TYPES::Epoch, drb_seed_input: DrbSeedInput) -> bool {
+        if self.drb_seeds.contains_key(&epoch) {
+            return false;
+        }
+        self.drb_seeds.insert(epoch, drb_seed_input);
+        true
+    }
+
+    /// Record the result of the DRB computation for an epoch.
+    pub fn add_drb_result(&mut self, epoch: TYPES::Epoch, drb_result: DrbResult) {
+        self.drb_results.insert(epoch, drb_result);
+    }
+
     /// Update the current view.
     /// # Errors
     /// Can return an error when the new view_number is not higher than the existing view number.
@@ -516,8 +633,8 @@ impl<TYPES: NodeType> Consensus<TYPES> {
         };
         let parent_vid = self
             .vid_shares()
-            .get(&parent_view_number)?
-            .get(public_key)
+            .get(&parent_view_number)
+            .and_then(|shares| shares.get(public_key))
             .cloned()
             .map(|prop| prop.data);
 
@@ -874,6 +991,25 @@ impl<TYPES: NodeType> Consensus<TYPES> {
         }
     }
 
+    /// Gets the epoch of the leaf with the given view number, if it is in the state map and
+    /// epochs are enabled.
+    #[must_use]
+    pub fn epoch_of_view(&self, view_number: TYPES::View) -> Option<TYPES::Epoch> {
+        if self.epoch_height == 0 {
+            return None;
+        }
+        let leaf_commit = self
+            .validated_state_map
+            .get(&view_number)?
+            .leaf_commitment()?;
+        let leaf = self.saved_leaves.get(&leaf_commit)?;
+
+        Some(TYPES::Epoch::new(epoch_from_block_number(
+            leaf.height(),
+            self.epoch_height,
+        )))
+    }
+
     /// Gets the last decided validated state.
     ///
     /// # Panics
@@ -395,7 +395,8 @@ pub struct QuorumProposal2<TYPES: NodeType> {
     #[serde(with = "serde_bytes")]
     pub drb_seed: [u8; 96],
 
-    /// the result of the DRB calculation
+    /// the result of the DRB calculation for the epoch after the one of this block, or zeros if
+    /// the proposer did not have it
     #[serde(with = "serde_bytes")]
     pub drb_result: [u8; 32],
 }
@@ -566,7 +567,8 @@ pub struct Leaf2<TYPES: NodeType> {
     #[serde(with = "serde_bytes")]
     pub drb_seed: [u8; 96],
 
-    /// the result of the DRB calculation
+    /// the result of the DRB calculation for the epoch after the one of this block, or zeros if
+    /// the proposer did not have it
     #[serde(with = "serde_bytes")]
     pub drb_result: [u8; 32],
 }
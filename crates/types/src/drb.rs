@@ -0,0 +1,97 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! The distributed randomness beacon (DRB) used for leader rotation.
+//!
+//! The seed for an epoch is derived from the aggregated signature of a decided QC, and the
+//! result is a long chain of hashes over it, so no node can predict the result before the seed
+//! is decided. The computation for epoch `e + DRB_EPOCHS_AHEAD` starts when the first block of
+//! epoch `e` is decided, which leaves at least a full epoch to finish it.
+
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{
+    data::serialize_signature2, simple_certificate::QuorumCertificate2,
+    traits::node_implementation::NodeType,
+};
+
+/// Input to the DRB computation: a digest of a decided QC signature.
+pub type DrbSeedInput = [u8; 32];
+
+/// Output of the DRB computation.
+pub type DrbResult = [u8; 32];
+
+/// Number of epochs between the epoch whose first decided block seeds a DRB computation and the
+/// epoch that uses the result.
+///
+/// The epochs up to and including this one have no seed and use [`INITIAL_DRB_RESULT`].
+pub const DRB_EPOCHS_AHEAD: u64 = 2;
+
+/// DRB result of the epochs before the first computed one.
+pub const INITIAL_DRB_RESULT: DrbResult = [0; 32];
+
+/// Number of hashes per second we assume a node computes in [`compute_drb_result`].
+///
+/// A conservative estimate for one core hashing SHA-256, rather than a benchmark; faster nodes
+/// finish early, slower ones risk missing the epoch.
+pub const HASHES_PER_SECOND: u64 = 1_000_000;
+
+/// Expected duration of a view, in milliseconds.
+pub const VIEW_DURATION_MS: u64 = 1_000;
+
+/// Time a DRB calculation will take, in terms of number of views.
+///
+/// The epoch height must leave at least this many views between the first block of an epoch
+/// and the end of the next one.
+pub const DRB_CALCULATION_NUM_VIEW: u64 = 300;
+
+/// Difficulty level of the DRB calculation.
+///
+/// Represents the number of times the hash function will be repeatedly called, so that a node
+/// hashing at [`HASHES_PER_SECOND`] spends [`DRB_CALCULATION_NUM_VIEW`] views on it.
+#[must_use]
+pub const fn difficulty_level() -> u64 {
+    HASHES_PER_SECOND * VIEW_DURATION_MS * DRB_CALCULATION_NUM_VIEW / 1_000
+}
+
+/// Derive the DRB seed input from a decided QC.
+///
+/// Returns `None` for a QC without signatures, such as the genesis QC.
+#[must_use]
+pub fn drb_seed_input<TYPES: NodeType>(qc: &QuorumCertificate2<TYPES>) -> Option<DrbSeedInput> {
+    let signatures = qc.signatures.as_ref()?;
+
+    Some(Sha256::digest(serialize_signature2::<TYPES>(signatures)).into())
+}
+
+/// Compute the DRB result for the leader rotation.
+///
+/// This is to be started two epochs in advance and spawned in a non-blocking thread.
+///
+/// # Arguments
+/// * `drb_seed_input` - Digest of the serialized QC signature.
+/// * `difficulty_level` - Number of hash iterations, normally [`NodeType::DRB_DIFFICULTY`].
+#[must_use]
+pub fn compute_drb_result(drb_seed_input: DrbSeedInput, difficulty_level: u64) -> DrbResult {
+    let mut hash: DrbResult = drb_seed_input;
+    for _iter in 0..difficulty_level {
+        hash = Sha256::digest(hash).into();
+    }
+
+    hash
+}
+
+/// Randomness for sampling the leader of `view_number` from a DRB result.
+#[must_use]
+pub fn leader_randomness(view_number: u64, drb_result: &DrbResult) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(drb_result);
+    hasher.update(view_number.to_le_bytes());
+
+    let mut randomness = [0u8; 64];
+    randomness.copy_from_slice(&hasher.finalize());
+    randomness
+}
@@ -6,14 +6,15 @@
 
 //! Events that a `HotShot` instance can emit
 
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
     data::{DaProposal, Leaf2, QuorumProposal2, UpgradeProposal, VidDisperseShare},
     error::HotShotError,
-    message::Proposal,
+    evidence::EquivocationEvidence,
+    message::{MessagePurpose, MessageSource, Proposal},
     simple_certificate::QuorumCertificate2,
     traits::{node_implementation::NodeType, ValidatedState},
 };
@@ -179,7 +180,138 @@ pub enum EventType<TYPES: NodeType> {
         /// Serialized data of the message
         data: Vec<u8>,
     },
+    /// A peer or key exceeded its rate limit, so we started dropping its messages of this purpose
+    ///
+    /// Emitted once each time the peer goes over the limit, not for every dropped message.
+    MessageRateLimited {
+        /// The peer or key we know the dropped messages came from
+        source: MessageSource<TYPES::SignatureKey>,
+        /// Purpose of the dropped messages
+        purpose: MessagePurpose,
+    },
+    /// A node signed two conflicting messages of the same kind for the same view
+    ///
+    /// The evidence was persisted before the event was emitted.
+    Equivocation {
+        /// Both signed messages, verifiable against the stake table
+        evidence: Arc<EquivocationEvidence<TYPES>>,
+    },
+    /// We could not fetch the leaves decided between two decides, so the next `Decide` event does
+    /// not follow on from the one before
+    ///
+    /// Emitted right before that `Decide` event.
+    DecideGap {
+        /// The view of the newest leaf of the `Decide` event before
+        last_decided_view: TYPES::View,
+        /// The view of the newest leaf we are missing, the parent of the oldest leaf of the next
+        /// `Decide` event
+        missing_until: TYPES::View,
+    },
+}
+
+impl<TYPES: NodeType> EventType<TYPES> {
+    /// The kind of the event
+    #[must_use]
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Error { .. } => EventKind::Error,
+            Self::Decide { .. } => EventKind::Decide,
+            Self::ReplicaViewTimeout { .. } => EventKind::ReplicaViewTimeout,
+            Self::ViewFinished { .. } => EventKind::ViewFinished,
+            Self::ViewTimeout { .. } => EventKind::ViewTimeout,
+            Self::Transactions { .. } => EventKind::Transactions,
+            Self::DaProposal { .. } => EventKind::DaProposal,
+            Self::QuorumProposal { .. } => EventKind::QuorumProposal,
+            Self::UpgradeProposal { .. } => EventKind::UpgradeProposal,
+            Self::ExternalMessageReceived { .. } => EventKind::ExternalMessageReceived,
+            Self::MessageRateLimited { .. } => EventKind::MessageRateLimited,
+            Self::Equivocation { .. } => EventKind::Equivocation,
+            Self::DecideGap { .. } => EventKind::DecideGap,
+        }
+    }
+}
+
+/// The kinds of [`EventType`], to select the events of a kind without matching on their contents
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EventKind {
+    /// [`EventType::Error`]
+    Error,
+    /// [`EventType::Decide`]
+    Decide,
+    /// [`EventType::ReplicaViewTimeout`]
+    ReplicaViewTimeout,
+    /// [`EventType::ViewFinished`]
+    ViewFinished,
+    /// [`EventType::ViewTimeout`]
+    ViewTimeout,
+    /// [`EventType::Transactions`]
+    Transactions,
+    /// [`EventType::DaProposal`]
+    DaProposal,
+    /// [`EventType::QuorumProposal`]
+    QuorumProposal,
+    /// [`EventType::UpgradeProposal`]
+    UpgradeProposal,
+    /// [`EventType::ExternalMessageReceived`]
+    ExternalMessageReceived,
+    /// [`EventType::MessageRateLimited`]
+    MessageRateLimited,
+    /// [`EventType::Equivocation`]
+    Equivocation,
+    /// [`EventType::DecideGap`]
+    DecideGap,
+}
+
+impl EventKind {
+    /// Every kind of event
+    pub const ALL: [Self; 13] = [
+        Self::Error,
+        Self::Decide,
+        Self::ReplicaViewTimeout,
+        Self::ViewFinished,
+        Self::ViewTimeout,
+        Self::Transactions,
+        Self::DaProposal,
+        Self::QuorumProposal,
+        Self::UpgradeProposal,
+        Self::ExternalMessageReceived,
+        Self::MessageRateLimited,
+        Self::Equivocation,
+        Self::DecideGap,
+    ];
+
+    /// The name of the kind, as used in URLs and configuration
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Decide => "decide",
+            Self::ReplicaViewTimeout => "replica_view_timeout",
+            Self::ViewFinished => "view_finished",
+            Self::ViewTimeout => "view_timeout",
+            Self::Transactions => "transactions",
+            Self::DaProposal => "da_proposal",
+            Self::QuorumProposal => "quorum_proposal",
+            Self::UpgradeProposal => "upgrade_proposal",
+            Self::ExternalMessageReceived => "external_message_received",
+            Self::MessageRateLimited => "message_rate_limited",
+            Self::Equivocation => "equivocation",
+            Self::DecideGap => "decide_gap",
+        }
+    }
 }
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| format!("Unknown event kind {name}"))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 /// A list of actions that we track for nodes
 pub enum HotShotAction {
@@ -0,0 +1,240 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! Evidence that a node signed two conflicting messages for the same view
+//!
+//! An [`EquivocationEvidence`] carries both signed messages, so anyone holding the stake table
+//! can check it with [`EquivocationEvidence::validate`] without trusting the node that reported
+//! it.
+
+use committable::Committable;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utils::anytrace::*;
+
+use crate::{
+    data::{DaProposal, Leaf2, QuorumProposal2},
+    message::{Proposal, UpgradeLock},
+    simple_vote::{DaVote, QuorumVote2, VersionedVoteData},
+    traits::{
+        election::Membership,
+        node_implementation::{NodeType, Versions},
+        signature_key::SignatureKey,
+    },
+    vote::{HasViewNumber, Vote},
+};
+
+/// The kinds of message a node may sign at most one of per view
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EquivocationKind {
+    /// A quorum vote
+    QuorumVote,
+    /// A DA vote
+    DaVote,
+    /// A quorum proposal, signed by the leader
+    QuorumProposal,
+    /// A DA proposal, signed by the leader
+    DaProposal,
+}
+
+/// A message signed by a node which commits it to something in a view
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(deserialize = "", serialize = ""))]
+pub enum SignedMessage<TYPES: NodeType> {
+    /// A quorum vote
+    QuorumVote(QuorumVote2<TYPES>),
+    /// A DA vote
+    DaVote(DaVote<TYPES>),
+    /// A quorum proposal
+    QuorumProposal(Proposal<TYPES, QuorumProposal2<TYPES>>),
+    /// A DA proposal
+    DaProposal(Proposal<TYPES, DaProposal<TYPES>>),
+}
+
+impl<TYPES: NodeType> SignedMessage<TYPES> {
+    /// The kind of the message
+    #[must_use]
+    pub fn kind(&self) -> EquivocationKind {
+        match self {
+            Self::QuorumVote(_) => EquivocationKind::QuorumVote,
+            Self::DaVote(_) => EquivocationKind::DaVote,
+            Self::QuorumProposal(_) => EquivocationKind::QuorumProposal,
+            Self::DaProposal(_) => EquivocationKind::DaProposal,
+        }
+    }
+
+    /// The view the message was signed for
+    #[must_use]
+    pub fn view_number(&self) -> TYPES::View {
+        match self {
+            Self::QuorumVote(vote) => vote.view_number(),
+            Self::DaVote(vote) => vote.view_number(),
+            Self::QuorumProposal(proposal) => proposal.data.view_number(),
+            Self::DaProposal(proposal) => proposal.data.view_number(),
+        }
+    }
+
+    /// The key that signed the message, if the message carries it.
+    ///
+    /// Proposals do not: they are signed by the leader of their view.
+    #[must_use]
+    pub fn signing_key(&self) -> Option<TYPES::SignatureKey> {
+        match self {
+            Self::QuorumVote(vote) => Some(vote.signing_key()),
+            Self::DaVote(vote) => Some(vote.signing_key()),
+            Self::QuorumProposal(_) | Self::DaProposal(_) => None,
+        }
+    }
+
+    /// The signature on the message
+    #[must_use]
+    pub fn signature(&self) -> <TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType {
+        match self {
+            Self::QuorumVote(vote) => vote.signature(),
+            Self::DaVote(vote) => vote.signature(),
+            Self::QuorumProposal(proposal) => proposal.signature.clone(),
+            Self::DaProposal(proposal) => proposal.signature.clone(),
+        }
+    }
+
+    /// The bytes the signature is over, computed the same way as when the message was signed
+    ///
+    /// # Errors
+    /// Returns an error if the version of the view of a vote is not one we support.
+    pub async fn signed_bytes<V: Versions>(
+        &self,
+        upgrade_lock: &UpgradeLock<TYPES, V>,
+    ) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::QuorumVote(vote) => {
+                VersionedVoteData::new(vote.data.clone(), vote.view_number, upgrade_lock)
+                    .await?
+                    .commit()
+                    .as_ref()
+                    .to_vec()
+            }
+            Self::DaVote(vote) => {
+                VersionedVoteData::new(vote.data.clone(), vote.view_number, upgrade_lock)
+                    .await?
+                    .commit()
+                    .as_ref()
+                    .to_vec()
+            }
+            Self::QuorumProposal(proposal) => Leaf2::from_quorum_proposal(&proposal.data)
+                .commit()
+                .as_ref()
+                .to_vec(),
+            Self::DaProposal(proposal) => {
+                Sha256::digest(&proposal.data.encoded_transactions).to_vec()
+            }
+        })
+    }
+
+    /// Check that `key` signed the message
+    ///
+    /// # Errors
+    /// Returns an error if the signature is not valid for `key`.
+    pub async fn validate_signature<V: Versions>(
+        &self,
+        key: &TYPES::SignatureKey,
+        upgrade_lock: &UpgradeLock<TYPES, V>,
+    ) -> Result<()> {
+        let signed_bytes = self.signed_bytes(upgrade_lock).await?;
+        ensure!(
+            key.validate(&self.signature(), &signed_bytes),
+            warn!(
+                "Invalid signature on {:?} for view {}",
+                self.kind(),
+                self.view_number()
+            )
+        );
+
+        Ok(())
+    }
+}
+
+/// Proof that `offender` signed two different messages of the same kind for the same view
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(deserialize = "", serialize = ""))]
+pub struct EquivocationEvidence<TYPES: NodeType> {
+    /// The key that signed both messages
+    pub offender: TYPES::SignatureKey,
+    /// The first message we saw
+    pub first: SignedMessage<TYPES>,
+    /// The conflicting message
+    pub second: SignedMessage<TYPES>,
+}
+
+impl<TYPES: NodeType> EquivocationEvidence<TYPES> {
+    /// The kind of the conflicting messages
+    #[must_use]
+    pub fn kind(&self) -> EquivocationKind {
+        self.first.kind()
+    }
+
+    /// The view both messages were signed for
+    #[must_use]
+    pub fn view_number(&self) -> TYPES::View {
+        self.first.view_number()
+    }
+
+    /// Check the evidence against the stake table of `epoch`.
+    ///
+    /// Both messages must be of the same kind and for the same view, sign different data, and
+    /// carry a valid signature by the offender. The offender must be a voter of the committee the
+    /// votes are for, or the leader of the view of the proposals.
+    ///
+    /// # Errors
+    /// Returns an error describing the first check that fails.
+    pub async fn validate<V: Versions>(
+        &self,
+        membership: &TYPES::Membership,
+        epoch: TYPES::Epoch,
+        upgrade_lock: &UpgradeLock<TYPES, V>,
+    ) -> Result<()> {
+        let view = self.view_number();
+        ensure!(
+            self.first.kind() == self.second.kind(),
+            warn!("Evidence messages are of different kinds")
+        );
+        ensure!(
+            self.second.view_number() == view,
+            warn!("Evidence messages are for different views")
+        );
+        for message in [&self.first, &self.second] {
+            if let Some(key) = message.signing_key() {
+                ensure!(
+                    key == self.offender,
+                    warn!("Evidence message was signed by another key")
+                );
+            }
+        }
+
+        let authorized = match self.kind() {
+            EquivocationKind::QuorumVote => membership.has_stake(&self.offender, epoch),
+            EquivocationKind::DaVote => membership.has_da_stake(&self.offender, epoch),
+            EquivocationKind::QuorumProposal | EquivocationKind::DaProposal => {
+                membership.leader(view, epoch)? == self.offender
+            }
+        };
+        ensure!(
+            authorized,
+            warn!("Offender may not sign a {:?} in view {}", self.kind(), view)
+        );
+
+        ensure!(
+            self.first.signed_bytes(upgrade_lock).await?
+                != self.second.signed_bytes(upgrade_lock).await?,
+            warn!("Evidence messages sign the same data")
+        );
+        self.first
+            .validate_signature(&self.offender, upgrade_lock)
+            .await?;
+        self.second
+            .validate_signature(&self.offender, upgrade_lock)
+            .await
+    }
+}
@@ -10,7 +10,8 @@ use url::Url;
 use vec1::Vec1;
 
 use crate::{
-    constants::REQUEST_DATA_DELAY, traits::signature_key::SignatureKey,
+    constants::REQUEST_DATA_DELAY, mempool_config::MempoolConfig,
+    rate_limit_config::RateLimitConfig, traits::signature_key::SignatureKey,
     upgrade_config::UpgradeConfig, HotShotConfig, PeerConfig, ValidatorConfig,
 };
 
@@ -55,6 +56,16 @@ pub struct HotShotConfigFile<KEY: SignatureKey> {
     pub upgrade: UpgradeConfig,
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+    /// Per-peer limits on the messages we accept from the network
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    /// Limits of the mempool of transactions this node has seen, or `None` to keep no mempool
+    #[serde(default)]
+    pub mempool: Option<MempoolConfig>,
+    /// Whether to recover the payloads of decided leaves from the VID shares of other nodes,
+    /// when we are not on the DA committee of their views
+    #[serde(default)]
+    pub recover_payloads: bool,
 }
 
 impl<KEY: SignatureKey> From<HotShotConfigFile<KEY>> for HotShotConfig<KEY> {
@@ -83,6 +94,9 @@ impl<KEY: SignatureKey> From<HotShotConfigFile<KEY>> for HotShotConfig<KEY> {
             start_voting_time: val.upgrade.start_voting_time,
             stop_voting_time: val.upgrade.stop_voting_time,
             epoch_height: val.epoch_height,
+            rate_limits: val.rate_limits,
+            mempool: val.mempool,
+            recover_payloads: val.recover_payloads,
         }
     }
 }
@@ -128,6 +142,9 @@ impl<KEY: SignatureKey> HotShotConfigFile<KEY> {
             builder_urls: default_builder_urls(),
             upgrade: UpgradeConfig::default(),
             epoch_height: 0,
+            rate_limits: RateLimitConfig::default(),
+            mempool: None,
+            recover_payloads: false,
         }
     }
 }
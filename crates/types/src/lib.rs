@@ -15,21 +15,31 @@ use traits::signature_key::SignatureKey;
 use url::Url;
 use vec1::Vec1;
 
-use crate::utils::bincode_opts;
+use crate::{
+    mempool_config::MempoolConfig, rate_limit_config::RateLimitConfig, utils::bincode_opts,
+};
 pub mod bundle;
 pub mod consensus;
 pub mod constants;
 pub mod data;
+pub mod drb;
 pub mod error;
 pub mod event;
+pub mod evidence;
 /// Holds the configuration file specification for a HotShot node.
 pub mod hotshot_config_file;
 pub mod light_client;
+
+/// Holds the mempool configuration specification for HotShot nodes.
+pub mod mempool_config;
 pub mod message;
 
 /// Holds the network configuration specification for HotShot nodes.
 pub mod network;
 pub mod qc;
+
+/// Holds the rate limit configuration specification for HotShot nodes.
+pub mod rate_limit_config;
 pub mod request_response;
 pub mod signature_key;
 pub mod simple_certificate;
@@ -206,6 +216,16 @@ pub struct HotShotConfig<KEY: SignatureKey> {
     pub stop_voting_time: u64,
     /// Number of blocks in an epoch, zero means there are no epochs
     pub epoch_height: u64,
+    /// Per-peer limits on the messages we accept from the network
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    /// Limits of the mempool of transactions this node has seen, or `None` to keep no mempool
+    #[serde(default)]
+    pub mempool: Option<MempoolConfig>,
+    /// Whether to recover the payloads of decided leaves from the VID shares of other nodes,
+    /// when we are not on the DA committee of their views
+    #[serde(default)]
+    pub recover_payloads: bool,
 }
 
 impl<KEY: SignatureKey> HotShotConfig<KEY> {
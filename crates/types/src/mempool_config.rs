@@ -0,0 +1,33 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/// Holds the limits of the mempool of transactions a node has seen but not yet seen decided.
+pub struct MempoolConfig {
+    /// Maximum number of transactions held at once
+    pub max_transactions: usize,
+    /// Maximum total size of the transactions held at once, in bytes
+    pub max_bytes: u64,
+    /// How long a transaction is held before it expires
+    pub max_age: Duration,
+    /// Maximum size of the blocks we build from the mempool as leader when no builder gives us
+    /// one in time, or `None` to propose an empty block instead
+    #[serde(default)]
+    pub fallback_block_bytes: Option<u64>,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: 10_000,
+            max_bytes: 16 * 1024 * 1024,
+            max_age: Duration::from_secs(5 * 60),
+            fallback_block_bytes: None,
+        }
+    }
+}
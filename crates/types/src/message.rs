@@ -11,12 +11,15 @@
 
 use std::{
     fmt::{self, Debug},
+    io::{Read, Write},
     marker::PhantomData,
     sync::Arc,
 };
 
 use async_lock::RwLock;
 use committable::Committable;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use libp2p_identity::PeerId;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use utils::anytrace::*;
 use vbs::{
@@ -79,7 +82,7 @@ impl<TYPES: NodeType> HasViewNumber<TYPES> for Message<TYPES> {
 pub struct Messages<TYPES: NodeType>(pub Vec<Message<TYPES>>);
 
 /// A message type agnostic description of a message's purpose
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub enum MessagePurpose {
     /// Message with a [quorum/DA] proposal.
     Proposal,
@@ -109,6 +112,15 @@ pub enum MessagePurpose {
     External,
 }
 
+/// Who we know a message came from, as opposed to the sender it claims
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub enum MessageSource<KEY> {
+    /// The peer the network authenticated the message as coming from
+    Peer(PeerId),
+    /// The key whose signature on the message we checked
+    Key(KEY),
+}
+
 // TODO (da) make it more customized to the consensus layer, maybe separating the specific message
 // data from the kind enum.
 /// Enum representation of any message type
@@ -142,6 +154,18 @@ impl<TYPES: NodeType> MessageKind<TYPES> {
     }
 }
 
+impl<TYPES: NodeType> MessageKind<TYPES> {
+    /// The purpose of this message
+    #[must_use]
+    pub fn purpose(&self) -> MessagePurpose {
+        match self {
+            MessageKind::Consensus(message) => message.purpose(),
+            MessageKind::Data(_) => MessagePurpose::Data,
+            MessageKind::External(_) => MessagePurpose::External,
+        }
+    }
+}
+
 impl<TYPES: NodeType> From<DataMessage<TYPES>> for MessageKind<TYPES> {
     fn from(m: DataMessage<TYPES>) -> Self {
         Self::Data(m)
@@ -156,7 +180,16 @@ impl<TYPES: NodeType> ViewMessage<TYPES> for MessageKind<TYPES> {
             MessageKind::Data(DataMessage::RequestData(msg)) => msg.view,
             MessageKind::Data(DataMessage::DataResponse(msg)) => match msg {
                 ResponseMessage::Found(m) => m.view_number(),
-                ResponseMessage::NotFound | ResponseMessage::Denied => TYPES::View::new(1),
+                ResponseMessage::Leaves(decided) => decided
+                    .leaves
+                    .last()
+                    .map_or(TYPES::View::new(1), Leaf2::view_number),
+                ResponseMessage::VidShares(shares) => shares
+                    .first()
+                    .map_or(TYPES::View::new(1), |share| share.view_number),
+                ResponseMessage::NotFound
+                | ResponseMessage::Denied
+                | ResponseMessage::DeniedFor(_) => TYPES::View::new(1),
             },
             MessageKind::External(_) => TYPES::View::new(1),
         }
@@ -244,6 +277,36 @@ pub enum SequencingMessage<TYPES: NodeType> {
 }
 
 impl<TYPES: NodeType> SequencingMessage<TYPES> {
+    /// The purpose of this message
+    fn purpose(&self) -> MessagePurpose {
+        match self {
+            SequencingMessage::General(general_message) => match general_message {
+                GeneralConsensusMessage::Proposal(_) => MessagePurpose::Proposal,
+                GeneralConsensusMessage::ProposalRequested(..) => MessagePurpose::Data,
+                GeneralConsensusMessage::ProposalResponse(_) => MessagePurpose::LatestProposal,
+                GeneralConsensusMessage::Vote(_)
+                | GeneralConsensusMessage::TimeoutVote(_)
+                | GeneralConsensusMessage::HighQc(_) => MessagePurpose::Vote,
+                GeneralConsensusMessage::ViewSyncPreCommitVote(_)
+                | GeneralConsensusMessage::ViewSyncCommitVote(_)
+                | GeneralConsensusMessage::ViewSyncFinalizeVote(_) => MessagePurpose::ViewSyncVote,
+                GeneralConsensusMessage::ViewSyncPreCommitCertificate(_)
+                | GeneralConsensusMessage::ViewSyncCommitCertificate(_)
+                | GeneralConsensusMessage::ViewSyncFinalizeCertificate(_) => {
+                    MessagePurpose::ViewSyncCertificate
+                }
+                GeneralConsensusMessage::UpgradeProposal(_) => MessagePurpose::UpgradeProposal,
+                GeneralConsensusMessage::UpgradeVote(_) => MessagePurpose::UpgradeVote,
+            },
+            SequencingMessage::Da(da_message) => match da_message {
+                DaConsensusMessage::DaProposal(_) => MessagePurpose::Proposal,
+                DaConsensusMessage::DaVote(_) => MessagePurpose::Vote,
+                DaConsensusMessage::DaCertificate(_) => MessagePurpose::DaCertificate,
+                DaConsensusMessage::VidDisperseMsg(_) => MessagePurpose::VidDisperse,
+            },
+        }
+    }
+
     /// Get the view number this message relates to
     fn view_number(&self) -> TYPES::View {
         match &self {
@@ -405,6 +468,90 @@ pub struct UpgradeLock<TYPES: NodeType, V: Versions> {
     pub _pd: PhantomData<V>,
 }
 
+/// Serialized messages with a payload at least this many bytes long are compressed, from
+/// [`Versions::Compression`] on
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Largest payload we are willing to decompress a message to, in bytes
+const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Marks a payload that is sent as is
+const UNCOMPRESSED: u8 = 0;
+
+/// Marks a payload compressed with deflate
+const DEFLATE: u8 = 1;
+
+/// Compress the payload of a version-prefixed serialized message.
+///
+/// The version prefix stays in the clear, so the receiver can tell from it whether to expect a
+/// compression flag after it. Payloads below [`COMPRESSION_THRESHOLD`], or that don't shrink,
+/// are flagged [`UNCOMPRESSED`] and sent as is.
+fn compress(serialized_message: &[u8]) -> Result<Vec<u8>> {
+    let payload = Version::deserialize(serialized_message)
+        .wrap()
+        .context(info!("Failed to read message version!"))?
+        .1;
+    let (header, payload) = serialized_message.split_at(serialized_message.len() - payload.len());
+
+    let mut compressed = header.to_vec();
+    if payload.len() >= COMPRESSION_THRESHOLD {
+        compressed.push(DEFLATE);
+        let mut encoder = DeflateEncoder::new(compressed, Compression::fast());
+        encoder
+            .write_all(payload)
+            .wrap()
+            .context(info!("Failed to compress message!"))?;
+        compressed = encoder
+            .finish()
+            .wrap()
+            .context(info!("Failed to compress message!"))?;
+
+        if compressed.len() < serialized_message.len() {
+            return Ok(compressed);
+        }
+        compressed.truncate(header.len());
+    }
+
+    compressed.push(UNCOMPRESSED);
+    compressed.extend_from_slice(payload);
+    Ok(compressed)
+}
+
+/// Undo [`compress`], restoring the version-prefixed serialized message.
+fn decompress(message: &[u8]) -> Result<Vec<u8>> {
+    let rest = Version::deserialize(message)
+        .wrap()
+        .context(info!("Failed to read message version!"))?
+        .1;
+    let (header, rest) = message.split_at(message.len() - rest.len());
+    let (&flag, payload) = rest
+        .split_first()
+        .context(info!("Message is missing its compression flag!"))?;
+
+    let mut decompressed = header.to_vec();
+    match flag {
+        UNCOMPRESSED => decompressed.extend_from_slice(payload),
+        DEFLATE => {
+            // Read one byte past the limit, to tell a payload at the limit from one beyond it.
+            DeflateDecoder::new(payload)
+                .take((MAX_DECOMPRESSED_SIZE + 1) as u64)
+                .read_to_end(&mut decompressed)
+                .wrap()
+                .context(info!("Failed to decompress message!"))?;
+            ensure!(
+                decompressed.len() - header.len() <= MAX_DECOMPRESSED_SIZE,
+                warn!(
+                    "Message decompresses to more than {} bytes!",
+                    MAX_DECOMPRESSED_SIZE
+                )
+            );
+        }
+        flag => bail!("Message has unknown compression flag {}!", flag),
+    }
+
+    Ok(decompressed)
+}
+
 impl<TYPES: NodeType, V: Versions> UpgradeLock<TYPES, V> {
     #[allow(clippy::new_without_default)]
     /// Create a new `UpgradeLock` for a fresh instance of HotShot
@@ -476,6 +623,22 @@ impl<TYPES: NodeType, V: Versions> UpgradeLock<TYPES, V> {
         &self,
         message: &M,
     ) -> Result<Vec<u8>> {
+        Ok(self.serialize_with_size(message).await?.0)
+    }
+
+    /// Serialize a message like [`UpgradeLock::serialize`], also returning the size it would have
+    /// without compression.
+    ///
+    /// Messages of version [`Versions::Compression`] or later are compressed if they are large
+    /// enough to benefit from it.
+    ///
+    /// # Errors
+    ///
+    /// Errors if serialization fails.
+    pub async fn serialize_with_size<M: HasViewNumber<TYPES> + Serialize>(
+        &self,
+        message: &M,
+    ) -> Result<(Vec<u8>, usize)> {
         let view = message.view_number();
 
         let version = self.version(view).await?;
@@ -489,13 +652,22 @@ impl<TYPES: NodeType, V: Versions> UpgradeLock<TYPES, V> {
             }
         };
 
-        serialized_message
+        let serialized_message = serialized_message
             .wrap()
-            .context(info!("Failed to serialize message!"))
+            .context(info!("Failed to serialize message!"))?;
+        let uncompressed_size = serialized_message.len();
+
+        if version < V::Compression::VERSION {
+            return Ok((serialized_message, uncompressed_size));
+        }
+
+        Ok((compress(&serialized_message)?, uncompressed_size))
     }
 
     /// Deserialize a message with a version number, using `message.view_number()` to determine the message's version. This function will fail on improperly versioned messages.
     ///
+    /// Messages of version [`Versions::Compression`] or later are decompressed first.
+    ///
     /// # Errors
     ///
     /// Errors if deserialization fails.
@@ -508,6 +680,14 @@ impl<TYPES: NodeType, V: Versions> UpgradeLock<TYPES, V> {
             .context(info!("Failed to read message version!"))?
             .0;
 
+        let decompressed;
+        let message = if actual_version >= V::Compression::VERSION {
+            decompressed = decompress(message)?;
+            &decompressed
+        } else {
+            message
+        };
+
         let deserialized_message: M = match actual_version {
             v if v == V::Base::VERSION => Serializer::<V::Base>::deserialize(message),
             v if v == V::Upgrade::VERSION => Serializer::<V::Upgrade>::deserialize(message),
@@ -0,0 +1,39 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+use std::collections::HashMap;
+
+use crate::message::MessagePurpose;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+/// A token bucket limiting how many messages of one purpose a single peer may send us.
+pub struct RateLimit {
+    /// Number of messages a peer may send at once, after being quiet for a while
+    pub burst: u32,
+    /// Number of messages per second a peer may keep sending
+    pub per_second: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(bound(deserialize = ""))]
+/// Holds the per-peer rate limits on the messages we accept from the network.
+///
+/// Messages of a purpose without a limit are never dropped, so the default configuration does
+/// not limit anything. Peers are told apart by the identity the network authenticated or, on
+/// networks that do not authenticate their peers, by the key of the sender once we checked its
+/// signature on a proposal, vote or data request. Other messages over such networks are not
+/// limited.
+pub struct RateLimitConfig {
+    /// The limit applied to each peer, by the purpose of the message
+    pub limits: HashMap<MessagePurpose, RateLimit>,
+    /// The quota of data requests of any kind we serve each peer with stake, if any.
+    ///
+    /// Requests over the quota are denied with [`DenialReason::RateLimited`].
+    ///
+    /// [`DenialReason::RateLimited`]: crate::traits::network::DenialReason::RateLimited
+    #[serde(default)]
+    pub request_quota: Option<RateLimit>,
+}
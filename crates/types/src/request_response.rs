@@ -7,10 +7,15 @@
 //! Types for the request/response implementations. This module incorporates all
 //! of the shared types for all of the network backends.
 
-use committable::{Committable, RawCommitmentBuilder};
+use committable::{Commitment, Committable, RawCommitmentBuilder};
 use serde::{Deserialize, Serialize};
+use utils::anytrace::*;
 
-use crate::traits::{node_implementation::NodeType, signature_key::SignatureKey};
+use crate::{
+    data::Leaf2,
+    traits::{node_implementation::NodeType, signature_key::SignatureKey},
+    vote::HasViewNumber,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 /// A signed request for a proposal.
@@ -31,3 +36,47 @@ impl<TYPES: NodeType> Committable for ProposalRequestPayload<TYPES> {
             .finalize()
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(bound(deserialize = ""))]
+/// A chain of decided leaves, sent in response to a request for leaves.
+pub struct DecidedLeaves<TYPES: NodeType> {
+    /// The leaves, oldest first, each one the parent of the next
+    pub leaves: Vec<Leaf2<TYPES>>,
+}
+
+impl<TYPES: NodeType> DecidedLeaves<TYPES> {
+    /// Check that the leaves form a chain ending in the leaf with commitment `newest`.
+    ///
+    /// We only ask for leaves up to one we already trust, so the parent links authenticate the
+    /// rest of the chain, whatever epoch its leaves are in.
+    ///
+    /// # Errors
+    /// Returns an error if there are no leaves, if the newest leaf is not `newest`, or if a leaf is
+    /// not the parent of the next one.
+    pub fn validate(&self, newest: Commitment<Leaf2<TYPES>>) -> Result<()> {
+        let newest_leaf = self
+            .leaves
+            .last()
+            .context(warn!("Response to a request for leaves has no leaves"))?;
+        ensure!(
+            newest_leaf.commit() == newest,
+            warn!(
+                "Newest leaf, for view {}, is not the one we asked for",
+                newest_leaf.view_number()
+            )
+        );
+        for pair in self.leaves.windows(2) {
+            ensure!(
+                pair[1].parent_commitment() == pair[0].commit(),
+                warn!(
+                    "Leaf for view {} is not the parent of the leaf for view {}",
+                    pair[0].view_number(),
+                    pair[1].view_number()
+                )
+            );
+        }
+
+        Ok(())
+    }
+}
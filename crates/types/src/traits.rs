@@ -9,6 +9,7 @@ pub mod auction_results_provider;
 pub mod block_contents;
 pub mod consensus_api;
 pub mod election;
+pub mod mempool;
 pub mod metrics;
 pub mod network;
 pub mod node_implementation;
@@ -10,7 +10,7 @@ use std::{collections::BTreeSet, fmt::Debug, num::NonZeroU64};
 use utils::anytrace::Result;
 
 use super::node_implementation::NodeType;
-use crate::{traits::signature_key::SignatureKey, PeerConfig};
+use crate::{drb::DrbResult, traits::signature_key::SignatureKey, PeerConfig};
 
 /// A protocol for determining membership in and participating in a committee.
 pub trait Membership<TYPES: NodeType>: Clone + Debug + Send + Sync {
@@ -114,15 +114,33 @@ pub trait Membership<TYPES: NodeType>: Clone + Debug + Send + Sync {
     /// Returns the number of total DA nodes in the committee in an epoch `epoch`
     fn da_total_nodes(&self, epoch: TYPES::Epoch) -> usize;
 
-    /// Returns the threshold for a specific `Membership` implementation
-    fn success_threshold(&self) -> NonZeroU64;
+    /// Returns the threshold for a specific `Membership` implementation in an epoch `epoch`
+    fn success_threshold(&self, epoch: TYPES::Epoch) -> NonZeroU64;
 
-    /// Returns the DA threshold for a specific `Membership` implementation
-    fn da_success_threshold(&self) -> NonZeroU64;
+    /// Returns the DA threshold for a specific `Membership` implementation in an epoch `epoch`
+    fn da_success_threshold(&self, epoch: TYPES::Epoch) -> NonZeroU64;
 
-    /// Returns the threshold for a specific `Membership` implementation
-    fn failure_threshold(&self) -> NonZeroU64;
+    /// Returns the threshold for a specific `Membership` implementation in an epoch `epoch`
+    fn failure_threshold(&self, epoch: TYPES::Epoch) -> NonZeroU64;
 
-    /// Returns the threshold required to upgrade the network protocol
-    fn upgrade_threshold(&self) -> NonZeroU64;
+    /// Returns the threshold required to upgrade the network protocol in an epoch `epoch`
+    fn upgrade_threshold(&self, epoch: TYPES::Epoch) -> NonZeroU64;
+
+    /// Hand the result of the DRB computation for `epoch` to the membership.
+    ///
+    /// Consensus calls this on one clone of the membership once the computation finishes, so
+    /// implementations that rotate leaders by DRB must share the results between clones. The
+    /// default implementation ignores the result.
+    fn add_drb_result(&self, _epoch: TYPES::Epoch, _drb_result: DrbResult) {}
+
+    /// Tell the membership that consensus decided the epoch root of `epoch`: the first block of
+    /// the epoch before it.
+    ///
+    /// Every node decides the same chain, so implementations whose stake table changes over time
+    /// should fix the stake table of `epoch` here rather than when it is first looked up. The
+    /// default implementation does nothing.
+    ///
+    /// When consensus restarts from a decided leaf, it calls this again for every epoch root up
+    /// to that leaf, oldest first, so the membership can rebuild the stake tables it fixed before.
+    fn add_epoch_root(&self, _epoch: TYPES::Epoch) {}
 }
@@ -0,0 +1,27 @@
+// Copyright (c) 2021-2024 Espresso Systems (espressosys.com)
+// This file is part of the HotShot repository.
+
+// You should have received a copy of the MIT License
+// along with the HotShot repository. If not, see <https://mit-license.org/>.
+
+//! This module defines how the application orders the transactions a node holds in its mempool,
+//! via the [`TransactionPriority`] trait.
+
+use super::node_implementation::NodeType;
+
+/// Orders the transactions in the mempool
+pub trait TransactionPriority<TYPES: NodeType>: Send + Sync {
+    /// The priority of `transaction`. Transactions with a higher priority are selected first and
+    /// evicted last; among equals, the transaction received first wins.
+    fn priority(&self, transaction: &TYPES::Transaction) -> u64;
+}
+
+/// Gives every transaction the same priority, so transactions are served in the order received
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FifoPriority;
+
+impl<TYPES: NodeType> TransactionPriority<TYPES> for FifoPriority {
+    fn priority(&self, _transaction: &TYPES::Transaction) -> u64 {
+        0
+    }
+}
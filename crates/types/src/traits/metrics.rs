@@ -231,6 +231,10 @@ dyn_clone::clone_trait_object!(Metrics);
 dyn_clone::clone_trait_object!(Gauge);
 dyn_clone::clone_trait_object!(Counter);
 dyn_clone::clone_trait_object!(Histogram);
+dyn_clone::clone_trait_object!(CounterFamily);
+dyn_clone::clone_trait_object!(GaugeFamily);
+dyn_clone::clone_trait_object!(HistogramFamily);
+dyn_clone::clone_trait_object!(TextFamily);
 
 #[cfg(test)]
 mod test {
@@ -9,7 +9,7 @@
 //! Contains types and traits used by `HotShot` to abstract over network access
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
     hash::Hash,
     pin::Pin,
@@ -20,6 +20,7 @@ use std::{
 use async_trait::async_trait;
 use dyn_clone::DynClone;
 use futures::{future::join_all, Future};
+use libp2p_identity::PeerId;
 use rand::{
     distributions::{Bernoulli, Uniform},
     prelude::Distribution,
@@ -27,9 +28,18 @@ use rand::{
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::{sync::mpsc::error::TrySendError, time::sleep};
+use vbs::version::StaticVersionType;
 
-use super::{node_implementation::NodeType, signature_key::SignatureKey};
-use crate::{data::ViewNumber, message::SequencingMessage, BoxSyncFuture};
+use super::{
+    node_implementation::{NodeType, Versions},
+    signature_key::SignatureKey,
+};
+use crate::{
+    data::{VidDisperseShare, ViewNumber},
+    message::{SequencingMessage, UpgradeLock},
+    request_response::DecidedLeaves,
+    BoxSyncFuture,
+};
 
 /// Centralized server specific errors
 #[derive(Debug, Error, Serialize, Deserialize)]
@@ -132,6 +142,29 @@ pub struct DataRequest<TYPES: NodeType> {
     pub signature: <TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType,
 }
 
+impl<TYPES: NodeType> DataRequest<TYPES> {
+    /// The data whose Sha256 hash the requester signs, in the format of the protocol version of
+    /// `view`.
+    ///
+    /// From [`Versions::SignedRequestViews`] on, the request is signed along with its view, so
+    /// responders can refuse a captured request replayed once the view is past. Before, only the
+    /// request is signed, so nodes that have yet to upgrade still understand each other.
+    ///
+    /// # Errors
+    /// If the request cannot be serialized.
+    pub async fn signed_data<V: Versions>(
+        request: &RequestKind<TYPES>,
+        view: TYPES::View,
+        upgrade_lock: &UpgradeLock<TYPES, V>,
+    ) -> bincode::Result<Vec<u8>> {
+        if upgrade_lock.version_infallible(view).await >= V::SignedRequestViews::VERSION {
+            bincode::serialize(&(request, view))
+        } else {
+            bincode::serialize(request)
+        }
+    }
+}
+
 /// Underlying data request
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum RequestKind<TYPES: NodeType> {
@@ -141,6 +174,27 @@ pub enum RequestKind<TYPES: NodeType> {
     DaProposal(TYPES::View),
     /// Request for quorum proposal for a view
     Proposal(TYPES::View),
+    /// Request the decided leaves with views in an inclusive range
+    Leaves(TYPES::View, TYPES::View),
+    /// Request the decided leaves with block heights in an inclusive range
+    LeavesByHeight(u64, u64),
+    /// Request all the VID shares a node has for a view, to recover its payload
+    VidShares(TYPES::View),
+}
+
+impl<TYPES: NodeType> RequestKind<TYPES> {
+    /// The name of the kind of request, to label metrics with
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vid(..) => "Vid",
+            Self::DaProposal(_) => "DaProposal",
+            Self::Proposal(_) => "Proposal",
+            Self::Leaves(..) => "Leaves",
+            Self::LeavesByHeight(..) => "LeavesByHeight",
+            Self::VidShares(_) => "VidShares",
+        }
+    }
 }
 
 /// A response for a request.  `SequencingMessage` is the same as other network messages
@@ -156,6 +210,43 @@ pub enum ResponseMessage<TYPES: NodeType> {
     NotFound,
     /// The Request was denied
     Denied,
+    /// Peer returned us a chain of decided leaves
+    Leaves(DecidedLeaves<TYPES>),
+    /// Peer returned us the VID shares it has for a view
+    VidShares(Vec<VidDisperseShare<TYPES>>),
+    /// The request was denied, and the peer told us why
+    DeniedFor(DenialReason),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Why a peer denied one of our requests.
+///
+/// An honest peer can deny an honest request for each of these reasons, so denials are never held
+/// against the peer.
+pub enum DenialReason {
+    /// The peer does not think we have stake
+    NoStake,
+    /// We made more requests of the peer than its quota for us allows
+    RateLimited,
+    /// The peer no longer keeps the data of the view we asked about
+    Pruned,
+    /// We signed the request for a view too long before the peer's current view, so it may be a
+    /// replay. Signing it again for our current view gets it answered.
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Misbehaviour that lowers a peer's standing with the network.
+///
+/// Networks that keep score of their peers disconnect from, and eventually refuse, peers that
+/// misbehave too often.
+pub enum PeerMisbehaviour {
+    /// The peer sent us something with an invalid signature
+    InvalidSignature,
+    /// The peer sent us a message we could not decode
+    UndecodableMessage,
+    /// The peer asked us to store a DHT record that failed validation
+    InvalidDhtRecord,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -244,6 +335,17 @@ pub trait ConnectedNetwork<K: SignatureKey + 'static>: Clone + Send + Sync + 'st
     /// If there is a network-related failure.
     async fn recv_message(&self) -> Result<Vec<u8>, NetworkError>;
 
+    /// Receive a message, along with the peer the network authenticated it as coming from.
+    ///
+    /// Unlike the sender a message claims, the peer cannot be forged. Networks that do not
+    /// authenticate their peers return `None` for it.
+    ///
+    /// # Errors
+    /// If there is a network-related failure.
+    async fn recv_message_from(&self) -> Result<(Vec<u8>, Option<PeerId>), NetworkError> {
+        Ok((self.recv_message().await?, None))
+    }
+
     /// queues lookup of a node
     ///
     /// # Errors
@@ -272,6 +374,18 @@ pub trait ConnectedNetwork<K: SignatureKey + 'static>: Clone + Send + Sync + 'st
     fn is_primary_down(&self) -> bool {
         false
     }
+
+    /// Report that `peer`, as returned by [`recv_message_from`](Self::recv_message_from),
+    /// misbehaved. Networks that do not keep score of their peers ignore this.
+    fn report_peer(&self, _peer: &PeerId, _misbehaviour: PeerMisbehaviour) {}
+
+    /// Only deliver the messages we send to the peers in `reachable`, or to every peer if `None`.
+    ///
+    /// This is used by tests to partition the network. Partitions may be asymmetric, since each
+    /// node only restricts the messages it sends.
+    fn set_reachable_peers(&self, _reachable: Option<HashSet<K>>) {
+        tracing::warn!("This network cannot be partitioned, ignoring the partition");
+    }
 }
 
 /// A channel generator for types that need asynchronous execution
@@ -26,6 +26,7 @@ use vbs::version::StaticVersionType;
 use super::{
     auction_results_provider::AuctionResultsProvider,
     block_contents::{BlockHeader, TestableBlock, Transaction},
+    mempool::TransactionPriority,
     network::{
         AsyncGenerator, ConnectedNetwork, NetworkReliability, TestableNetworkingImplementation,
     },
@@ -36,6 +37,7 @@ use super::{
 };
 use crate::{
     data::{Leaf2, TestableLeaf},
+    drb::difficulty_level,
     traits::{
         election::Membership, signature_key::SignatureKey, states::InstanceState, BlockPayload,
     },
@@ -68,6 +70,9 @@ pub trait NodeImplementation<TYPES: NodeType>:
 
     /// The auction results type for Solver interactions
     type AuctionResultsProvider: AuctionResultsProvider<TYPES>;
+
+    /// The order of the transactions in the mempool
+    type TransactionPriority: TransactionPriority<TYPES> + Default + 'static;
 }
 
 /// extra functions required on a node implementation to be usable by hotshot-testing
@@ -252,6 +257,12 @@ pub trait NodeType:
 
     /// The type builder uses to sign its messages
     type BuilderSignatureKey: BuilderSignatureKey;
+
+    /// Number of hash iterations in the DRB computation.
+    ///
+    /// Every node must compute the same DRB results, so this is part of the protocol rather than
+    /// of the config of a node.
+    const DRB_DIFFICULTY: u64 = difficulty_level();
 }
 
 /// Version information for HotShot
@@ -270,4 +281,10 @@ pub trait Versions: Clone + Copy + Debug + Send + Sync + 'static {
 
     /// The version at which to switch over to epochs logic
     type Epochs: StaticVersionType;
+
+    /// The version from which large messages are compressed on the wire
+    type Compression: StaticVersionType;
+
+    /// The version from which data requests are signed along with their view
+    type SignedRequestViews: StaticVersionType;
 }
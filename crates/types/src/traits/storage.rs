@@ -11,20 +11,109 @@
 
 use std::collections::BTreeMap;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
 use jf_vid::VidScheme;
+use serde::{Deserialize, Serialize};
 
-use super::node_implementation::NodeType;
+use super::node_implementation::{ConsensusTime, NodeType};
 use crate::{
     consensus::{CommitmentMap, View},
     data::{DaProposal, Leaf, Leaf2, QuorumProposal, QuorumProposal2, VidDisperseShare},
+    drb::DrbResult,
     event::HotShotAction,
+    evidence::EquivocationEvidence,
     message::Proposal,
     simple_certificate::{QuorumCertificate, QuorumCertificate2, UpgradeCertificate},
     vid::VidSchemeType,
 };
 
+/// Consensus state read back from a [`Storage`], sufficient to restart a node without risking a
+/// double vote.
+#[derive(Clone, Debug)]
+pub struct PersistedConsensusState<TYPES: NodeType> {
+    /// The most recently decided leaf, if any leaf was decided yet.
+    pub anchor_leaf: Option<Leaf2<TYPES>>,
+    /// The highest QC seen.
+    pub high_qc: Option<QuorumCertificate2<TYPES>>,
+    /// The view of the last vote or proposal we sent.
+    pub last_actioned_view: Option<TYPES::View>,
+    /// The epoch we were in when we sent the last vote or proposal.
+    pub last_actioned_epoch: Option<TYPES::Epoch>,
+    /// Proposals we sent, to provide to others for catchup.
+    pub saved_proposals: BTreeMap<TYPES::View, Proposal<TYPES, QuorumProposal2<TYPES>>>,
+    /// Leaves that were seen, but not yet decided on.
+    pub undecided_leaves: CommitmentMap<Leaf2<TYPES>>,
+    /// Not yet decided state.
+    pub undecided_state: BTreeMap<TYPES::View, View<TYPES>>,
+    /// The most recently decided upgrade certificate.
+    pub decided_upgrade_certificate: Option<UpgradeCertificate<TYPES>>,
+    /// DRB results, by the epoch whose leaders they determine.
+    pub drb_results: BTreeMap<TYPES::Epoch, DrbResult>,
+}
+
+impl<TYPES: NodeType> Default for PersistedConsensusState<TYPES> {
+    fn default() -> Self {
+        Self {
+            anchor_leaf: None,
+            high_qc: None,
+            last_actioned_view: None,
+            last_actioned_epoch: None,
+            saved_proposals: BTreeMap::new(),
+            undecided_leaves: CommitmentMap::new(),
+            undecided_state: BTreeMap::new(),
+            decided_upgrade_certificate: None,
+            drb_results: BTreeMap::new(),
+        }
+    }
+}
+
+/// How much decided data a [`Storage`] keeps once consensus no longer needs it.
+///
+/// Only the per-view data served to other nodes for catchup (VID shares, DA proposals, quorum
+/// proposals and decided leaves) is pruned. The state needed to restart consensus is always kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetentionPolicy {
+    /// Keep the data of the last `n` decided views.
+    Views(u64),
+    /// Keep the data of as many of the most recently decided views as fit in `n` bytes.
+    Bytes(u64),
+    /// Keep everything, as an archival node does.
+    #[default]
+    Archive,
+}
+
+impl RetentionPolicy {
+    /// The earliest view to retain once `decided_view` is decided, or `None` if nothing is ever
+    /// pruned.
+    ///
+    /// `view_sizes` yields the number of bytes stored for each view, in ascending view order. The
+    /// returned view is never above `decided_view`.
+    pub fn horizon<TIME: ConsensusTime>(
+        self,
+        decided_view: TIME,
+        view_sizes: impl DoubleEndedIterator<Item = (TIME, u64)>,
+    ) -> Option<TIME> {
+        match self {
+            Self::Views(count) => Some(TIME::new(
+                decided_view.saturating_sub(count.saturating_sub(1)),
+            )),
+            Self::Bytes(budget) => {
+                let mut total = 0u64;
+                let (over_budget, _) = view_sizes
+                    .rev()
+                    .filter(|(view, _)| *view <= decided_view)
+                    .find(|(_, size)| {
+                    total = total.saturating_add(*size);
+                    total > budget
+                })?;
+                Some(TIME::new(*over_budget + 1).min(decided_view))
+            }
+            Self::Archive => None,
+        }
+    }
+}
+
 /// Abstraction for storing a variety of consensus payload datum.
 #[async_trait]
 pub trait Storage<TYPES: NodeType>: Send + Sync + Clone {
@@ -46,8 +135,13 @@ pub trait Storage<TYPES: NodeType>: Send + Sync + Clone {
         &self,
         proposal: &Proposal<TYPES, QuorumProposal2<TYPES>>,
     ) -> Result<()>;
-    /// Record a HotShotAction taken.
-    async fn record_action(&self, view: TYPES::View, action: HotShotAction) -> Result<()>;
+    /// Record a HotShotAction taken in `view`, while we were in `epoch`.
+    async fn record_action(
+        &self,
+        view: TYPES::View,
+        epoch: TYPES::Epoch,
+        action: HotShotAction,
+    ) -> Result<()>;
     /// Update the current high QC in storage.
     async fn update_high_qc(&self, high_qc: QuorumCertificate<TYPES>) -> Result<()>;
     /// Update the current high QC in storage.
@@ -71,6 +165,106 @@ pub trait Storage<TYPES: NodeType>: Send + Sync + Clone {
         &self,
         decided_upgrade_certificate: Option<UpgradeCertificate<TYPES>>,
     ) -> Result<()>;
+    /// Update the most recently decided leaf, which consensus restarts from.
+    ///
+    /// Storage that cannot restart consensus may ignore this.
+    async fn update_anchor_leaf(&self, _leaf: Leaf2<TYPES>) -> Result<()> {
+        Ok(())
+    }
+    /// Load everything needed to restart consensus, as persisted through the other methods of
+    /// this trait.
+    ///
+    /// Storage that cannot restart consensus returns an error, as starting over from genesis
+    /// could make us vote twice in the same view.
+    async fn load_consensus_state(&self) -> Result<PersistedConsensusState<TYPES>> {
+        bail!("This storage cannot restart consensus")
+    }
+    /// Store the result of the DRB computation for `epoch`, so a restarted node knows the leaders
+    /// of the epoch without computing it again.
+    ///
+    /// Storage that cannot restart consensus may ignore this.
+    async fn add_drb_result(&self, _epoch: TYPES::Epoch, _drb_result: DrbResult) -> Result<()> {
+        Ok(())
+    }
+    /// Load the VID share for `view` addressed to `key`, if it is stored.
+    ///
+    /// Storage that cannot read back what it stores has nothing to load.
+    async fn load_vid_share(
+        &self,
+        _view: TYPES::View,
+        _key: &TYPES::SignatureKey,
+    ) -> Result<Option<Proposal<TYPES, VidDisperseShare<TYPES>>>> {
+        Ok(None)
+    }
+    /// Load the quorum proposal for `view`, if it is stored.
+    ///
+    /// Storage that cannot read back what it stores has nothing to load.
+    async fn load_quorum_proposal(
+        &self,
+        _view: TYPES::View,
+    ) -> Result<Option<Proposal<TYPES, QuorumProposal2<TYPES>>>> {
+        Ok(None)
+    }
+    /// Add leaves that were decided, so we can serve them to nodes catching up. Decided leaves
+    /// are pruned along with the rest of the data of their views.
+    ///
+    /// Storage that cannot read back what it stores may ignore this.
+    async fn append_decided_leaves(&self, _leaves: &[Leaf2<TYPES>]) -> Result<()> {
+        Ok(())
+    }
+    /// Load the newest `limit` stored decided leaves with views from `from` to `to`, inclusive,
+    /// oldest first.
+    ///
+    /// Storage that cannot read back what it stores has nothing to load.
+    async fn load_decided_leaves(
+        &self,
+        _from: TYPES::View,
+        _to: TYPES::View,
+        _limit: usize,
+    ) -> Result<Vec<Leaf2<TYPES>>> {
+        Ok(Vec::new())
+    }
+    /// Load the newest `limit` stored decided leaves with block heights from `from` to `to`,
+    /// inclusive, oldest first.
+    ///
+    /// Storage that cannot read back what it stores has nothing to load.
+    async fn load_decided_leaves_by_height(
+        &self,
+        _from: u64,
+        _to: u64,
+        _limit: usize,
+    ) -> Result<Vec<Leaf2<TYPES>>> {
+        Ok(Vec::new())
+    }
+    /// Add evidence of a node equivocating to the store. Evidence is never pruned.
+    ///
+    /// Storage that cannot read back what it stores may ignore this.
+    async fn append_equivocation_evidence(
+        &self,
+        _evidence: &EquivocationEvidence<TYPES>,
+    ) -> Result<()> {
+        Ok(())
+    }
+    /// Load all the evidence of equivocation in the store.
+    ///
+    /// Storage that cannot read back what it stores has nothing to load.
+    async fn load_equivocation_evidence(&self) -> Result<Vec<EquivocationEvidence<TYPES>>> {
+        Ok(Vec::new())
+    }
+    /// Prune the data of decided views that fall outside of the retention policy, now that
+    /// `decided_view` has been decided. Data at or above `decided_view` is never pruned.
+    ///
+    /// Storage that keeps everything ignores this.
+    async fn collect_garbage(&self, _decided_view: TYPES::View) -> Result<()> {
+        Ok(())
+    }
+    /// The earliest view whose data is still retained. Data of any earlier view was pruned and
+    /// will never be available again.
+    ///
+    /// Storage that never prunes retains everything from genesis.
+    async fn retained_from(&self) -> TYPES::View {
+        TYPES::View::genesis()
+    }
     /// Migrate leaves from `Leaf` to `Leaf2`, and proposals from `QuorumProposal` to `QuorumProposal2`
     async fn migrate_consensus(
         &self,
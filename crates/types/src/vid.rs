@@ -58,22 +58,11 @@ use crate::{
 /// - [RFC: Type alias impl trait (TAIT)](https://github.com/rust-lang/rfcs/blob/master/text/2515-type_alias_impl_trait.md)
 ///
 /// # Panics
-/// When the construction fails for the underlying VID scheme.
+/// When there are no storage nodes, or the construction fails for the underlying VID scheme.
 #[must_use]
 #[memoize::memoize(SharedCache, Capacity: 10)]
 pub fn vid_scheme(num_storage_nodes: usize) -> VidSchemeType {
-    // recovery_threshold is currently num_storage_nodes rounded down to a power of two
-    // TODO recovery_threshold should be a function of the desired erasure code rate
-    // https://github.com/EspressoSystems/HotShot/issues/2152
-    let recovery_threshold = 1 << num_storage_nodes.ilog2();
-
-    #[allow(clippy::panic)]
-    let num_storage_nodes = u32::try_from(num_storage_nodes).unwrap_or_else(|err| {
-        panic!(
-            "num_storage_nodes {num_storage_nodes} should fit into u32; \
-                error: {err}"
-        )
-    });
+    let (num_storage_nodes, recovery_threshold) = advz_parameters(num_storage_nodes);
 
     // TODO panic, return `Result`, or make `new` infallible upstream (eg. by panicking)?
     #[allow(clippy::panic)]
@@ -85,15 +74,42 @@ pub fn vid_scheme(num_storage_nodes: usize) -> VidSchemeType {
     )
 }
 
+/// Number of distinct shares needed to recover a payload dispersed to `num_storage_nodes` nodes
+/// with [`vid_scheme()`], or `None` if there are no storage nodes.
+#[must_use]
+pub fn vid_recovery_threshold(num_storage_nodes: usize) -> Option<usize> {
+    // recovery_threshold is currently num_storage_nodes rounded down to a power of two
+    // TODO recovery_threshold should be a function of the desired erasure code rate
+    // https://github.com/EspressoSystems/HotShot/issues/2152
+    num_storage_nodes.checked_ilog2().map(|log| 1 << log)
+}
+
+/// The number of storage nodes and the recovery threshold to construct [`Advz`] with.
+///
+/// # Panics
+/// When there are no storage nodes, or more than fit into a `u32`.
+fn advz_parameters(num_storage_nodes: usize) -> (u32, u32) {
+    #[allow(clippy::panic)]
+    let recovery_threshold = vid_recovery_threshold(num_storage_nodes)
+        .unwrap_or_else(|| panic!("VID needs at least one storage node"));
+
+    // The threshold is at most the number of storage nodes, so it fits whenever they do
+    #[allow(clippy::panic)]
+    let (Ok(num_storage_nodes), Ok(recovery_threshold)) = (
+        u32::try_from(num_storage_nodes),
+        u32::try_from(recovery_threshold),
+    ) else {
+        panic!("num_storage_nodes {num_storage_nodes} should fit into u32");
+    };
+
+    (num_storage_nodes, recovery_threshold)
+}
+
 /// Similar to [`vid_scheme()`], but with `KZG_SRS_TEST` for testing purpose only.
 #[cfg(feature = "test-srs")]
 #[memoize::memoize(SharedCache, Capacity: 10)]
 pub fn vid_scheme_for_test(num_storage_nodes: usize) -> VidSchemeType {
-    let recovery_threshold = 1 << num_storage_nodes.ilog2();
-    #[allow(clippy::panic)]
-    let num_storage_nodes = u32::try_from(num_storage_nodes).unwrap_or_else(|err| {
-        panic!("num_storage_nodes {num_storage_nodes} should fit into u32; error: {err}")
-    });
+    let (num_storage_nodes, recovery_threshold) = advz_parameters(num_storage_nodes);
     #[allow(clippy::panic)]
     VidSchemeType(
         Advz::new(num_storage_nodes, recovery_threshold, &*KZG_SRS_TEST).unwrap_or_else(|err| {